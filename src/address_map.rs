@@ -0,0 +1,406 @@
+//! Address-mapped manufacturer SysEx: Roland Data Set/Request and Yamaha parameter change and bulk dump.
+//!
+//! Both lay out a device's parameters in a space of 7-bit addresses,
+//! writing bytes at an address and requesting bytes from one.
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//!
+//! Reference: Roland "Roland Exclusive Messages" (DT1/RQ1), Yamaha XG specification
+
+use anyhow::{Result, anyhow, bail};
+use std::fmt;
+use crate::manufacturer;
+
+/// An address of one to four 7-bit bytes, most significant first.
+///
+/// The bytes pack into a number without gaps, so address arithmetic carries from 0x7F into the next byte.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    value: u32,
+    len: u8,
+}
+
+impl Address {
+    pub fn new(bytes: &[u8]) -> Result<Address> {
+        if !(1..=4).contains(&bytes.len()) {
+            bail!("addresses are 1 to 4 bytes, not {}", bytes.len());
+        }
+        if bytes.iter().any(|byte| *byte > 0x7F) {
+            bail!("address byte out of 7-bit range: {:02X?}", bytes);
+        }
+        Ok(Address {
+            value: pack(bytes),
+            len: bytes.len() as u8,
+        })
+    }
+
+    /// An address of `len` bytes holding a packed 7-bit value.
+    pub fn from_value(value: u32, len: usize) -> Result<Address> {
+        if !(1..=4).contains(&len) || value >> (len * 7) != 0 {
+            bail!("address {:#X} out of range for {} bytes", value, len);
+        }
+        Ok(Address { value, len: len as u8 })
+    }
+
+    /// The address as a packed 7-bit value.
+    pub fn value(self) -> u32 {
+        self.value
+    }
+
+    pub fn byte_count(self) -> usize {
+        self.len as usize
+    }
+
+    pub fn bytes(self) -> Vec<u8> {
+        unpack(self.value, self.len as usize)
+    }
+
+    /// The address `offset` bytes later, if it fits in the same number of bytes.
+    pub fn checked_add(self, offset: u32) -> Option<Address> {
+        let value = self.value.checked_add(offset)?;
+        Address::from_value(value, self.byte_count()).ok()
+    }
+
+    /// The number of bytes from this address to a later one.
+    pub fn offset_to(self, other: Address) -> Option<u32> {
+        other.value.checked_sub(self.value)
+    }
+}
+
+/// Formats as hex bytes, such as `40 00 7F`.
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, byte) in self.bytes().iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+fn pack(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |value, byte| value << 7 | *byte as u32)
+}
+
+fn unpack(value: u32, len: usize) -> Vec<u8> {
+    (0..len).rev().map(|index| (value >> (index * 7)) as u8 & 0x7F).collect()
+}
+
+/// The checksum of Roland and Yamaha: the 7-bit value making the sum of the bytes and checksum a multiple of 128.
+pub fn checksum(bytes: &[u8]) -> u8 {
+    let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) & 0x7F;
+    (0x80 - sum) & 0x7F
+}
+
+/// Whether bytes followed by their checksum sum to a multiple of 128.
+pub fn verify_checksum(bytes_and_checksum: &[u8]) -> bool {
+    bytes_and_checksum.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) & 0x7F == 0
+}
+
+/// Bytes at an address, from a dump.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct Record {
+    pub address: Address,
+    pub data: Vec<u8>,
+}
+
+pub const ROLAND_REQUEST: u8 = 0x11;
+pub const ROLAND_DATA_SET: u8 = 0x12;
+
+/// The model ID and address width of a Roland device, needed to parse its messages.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RolandModel {
+    pub model_id: Vec<u8>,
+    pub address_len: usize,
+}
+
+impl RolandModel {
+    /// GS sound modules.
+    pub fn gs() -> RolandModel {
+        RolandModel {
+            model_id: vec![0x42],
+            address_len: 3,
+        }
+    }
+
+    /// Parses a Data Set or Request for this model, returning `None` for other messages.
+    pub fn parse(&self, data: &[u8]) -> Result<Option<RolandMessage>> {
+        let id_len = self.model_id.len();
+        let [0x41, device_id, ref rest @ ..] = *data else {
+            return Ok(None);
+        };
+        if !rest.starts_with(&self.model_id) || rest.len() <= id_len {
+            return Ok(None);
+        }
+        let command = rest[id_len];
+        let body = &rest[id_len + 1..];
+        if command != ROLAND_DATA_SET && command != ROLAND_REQUEST {
+            return Ok(None);
+        }
+        if body.len() < self.address_len + 1 {
+            bail!("truncated Roland message");
+        }
+        if !verify_checksum(body) {
+            bail!("Roland checksum mismatch in {:02X?}", data);
+        }
+        let (address, rest) = body[..body.len() - 1].split_at(self.address_len);
+        let address = Address::new(address)?;
+        let body = if command == ROLAND_DATA_SET {
+            RolandBody::DataSet(rest.to_vec())
+        } else {
+            if rest.len() != self.address_len {
+                bail!("Roland request size must be {} bytes", self.address_len);
+            }
+            RolandBody::Request { size: pack(rest) }
+        };
+        Ok(Some(RolandMessage {
+            device_id,
+            model_id: self.model_id.clone(),
+            address,
+            body,
+        }))
+    }
+
+    /// The records of every Data Set for this model, skipping other messages.
+    pub fn decode_dump<'a>(&self, messages: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<Record>> {
+        let mut records = Vec::new();
+        for data in messages {
+            if let Some(RolandMessage { address, body: RolandBody::DataSet(data), .. }) = self.parse(data)? {
+                records.push(Record { address, data });
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum RolandBody {
+    /// DT1.
+    DataSet(Vec<u8>),
+    /// RQ1, for `size` bytes from the address.
+    Request {
+        size: u32,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct RolandMessage {
+    /// Usually 0x10 to 0x1F; 0x7F addresses all devices.
+    pub device_id: u8,
+    pub model_id: Vec<u8>,
+    pub address: Address,
+    pub body: RolandBody,
+}
+
+impl RolandMessage {
+    /// Splits data into Data Sets of at most `max_len` bytes each, at consecutive addresses.
+    pub fn data_sets(device_id: u8, model: &RolandModel, address: Address, data: &[u8], max_len: usize) -> Result<Vec<RolandMessage>> {
+        if max_len == 0 {
+            bail!("maximum Data Set length must be positive");
+        }
+        if address.byte_count() != model.address_len {
+            bail!("address {} does not have {} bytes", address, model.address_len);
+        }
+        data.chunks(max_len)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let address = address.checked_add((index * max_len) as u32)
+                    .ok_or_else(|| anyhow!("Data Set past the end of the address space"))?;
+                Ok(RolandMessage {
+                    device_id,
+                    model_id: model.model_id.clone(),
+                    address,
+                    body: RolandBody::DataSet(chunk.to_vec()),
+                })
+            })
+            .collect()
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        manufacturer::ROLAND.encode(buf);
+        buf.push(self.device_id);
+        buf.extend_from_slice(&self.model_id);
+        let checked = match &self.body {
+            RolandBody::DataSet(data) => {
+                buf.push(ROLAND_DATA_SET);
+                let checked = buf.len();
+                buf.extend(self.address.bytes());
+                buf.extend_from_slice(data);
+                checked
+            }
+            RolandBody::Request { size } => {
+                buf.push(ROLAND_REQUEST);
+                let checked = buf.len();
+                buf.extend(self.address.bytes());
+                buf.extend(Address::from_value(*size, self.address.byte_count())?.bytes());
+                checked
+            }
+        };
+        buf.push(checksum(&buf[checked..]));
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("Roland message byte out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+}
+
+/// The Yamaha model ID of XG.
+pub const YAMAHA_XG: u8 = 0x4C;
+
+/// The length of Yamaha parameter addresses.
+pub const YAMAHA_ADDRESS_LEN: usize = 3;
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum YamahaBody {
+    /// Bulk dump, with a byte count and checksum.
+    BulkDump(Vec<u8>),
+    /// Parameter change, without a checksum.
+    ParameterChange(Vec<u8>),
+    DumpRequest,
+    ParameterRequest,
+}
+
+impl YamahaBody {
+    /// The high nibble of the byte holding the device number.
+    fn kind(&self) -> u8 {
+        match self {
+            YamahaBody::BulkDump(_) => 0x00,
+            YamahaBody::ParameterChange(_) => 0x10,
+            YamahaBody::DumpRequest => 0x20,
+            YamahaBody::ParameterRequest => 0x30,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct YamahaMessage {
+    /// 0 to 15.
+    pub device_number: u8,
+    pub model_id: u8,
+    pub address: Address,
+    pub body: YamahaBody,
+}
+
+impl YamahaMessage {
+    /// Parses a message with a one-byte model ID and three-byte address,
+    /// returning `None` for other messages.
+    pub fn parse(data: &[u8]) -> Result<Option<YamahaMessage>> {
+        let [0x43, device, model_id, ref rest @ ..] = *data else {
+            return Ok(None);
+        };
+        let device_number = device & 0x0F;
+        let (address, body) = match device & 0x70 {
+            0x00 => {
+                if rest.len() < 2 + YAMAHA_ADDRESS_LEN + 1 {
+                    bail!("truncated Yamaha bulk dump");
+                }
+                if !verify_checksum(rest) {
+                    bail!("Yamaha checksum mismatch in {:02X?}", data);
+                }
+                let count = pack(&rest[..2]) as usize;
+                let (address, data) = rest[2..rest.len() - 1].split_at(YAMAHA_ADDRESS_LEN);
+                if data.len() != count {
+                    bail!("Yamaha bulk dump of {} bytes declares {}", data.len(), count);
+                }
+                (address, YamahaBody::BulkDump(data.to_vec()))
+            }
+            0x10 => {
+                if rest.len() < YAMAHA_ADDRESS_LEN {
+                    bail!("truncated Yamaha parameter change");
+                }
+                let (address, data) = rest.split_at(YAMAHA_ADDRESS_LEN);
+                (address, YamahaBody::ParameterChange(data.to_vec()))
+            }
+            0x20 | 0x30 => {
+                if rest.len() != YAMAHA_ADDRESS_LEN {
+                    bail!("Yamaha request must have a {}-byte address", YAMAHA_ADDRESS_LEN);
+                }
+                (rest, if device & 0x70 == 0x20 { YamahaBody::DumpRequest } else { YamahaBody::ParameterRequest })
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(YamahaMessage {
+            device_number,
+            model_id,
+            address: Address::new(address)?,
+            body,
+        }))
+    }
+
+    /// The records of every bulk dump and parameter change for a model, skipping other messages.
+    ///
+    /// Messages for other models are skipped before parsing,
+    /// since they may use formats of their own, such as the DX7's.
+    pub fn decode_dump<'a>(model_id: u8, messages: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<Record>> {
+        let mut records = Vec::new();
+        for data in messages {
+            if !matches!(data, [0x43, _, id, ..] if *id == model_id) {
+                continue;
+            }
+            if let Some(YamahaMessage { address, body: YamahaBody::BulkDump(data) | YamahaBody::ParameterChange(data), .. }) = YamahaMessage::parse(data)? {
+                records.push(Record { address, data });
+            }
+        }
+        Ok(records)
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        if self.device_number > 0x0F {
+            bail!("Yamaha device number out of range: {}", self.device_number);
+        }
+        if self.address.byte_count() != YAMAHA_ADDRESS_LEN {
+            bail!("Yamaha addresses are {} bytes: {}", YAMAHA_ADDRESS_LEN, self.address);
+        }
+        let start = buf.len();
+        manufacturer::YAMAHA.encode(buf);
+        buf.extend([self.body.kind() | self.device_number, self.model_id]);
+        match &self.body {
+            YamahaBody::BulkDump(data) => {
+                let checked = buf.len();
+                let count = Address::from_value(data.len() as u32, 2)
+                    .map_err(|_| anyhow!("Yamaha bulk dump too long: {} bytes", data.len()))?;
+                buf.extend(count.bytes());
+                buf.extend(self.address.bytes());
+                buf.extend_from_slice(data);
+                buf.push(checksum(&buf[checked..]));
+            }
+            YamahaBody::ParameterChange(data) => {
+                buf.extend(self.address.bytes());
+                buf.extend_from_slice(data);
+            }
+            YamahaBody::DumpRequest | YamahaBody::ParameterRequest => buf.extend(self.address.bytes()),
+        }
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("Yamaha message byte out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+}
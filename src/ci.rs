@@ -0,0 +1,867 @@
+//! MIDI Capability Inquiry messages, and a responder and initiator for them.
+//!
+//! MIDI-CI messages are Universal Non-Realtime SysEx with sub-ID#1 `0D`.
+//! Every message carries the 28-bit MUIDs of its source and destination.
+//!
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//!
+//! Reference: M2-101-UM MIDI Capability Inquiry (MIDI-CI) 1.2
+
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+
+pub const UNIVERSAL_NON_REALTIME: u8 = 0x7E;
+pub const SUB_ID_1_MIDI_CI: u8 = 0x0D;
+/// The MIDI-CI message version written by this module.
+pub const MESSAGE_VERSION: u8 = 0x02;
+
+/// The device ID byte addressing the whole function block rather than a channel.
+pub const DEVICE_ID_FUNCTION_BLOCK: u8 = 0x7F;
+/// The device ID byte addressing the whole group.
+pub const DEVICE_ID_GROUP: u8 = 0x7E;
+
+/// Capability inquiry category bits of Discovery messages.
+pub mod categories {
+    pub const PROFILE_CONFIGURATION: u8 = 1 << 2;
+    pub const PROPERTY_EXCHANGE: u8 = 1 << 3;
+    pub const PROCESS_INQUIRY: u8 = 1 << 4;
+}
+
+/// Reference: M2-101-UM table 4
+pub mod sub_id_2 {
+    pub const PROFILE_INQUIRY: u8 = 0x20;
+    pub const REPLY_TO_PROFILE_INQUIRY: u8 = 0x21;
+    pub const SET_PROFILE_ON: u8 = 0x22;
+    pub const SET_PROFILE_OFF: u8 = 0x23;
+    pub const PROFILE_ENABLED_REPORT: u8 = 0x24;
+    pub const PROFILE_DISABLED_REPORT: u8 = 0x25;
+    pub const PROPERTY_EXCHANGE_CAPABILITIES_INQUIRY: u8 = 0x30;
+    pub const REPLY_TO_PROPERTY_EXCHANGE_CAPABILITIES: u8 = 0x31;
+    pub const GET_PROPERTY_DATA: u8 = 0x34;
+    pub const REPLY_TO_GET_PROPERTY_DATA: u8 = 0x35;
+    pub const SET_PROPERTY_DATA: u8 = 0x36;
+    pub const REPLY_TO_SET_PROPERTY_DATA: u8 = 0x37;
+    pub const SUBSCRIPTION: u8 = 0x38;
+    pub const REPLY_TO_SUBSCRIPTION: u8 = 0x39;
+    pub const NOTIFY: u8 = 0x3F;
+    pub const DISCOVERY: u8 = 0x70;
+    pub const REPLY_TO_DISCOVERY: u8 = 0x71;
+    pub const INVALIDATE_MUID: u8 = 0x7E;
+    pub const NAK: u8 = 0x7F;
+}
+
+/// A 28-bit MIDI-CI unique identifier.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Muid(u32);
+
+impl Muid {
+    /// The destination of messages to all devices.
+    pub const BROADCAST: Muid = Muid(0x0FFF_FFFF);
+
+    pub fn is_broadcast(self) -> bool {
+        self == Muid::BROADCAST
+    }
+}
+
+impl TryFrom<u32> for Muid {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Muid> {
+        if value <= 0x0FFF_FFFF {
+            Ok(Muid(value))
+        } else {
+            Err(anyhow!("MUID out of range: {:08X}", value))
+        }
+    }
+}
+
+impl From<Muid> for u32 {
+    fn from(other: Muid) -> u32 {
+        other.0
+    }
+}
+
+/// A 5-byte profile ID.
+///
+/// Standard profiles begin with `7E`; others begin with a manufacturer ID.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ProfileId(pub [u8; 5]);
+
+/// Identity fields of Discovery and Reply to Discovery.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    pub manufacturer_id: [u8; 3],
+    /// LSB first.
+    pub family: [u8; 2],
+    /// LSB first.
+    pub model: [u8; 2],
+    pub software_revision: [u8; 4],
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct Discovery {
+    pub identity: DeviceIdentity,
+    /// Bits from [`categories`].
+    pub categories: u8,
+    pub max_sysex_size: u32,
+    pub output_path_id: u8,
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct DiscoveryReply {
+    pub identity: DeviceIdentity,
+    pub categories: u8,
+    pub max_sysex_size: u32,
+    /// The initiator's output path ID from the Discovery being answered.
+    pub output_path_id: u8,
+    /// The function block of the replying device, or `7F` if none.
+    pub function_block: u8,
+}
+
+/// Reference: M2-101-UM section 5.11
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct Nak {
+    pub original_sub_id_2: u8,
+    pub status_code: u8,
+    pub status_data: u8,
+    pub details: [u8; 5],
+    pub message: Vec<u8>,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum CiBody {
+    Discovery(Discovery),
+    ReplyToDiscovery(DiscoveryReply),
+    InvalidateMuid {
+        target: Muid,
+    },
+    Nak(Nak),
+    ProfileInquiry,
+    ReplyToProfileInquiry {
+        enabled: Vec<ProfileId>,
+        disabled: Vec<ProfileId>,
+    },
+    SetProfileOn {
+        profile: ProfileId,
+        /// Channels to enable, 0 for a single channel or the whole function block.
+        channels: u16,
+    },
+    SetProfileOff {
+        profile: ProfileId,
+    },
+    ProfileEnabledReport {
+        profile: ProfileId,
+        channels: u16,
+    },
+    ProfileDisabledReport {
+        profile: ProfileId,
+        channels: u16,
+    },
+    PropertyExchangeCapabilitiesInquiry {
+        simultaneous_requests: u8,
+        major_version: u8,
+        minor_version: u8,
+    },
+    ReplyToPropertyExchangeCapabilities {
+        simultaneous_requests: u8,
+        major_version: u8,
+        minor_version: u8,
+    },
+    /// A message not otherwise decoded, such as Property Exchange data.
+    Other {
+        sub_id_2: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct CiMessage {
+    /// A channel, or [`DEVICE_ID_FUNCTION_BLOCK`] or [`DEVICE_ID_GROUP`].
+    pub device_id: u8,
+    pub version: u8,
+    pub source: Muid,
+    pub destination: Muid,
+    pub body: CiBody,
+}
+
+/// Reads fields from the body of a message.
+pub(crate) struct Reader<'buf> {
+    bytes: &'buf [u8],
+}
+
+impl<'buf> Reader<'buf> {
+    pub(crate) fn new(bytes: &'buf [u8]) -> Reader<'buf> {
+        Reader { bytes }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'buf [u8]> {
+        if self.bytes.len() < len {
+            bail!("truncated MIDI-CI message");
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().expect("length"))
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    /// A 14-bit value, LSB first.
+    pub(crate) fn u14(&mut self) -> Result<u16> {
+        let [lsb, msb] = self.array()?;
+        Ok((msb as u16) << 7 | lsb as u16)
+    }
+
+    /// A 28-bit value, LSB first.
+    pub(crate) fn u28(&mut self) -> Result<u32> {
+        let bytes: [u8; 4] = self.array()?;
+        Ok(bytes.iter().rev().fold(0, |value, &byte| value << 7 | byte as u32))
+    }
+
+    pub(crate) fn rest(&mut self) -> &'buf [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+pub(crate) fn write_u14(value: u16, buf: &mut Vec<u8>) {
+    buf.extend([(value & 0x7F) as u8, (value >> 7 & 0x7F) as u8]);
+}
+
+pub(crate) fn write_u28(value: u32, buf: &mut Vec<u8>) {
+    for shift in [0, 7, 14, 21] {
+        buf.push((value >> shift & 0x7F) as u8);
+    }
+}
+
+impl DeviceIdentity {
+    fn read(reader: &mut Reader) -> Result<DeviceIdentity> {
+        Ok(DeviceIdentity {
+            manufacturer_id: reader.array()?,
+            family: reader.array()?,
+            model: reader.array()?,
+            software_revision: reader.array()?,
+        })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.manufacturer_id);
+        buf.extend_from_slice(&self.family);
+        buf.extend_from_slice(&self.model);
+        buf.extend_from_slice(&self.software_revision);
+    }
+}
+
+impl CiMessage {
+    pub fn is_ci(data: &[u8]) -> bool {
+        data.len() >= 3 && data[0] == UNIVERSAL_NON_REALTIME && data[2] == SUB_ID_1_MIDI_CI
+    }
+
+    /// Parses SysEx data, without `F0` and `F7`.
+    ///
+    /// Fields added by later MIDI-CI versions are optional,
+    /// and trailing fields of unknown use are ignored.
+    pub fn parse(data: &[u8]) -> Result<CiMessage> {
+        if !CiMessage::is_ci(data) {
+            bail!("not a MIDI-CI message");
+        }
+        let mut reader = Reader::new(&data[1..]);
+        let device_id = reader.u8()?;
+        reader.u8()?;
+        let sub_id_2 = reader.u8()?;
+        let version = reader.u8()?;
+        let source = Muid(reader.u28()?);
+        let destination = Muid(reader.u28()?);
+        let optional_u8 = |reader: &mut Reader| if reader.is_empty() { Ok(0) } else { reader.u8() };
+
+        let body = match sub_id_2 {
+            sub_id_2::DISCOVERY => CiBody::Discovery(Discovery {
+                identity: DeviceIdentity::read(&mut reader)?,
+                categories: reader.u8()?,
+                max_sysex_size: reader.u28()?,
+                output_path_id: optional_u8(&mut reader)?,
+            }),
+            sub_id_2::REPLY_TO_DISCOVERY => CiBody::ReplyToDiscovery(DiscoveryReply {
+                identity: DeviceIdentity::read(&mut reader)?,
+                categories: reader.u8()?,
+                max_sysex_size: reader.u28()?,
+                output_path_id: optional_u8(&mut reader)?,
+                function_block: if reader.is_empty() { DEVICE_ID_FUNCTION_BLOCK } else { reader.u8()? },
+            }),
+            sub_id_2::INVALIDATE_MUID => CiBody::InvalidateMuid {
+                target: Muid(reader.u28()?),
+            },
+            sub_id_2::NAK => {
+                if reader.is_empty() {
+                    CiBody::Nak(Nak {
+                        original_sub_id_2: 0,
+                        status_code: 0,
+                        status_data: 0,
+                        details: [0; 5],
+                        message: vec![],
+                    })
+                } else {
+                    let original_sub_id_2 = reader.u8()?;
+                    let status_code = reader.u8()?;
+                    let status_data = reader.u8()?;
+                    let details = reader.array()?;
+                    let len = reader.u14()? as usize;
+                    CiBody::Nak(Nak {
+                        original_sub_id_2,
+                        status_code,
+                        status_data,
+                        details,
+                        message: reader.bytes(len)?.to_vec(),
+                    })
+                }
+            }
+            sub_id_2::PROFILE_INQUIRY => CiBody::ProfileInquiry,
+            sub_id_2::REPLY_TO_PROFILE_INQUIRY => {
+                let mut read_profiles = |reader: &mut Reader| -> Result<Vec<ProfileId>> {
+                    let count = reader.u14()?;
+                    (0..count).map(|_| Ok(ProfileId(reader.array()?))).collect()
+                };
+                CiBody::ReplyToProfileInquiry {
+                    enabled: read_profiles(&mut reader)?,
+                    disabled: read_profiles(&mut reader)?,
+                }
+            }
+            sub_id_2::SET_PROFILE_ON => CiBody::SetProfileOn {
+                profile: ProfileId(reader.array()?),
+                channels: if reader.is_empty() { 0 } else { reader.u14()? },
+            },
+            sub_id_2::SET_PROFILE_OFF => CiBody::SetProfileOff {
+                profile: ProfileId(reader.array()?),
+            },
+            sub_id_2::PROFILE_ENABLED_REPORT => CiBody::ProfileEnabledReport {
+                profile: ProfileId(reader.array()?),
+                channels: if reader.is_empty() { 0 } else { reader.u14()? },
+            },
+            sub_id_2::PROFILE_DISABLED_REPORT => CiBody::ProfileDisabledReport {
+                profile: ProfileId(reader.array()?),
+                channels: if reader.is_empty() { 0 } else { reader.u14()? },
+            },
+            sub_id_2::PROPERTY_EXCHANGE_CAPABILITIES_INQUIRY => CiBody::PropertyExchangeCapabilitiesInquiry {
+                simultaneous_requests: reader.u8()?,
+                major_version: optional_u8(&mut reader)?,
+                minor_version: optional_u8(&mut reader)?,
+            },
+            sub_id_2::REPLY_TO_PROPERTY_EXCHANGE_CAPABILITIES => CiBody::ReplyToPropertyExchangeCapabilities {
+                simultaneous_requests: reader.u8()?,
+                major_version: optional_u8(&mut reader)?,
+                minor_version: optional_u8(&mut reader)?,
+            },
+            _ => CiBody::Other {
+                sub_id_2,
+                data: reader.rest().to_vec(),
+            },
+        };
+
+        Ok(CiMessage {
+            device_id,
+            version,
+            source,
+            destination,
+            body,
+        })
+    }
+
+    pub fn sub_id_2(&self) -> u8 {
+        match &self.body {
+            CiBody::Discovery(_) => sub_id_2::DISCOVERY,
+            CiBody::ReplyToDiscovery(_) => sub_id_2::REPLY_TO_DISCOVERY,
+            CiBody::InvalidateMuid { .. } => sub_id_2::INVALIDATE_MUID,
+            CiBody::Nak(_) => sub_id_2::NAK,
+            CiBody::ProfileInquiry => sub_id_2::PROFILE_INQUIRY,
+            CiBody::ReplyToProfileInquiry { .. } => sub_id_2::REPLY_TO_PROFILE_INQUIRY,
+            CiBody::SetProfileOn { .. } => sub_id_2::SET_PROFILE_ON,
+            CiBody::SetProfileOff { .. } => sub_id_2::SET_PROFILE_OFF,
+            CiBody::ProfileEnabledReport { .. } => sub_id_2::PROFILE_ENABLED_REPORT,
+            CiBody::ProfileDisabledReport { .. } => sub_id_2::PROFILE_DISABLED_REPORT,
+            CiBody::PropertyExchangeCapabilitiesInquiry { .. } => sub_id_2::PROPERTY_EXCHANGE_CAPABILITIES_INQUIRY,
+            CiBody::ReplyToPropertyExchangeCapabilities { .. } => sub_id_2::REPLY_TO_PROPERTY_EXCHANGE_CAPABILITIES,
+            CiBody::Other { sub_id_2, .. } => *sub_id_2,
+        }
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        buf.extend([UNIVERSAL_NON_REALTIME, self.device_id, SUB_ID_1_MIDI_CI, self.sub_id_2(), self.version]);
+        write_u28(self.source.0, buf);
+        write_u28(self.destination.0, buf);
+
+        match &self.body {
+            CiBody::Discovery(discovery) => {
+                discovery.identity.write(buf);
+                buf.push(discovery.categories);
+                write_u28(discovery.max_sysex_size, buf);
+                buf.push(discovery.output_path_id);
+            }
+            CiBody::ReplyToDiscovery(reply) => {
+                reply.identity.write(buf);
+                buf.push(reply.categories);
+                write_u28(reply.max_sysex_size, buf);
+                buf.push(reply.output_path_id);
+                buf.push(reply.function_block);
+            }
+            CiBody::InvalidateMuid { target } => {
+                write_u28(target.0, buf);
+            }
+            CiBody::Nak(nak) => {
+                buf.extend([nak.original_sub_id_2, nak.status_code, nak.status_data]);
+                buf.extend_from_slice(&nak.details);
+                let len = u16::try_from(nak.message.len()).ok().filter(|len| *len <= 0x3FFF)
+                    .ok_or_else(|| anyhow!("NAK message too long"))?;
+                write_u14(len, buf);
+                buf.extend_from_slice(&nak.message);
+            }
+            CiBody::ProfileInquiry => { }
+            CiBody::ReplyToProfileInquiry { enabled, disabled } => {
+                for profiles in [enabled, disabled] {
+                    let count = u16::try_from(profiles.len()).ok().filter(|len| *len <= 0x3FFF)
+                        .ok_or_else(|| anyhow!("too many profiles"))?;
+                    write_u14(count, buf);
+                    for profile in profiles {
+                        buf.extend_from_slice(&profile.0);
+                    }
+                }
+            }
+            CiBody::SetProfileOn { profile, channels }
+            | CiBody::ProfileEnabledReport { profile, channels }
+            | CiBody::ProfileDisabledReport { profile, channels } => {
+                buf.extend_from_slice(&profile.0);
+                write_u14(*channels, buf);
+            }
+            CiBody::SetProfileOff { profile } => {
+                buf.extend_from_slice(&profile.0);
+                write_u14(0, buf);
+            }
+            CiBody::PropertyExchangeCapabilitiesInquiry { simultaneous_requests, major_version, minor_version }
+            | CiBody::ReplyToPropertyExchangeCapabilities { simultaneous_requests, major_version, minor_version } => {
+                buf.extend([*simultaneous_requests, *major_version, *minor_version]);
+            }
+            CiBody::Other { data, .. } => {
+                buf.extend_from_slice(data);
+            }
+        }
+
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("MIDI-CI field out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+}
+
+/// The state of a device that answers MIDI-CI inquiries.
+///
+/// Handles Discovery, Invalidate MUID, profile configuration
+/// and Property Exchange capability inquiries addressed to its MUID or broadcast,
+/// and answers other messages addressed to it with NAK.
+pub struct Responder {
+    pub muid: Muid,
+    pub device_id: u8,
+    pub identity: DeviceIdentity,
+    pub categories: u8,
+    pub max_sysex_size: u32,
+    /// Profiles supported by the device, and whether each is enabled.
+    pub profiles: Vec<(ProfileId, bool)>,
+    /// Simultaneous Property Exchange requests supported, if Property Exchange is.
+    pub property_exchange_requests: Option<u8>,
+    initiators: HashMap<Muid, DeviceIdentity>,
+}
+
+impl Responder {
+    pub fn new(muid: Muid, identity: DeviceIdentity) -> Responder {
+        Responder {
+            muid,
+            device_id: DEVICE_ID_FUNCTION_BLOCK,
+            identity,
+            categories: 0,
+            max_sysex_size: 512,
+            profiles: vec![],
+            property_exchange_requests: None,
+            initiators: HashMap::new(),
+        }
+    }
+
+    /// Initiators that have sent Discovery and not been invalidated.
+    pub fn initiators(&self) -> impl Iterator<Item = (&Muid, &DeviceIdentity)> {
+        self.initiators.iter()
+    }
+
+    /// Handles a message, returning any replies.
+    pub fn handle(&mut self, message: &CiMessage) -> Vec<CiMessage> {
+        if message.destination != self.muid && !message.destination.is_broadcast() {
+            return vec![];
+        }
+        if message.source == self.muid {
+            log::warn!("MIDI-CI message from our own MUID {:?}", self.muid);
+            return vec![];
+        }
+
+        let reply_to = |body| CiMessage {
+            device_id: message.device_id,
+            version: MESSAGE_VERSION,
+            source: self.muid,
+            destination: message.source,
+            body,
+        };
+        let broadcast = |body| CiMessage {
+            device_id: message.device_id,
+            version: MESSAGE_VERSION,
+            source: self.muid,
+            destination: Muid::BROADCAST,
+            body,
+        };
+
+        match &message.body {
+            CiBody::Discovery(discovery) => {
+                self.initiators.insert(message.source, discovery.identity);
+                vec![reply_to(CiBody::ReplyToDiscovery(DiscoveryReply {
+                    identity: self.identity,
+                    categories: self.categories,
+                    max_sysex_size: self.max_sysex_size,
+                    output_path_id: discovery.output_path_id,
+                    function_block: DEVICE_ID_FUNCTION_BLOCK,
+                }))]
+            }
+            CiBody::InvalidateMuid { target } => {
+                self.initiators.remove(target);
+                vec![]
+            }
+            CiBody::ProfileInquiry if self.categories & categories::PROFILE_CONFIGURATION != 0 => {
+                let profiles = |enabled: bool| self.profiles.iter()
+                    .filter(|(_, is_enabled)| *is_enabled == enabled)
+                    .map(|(profile, _)| *profile)
+                    .collect();
+                vec![reply_to(CiBody::ReplyToProfileInquiry {
+                    enabled: profiles(true),
+                    disabled: profiles(false),
+                })]
+            }
+            CiBody::SetProfileOn { profile, .. } | CiBody::SetProfileOff { profile }
+                if self.categories & categories::PROFILE_CONFIGURATION != 0 =>
+            {
+                let enable = matches!(message.body, CiBody::SetProfileOn { .. });
+                match self.profiles.iter_mut().find(|(id, _)| id == profile) {
+                    Some((_, enabled)) => {
+                        *enabled = enable;
+                        let profile = *profile;
+                        vec![broadcast(if enable {
+                            CiBody::ProfileEnabledReport { profile, channels: 0 }
+                        } else {
+                            CiBody::ProfileDisabledReport { profile, channels: 0 }
+                        })]
+                    }
+                    None => vec![reply_to(CiBody::Nak(self.nak(message)))],
+                }
+            }
+            CiBody::PropertyExchangeCapabilitiesInquiry { .. } if self.property_exchange_requests.is_some() => {
+                vec![reply_to(CiBody::ReplyToPropertyExchangeCapabilities {
+                    simultaneous_requests: self.property_exchange_requests.unwrap_or(1),
+                    major_version: 0,
+                    minor_version: 0,
+                })]
+            }
+            CiBody::ReplyToDiscovery(_)
+            | CiBody::Nak(_)
+            | CiBody::ReplyToProfileInquiry { .. }
+            | CiBody::ProfileEnabledReport { .. }
+            | CiBody::ProfileDisabledReport { .. }
+            | CiBody::ReplyToPropertyExchangeCapabilities { .. } => vec![],
+            // Left to a Property Exchange implementation, such as [`crate::property_exchange`].
+            CiBody::Other { sub_id_2: sub_id_2::GET_PROPERTY_DATA..=sub_id_2::NOTIFY, .. }
+                if self.property_exchange_requests.is_some() => vec![],
+            _ if message.destination.is_broadcast() => vec![],
+            _ => vec![reply_to(CiBody::Nak(self.nak(message)))],
+        }
+    }
+
+    fn nak(&self, message: &CiMessage) -> Nak {
+        Nak {
+            original_sub_id_2: message.sub_id_2(),
+            // "Message not supported"
+            status_code: 0x01,
+            status_data: 0,
+            details: [0; 5],
+            message: vec![],
+        }
+    }
+}
+
+/// The state of a device that discovers and queries MIDI-CI devices.
+pub struct Initiator {
+    pub muid: Muid,
+    pub identity: DeviceIdentity,
+    pub categories: u8,
+    pub max_sysex_size: u32,
+    devices: HashMap<Muid, DiscoveredDevice>,
+}
+
+/// What an initiator has learned about a responder.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct DiscoveredDevice {
+    pub reply: DiscoveryReply,
+    pub enabled_profiles: Vec<ProfileId>,
+    pub disabled_profiles: Vec<ProfileId>,
+    pub property_exchange_requests: Option<u8>,
+}
+
+impl Initiator {
+    pub fn new(muid: Muid, identity: DeviceIdentity) -> Initiator {
+        Initiator {
+            muid,
+            identity,
+            categories: 0,
+            max_sysex_size: 512,
+            devices: HashMap::new(),
+        }
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = (&Muid, &DiscoveredDevice)> {
+        self.devices.iter()
+    }
+
+    pub fn device(&self, muid: Muid) -> Option<&DiscoveredDevice> {
+        self.devices.get(&muid)
+    }
+
+    /// A broadcast Discovery message.
+    pub fn discovery(&self) -> CiMessage {
+        self.message(Muid::BROADCAST, CiBody::Discovery(Discovery {
+            identity: self.identity,
+            categories: self.categories,
+            max_sysex_size: self.max_sysex_size,
+            output_path_id: 0,
+        }))
+    }
+
+    /// A message from this initiator to the function block of a device.
+    pub fn message(&self, destination: Muid, body: CiBody) -> CiMessage {
+        CiMessage {
+            device_id: DEVICE_ID_FUNCTION_BLOCK,
+            version: MESSAGE_VERSION,
+            source: self.muid,
+            destination,
+            body,
+        }
+    }
+
+    /// Records what a reply says about its sender.
+    pub fn handle(&mut self, message: &CiMessage) {
+        if message.destination != self.muid && !message.destination.is_broadcast() {
+            return;
+        }
+        match &message.body {
+            CiBody::ReplyToDiscovery(reply) => {
+                self.devices.insert(message.source, DiscoveredDevice {
+                    reply: *reply,
+                    enabled_profiles: vec![],
+                    disabled_profiles: vec![],
+                    property_exchange_requests: None,
+                });
+            }
+            CiBody::InvalidateMuid { target } => {
+                self.devices.remove(target);
+            }
+            _ => {
+                let Some(device) = self.devices.get_mut(&message.source) else {
+                    return;
+                };
+                match &message.body {
+                    CiBody::ReplyToProfileInquiry { enabled, disabled } => {
+                        device.enabled_profiles = enabled.clone();
+                        device.disabled_profiles = disabled.clone();
+                    }
+                    CiBody::ProfileEnabledReport { profile, .. } => {
+                        device.disabled_profiles.retain(|id| id != profile);
+                        if !device.enabled_profiles.contains(profile) {
+                            device.enabled_profiles.push(*profile);
+                        }
+                    }
+                    CiBody::ProfileDisabledReport { profile, .. } => {
+                        device.enabled_profiles.retain(|id| id != profile);
+                        if !device.disabled_profiles.contains(profile) {
+                            device.disabled_profiles.push(*profile);
+                        }
+                    }
+                    CiBody::ReplyToPropertyExchangeCapabilities { simultaneous_requests, .. } => {
+                        device.property_exchange_requests = Some(*simultaneous_requests);
+                    }
+                    _ => { }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{MessageParseOutcomeStatus, Parser};
+
+    const RESPONDER: u32 = 0x0123_4567;
+    const INITIATOR: u32 = 0x0765_4321;
+    const PROFILE_A: ProfileId = ProfileId([0x7E, 0x21, 0x00, 0x01, 0x00]);
+    const PROFILE_B: ProfileId = ProfileId([0x7E, 0x22, 0x00, 0x01, 0x00]);
+
+    fn identity(model: u8) -> DeviceIdentity {
+        DeviceIdentity {
+            manufacturer_id: [0x00, 0x20, 0x29],
+            family: [0x01, 0x00],
+            model: [model, 0x00],
+            software_revision: [1, 0, 0, 0],
+        }
+    }
+
+    fn responder(categories: u8) -> Responder {
+        let mut responder = Responder::new(Muid::try_from(RESPONDER).unwrap(), identity(1));
+        responder.categories = categories;
+        responder.profiles = vec![(PROFILE_A, false), (PROFILE_B, true)];
+        responder
+    }
+
+    fn initiator() -> Initiator {
+        Initiator::new(Muid::try_from(INITIATOR).unwrap(), identity(2))
+    }
+
+    /// Sends a message as SysEx bytes, as a simulated link between the two devices would.
+    fn transmit(message: &CiMessage) -> CiMessage {
+        let bytes = message.to_sysex_bytes().unwrap();
+        let outcome = Parser::new().parse(&bytes).unwrap();
+        assert_eq!(outcome.bytes_consumed, bytes.len());
+        let MessageParseOutcomeStatus::SystemExclusiveMessage(data) = outcome.status else {
+            panic!("not SysEx: {:?}", outcome.status);
+        };
+        CiMessage::parse(data).unwrap()
+    }
+
+    /// Sends a message from the initiator, and its replies back to the initiator.
+    fn exchange(responder: &mut Responder, initiator: &mut Initiator, body: CiBody) -> Vec<CiMessage> {
+        let message = initiator.message(responder.muid, body);
+        let replies: Vec<CiMessage> = responder.handle(&transmit(&message)).iter().map(transmit).collect();
+        for reply in &replies {
+            initiator.handle(reply);
+        }
+        replies
+    }
+
+    fn discover(responder: &mut Responder, initiator: &mut Initiator) {
+        let replies = responder.handle(&transmit(&initiator.discovery()));
+        assert_eq!(replies.len(), 1);
+        initiator.handle(&transmit(&replies[0]));
+    }
+
+    #[test]
+    fn discovery_and_reply() {
+        let mut responder = responder(categories::PROFILE_CONFIGURATION);
+        let mut initiator = initiator();
+        discover(&mut responder, &mut initiator);
+
+        let device = initiator.device(responder.muid).unwrap();
+        assert_eq!(device.reply.identity, identity(1));
+        assert_eq!(device.reply.categories, categories::PROFILE_CONFIGURATION);
+        assert_eq!(device.reply.max_sysex_size, 512);
+        assert_eq!(responder.initiators().collect::<Vec<_>>(), [(&initiator.muid, &identity(2))]);
+    }
+
+    #[test]
+    fn profile_inquiry_and_set_on_off() {
+        let mut responder = responder(categories::PROFILE_CONFIGURATION);
+        let mut initiator = initiator();
+        discover(&mut responder, &mut initiator);
+
+        exchange(&mut responder, &mut initiator, CiBody::ProfileInquiry);
+        let device = initiator.device(responder.muid).unwrap();
+        assert_eq!(device.enabled_profiles, [PROFILE_B]);
+        assert_eq!(device.disabled_profiles, [PROFILE_A]);
+
+        let replies = exchange(&mut responder, &mut initiator, CiBody::SetProfileOn { profile: PROFILE_A, channels: 0 });
+        assert_eq!(replies[0].destination, Muid::BROADCAST);
+        assert_eq!(replies[0].body, CiBody::ProfileEnabledReport { profile: PROFILE_A, channels: 0 });
+        exchange(&mut responder, &mut initiator, CiBody::SetProfileOff { profile: PROFILE_B });
+        assert_eq!(responder.profiles, [(PROFILE_A, true), (PROFILE_B, false)]);
+        let device = initiator.device(responder.muid).unwrap();
+        assert_eq!(device.enabled_profiles, [PROFILE_A]);
+        assert_eq!(device.disabled_profiles, [PROFILE_B]);
+    }
+
+    #[test]
+    fn nak_for_unsupported_messages() {
+        let mut responder = responder(0);
+        let mut initiator = initiator();
+        discover(&mut responder, &mut initiator);
+
+        let replies = exchange(&mut responder, &mut initiator, CiBody::ProfileInquiry);
+        let [CiMessage { body: CiBody::Nak(nak), destination, .. }] = replies.as_slice() else {
+            panic!("expected NAK: {:?}", replies);
+        };
+        assert_eq!(*destination, initiator.muid);
+        assert_eq!(nak.original_sub_id_2, sub_id_2::PROFILE_INQUIRY);
+        assert_eq!(nak.status_code, 0x01);
+
+        let mut responder = self::responder(categories::PROFILE_CONFIGURATION);
+        let unknown = ProfileId([0x7E, 0x7F, 0x00, 0x01, 0x00]);
+        let replies = exchange(&mut responder, &mut initiator, CiBody::SetProfileOn { profile: unknown, channels: 0 });
+        assert!(matches!(&replies[..], [CiMessage { body: CiBody::Nak(Nak { original_sub_id_2: sub_id_2::SET_PROFILE_ON, .. }), .. }]));
+
+        let broadcast = initiator.message(Muid::BROADCAST, CiBody::Other { sub_id_2: 0x40, data: vec![] });
+        assert_eq!(responder.handle(&transmit(&broadcast)), []);
+    }
+
+    #[test]
+    fn invalidate_muid() {
+        let mut responder = responder(0);
+        let mut initiator = initiator();
+        discover(&mut responder, &mut initiator);
+
+        let invalidate = initiator.message(Muid::BROADCAST, CiBody::InvalidateMuid { target: initiator.muid });
+        assert_eq!(responder.handle(&transmit(&invalidate)), []);
+        assert_eq!(responder.initiators().count(), 0);
+
+        let invalidate = CiMessage {
+            device_id: DEVICE_ID_FUNCTION_BLOCK,
+            version: MESSAGE_VERSION,
+            source: responder.muid,
+            destination: Muid::BROADCAST,
+            body: CiBody::InvalidateMuid { target: responder.muid },
+        };
+        initiator.handle(&transmit(&invalidate));
+        assert_eq!(initiator.device(responder.muid), None);
+    }
+
+    #[test]
+    fn messages_for_other_muids_ignored() {
+        let mut responder = responder(categories::PROFILE_CONFIGURATION);
+        let initiator = initiator();
+        let other = Muid::try_from(0x0111_1111).unwrap();
+        assert_eq!(responder.handle(&transmit(&initiator.message(other, CiBody::ProfileInquiry))), []);
+    }
+}
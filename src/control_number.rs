@@ -2,6 +2,8 @@
 
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 #[derive(IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum ControlNumber {
@@ -78,6 +80,54 @@ pub enum ControlNumber {
     LegatoFootswitch,
     Hold2,
 
-    TODO,
-    
+    SoundController1 = 70,
+    SoundController2,
+    SoundController3,
+    SoundController4,
+    SoundController5,
+    SoundController6,
+    SoundController7,
+    SoundController8,
+    SoundController9,
+    SoundController10,
+    GeneralPurposeController5,
+    GeneralPurposeController6,
+    GeneralPurposeController7,
+    GeneralPurposeController8,
+    PortamentoControl,
+    Undefined85 = 85,
+    Undefined86 = 86,
+    Undefined87 = 87,
+    HighResolutionVelocityPrefix,
+    Undefined89 = 89,
+    Undefined90 = 90,
+    Effects1Depth,
+    Effects2Depth,
+    Effects3Depth,
+    Effects4Depth,
+    Effects5Depth,
+    DataIncrement,
+    DataDecrement,
+    NonRegisteredParameterNumberLSB,
+    NonRegisteredParameterNumberMSB,
+    RegisteredParameterNumberLSB,
+    RegisteredParameterNumberMSB,
+    Undefined102 = 102,
+    Undefined103 = 103,
+    Undefined104 = 104,
+    Undefined105 = 105,
+    Undefined106 = 106,
+    Undefined107 = 107,
+    Undefined108 = 108,
+    Undefined109 = 109,
+    Undefined110 = 110,
+    Undefined111 = 111,
+    Undefined112 = 112,
+    Undefined113 = 113,
+    Undefined114 = 114,
+    Undefined115 = 115,
+    Undefined116 = 116,
+    Undefined117 = 117,
+    Undefined118 = 118,
+    Undefined119 = 119,
 }
@@ -0,0 +1,374 @@
+//! A compact, stable text form of messages, for logs and golden tests.
+//!
+//! Channels are numbered from 1 and notes are named as in [`NoteNaming::SCIENTIFIC`]:
+//!
+//! ```text
+//! ch1 NoteOn C4 vel=100
+//! ch1 NoteOff C4 vel=0
+//! ch10 PolyPressure F#2 val=40
+//! ch1 CC 7 val=100
+//! ch1 Program 5
+//! ch1 ChannelPressure 64
+//! ch1 PitchBend 8192
+//! ch1 AllNotesOff
+//! ch1 LocalControl val=127
+//! Clock
+//! SysEx
+//! ```
+//!
+//! Channel mode messages show their data byte as `val=` unless it is 0.
+//! Parsing accepts what is displayed, ignoring case, and also note numbers in place of names.
+//! Anything after `SysEx` is ignored, so a [`crate::sysex::Summary`] parses too.
+
+use anyhow::{Context, Result, anyhow, bail};
+use std::fmt;
+use std::str::FromStr;
+use crate::assert_from::AssertFrom;
+use crate::message::*;
+use crate::note_name::NoteNaming;
+
+impl fmt::Display for MidiChannelId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ch{}", u8::from(*self) + 1)
+    }
+}
+
+impl FromStr for MidiChannelId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<MidiChannelId> {
+        let number = strip_prefix_ignore_case(s, "ch").ok_or_else(|| anyhow!("invalid channel {:?}", s))?;
+        let number: u8 = number.parse().with_context(|| format!("invalid channel {:?}", s))?;
+        number.checked_sub(1)
+            .and_then(|channel| MidiChannelId::try_from(channel).ok())
+            .ok_or_else(|| anyhow!("channel {:?} out of range", s))
+    }
+}
+
+impl fmt::Display for cvm::NoteNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&NoteNaming::SCIENTIFIC.name(*self))
+    }
+}
+
+impl FromStr for cvm::NoteNumber {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<cvm::NoteNumber> {
+        if s.bytes().all(|byte| byte.is_ascii_digit()) {
+            Ok(cvm::NoteNumber(parse_u7(s)?))
+        } else {
+            NoteNaming::SCIENTIFIC.parse(s)
+        }
+    }
+}
+
+impl fmt::Display for ChannelVoiceMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelVoiceMessage::NoteOff(m) => write!(f, "NoteOff {} vel={}", m.note_number, u8::from(m.velocity.0)),
+            ChannelVoiceMessage::NoteOn(m) => write!(f, "NoteOn {} vel={}", m.note_number, u8::from(m.velocity.0)),
+            ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(m) => {
+                write!(f, "PolyPressure {} val={}", m.note_number, u8::from(m.value))
+            }
+            ChannelVoiceMessage::ControlChange(m) => {
+                write!(f, "CC {} val={}", u8::from(m.control_number.0), u8::from(m.value))
+            }
+            ChannelVoiceMessage::ProgramChange(m) => write!(f, "Program {}", u8::from(m.program_number.0)),
+            ChannelVoiceMessage::ChannelPressureAftertouch(m) => write!(f, "ChannelPressure {}", u8::from(m.value)),
+            ChannelVoiceMessage::PitchBendChange(m) => write!(f, "PitchBend {}", u16::from(m.value)),
+        }
+    }
+}
+
+impl FromStr for ChannelVoiceMessage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ChannelVoiceMessage> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        let message = match tokens[..] {
+            [kind, note, velocity] if kind.eq_ignore_ascii_case("NoteOff") => {
+                ChannelVoiceMessage::NoteOff(cvm::NoteOff {
+                    note_number: note.parse()?,
+                    velocity: cvm::KeyVelocity(parse_field(velocity, "vel")?),
+                })
+            }
+            [kind, note, velocity] if kind.eq_ignore_ascii_case("NoteOn") => {
+                ChannelVoiceMessage::NoteOn(cvm::NoteOn {
+                    note_number: note.parse()?,
+                    velocity: cvm::KeyVelocity(parse_field(velocity, "vel")?),
+                })
+            }
+            [kind, note, value] if kind.eq_ignore_ascii_case("PolyPressure") => {
+                ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(cvm::PolyphonicKeyPressureAftertouch {
+                    note_number: note.parse()?,
+                    value: parse_field(value, "val")?,
+                })
+            }
+            [kind, control, value] if kind.eq_ignore_ascii_case("CC") => {
+                ChannelVoiceMessage::ControlChange(cvm::ControlChange {
+                    control_number: cvm::ControlNumber(parse_u7(control)?),
+                    value: parse_field(value, "val")?,
+                })
+            }
+            [kind, program] if kind.eq_ignore_ascii_case("Program") => {
+                ChannelVoiceMessage::ProgramChange(cvm::ProgramChange {
+                    program_number: cvm::ProgramNumber(parse_u7(program)?),
+                })
+            }
+            [kind, value] if kind.eq_ignore_ascii_case("ChannelPressure") => {
+                ChannelVoiceMessage::ChannelPressureAftertouch(cvm::ChannelPressureAftertouch {
+                    value: parse_u7(value)?,
+                })
+            }
+            [kind, value] if kind.eq_ignore_ascii_case("PitchBend") => {
+                let value: u16 = value.parse().with_context(|| format!("invalid pitch bend {:?}", value))?;
+                ChannelVoiceMessage::PitchBendChange(cvm::PitchBendChange {
+                    value: cvm::Unsigned14::try_from(value)?,
+                })
+            }
+            _ => bail!("invalid channel voice message {:?}", s),
+        };
+        Ok(message)
+    }
+}
+
+const CHANNEL_MODE_NAMES: [(ChannelModeMessage, &str); 8] = [
+    (ChannelModeMessage::AllSoundOff, "AllSoundOff"),
+    (ChannelModeMessage::ResetAllControllers, "ResetAllControllers"),
+    (ChannelModeMessage::LocalControl, "LocalControl"),
+    (ChannelModeMessage::AllNotesOff, "AllNotesOff"),
+    (ChannelModeMessage::OmniOff, "OmniOff"),
+    (ChannelModeMessage::OmniOn, "OmniOn"),
+    (ChannelModeMessage::MonoOn, "MonoOn"),
+    (ChannelModeMessage::PolyOn, "PolyOn"),
+];
+
+impl fmt::Display for ChannelModeMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = CHANNEL_MODE_NAMES.iter().find(|(message, _)| message == self).expect("every mode is named");
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ChannelModeMessage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ChannelModeMessage> {
+        CHANNEL_MODE_NAMES.iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(message, _)| *message)
+            .ok_or_else(|| anyhow!("invalid channel mode message {:?}", s))
+    }
+}
+
+impl fmt::Display for ChannelMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            ChannelMessageType::ChannelVoice(message) => write!(f, "{} {}", self.channel, message),
+            ChannelMessageType::ChannelMode { mode, value } => {
+                write!(f, "{} {}", self.channel, mode)?;
+                match u8::from(*value) {
+                    0 => Ok(()),
+                    value => write!(f, " val={}", value),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ChannelMessage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ChannelMessage> {
+        let s = s.trim();
+        let (channel, rest) = s.split_once(char::is_whitespace).ok_or_else(|| anyhow!("invalid channel message {:?}", s))?;
+        let rest = rest.trim();
+        let mut tokens = rest.split_whitespace();
+        let mode = tokens.next().and_then(|mode| mode.parse::<ChannelModeMessage>().ok());
+        let message = match (mode, tokens.next(), tokens.next()) {
+            (Some(mode), None, _) => ChannelMessageType::ChannelMode {
+                mode,
+                value: cvm::Unsigned7::assert_from(0),
+            },
+            (Some(mode), Some(value), None) => ChannelMessageType::ChannelMode {
+                mode,
+                value: parse_field(value, "val")?,
+            },
+            _ => ChannelMessageType::ChannelVoice(rest.parse()?),
+        };
+        Ok(ChannelMessage {
+            channel: channel.parse()?,
+            message,
+        })
+    }
+}
+
+const SYSTEM_REAL_TIME_NAMES: [(SystemRealTimeMessage, &str); 8] = [
+    (SystemRealTimeMessage::TimingClock, "Clock"),
+    (SystemRealTimeMessage::Undefined1, "Undefined(F9)"),
+    (SystemRealTimeMessage::Start, "Start"),
+    (SystemRealTimeMessage::Continue, "Continue"),
+    (SystemRealTimeMessage::Stop, "Stop"),
+    (SystemRealTimeMessage::Undefined2, "Undefined(FD)"),
+    (SystemRealTimeMessage::ActiveSensing, "ActiveSensing"),
+    (SystemRealTimeMessage::SystemReset, "Reset"),
+];
+
+impl fmt::Display for SystemRealTimeMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = SYSTEM_REAL_TIME_NAMES.iter().find(|(message, _)| message == self).expect("every message is named");
+        f.write_str(name)
+    }
+}
+
+impl FromStr for SystemRealTimeMessage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<SystemRealTimeMessage> {
+        SYSTEM_REAL_TIME_NAMES.iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+            .map(|(message, _)| *message)
+            .ok_or_else(|| anyhow!("invalid system real-time message {:?}", s))
+    }
+}
+
+impl fmt::Display for SystemMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SystemMessage::SystemCommon(_) => f.write_str("SystemCommon"),
+            SystemMessage::SystemRealTime(message) => write!(f, "{}", message),
+            SystemMessage::SystemExclusive(_) => f.write_str("SysEx"),
+        }
+    }
+}
+
+impl FromStr for SystemMessage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<SystemMessage> {
+        let s = s.trim();
+        let first = s.split_whitespace().next().unwrap_or_default();
+        if first.eq_ignore_ascii_case("SysEx") {
+            Ok(SystemMessage::SystemExclusive(SystemExclusiveMessage))
+        } else if s.eq_ignore_ascii_case("SystemCommon") {
+            Ok(SystemMessage::SystemCommon(SystemCommonMessage))
+        } else {
+            Ok(SystemMessage::SystemRealTime(s.parse()?))
+        }
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::Channel(message) => write!(f, "{}", message),
+            Message::System(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Message> {
+        let s = s.trim();
+        if strip_prefix_ignore_case(s, "ch").is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit())) {
+            Ok(Message::Channel(s.parse()?))
+        } else {
+            Ok(Message::System(s.parse()?))
+        }
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = s.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix).then(|| &s[prefix.len()..])
+}
+
+fn parse_u7(s: &str) -> Result<cvm::Unsigned7> {
+    let value: u8 = s.parse().with_context(|| format!("invalid value {:?}", s))?;
+    cvm::Unsigned7::try_from(value).map_err(|_| anyhow!("value {:?} out of range", s))
+}
+
+/// Parses `key=value`.
+fn parse_field(s: &str, key: &str) -> Result<cvm::Unsigned7> {
+    let value = strip_prefix_ignore_case(s, key)
+        .and_then(|rest| rest.strip_prefix('='))
+        .ok_or_else(|| anyhow!("expected {}=, found {:?}", key, s))?;
+    parse_u7(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{MessageParseOutcomeStatus, Parser};
+    use crate::sysex::Summary;
+
+    fn from_bytes(bytes: &[u8]) -> Message {
+        match Parser::new().parse(bytes).unwrap().status {
+            MessageParseOutcomeStatus::Message(message) => message,
+            status => panic!("{:02X?} parsed as {:?}", bytes, status),
+        }
+    }
+
+    fn assert_round_trip(message: &Message, text: &str) {
+        assert_eq!(message.to_string(), text);
+        assert_eq!(&text.parse::<Message>().unwrap(), message, "{}", text);
+    }
+
+    #[test]
+    fn round_trip() {
+        let cases: &[(&[u8], &str)] = &[
+            (&[0x80, 0x3C, 0x00], "ch1 NoteOff C4 vel=0"),
+            (&[0x90, 0x3C, 0x64], "ch1 NoteOn C4 vel=100"),
+            (&[0xA9, 0x2A, 0x28], "ch10 PolyPressure F#2 val=40"),
+            (&[0xB0, 0x07, 0x64], "ch1 CC 7 val=100"),
+            (&[0xCF, 0x05], "ch16 Program 5"),
+            (&[0xD0, 0x40], "ch1 ChannelPressure 64"),
+            (&[0xE0, 0x00, 0x40], "ch1 PitchBend 8192"),
+            (&[0xE0, 0x00, 0x00], "ch1 PitchBend 0"),
+            (&[0xE0, 0x7F, 0x7F], "ch1 PitchBend 16383"),
+            (&[0xB0, 0x78, 0x00], "ch1 AllSoundOff"),
+            (&[0xB0, 0x79, 0x00], "ch1 ResetAllControllers"),
+            (&[0xB0, 0x7A, 0x7F], "ch1 LocalControl val=127"),
+            (&[0xB0, 0x7A, 0x00], "ch1 LocalControl"),
+            (&[0xB0, 0x7B, 0x00], "ch1 AllNotesOff"),
+            (&[0xB0, 0x7C, 0x00], "ch1 OmniOff"),
+            (&[0xB0, 0x7D, 0x00], "ch1 OmniOn"),
+            (&[0xB1, 0x7E, 0x02], "ch2 MonoOn val=2"),
+            (&[0xB0, 0x7F, 0x00], "ch1 PolyOn"),
+            (&[0xF8], "Clock"),
+            (&[0xF9], "Undefined(F9)"),
+            (&[0xFA], "Start"),
+            (&[0xFB], "Continue"),
+            (&[0xFC], "Stop"),
+            (&[0xFD], "Undefined(FD)"),
+            (&[0xFE], "ActiveSensing"),
+            (&[0xFF], "Reset"),
+        ];
+        for (bytes, text) in cases {
+            assert_round_trip(&from_bytes(bytes), text);
+        }
+        assert_round_trip(&Message::System(SystemMessage::SystemCommon(SystemCommonMessage)), "SystemCommon");
+    }
+
+    #[test]
+    fn sysex_with_summary() {
+        let sysex = Message::System(SystemMessage::SystemExclusive(SystemExclusiveMessage));
+        assert_round_trip(&sysex, "SysEx");
+        let data = [0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41];
+        let summary = Summary(&data).to_string();
+        assert_eq!(summary, "SysEx Roland 9 bytes");
+        assert_eq!(summary.parse::<Message>().unwrap(), sysex);
+    }
+
+    #[test]
+    fn parse_variants() {
+        assert_eq!("CH1 noteon 60 VEL=100".parse::<Message>().unwrap(), from_bytes(&[0x90, 0x3C, 0x64]));
+        assert_eq!("ch1 LocalControl val=0".parse::<Message>().unwrap(), from_bytes(&[0xB0, 0x7A, 0x00]));
+        assert!("ch1 PitchBend 16384".parse::<Message>().is_err());
+        assert!("ch17 NoteOn C4 vel=1".parse::<Message>().is_err());
+        assert!("ch1 LocalControl 127".parse::<Message>().is_err());
+    }
+}
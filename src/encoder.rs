@@ -0,0 +1,91 @@
+//! Serialization of messages to MIDI 1.0 byte streams.
+//!
+//! This is the inverse of [`crate::parser`],
+//! and never uses running status.
+
+use anyhow::{Result, bail};
+use crate::message::*;
+use crate::parser::{status_nibbles, system_status_bytes};
+
+impl Message {
+    /// Appends the message's bytes, starting with its status byte.
+    ///
+    /// Fails for the system common and system exclusive message types,
+    /// which don't yet carry their data.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            Message::Channel(message) => {
+                message.encode(buf);
+                Ok(())
+            }
+            Message::System(SystemMessage::SystemRealTime(message)) => {
+                buf.push(u8::from(*message));
+                Ok(())
+            }
+            Message::System(SystemMessage::SystemCommon(_)) => {
+                bail!("can't encode system common message")
+            }
+            Message::System(SystemMessage::SystemExclusive(_)) => {
+                bail!("can't encode system exclusive message")
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl ChannelMessage {
+    pub fn status_byte(&self) -> u8 {
+        let nibble = match &self.message {
+            ChannelMessageType::ChannelVoice(message) => match message {
+                ChannelVoiceMessage::NoteOff(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_NOTE_OFF,
+                ChannelVoiceMessage::NoteOn(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_NOTE_ON,
+                ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_POLYPHONIC_KEY_PRESSURE_AFTERTOUCH,
+                ChannelVoiceMessage::ControlChange(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_CONTROL_CHANGE_OR_CHANNEL_MODE_MESSAGE,
+                ChannelVoiceMessage::ProgramChange(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_PROGRAM_CHANGE,
+                ChannelVoiceMessage::ChannelPressureAftertouch(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_CHANNEL_PRESSURE_AFTERTOUCH,
+                ChannelVoiceMessage::PitchBendChange(_) => status_nibbles::CHANNEL_VOICE_MESSAGE_PITCH_BEND_CHANGE,
+            },
+            ChannelMessageType::ChannelMode { .. } => status_nibbles::CHANNEL_VOICE_MESSAGE_CONTROL_CHANGE_OR_CHANNEL_MODE_MESSAGE,
+        };
+        nibble << 4 | u8::from(self.channel)
+    }
+
+    /// Appends the message's bytes, starting with its status byte.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(self.status_byte());
+        match &self.message {
+            ChannelMessageType::ChannelVoice(message) => match message {
+                ChannelVoiceMessage::NoteOff(m) => {
+                    buf.extend([u8::from(m.note_number.0), u8::from(m.velocity.0)]);
+                }
+                ChannelVoiceMessage::NoteOn(m) => {
+                    buf.extend([u8::from(m.note_number.0), u8::from(m.velocity.0)]);
+                }
+                ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(m) => {
+                    buf.extend([u8::from(m.note_number.0), u8::from(m.value)]);
+                }
+                ChannelVoiceMessage::ControlChange(m) => {
+                    buf.extend([u8::from(m.control_number.0), u8::from(m.value)]);
+                }
+                ChannelVoiceMessage::ProgramChange(m) => {
+                    buf.push(u8::from(m.program_number.0));
+                }
+                ChannelVoiceMessage::ChannelPressureAftertouch(m) => {
+                    buf.push(u8::from(m.value));
+                }
+                ChannelVoiceMessage::PitchBendChange(m) => {
+                    let value = u16::from(m.value);
+                    buf.extend([(value & 0x7F) as u8, (value >> 7) as u8]);
+                }
+            },
+            ChannelMessageType::ChannelMode { mode, value } => {
+                buf.extend([u8::from(*mode), u8::from(*value)]);
+            }
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! General MIDI names: instruments, families, percussion keys and drum kits.
+//!
+//! Program numbers are as sent, from 0, though documents usually list them from 1.
+//! Name lookups ignore case.
+//!
+//! Reference: General MIDI System Level 1 and 2; Roland GS and Yamaha XG drum kit lists
+
+use crate::message::cvm::{NoteNumber, ProgramNumber, Unsigned7};
+
+/// The MIDI channel of percussion, numbered from 1.
+pub const PERCUSSION_CHANNEL: u8 = 10;
+
+/// The bank select MSB of GM2 melodic sounds.
+pub const GM2_MELODY_BANK_MSB: u8 = 0x79;
+/// The bank select MSB of GM2 drum kits.
+pub const GM2_RHYTHM_BANK_MSB: u8 = 0x78;
+/// The bank select MSB of XG drum kits.
+pub const XG_DRUM_BANK_MSB: u8 = 0x7F;
+/// The bank select MSB of XG sound effect kits.
+pub const XG_SFX_BANK_MSB: u8 = 0x7E;
+
+const PROGRAM_NAMES: [&str; 128] = [
+    "Acoustic Grand Piano", "Bright Acoustic Piano", "Electric Grand Piano", "Honky-tonk Piano",
+    "Electric Piano 1", "Electric Piano 2", "Harpsichord", "Clavi",
+    "Celesta", "Glockenspiel", "Music Box", "Vibraphone",
+    "Marimba", "Xylophone", "Tubular Bells", "Dulcimer",
+    "Drawbar Organ", "Percussive Organ", "Rock Organ", "Church Organ",
+    "Reed Organ", "Accordion", "Harmonica", "Tango Accordion",
+    "Acoustic Guitar (nylon)", "Acoustic Guitar (steel)", "Electric Guitar (jazz)", "Electric Guitar (clean)",
+    "Electric Guitar (muted)", "Overdriven Guitar", "Distortion Guitar", "Guitar harmonics",
+    "Acoustic Bass", "Electric Bass (finger)", "Electric Bass (pick)", "Fretless Bass",
+    "Slap Bass 1", "Slap Bass 2", "Synth Bass 1", "Synth Bass 2",
+    "Violin", "Viola", "Cello", "Contrabass",
+    "Tremolo Strings", "Pizzicato Strings", "Orchestral Harp", "Timpani",
+    "String Ensemble 1", "String Ensemble 2", "SynthStrings 1", "SynthStrings 2",
+    "Choir Aahs", "Voice Oohs", "Synth Voice", "Orchestra Hit",
+    "Trumpet", "Trombone", "Tuba", "Muted Trumpet",
+    "French Horn", "Brass Section", "SynthBrass 1", "SynthBrass 2",
+    "Soprano Sax", "Alto Sax", "Tenor Sax", "Baritone Sax",
+    "Oboe", "English Horn", "Bassoon", "Clarinet",
+    "Piccolo", "Flute", "Recorder", "Pan Flute",
+    "Blown Bottle", "Shakuhachi", "Whistle", "Ocarina",
+    "Lead 1 (square)", "Lead 2 (sawtooth)", "Lead 3 (calliope)", "Lead 4 (chiff)",
+    "Lead 5 (charang)", "Lead 6 (voice)", "Lead 7 (fifths)", "Lead 8 (bass + lead)",
+    "Pad 1 (new age)", "Pad 2 (warm)", "Pad 3 (polysynth)", "Pad 4 (choir)",
+    "Pad 5 (bowed)", "Pad 6 (metallic)", "Pad 7 (halo)", "Pad 8 (sweep)",
+    "FX 1 (rain)", "FX 2 (soundtrack)", "FX 3 (crystal)", "FX 4 (atmosphere)",
+    "FX 5 (brightness)", "FX 6 (goblins)", "FX 7 (echoes)", "FX 8 (sci-fi)",
+    "Sitar", "Banjo", "Shamisen", "Koto",
+    "Kalimba", "Bag pipe", "Fiddle", "Shanai",
+    "Tinkle Bell", "Agogo", "Steel Drums", "Woodblock",
+    "Taiko Drum", "Melodic Tom", "Synth Drum", "Reverse Cymbal",
+    "Guitar Fret Noise", "Breath Noise", "Seashore", "Bird Tweet",
+    "Telephone Ring", "Helicopter", "Applause", "Gunshot",
+];
+
+/// GM2 sounds besides the GM1 sound of each program, by program and bank select LSB.
+///
+/// Reference: General MIDI 2, "GM2 Sound Set"
+const GM2_VARIATIONS: &[(u8, u8, &str)] = &[
+    (0, 1, "Wide Acoustic Grand"),
+    (0, 2, "Dark Acoustic Grand"),
+    (1, 1, "Wide Bright Acoustic"),
+    (2, 1, "Wide Electric Grand"),
+    (3, 1, "Wide Honky-tonk"),
+    (4, 1, "Detuned Electric Piano 1"),
+    (4, 2, "Electric Piano 1 Variation"),
+    (4, 3, "60's Electric Piano"),
+    (5, 1, "Detuned Electric Piano 2"),
+    (5, 2, "Electric Piano 2 Variation"),
+    (5, 3, "Legend Electric Piano"),
+    (5, 4, "Phase Electric Piano"),
+    (6, 1, "Coupled Harpsichord"),
+    (6, 2, "Wide Harpsichord"),
+    (6, 3, "Open Harpsichord"),
+    (7, 1, "Pulse Clavinet"),
+    (11, 1, "Wet Vibraphone"),
+    (12, 1, "Wide Marimba"),
+    (14, 1, "Church Bell"),
+    (14, 2, "Carillon"),
+    (15, 1, "Santur"),
+    (16, 1, "Detuned Drawbar Organ"),
+    (16, 2, "Italian 60's Organ"),
+    (16, 3, "Drawbar Organ 2"),
+    (17, 1, "Detuned Percussive Organ"),
+    (17, 2, "Percussive Organ 2"),
+    (19, 1, "Church Organ Octave Mix"),
+    (19, 2, "Detuned Church Organ"),
+    (20, 1, "Puff Organ"),
+    (21, 1, "Accordion 2"),
+    (24, 1, "Ukulele"),
+    (24, 2, "Open Nylon Guitar"),
+    (24, 3, "Nylon Guitar 2"),
+    (25, 1, "12-Strings Guitar"),
+    (25, 2, "Mandolin"),
+    (25, 3, "Steel Guitar with Body Sound"),
+    (26, 1, "Pedal Steel Guitar"),
+    (27, 1, "Detuned Clean Electric Guitar"),
+    (27, 2, "Mid Tone Guitar"),
+    (28, 1, "Funk Cutting Guitar"),
+    (28, 2, "Muted Velo-Sensitive Guitar"),
+    (28, 3, "Jazz Man"),
+    (29, 1, "Guitar Pinch"),
+    (30, 1, "Distortion Guitar (with Feedback)"),
+    (30, 2, "Distorted Rhythm Guitar"),
+    (31, 1, "Guitar Feedback"),
+    (33, 1, "Finger Slap Bass"),
+    (38, 1, "Synth Bass (warm)"),
+    (38, 2, "Synth Bass 3 (resonance)"),
+    (38, 3, "Clavi Bass"),
+    (38, 4, "Hammer"),
+    (39, 1, "Synth Bass 4 (attack)"),
+    (39, 2, "Synth Bass (rubber)"),
+    (39, 3, "Attack Pulse"),
+    (40, 1, "Slow Violin"),
+    (46, 1, "Yang Qin"),
+    (48, 1, "Strings and Brass"),
+    (48, 2, "60s Strings"),
+    (50, 1, "Synth Strings 3"),
+    (52, 1, "Choir Aahs 2"),
+    (53, 1, "Humming"),
+    (54, 1, "Analog Voice"),
+    (55, 1, "Bass Hit Plus"),
+    (55, 2, "6th Hit"),
+    (55, 3, "Euro Hit"),
+    (56, 1, "Dark Trumpet Soft"),
+    (57, 1, "Trombone 2"),
+    (57, 2, "Bright Trombone"),
+    (59, 1, "Muted Trumpet 2"),
+    (60, 1, "French Horn 2 (warm)"),
+    (61, 1, "Brass 2 (octave mix)"),
+    (62, 1, "Synth Brass 3"),
+    (62, 2, "Analog Synth Brass 1"),
+    (62, 3, "Jump Brass"),
+    (63, 1, "Synth Brass 4"),
+    (63, 2, "Analog Synth Brass 2"),
+    (80, 1, "Square"),
+    (80, 2, "Sine Wave"),
+    (81, 1, "Saw"),
+    (81, 2, "Doctor Solo"),
+    (81, 3, "Natural Lead"),
+    (81, 4, "Sequenced Saw"),
+    (87, 1, "Soft Wrl"),
+    (89, 1, "Sine Pad"),
+    (91, 1, "Itopia"),
+    (98, 1, "Synth Mallet"),
+    (102, 1, "Echo Bell"),
+    (102, 2, "Echo Pan"),
+    (104, 1, "Sitar 2 (bend)"),
+    (107, 1, "Taisho Koto"),
+    (115, 1, "Castanets"),
+    (116, 1, "Concert Bass Drum"),
+    (117, 1, "Melodic Tom 2 (power)"),
+    (118, 1, "Rhythm Box Tom"),
+    (118, 2, "Electric Drum"),
+    (120, 1, "Guitar Cutting Noise"),
+    (120, 2, "Acoustic Bass String Slap"),
+    (121, 1, "Flute Key Click"),
+    (122, 1, "Rain"),
+    (122, 2, "Thunder"),
+    (122, 3, "Wind"),
+    (122, 4, "Stream"),
+    (122, 5, "Bubble"),
+    (123, 1, "Dog"),
+    (123, 2, "Horse Gallop"),
+    (123, 3, "Bird Tweet 2"),
+    (124, 1, "Telephone Ring 2"),
+    (124, 2, "Door Creaking"),
+    (124, 3, "Door"),
+    (124, 4, "Scratch"),
+    (124, 5, "Wind Chime"),
+    (125, 1, "Car Engine"),
+    (125, 2, "Car Stop"),
+    (125, 3, "Car Pass"),
+    (125, 4, "Car Crash"),
+    (125, 5, "Siren"),
+    (125, 6, "Train"),
+    (125, 7, "Jetplane"),
+    (125, 8, "Starship"),
+    (125, 9, "Burst Noise"),
+    (126, 1, "Laughing"),
+    (126, 2, "Screaming"),
+    (126, 3, "Punch"),
+    (126, 4, "Heart Beat"),
+    (126, 5, "Footsteps"),
+    (127, 1, "Machine Gun"),
+    (127, 2, "Lasergun"),
+    (127, 3, "Explosion"),
+];
+
+/// GM2 percussion, keys 27 to 87; GM1 defines 35 to 81.
+const PERCUSSION_NAMES: [&str; 61] = [
+    "High Q", "Slap", "Scratch Push", "Scratch Pull",
+    "Sticks", "Square Click", "Metronome Click", "Metronome Bell",
+    "Acoustic Bass Drum", "Bass Drum 1", "Side Stick", "Acoustic Snare",
+    "Hand Clap", "Electric Snare", "Low Floor Tom", "Closed Hi-Hat",
+    "High Floor Tom", "Pedal Hi-Hat", "Low Tom", "Open Hi-Hat",
+    "Low-Mid Tom", "Hi-Mid Tom", "Crash Cymbal 1", "High Tom",
+    "Ride Cymbal 1", "Chinese Cymbal", "Ride Bell", "Tambourine",
+    "Splash Cymbal", "Cowbell", "Crash Cymbal 2", "Vibraslap",
+    "Ride Cymbal 2", "Hi Bongo", "Low Bongo", "Mute Hi Conga",
+    "Open Hi Conga", "Low Conga", "High Timbale", "Low Timbale",
+    "High Agogo", "Low Agogo", "Cabasa", "Maracas",
+    "Short Whistle", "Long Whistle", "Short Guiro", "Long Guiro",
+    "Claves", "Hi Wood Block", "Low Wood Block", "Mute Cuica",
+    "Open Cuica", "Mute Triangle", "Open Triangle", "Shaker",
+    "Jingle Bell", "Belltree", "Castanets", "Mute Surdo",
+    "Open Surdo",
+];
+const FIRST_PERCUSSION_KEY: u8 = 27;
+
+/// GM2 drum kits, by program; GS uses the same programs.
+const GM2_DRUM_KITS: &[(u8, &str)] = &[
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "Analog Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+
+const GS_DRUM_KITS: &[(u8, &str)] = &[
+    (0, "STANDARD"),
+    (8, "ROOM"),
+    (16, "POWER"),
+    (24, "ELECTRONIC"),
+    (25, "TR-808"),
+    (32, "JAZZ"),
+    (40, "BRUSH"),
+    (48, "ORCHESTRA"),
+    (56, "SFX"),
+    (127, "CM-64/CM-32L"),
+];
+
+/// XG drum kits, by bank select MSB and program.
+const XG_DRUM_KITS: &[(u8, u8, &str)] = &[
+    (XG_DRUM_BANK_MSB, 0, "Standard Kit"),
+    (XG_DRUM_BANK_MSB, 1, "Standard Kit 2"),
+    (XG_DRUM_BANK_MSB, 8, "Room Kit"),
+    (XG_DRUM_BANK_MSB, 16, "Rock Kit"),
+    (XG_DRUM_BANK_MSB, 24, "Electro Kit"),
+    (XG_DRUM_BANK_MSB, 25, "Analog Kit"),
+    (XG_DRUM_BANK_MSB, 32, "Jazz Kit"),
+    (XG_DRUM_BANK_MSB, 40, "Brush Kit"),
+    (XG_DRUM_BANK_MSB, 48, "Classic Kit"),
+    (XG_SFX_BANK_MSB, 0, "SFX Kit 1"),
+    (XG_SFX_BANK_MSB, 1, "SFX Kit 2"),
+];
+
+/// The sixteen groups of eight GM programs.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Family {
+    Piano,
+    ChromaticPercussion,
+    Organ,
+    Guitar,
+    Bass,
+    Strings,
+    Ensemble,
+    Brass,
+    Reed,
+    Pipe,
+    SynthLead,
+    SynthPad,
+    SynthEffects,
+    Ethnic,
+    Percussive,
+    SoundEffects,
+}
+
+impl Family {
+    pub const ALL: [Family; 16] = [
+        Family::Piano,
+        Family::ChromaticPercussion,
+        Family::Organ,
+        Family::Guitar,
+        Family::Bass,
+        Family::Strings,
+        Family::Ensemble,
+        Family::Brass,
+        Family::Reed,
+        Family::Pipe,
+        Family::SynthLead,
+        Family::SynthPad,
+        Family::SynthEffects,
+        Family::Ethnic,
+        Family::Percussive,
+        Family::SoundEffects,
+    ];
+
+    pub fn of(program: ProgramNumber) -> Family {
+        Family::ALL[(u8::from(program.0) / 8) as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Piano => "Piano",
+            Family::ChromaticPercussion => "Chromatic Percussion",
+            Family::Organ => "Organ",
+            Family::Guitar => "Guitar",
+            Family::Bass => "Bass",
+            Family::Strings => "Strings",
+            Family::Ensemble => "Ensemble",
+            Family::Brass => "Brass",
+            Family::Reed => "Reed",
+            Family::Pipe => "Pipe",
+            Family::SynthLead => "Synth Lead",
+            Family::SynthPad => "Synth Pad",
+            Family::SynthEffects => "Synth Effects",
+            Family::Ethnic => "Ethnic",
+            Family::Percussive => "Percussive",
+            Family::SoundEffects => "Sound Effects",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Family> {
+        Family::ALL.into_iter().find(|family| family.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The eight programs of the family.
+    pub fn programs(self) -> impl Iterator<Item = ProgramNumber> {
+        let first = self as u8 * 8;
+        (first..first + 8).map(program_number)
+    }
+}
+
+fn program_number(program: u8) -> ProgramNumber {
+    ProgramNumber(Unsigned7::try_from(program).expect("7-bit program"))
+}
+
+pub fn program_name(program: ProgramNumber) -> &'static str {
+    PROGRAM_NAMES[u8::from(program.0) as usize]
+}
+
+pub fn program_from_name(name: &str) -> Option<ProgramNumber> {
+    PROGRAM_NAMES.iter()
+        .position(|program_name| program_name.eq_ignore_ascii_case(name))
+        .map(|program| program_number(program as u8))
+}
+
+/// The GM2 name of a melodic sound, selected by bank select LSB under [`GM2_MELODY_BANK_MSB`].
+///
+/// Banks without a variation of the program fall back to its GM1 sound, as GM2 receivers do.
+pub fn gm2_program_name(bank_lsb: u8, program: ProgramNumber) -> &'static str {
+    let program_byte = u8::from(program.0);
+    GM2_VARIATIONS.iter()
+        .find(|(p, lsb, _)| *p == program_byte && *lsb == bank_lsb)
+        .map_or_else(|| program_name(program), |(_, _, name)| *name)
+}
+
+/// The bank select LSB and program of a GM2 melodic sound.
+pub fn gm2_program_from_name(name: &str) -> Option<(u8, ProgramNumber)> {
+    program_from_name(name).map(|program| (0, program)).or_else(|| {
+        GM2_VARIATIONS.iter()
+            .find(|(_, _, variation)| variation.eq_ignore_ascii_case(name))
+            .map(|(program, lsb, _)| (*lsb, program_number(*program)))
+    })
+}
+
+/// The GM2 name of a percussion key, which includes the GM1 keys.
+pub fn percussion_name(note: NoteNumber) -> Option<&'static str> {
+    let index = u8::from(note.0).checked_sub(FIRST_PERCUSSION_KEY)?;
+    PERCUSSION_NAMES.get(index as usize).copied()
+}
+
+/// Whether a key is defined by GM1, rather than only GM2.
+pub fn is_gm1_percussion(note: NoteNumber) -> bool {
+    (35..=81).contains(&u8::from(note.0))
+}
+
+pub fn percussion_from_name(name: &str) -> Option<NoteNumber> {
+    PERCUSSION_NAMES.iter()
+        .position(|percussion_name| percussion_name.eq_ignore_ascii_case(name))
+        .map(|index| NoteNumber(Unsigned7::try_from(FIRST_PERCUSSION_KEY + index as u8).expect("7-bit key")))
+}
+
+fn kit_name(kits: &[(u8, &'static str)], program: ProgramNumber) -> Option<&'static str> {
+    kits.iter().find(|(p, _)| *p == u8::from(program.0)).map(|(_, name)| *name)
+}
+
+fn kit_from_name(kits: &[(u8, &'static str)], name: &str) -> Option<ProgramNumber> {
+    kits.iter().find(|(_, kit)| kit.eq_ignore_ascii_case(name)).map(|(program, _)| program_number(*program))
+}
+
+/// The GM2 drum kit of a program under [`GM2_RHYTHM_BANK_MSB`].
+pub fn gm2_drum_kit_name(program: ProgramNumber) -> Option<&'static str> {
+    kit_name(GM2_DRUM_KITS, program)
+}
+
+pub fn gm2_drum_kit_from_name(name: &str) -> Option<ProgramNumber> {
+    kit_from_name(GM2_DRUM_KITS, name)
+}
+
+/// The GS drum kit of a program on a drum part.
+pub fn gs_drum_kit_name(program: ProgramNumber) -> Option<&'static str> {
+    kit_name(GS_DRUM_KITS, program)
+}
+
+pub fn gs_drum_kit_from_name(name: &str) -> Option<ProgramNumber> {
+    kit_from_name(GS_DRUM_KITS, name)
+}
+
+/// The XG drum kit of a program under [`XG_DRUM_BANK_MSB`] or [`XG_SFX_BANK_MSB`].
+pub fn xg_drum_kit_name(bank_msb: u8, program: ProgramNumber) -> Option<&'static str> {
+    XG_DRUM_KITS.iter()
+        .find(|(msb, p, _)| *msb == bank_msb && *p == u8::from(program.0))
+        .map(|(_, _, name)| *name)
+}
+
+/// The bank select MSB and program of an XG drum kit.
+pub fn xg_drum_kit_from_name(name: &str) -> Option<(u8, ProgramNumber)> {
+    XG_DRUM_KITS.iter()
+        .find(|(_, _, kit)| kit.eq_ignore_ascii_case(name))
+        .map(|(msb, program, _)| (*msb, program_number(*program)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gm2_variations_round_trip() {
+        assert_eq!(GM2_VARIATIONS.len(), 128);
+        for &(program, lsb, name) in GM2_VARIATIONS {
+            let program = program_number(program);
+            assert_eq!(gm2_program_name(lsb, program), name);
+            assert_eq!(gm2_program_from_name(name), Some((lsb, program)), "{}", name);
+        }
+        assert_eq!(gm2_program_name(1, program_number(15)), "Santur");
+        assert_eq!(gm2_program_name(0, program_number(15)), "Dulcimer");
+    }
+}
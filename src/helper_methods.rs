@@ -1,6 +1,10 @@
 //! Methods that provide some higher-level interpretation of MIDI messages.
 
+use anyhow::{Result, anyhow};
+use crate::assert_from::AssertFrom;
 use crate::message::{self, cvm};
+use crate::pitch_bend::BendRange;
+use crate::tuning;
 
 impl message::ChannelVoiceMessage {
     /// Returns if the note should turn off.
@@ -16,10 +20,119 @@ impl message::ChannelVoiceMessage {
     }
 }
 
+impl cvm::NoteNumber {
+    /// Middle C.
+    pub const MIDDLE_C: u8 = 60;
+    /// The note A4 is tuned to.
+    pub const A4: u8 = 69;
+
+    /// The frequency in twelve-tone equal temperament, with A4 at `a4` Hz.
+    pub fn frequency(&self, a4: f64) -> f64 {
+        tuning::semitones_to_frequency(u8::from(self.0) as f64, a4)
+    }
+
+    /// The nearest note to a frequency, and how many cents the frequency is above it.
+    ///
+    /// Frequencies beyond the range of notes give the lowest or highest note.
+    pub fn from_frequency(frequency: f64, a4: f64) -> Result<(cvm::NoteNumber, f64)> {
+        if !(frequency > 0.0 && frequency.is_finite() && a4 > 0.0 && a4.is_finite()) {
+            return Err(anyhow!("invalid frequency {} with A4 at {}", frequency, a4));
+        }
+        let semitones = tuning::frequency_to_semitones(frequency, a4);
+        let note = cvm::NoteNumber(cvm::Unsigned7::assert_from(semitones.round().clamp(0.0, 127.0) as u8));
+        let cents = (semitones - u8::from(note.0) as f64) * 100.0;
+        Ok((note, cents))
+    }
+
+    /// Transposes by `semitones`, or `None` if that leaves the range of notes.
+    pub fn checked_add(&self, semitones: i32) -> Option<cvm::NoteNumber> {
+        let note = u8::try_from((u8::from(self.0) as i32).checked_add(semitones)?).ok()?;
+        cvm::Unsigned7::try_from(note).ok().map(cvm::NoteNumber)
+    }
+
+    /// Transposes by `semitones`, stopping at note 0 or 127.
+    pub fn saturating_add(&self, semitones: i32) -> cvm::NoteNumber {
+        let note = (u8::from(self.0) as i32).saturating_add(semitones).clamp(0, 127);
+        cvm::NoteNumber(cvm::Unsigned7::assert_from(note as u8))
+    }
+
+    /// The interval up to `other` in semitones, negative if `other` is lower.
+    pub fn interval_to(&self, other: cvm::NoteNumber) -> i32 {
+        u8::from(other.0) as i32 - u8::from(self.0) as i32
+    }
+
+    /// Whether the note is a black key.
+    pub fn is_black_key(&self) -> bool {
+        matches!(u8::from(self.0) % 12, 1 | 3 | 6 | 8 | 10)
+    }
+}
+
 impl cvm::PitchBendChange {
+    /// The value of no bend.
+    pub const CENTER: u16 = 0x2000;
+
     /// Reference: todo
     pub fn is_centered(&self) -> bool {
-        u16::from(self.value) == 0x2000
+        u16::from(self.value) == Self::CENTER
+    }
+
+    /// The value relative to center, from -8192 to 8191.
+    pub fn offset(&self) -> i16 {
+        u16::from(self.value) as i16 - Self::CENTER as i16
+    }
+
+    pub fn from_offset(offset: i16) -> Result<cvm::PitchBendChange> {
+        let value = u16::try_from(offset as i32 + Self::CENTER as i32)
+            .map_err(|_| anyhow!("pitch bend offset out of range: {}", offset))?;
+        Ok(cvm::PitchBendChange {
+            value: cvm::Unsigned14::try_from(value)?,
+        })
+    }
+
+    /// The bend from -1.0 to 1.0.
+    ///
+    /// Both extremes are reached: the minimum value is -1.0 and the maximum 1.0,
+    /// though there are 8192 steps below center and 8191 above.
+    pub fn normalized(&self) -> f32 {
+        let offset = self.offset() as f32;
+        if offset < 0.0 {
+            offset / 8192.0
+        } else {
+            offset / 8191.0
+        }
+    }
+
+    /// Rounds a bend from -1.0 to 1.0 to the nearest value, clamping it.
+    pub fn from_normalized(bend: f32) -> cvm::PitchBendChange {
+        let bend = if bend.is_nan() { 0.0 } else { bend.clamp(-1.0, 1.0) };
+        let offset = if bend < 0.0 { bend * 8192.0 } else { bend * 8191.0 };
+        cvm::PitchBendChange::from_offset(offset.round() as i16).expect("offset in range")
+    }
+
+    /// The bend in semitones, given the receiver's bend range.
+    pub fn semitones(&self, range: BendRange) -> f32 {
+        self.normalized() * range.as_semitones()
+    }
+
+    /// The value bending by `semitones`, clamped to the bend range.
+    pub fn from_semitones(semitones: f32, range: BendRange) -> cvm::PitchBendChange {
+        let range = range.as_semitones();
+        if range == 0.0 {
+            return cvm::PitchBendChange {
+                value: cvm::Unsigned14::assert_from(Self::CENTER),
+            };
+        }
+        cvm::PitchBendChange::from_normalized(semitones / range)
+    }
+
+    /// The bend in cents, given the receiver's bend range.
+    pub fn cents(&self, range: BendRange) -> f32 {
+        self.semitones(range) * 100.0
+    }
+
+    /// The value bending by `cents`, clamped to the bend range.
+    pub fn from_cents(cents: f32, range: BendRange) -> cvm::PitchBendChange {
+        cvm::PitchBendChange::from_semitones(cents / 100.0, range)
     }
 }
 
@@ -0,0 +1,306 @@
+//! Text logs of MIDI bytes, as pasted into bug reports and kept as test fixtures.
+//!
+//! Each line holds an optional timestamp, then bytes as two-digit hex separated by whitespace:
+//!
+//! ```text
+//! # a comment
+//! 0.000 90 3C 7F
+//! 0.500 80 3C 00
+//! F0 7E 7F 06 01 F7
+//! ```
+//!
+//! Timestamps may be seconds (`1.5`, `1.5s`), milliseconds (`150ms`), clock time (`01:02.500`),
+//! optionally in brackets, or eight hex digits of milliseconds as MIDI-OX writes them.
+//! Messages may span lines; each takes the time of the line it starts on.
+//! System common messages, such as MTC quarter frames, are skipped with a warning
+//! until the parser supports them.
+
+use anyhow::{Context, Result, anyhow, bail};
+use std::fmt;
+use std::time::Duration;
+use crate::message::*;
+use crate::parser::{MessageParseOutcomeStatus, Parser};
+use crate::sysex::Summary;
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum LoggedMessage {
+    Message(Message),
+    /// SysEx data, without `F0` and `F7`, as returned by [`Parser::parse`].
+    SysEx(Vec<u8>),
+}
+
+/// Formats messages as [`Message`] does, and SysEx as a [`crate::sysex::Summary`].
+impl fmt::Display for LoggedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoggedMessage::Message(message) => write!(f, "{}", message),
+            LoggedMessage::SysEx(data) => write!(f, "{}", Summary(data)),
+        }
+    }
+}
+
+/// A message read from a log, with the bytes that carried it.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    pub time: Option<Duration>,
+    /// As read, but starting with the status byte even if the log used running status.
+    pub bytes: Vec<u8>,
+    pub message: LoggedMessage,
+}
+
+impl LogEvent {
+    pub fn from_message(time: Option<Duration>, message: Message) -> Result<LogEvent> {
+        Ok(LogEvent {
+            time,
+            bytes: message.to_bytes()?,
+            message: LoggedMessage::Message(message),
+        })
+    }
+
+    /// A SysEx message from its data, without `F0` and `F7`.
+    pub fn from_sysex(time: Option<Duration>, data: &[u8]) -> LogEvent {
+        let mut bytes = Vec::with_capacity(data.len() + 2);
+        bytes.push(0xF0);
+        bytes.extend_from_slice(data);
+        bytes.push(0xF7);
+        LogEvent {
+            time,
+            bytes,
+            message: LoggedMessage::SysEx(data.to_vec()),
+        }
+    }
+}
+
+/// A line's timestamp and bytes.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub time: Option<Duration>,
+    pub bytes: Vec<u8>,
+}
+
+fn parse_byte(token: &str) -> Option<u8> {
+    if token.len() == 2 {
+        u8::from_str_radix(token, 16).ok()
+    } else {
+        None
+    }
+}
+
+fn parse_seconds(text: &str) -> Option<f64> {
+    let seconds: f64 = text.parse().ok()?;
+    (seconds >= 0.0 && seconds.is_finite()).then_some(seconds)
+}
+
+/// Parses a timestamp token, or returns `None` if it isn't one.
+pub fn parse_timestamp(token: &str) -> Option<Duration> {
+    let token = token.strip_prefix('[').and_then(|token| token.strip_suffix(']')).unwrap_or(token);
+    if token.len() == 8 && token.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return u64::from_str_radix(token, 16).ok().map(Duration::from_millis);
+    }
+    if let Some(millis) = token.strip_suffix("ms") {
+        return parse_seconds(millis).map(|millis| Duration::from_secs_f64(millis / 1000.0));
+    }
+    if let Some(seconds) = token.strip_suffix('s') {
+        return parse_seconds(seconds).map(Duration::from_secs_f64);
+    }
+    if token.contains(':') {
+        let mut seconds = 0.0;
+        for part in token.split(':') {
+            seconds = seconds * 60.0 + parse_seconds(part)?;
+        }
+        return Some(Duration::from_secs_f64(seconds));
+    }
+    if token.contains('.') {
+        return parse_seconds(token).map(Duration::from_secs_f64);
+    }
+    None
+}
+
+/// Parses one line, returning `None` for blank lines and comments.
+///
+/// Comments start with `#`, `;` or `//` and run to the end of the line.
+pub fn parse_line(line: &str) -> Result<Option<LogLine>> {
+    let line = ["#", ";", "//"].iter()
+        .filter_map(|marker| line.find(marker))
+        .min()
+        .map_or(line, |comment| &line[..comment]);
+    let mut tokens = line.split_whitespace().peekable();
+    let Some(first) = tokens.peek() else {
+        return Ok(None);
+    };
+    let time = match parse_byte(first) {
+        Some(_) => None,
+        None => {
+            let time = parse_timestamp(first).ok_or_else(|| anyhow!("invalid byte or timestamp {:?}", first))?;
+            tokens.next();
+            Some(time)
+        }
+    };
+    let bytes = tokens
+        .map(|token| parse_byte(token).ok_or_else(|| anyhow!("invalid hex byte {:?}", token)))
+        .collect::<Result<_>>()?;
+    Ok(Some(LogLine { time, bytes }))
+}
+
+/// Reads a log line by line, passing its bytes through a [`Parser`].
+pub struct LogReader {
+    parser: Parser,
+    pending: Vec<u8>,
+    pending_time: Option<Duration>,
+    line_number: usize,
+}
+
+impl Default for LogReader {
+    fn default() -> LogReader {
+        LogReader::new()
+    }
+}
+
+impl LogReader {
+    pub fn new() -> LogReader {
+        LogReader {
+            parser: Parser::new(),
+            pending: Vec::new(),
+            pending_time: None,
+            line_number: 0,
+        }
+    }
+
+    /// Reads a line, pushing the messages it completes.
+    ///
+    /// Stray data bytes and broken messages are skipped with a warning, as a receiver would.
+    pub fn push_line(&mut self, line: &str, out: &mut Vec<LogEvent>) -> Result<()> {
+        self.line_number += 1;
+        let line_number = self.line_number;
+        let Some(LogLine { time, bytes }) = parse_line(line).with_context(|| format!("line {}", line_number))? else {
+            return Ok(());
+        };
+        if self.pending.is_empty() || self.pending_time.is_none() {
+            self.pending_time = time;
+        }
+        self.pending.extend(bytes);
+
+        loop {
+            if let Some(len) = self.pending.first().and_then(|status| system_common_data_len(*status)) {
+                let data_len = self.pending[1..].iter().take(len).take_while(|byte| **byte < 0x80).count();
+                if data_len < len && 1 + data_len == self.pending.len() {
+                    break;
+                }
+                log::warn!("line {}: skipping system common message {}", line_number, format_bytes(&self.pending[..1 + data_len]));
+                self.pending.drain(..1 + data_len);
+                // System common messages cancel running status.
+                self.parser = Parser::new();
+                if self.pending.is_empty() {
+                    break;
+                }
+                self.pending_time = time;
+                continue;
+            }
+            let outcome = self.parser.parse(&self.pending).with_context(|| format!("line {}", line_number))?;
+            let consumed = outcome.bytes_consumed;
+            let event_time = self.pending_time;
+            match outcome.status {
+                MessageParseOutcomeStatus::NeedMoreBytes(_) => break,
+                MessageParseOutcomeStatus::Message(message) => {
+                    let mut bytes = Vec::with_capacity(consumed + 1);
+                    if let (Message::Channel(channel_message), Some(0x00..=0x7F)) = (&message, self.pending.first()) {
+                        bytes.push(channel_message.status_byte());
+                    }
+                    bytes.extend_from_slice(&self.pending[..consumed]);
+                    out.push(LogEvent {
+                        time: event_time,
+                        bytes,
+                        message: LoggedMessage::Message(message),
+                    });
+                }
+                MessageParseOutcomeStatus::SystemExclusiveMessage(data) => {
+                    out.push(LogEvent::from_sysex(event_time, data));
+                }
+                MessageParseOutcomeStatus::InterruptingSystemRealTimeMessage { message, byte_index } => {
+                    self.pending.remove(byte_index);
+                    out.push(LogEvent::from_message(event_time, Message::System(SystemMessage::SystemRealTime(message)))?);
+                    continue;
+                }
+                status => log::warn!("line {}: skipping {:?} in {:02X?}", line_number, status, &self.pending[..consumed]),
+            }
+            self.pending.drain(..consumed);
+            if self.pending.is_empty() {
+                break;
+            }
+            self.pending_time = time;
+        }
+        Ok(())
+    }
+
+    /// Fails if the log ended within a message.
+    pub fn finish(self) -> Result<()> {
+        if !self.pending.is_empty() {
+            bail!("log ends within a message: {}", format_bytes(&self.pending));
+        }
+        Ok(())
+    }
+}
+
+/// The number of data bytes of a system common message, `F1` to `F6`.
+fn system_common_data_len(status: u8) -> Option<usize> {
+    match status {
+        0xF1 | 0xF3 => Some(1),
+        0xF2 => Some(2),
+        0xF4..=0xF6 => Some(0),
+        _ => None,
+    }
+}
+
+/// Reads every message of a log.
+pub fn read_log(text: &str) -> Result<Vec<LogEvent>> {
+    let mut reader = LogReader::new();
+    let mut events = Vec::new();
+    for line in text.lines() {
+        reader.push_line(line, &mut events)?;
+    }
+    reader.finish()?;
+    Ok(events)
+}
+
+/// Formats bytes as two-digit hex separated by spaces, such as `90 3C 7F`.
+pub fn format_bytes(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 3);
+    for (index, byte) in bytes.iter().enumerate() {
+        if index > 0 {
+            text.push(' ');
+        }
+        text.push_str(&format!("{:02X}", byte));
+    }
+    text
+}
+
+/// Formats a time in seconds, to the millisecond when exact and the microsecond otherwise.
+pub fn format_timestamp(time: Duration) -> String {
+    if time.subsec_nanos().is_multiple_of(1_000_000) {
+        format!("{}.{:03}", time.as_secs(), time.subsec_millis())
+    } else {
+        format!("{}.{:06}", time.as_secs(), time.subsec_micros())
+    }
+}
+
+/// Appends an event as a line of the log format.
+pub fn write_event(event: &LogEvent, text: &mut String) {
+    if let Some(time) = event.time {
+        text.push_str(&format_timestamp(time));
+        text.push(' ');
+    }
+    text.push_str(&format_bytes(&event.bytes));
+    text.push('\n');
+}
+
+/// Writes events as a log, one line each.
+pub fn write_log(events: &[LogEvent]) -> String {
+    let mut text = String::new();
+    for event in events {
+        write_event(event, &mut text);
+    }
+    text
+}
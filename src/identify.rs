@@ -0,0 +1,221 @@
+//! Identifying connected devices with Identity Request and Identity Reply.
+//!
+//! The [`Identifier`] sends a request and collects the replies to it until its timeout.
+//! Like the [`crate::sequencer::Sequencer`], it reads no clock of its own:
+//! the caller passes the current time to each method.
+//!
+//! Reference: MIDI spec, General Information: Device Inquiry
+
+use anyhow::Result;
+use std::time::Duration;
+use crate::parser::MessageParseOutcomeStatus;
+use crate::sysex::{self, Identity, NonRealtimeMessage, SysEx};
+
+/// A device that replied to an Identity Request.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct IdentifiedDevice {
+    /// The device ID of the reply, usually the device's own.
+    pub device_id: u8,
+    pub identity: Identity,
+}
+
+/// The complete Identity Request, `F0 7E <device ID> 06 01 F7`.
+pub fn identity_request(device_id: u8) -> Vec<u8> {
+    SysEx::UniversalNonRealtime {
+        device_id,
+        message: NonRealtimeMessage::IdentityRequest,
+    }.to_sysex_bytes().expect("7-bit device ID")
+}
+
+/// Sends Identity Requests and correlates the replies.
+pub struct Identifier {
+    device_id: u8,
+    deadline: Option<Duration>,
+    devices: Vec<IdentifiedDevice>,
+    /// How long to wait for replies after a request.
+    pub timeout: Duration,
+}
+
+impl Identifier {
+    /// Asks the device with `device_id`, or every device with [`sysex::ALL_CALL`].
+    pub fn new(device_id: u8) -> Identifier {
+        Identifier {
+            device_id: device_id & 0x7F,
+            deadline: None,
+            devices: Vec::new(),
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    /// Starts a new inquiry, forgetting earlier replies,
+    /// and returns the request to send.
+    pub fn request(&mut self, now: Duration) -> Vec<u8> {
+        self.deadline = Some(now + self.timeout);
+        self.devices.clear();
+        identity_request(self.device_id)
+    }
+
+    /// Whether replies are still awaited.
+    pub fn is_pending(&self, now: Duration) -> bool {
+        self.deadline.is_some_and(|deadline| now < deadline)
+    }
+
+    /// Handles a SysEx message, as returned by [`crate::parser::Parser::parse`],
+    /// returning the device if it was a reply to the current request.
+    ///
+    /// Other messages and replies received after the timeout are ignored.
+    pub fn handle_sysex(&mut self, data: &[u8], now: Duration) -> Result<Option<IdentifiedDevice>> {
+        if !self.is_pending(now) || !matches!(data, [sysex::UNIVERSAL_NON_REALTIME, _, sysex::non_realtime::GENERAL_INFORMATION, sysex::non_realtime::IDENTITY_REPLY, ..]) {
+            return Ok(None);
+        }
+        let SysEx::UniversalNonRealtime { device_id, message: NonRealtimeMessage::IdentityReply(identity) } = SysEx::parse(data)? else {
+            return Ok(None);
+        };
+        if self.device_id != sysex::ALL_CALL && device_id != self.device_id {
+            return Ok(None);
+        }
+        let device = IdentifiedDevice { device_id, identity };
+        if self.devices.contains(&device) {
+            return Ok(None);
+        }
+        self.devices.push(device.clone());
+        Ok(Some(device))
+    }
+
+    /// Handles the outcome of [`crate::parser::Parser::parse`].
+    pub fn handle_outcome(&mut self, status: &MessageParseOutcomeStatus, now: Duration) -> Result<Option<IdentifiedDevice>> {
+        match status {
+            MessageParseOutcomeStatus::SystemExclusiveMessage(data) => self.handle_sysex(data, now),
+            _ => Ok(None),
+        }
+    }
+
+    /// Ends the inquiry once its timeout has passed, returning every device that replied.
+    pub fn expire(&mut self, now: Duration) -> Option<Vec<IdentifiedDevice>> {
+        match self.deadline {
+            Some(deadline) if now >= deadline => {
+                self.deadline = None;
+                Some(std::mem::take(&mut self.devices))
+            }
+            _ => None,
+        }
+    }
+
+    /// The devices that have replied to the current request.
+    pub fn devices(&self) -> &[IdentifiedDevice] {
+        &self.devices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manufacturer;
+    use crate::parser::Parser;
+
+    /// A device that answers Identity Requests addressed to it.
+    struct FakeDevice {
+        device_id: u8,
+        identity: Identity,
+    }
+
+    impl FakeDevice {
+        fn receive(&self, bytes: &[u8]) -> Option<Vec<u8>> {
+            let [0xF0, data @ .., 0xF7] = bytes else {
+                return None;
+            };
+            match SysEx::parse(data).ok()? {
+                SysEx::UniversalNonRealtime { device_id, message: NonRealtimeMessage::IdentityRequest }
+                    if device_id == self.device_id || device_id == sysex::ALL_CALL => {
+                    Some(self.reply())
+                }
+                _ => None,
+            }
+        }
+
+        fn reply(&self) -> Vec<u8> {
+            SysEx::UniversalNonRealtime {
+                device_id: self.device_id,
+                message: NonRealtimeMessage::IdentityReply(self.identity.clone()),
+            }.to_sysex_bytes().unwrap()
+        }
+    }
+
+    fn device(device_id: u8, model: u16) -> FakeDevice {
+        FakeDevice {
+            device_id,
+            identity: Identity {
+                manufacturer_id: manufacturer::ROLAND,
+                family: 0x0042,
+                model,
+                version: [1, 0, 0, 0],
+            },
+        }
+    }
+
+    fn feed(identifier: &mut Identifier, bytes: &[u8], now: Duration) -> Option<IdentifiedDevice> {
+        let outcome = Parser::new().parse(bytes).unwrap();
+        assert_eq!(outcome.bytes_consumed, bytes.len());
+        identifier.handle_outcome(&outcome.status, now).unwrap()
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn reply_before_deadline() {
+        let fake = device(0x10, 1);
+        let mut identifier = Identifier::new(0x10);
+        let request = identifier.request(ms(0));
+        assert_eq!(request, [0xF0, 0x7E, 0x10, 0x06, 0x01, 0xF7]);
+        assert!(identifier.is_pending(ms(0)));
+
+        let reply = fake.receive(&request).unwrap();
+        let found = feed(&mut identifier, &reply, ms(100)).unwrap();
+        assert_eq!(found, IdentifiedDevice { device_id: 0x10, identity: fake.identity.clone() });
+        assert_eq!(identifier.devices(), [found]);
+    }
+
+    #[test]
+    fn duplicate_and_late_replies_ignored() {
+        let first = device(0x10, 1);
+        let second = device(0x11, 2);
+        let mut identifier = Identifier::new(sysex::ALL_CALL);
+        let request = identifier.request(ms(0));
+
+        let reply = first.receive(&request).unwrap();
+        assert!(feed(&mut identifier, &reply, ms(100)).is_some());
+        assert_eq!(feed(&mut identifier, &reply, ms(200)), None);
+
+        let late = second.receive(&request).unwrap();
+        assert!(!identifier.is_pending(ms(1000)));
+        assert_eq!(feed(&mut identifier, &late, ms(1500)), None);
+        assert_eq!(identifier.devices().len(), 1);
+    }
+
+    #[test]
+    fn other_device_ignored() {
+        let mut identifier = Identifier::new(0x10);
+        identifier.request(ms(0));
+        assert_eq!(feed(&mut identifier, &device(0x11, 1).reply(), ms(100)), None);
+    }
+
+    #[test]
+    fn expire_returns_devices() {
+        let devices = [device(0x10, 1), device(0x11, 2)];
+        let mut identifier = Identifier::new(sysex::ALL_CALL);
+        let request = identifier.request(ms(0));
+        for (index, fake) in devices.iter().enumerate() {
+            let reply = fake.receive(&request).unwrap();
+            assert!(feed(&mut identifier, &reply, ms(10 * index as u64)).is_some());
+        }
+
+        assert_eq!(identifier.expire(ms(999)), None);
+        let found = identifier.expire(ms(1000)).unwrap();
+        assert_eq!(found.iter().map(|device| device.device_id).collect::<Vec<_>>(), [0x10, 0x11]);
+        assert_eq!(identifier.expire(ms(2000)), None);
+        assert!(identifier.devices().is_empty());
+    }
+}
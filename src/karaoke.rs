@@ -0,0 +1,167 @@
+//! Lyrics from karaoke (`.kar`) files.
+//!
+//! A `.kar` file is an SMF whose lyrics are Text meta events, one per syllable.
+//! Text beginning with `@` is a header tag rather than a lyric:
+//! `@K` marks the file type, `@V` the format version, `@L` the language,
+//! `@I` information lines, and `@T` the title, artist and sequencer credits in that order.
+//! A syllable beginning with `\` starts a new paragraph (a screen clear)
+//! and one beginning with `/` starts a new line.
+//!
+//! Files without Text lyrics fall back to Lyric meta events,
+//! where a carriage return or line feed ends a line.
+//!
+//! Reference: Tune 1000 karaoke file format
+
+use anyhow::Result;
+use crate::smf::{Smf, TrackEventKind, MetaEvent, TextKind};
+use crate::smf_convert::merge_tracks;
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Default)]
+pub struct LyricTrack {
+    /// The `@K` file type tag, e.g. "MIDI KARAOKE FILE".
+    pub file_type: Option<String>,
+    pub version: Option<String>,
+    pub language: Option<String>,
+    /// `@T` lines: usually the title, then the artist, then the sequencer credit.
+    pub titles: Vec<String>,
+    /// `@I` lines.
+    pub info: Vec<String>,
+    pub lines: Vec<LyricLine>,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Default)]
+pub struct LyricLine {
+    /// Whether this line begins a new paragraph, i.e. the display is cleared first.
+    pub starts_paragraph: bool,
+    pub syllables: Vec<Syllable>,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct Syllable {
+    pub tick: u64,
+    pub seconds: f64,
+    /// The syllable text without line break markers,
+    /// including any spaces that separate it from neighboring words.
+    pub text: String,
+}
+
+impl LyricTrack {
+    /// The song title: the first `@T` line.
+    pub fn title(&self) -> Option<&str> {
+        self.titles.first().map(String::as_str)
+    }
+}
+
+impl LyricLine {
+    pub fn text(&self) -> String {
+        self.syllables.iter().map(|syllable| syllable.text.as_str()).collect()
+    }
+}
+
+/// Extracts timed lyrics from all tracks of a karaoke file.
+///
+/// Text is decoded as Latin-1, the de facto encoding of `.kar` files.
+pub fn extract_lyrics(smf: &Smf) -> Result<LyricTrack> {
+    let tempo_map = smf.tempo_map()?;
+    let merged = merge_tracks(&smf.tracks);
+
+    let texts = |wanted: TextKind| {
+        merged.events.iter().filter_map(move |event| match &event.kind {
+            TrackEventKind::Meta(MetaEvent::Text { kind, text }) if *kind == wanted => {
+                Some((event.tick, decode_latin1(text)))
+            }
+            _ => None,
+        })
+    };
+
+    let mut lyrics = LyricTrack::default();
+    let mut builder = LineBuilder::default();
+
+    for (tick, text) in texts(TextKind::Text) {
+        if let Some(tag) = text.strip_prefix('@') {
+            let mut chars = tag.chars();
+            let tag_type = chars.next();
+            let value = chars.as_str().to_string();
+            match tag_type {
+                Some('K') => lyrics.file_type = Some(value),
+                Some('V') => lyrics.version = Some(value),
+                Some('L') => lyrics.language = Some(value),
+                Some('T') => lyrics.titles.push(value),
+                Some('I') => lyrics.info.push(value),
+                _ => log::debug!("unknown karaoke tag {:?}", text),
+            }
+            continue;
+        }
+
+        let seconds = tempo_map.ticks_to_seconds(tick);
+        if let Some(text) = text.strip_prefix('\\') {
+            builder.break_line(&mut lyrics.lines, true);
+            builder.push(tick, seconds, text);
+        } else if let Some(text) = text.strip_prefix('/') {
+            builder.break_line(&mut lyrics.lines, false);
+            builder.push(tick, seconds, text);
+        } else {
+            builder.push(tick, seconds, &text);
+        }
+    }
+
+    if builder.is_empty() && lyrics.lines.is_empty() {
+        for (tick, text) in texts(TextKind::Lyric) {
+            let seconds = tempo_map.ticks_to_seconds(tick);
+            if text.starts_with(['\r', '\n']) {
+                builder.break_line(&mut lyrics.lines, false);
+            }
+            let line_ends = text.ends_with(['\r', '\n']);
+            let text = text.trim_matches(['\r', '\n']);
+            if !text.is_empty() {
+                builder.push(tick, seconds, text);
+            }
+            if line_ends {
+                builder.break_line(&mut lyrics.lines, false);
+            }
+        }
+    }
+
+    builder.break_line(&mut lyrics.lines, false);
+
+    Ok(lyrics)
+}
+
+#[derive(Default)]
+struct LineBuilder {
+    line: LyricLine,
+    next_starts_paragraph: bool,
+}
+
+impl LineBuilder {
+    fn is_empty(&self) -> bool {
+        self.line.syllables.is_empty()
+    }
+
+    fn push(&mut self, tick: u64, seconds: f64, text: &str) {
+        if self.line.syllables.is_empty() && self.next_starts_paragraph {
+            self.line.starts_paragraph = true;
+            self.next_starts_paragraph = false;
+        }
+        self.line.syllables.push(Syllable {
+            tick,
+            seconds,
+            text: text.to_string(),
+        });
+    }
+
+    /// Finishes the current line, if any.
+    fn break_line(&mut self, lines: &mut Vec<LyricLine>, starts_paragraph: bool) {
+        if !self.line.syllables.is_empty() {
+            lines.push(std::mem::take(&mut self.line));
+        }
+        self.next_starts_paragraph |= starts_paragraph;
+    }
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| byte as char).collect()
+}
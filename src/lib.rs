@@ -1,7 +1,37 @@
 #![allow(unused)]
 
+pub mod address_map;
 mod assert_from;
+pub mod ci;
 mod control_number;
+mod display;
+mod encoder;
+pub mod gm;
+pub mod hex_log;
+pub mod identify;
+pub mod karaoke;
+pub mod manufacturer;
+pub mod mcoded7;
 pub mod message;
+pub mod mmc;
+pub mod mpe;
+pub mod msc;
+pub mod note_name;
+pub mod packing;
 pub mod parser;
+pub mod pitch_bend;
+pub mod property_exchange;
+pub mod recorder;
+pub mod rmid;
+pub mod rpn;
+pub mod scala;
+pub mod sds;
+pub mod sequencer;
+pub mod smf;
+pub mod smf_convert;
+pub mod sysex;
+pub mod tempo_map;
+pub mod translate;
+pub mod tuning;
+pub mod ump;
 mod helper_methods;
@@ -0,0 +1,756 @@
+//! Manufacturer IDs, the first bytes of manufacturer SysEx messages.
+//!
+//! IDs are one byte, or three bytes starting with 0.
+//! 0x7D to 0x7F are not manufacturers; see [`crate::sysex`].
+//!
+//! Reference: MIDI spec table VI, MMA Manufacturer SysEx ID Numbers
+
+use anyhow::{Result, bail};
+use std::fmt;
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ManufacturerId {
+    /// 0x01 to 0x7C.
+    Short(u8),
+    /// The two bytes following 0x00.
+    Extended([u8; 2]),
+}
+
+pub const SEQUENTIAL: ManufacturerId = ManufacturerId::Short(0x01);
+pub const MOOG: ManufacturerId = ManufacturerId::Short(0x04);
+pub const KURZWEIL: ManufacturerId = ManufacturerId::Short(0x07);
+pub const OBERHEIM: ManufacturerId = ManufacturerId::Short(0x10);
+pub const E_MU: ManufacturerId = ManufacturerId::Short(0x18);
+pub const CLAVIA: ManufacturerId = ManufacturerId::Short(0x33);
+pub const WALDORF: ManufacturerId = ManufacturerId::Short(0x3E);
+pub const KAWAI: ManufacturerId = ManufacturerId::Short(0x40);
+pub const ROLAND: ManufacturerId = ManufacturerId::Short(0x41);
+pub const KORG: ManufacturerId = ManufacturerId::Short(0x42);
+pub const YAMAHA: ManufacturerId = ManufacturerId::Short(0x43);
+pub const CASIO: ManufacturerId = ManufacturerId::Short(0x44);
+pub const AKAI: ManufacturerId = ManufacturerId::Short(0x47);
+pub const ALESIS: ManufacturerId = ManufacturerId::Extended([0x00, 0x0E]);
+pub const NOVATION: ManufacturerId = ManufacturerId::Extended([0x20, 0x29]);
+pub const BEHRINGER: ManufacturerId = ManufacturerId::Extended([0x20, 0x32]);
+pub const ARTURIA: ManufacturerId = ManufacturerId::Extended([0x20, 0x6B]);
+
+/// One-byte IDs, with gaps where no ID is assigned.
+const SHORT_NAMES: &[(u8, &str)] = &[
+    (0x01, "Sequential Circuits"),
+    (0x02, "IDP"),
+    (0x03, "Voyetra Turtle Beach"),
+    (0x04, "Moog Music"),
+    (0x05, "Passport Designs"),
+    (0x06, "Lexicon"),
+    (0x07, "Kurzweil / Young Chang"),
+    (0x08, "Fender"),
+    (0x09, "MIDI9"),
+    (0x0A, "AKG Acoustics"),
+    (0x0B, "Voyce Music"),
+    (0x0C, "WaveFrame"),
+    (0x0D, "ADA Signal Processors"),
+    (0x0E, "Garfield Electronics"),
+    (0x0F, "Ensoniq"),
+    (0x10, "Oberheim"),
+    (0x11, "Apple"),
+    (0x12, "Grey Matter Response"),
+    (0x13, "Digidesign"),
+    (0x14, "Palmtree Instruments"),
+    (0x15, "JLCooper Electronics"),
+    (0x16, "Lowrey Organ Company"),
+    (0x17, "Adams-Smith"),
+    (0x18, "E-mu"),
+    (0x19, "Harmony Systems"),
+    (0x1A, "ART"),
+    (0x1B, "Baldwin"),
+    (0x1C, "Eventide"),
+    (0x1D, "Inventronics"),
+    (0x1E, "Key Concepts"),
+    (0x1F, "Clarity"),
+    (0x20, "Passac"),
+    (0x21, "Proel Labs (SIEL)"),
+    (0x22, "Synthaxe"),
+    (0x23, "Stepp"),
+    (0x24, "Hohner"),
+    (0x25, "Twister"),
+    (0x26, "Ketron"),
+    (0x27, "Jellinghaus MS"),
+    (0x28, "Southworth Music Systems"),
+    (0x29, "PPG"),
+    (0x2A, "JEN"),
+    (0x2B, "Solid State Logic Organ Systems"),
+    (0x2C, "Audio Veritrieb-P. Struven"),
+    (0x2D, "Neve"),
+    (0x2E, "Soundtracs"),
+    (0x2F, "Elka"),
+    (0x30, "Dynacord"),
+    (0x31, "Viscount International"),
+    (0x32, "Drawmer"),
+    (0x33, "Clavia Digital Instruments"),
+    (0x34, "Audio Architecture"),
+    (0x35, "Generalmusic"),
+    (0x36, "Cheetah Marketing"),
+    (0x37, "C.T.M."),
+    (0x38, "Simmons"),
+    (0x39, "Soundcraft Electronics"),
+    (0x3A, "Steinberg"),
+    (0x3B, "Wersi"),
+    (0x3C, "AVAB Niethammer"),
+    (0x3D, "Digigram"),
+    (0x3E, "Waldorf Electronics"),
+    (0x3F, "Quasimidi"),
+    (0x40, "Kawai"),
+    (0x41, "Roland"),
+    (0x42, "Korg"),
+    (0x43, "Yamaha"),
+    (0x44, "Casio"),
+    (0x46, "Kamiya Studio"),
+    (0x47, "Akai"),
+    (0x48, "Victor Company of Japan"),
+    (0x4B, "Fujitsu"),
+    (0x4C, "Sony"),
+    (0x4E, "Teac"),
+    (0x50, "Matsushita Electric"),
+    (0x51, "Fostex"),
+    (0x52, "Zoom"),
+    (0x54, "Matsushita Communication Industrial"),
+    (0x55, "Suzuki Musical Instruments"),
+    (0x56, "Fuji Sound"),
+    (0x57, "Acoustic Technical Laboratory"),
+    (0x59, "Faith"),
+    (0x5A, "Internet Corporation"),
+    (0x5C, "Seekers"),
+    (0x5F, "SD Card Association"),
+];
+
+/// Three-byte IDs, by the two bytes after 0x00, with gaps where no ID is assigned.
+const EXTENDED_NAMES: &[([u8; 2], &str)] = &[
+    ([0x00, 0x01], "Time/Warner Interactive"),
+    ([0x00, 0x02], "Advanced Gravis"),
+    ([0x00, 0x03], "Media Vision"),
+    ([0x00, 0x04], "Dornes Research Group"),
+    ([0x00, 0x05], "K-Muse"),
+    ([0x00, 0x06], "Stypher"),
+    ([0x00, 0x07], "Digital Music Corp."),
+    ([0x00, 0x08], "IOTA Systems"),
+    ([0x00, 0x09], "New England Digital"),
+    ([0x00, 0x0A], "Artisyn"),
+    ([0x00, 0x0B], "IVL Technologies"),
+    ([0x00, 0x0C], "Southern Music Systems"),
+    ([0x00, 0x0D], "Lake Butler Sound Company"),
+    ([0x00, 0x0E], "Alesis"),
+    ([0x00, 0x0F], "Sound Creation"),
+    ([0x00, 0x10], "DOD Electronics"),
+    ([0x00, 0x11], "Studer-Editech"),
+    ([0x00, 0x12], "Sonus"),
+    ([0x00, 0x13], "Temporal Acuity Products"),
+    ([0x00, 0x14], "Perfect Fretworks"),
+    ([0x00, 0x15], "KAT"),
+    ([0x00, 0x16], "Opcode"),
+    ([0x00, 0x17], "Rane"),
+    ([0x00, 0x18], "Anadi Electronique"),
+    ([0x00, 0x19], "KMX"),
+    ([0x00, 0x1A], "Allen & Heath"),
+    ([0x00, 0x1B], "Peavey Electronics"),
+    ([0x00, 0x1C], "360 Systems"),
+    ([0x00, 0x1D], "Spectrum Design and Development"),
+    ([0x00, 0x1E], "Marquis Music"),
+    ([0x00, 0x1F], "Zeta Systems"),
+    ([0x00, 0x20], "Axxes"),
+    ([0x00, 0x21], "Orban"),
+    ([0x00, 0x22], "Indian Valley Mfg."),
+    ([0x00, 0x23], "Triton"),
+    ([0x00, 0x24], "KTI"),
+    ([0x00, 0x25], "Breakaway Technologies"),
+    ([0x00, 0x26], "Leprecon / CAE"),
+    ([0x00, 0x27], "Harrison Systems"),
+    ([0x00, 0x28], "Future Lab / Mark Kuo"),
+    ([0x00, 0x29], "Rocktron"),
+    ([0x00, 0x2A], "PianoDisc"),
+    ([0x00, 0x2B], "Cannon Research Group"),
+    ([0x00, 0x2D], "Rodgers Instruments"),
+    ([0x00, 0x2E], "Blue Sky Logic"),
+    ([0x00, 0x2F], "Encore Electronics"),
+    ([0x00, 0x30], "Uptown"),
+    ([0x00, 0x31], "Voce"),
+    ([0x00, 0x32], "CTI Audio"),
+    ([0x00, 0x33], "S3"),
+    ([0x00, 0x34], "Broderbund / Red Orb"),
+    ([0x00, 0x35], "Allen Organ"),
+    ([0x00, 0x37], "Music Quest"),
+    ([0x00, 0x38], "Aphex"),
+    ([0x00, 0x39], "Gallien Krueger"),
+    ([0x00, 0x3A], "IBM"),
+    ([0x00, 0x3B], "Mark of the Unicorn"),
+    ([0x00, 0x3C], "Hotz"),
+    ([0x00, 0x3D], "ETA Lighting"),
+    ([0x00, 0x3E], "NSI"),
+    ([0x00, 0x3F], "Ad Lib"),
+    ([0x00, 0x40], "Richmond Sound Design"),
+    ([0x00, 0x41], "Microsoft"),
+    ([0x00, 0x42], "Mindscape"),
+    ([0x00, 0x43], "Russ Jones Marketing / Niche"),
+    ([0x00, 0x44], "Intone"),
+    ([0x00, 0x45], "Advanced Remote Technologies"),
+    ([0x00, 0x46], "White Instruments"),
+    ([0x00, 0x47], "GT Electronics / Groove Tubes"),
+    ([0x00, 0x48], "Pacific Research & Engineering"),
+    ([0x00, 0x49], "Timeline Vista"),
+    ([0x00, 0x4A], "Mesa Boogie"),
+    ([0x00, 0x4B], "FSLI"),
+    ([0x00, 0x4C], "Sequoia Development Group"),
+    ([0x00, 0x4D], "Studio Electronics"),
+    ([0x00, 0x4E], "Euphonix"),
+    ([0x00, 0x4F], "InterMIDI"),
+    ([0x00, 0x50], "MIDI Solutions"),
+    ([0x00, 0x51], "3DO Company"),
+    ([0x00, 0x52], "Lightwave Research / High End Systems"),
+    ([0x00, 0x53], "Micro-W"),
+    ([0x00, 0x54], "Spectral Synthesis"),
+    ([0x00, 0x55], "Lone Wolf"),
+    ([0x00, 0x56], "Studio Technologies"),
+    ([0x00, 0x57], "Peterson Electro-Musical Products"),
+    ([0x00, 0x58], "Atari"),
+    ([0x00, 0x59], "Marion Systems"),
+    ([0x00, 0x5A], "Design Event"),
+    ([0x00, 0x5B], "Winjammer Software"),
+    ([0x00, 0x5C], "AT&T Bell Laboratories"),
+    ([0x00, 0x5E], "Symetrix"),
+    ([0x00, 0x5F], "MIDI the World"),
+    ([0x00, 0x60], "Spatializer"),
+    ([0x00, 0x61], "Micros 'N MIDI"),
+    ([0x00, 0x62], "Accordians International"),
+    ([0x00, 0x63], "EuPhonics"),
+    ([0x00, 0x64], "Musonix"),
+    ([0x00, 0x65], "Turtle Beach Systems"),
+    ([0x00, 0x66], "Mackie"),
+    ([0x00, 0x67], "Compuserve"),
+    ([0x00, 0x68], "BEC Technologies"),
+    ([0x00, 0x69], "QRS Music"),
+    ([0x00, 0x6A], "PG Music"),
+    ([0x00, 0x6B], "Sierra Semiconductor"),
+    ([0x00, 0x6C], "EpiGraf"),
+    ([0x00, 0x6D], "Electronics Diversified"),
+    ([0x00, 0x6E], "Tune 1000"),
+    ([0x00, 0x6F], "Advanced Micro Devices"),
+    ([0x00, 0x70], "Mediamation"),
+    ([0x00, 0x71], "Sabine Musical Mfg."),
+    ([0x00, 0x72], "Woog Labs"),
+    ([0x00, 0x73], "Micropolis"),
+    ([0x00, 0x74], "Ta Horng Musical Instrument"),
+    ([0x00, 0x75], "e-Tek Labs"),
+    ([0x00, 0x76], "Electro-Voice"),
+    ([0x00, 0x77], "Midisoft"),
+    ([0x00, 0x78], "QSound Labs"),
+    ([0x00, 0x79], "Westrex"),
+    ([0x00, 0x7A], "Nvidia"),
+    ([0x00, 0x7B], "ESS Technology"),
+    ([0x00, 0x7C], "Media Trix Peripherals"),
+    ([0x00, 0x7D], "Brooktree"),
+    ([0x00, 0x7E], "Otari"),
+    ([0x00, 0x7F], "Key Electronics"),
+    ([0x01, 0x00], "Shure"),
+    ([0x01, 0x01], "AuraSound"),
+    ([0x01, 0x02], "Crystal Semiconductor"),
+    ([0x01, 0x03], "Conexant"),
+    ([0x01, 0x04], "Silicon Graphics"),
+    ([0x01, 0x05], "M-Audio"),
+    ([0x01, 0x06], "PreSonus"),
+    ([0x01, 0x08], "Topaz Enterprises"),
+    ([0x01, 0x09], "Cast Lighting"),
+    ([0x01, 0x0A], "Microsoft Consumer Division"),
+    ([0x01, 0x0B], "Sonic Foundry"),
+    ([0x01, 0x0C], "Line 6"),
+    ([0x01, 0x0D], "Beatnik"),
+    ([0x01, 0x0E], "Van Koevering Company"),
+    ([0x01, 0x0F], "Altech Systems"),
+    ([0x01, 0x10], "S & S Research"),
+    ([0x01, 0x11], "VLSI Technology"),
+    ([0x01, 0x12], "Chromatic Research"),
+    ([0x01, 0x13], "Sapphire"),
+    ([0x01, 0x14], "IDRC"),
+    ([0x01, 0x15], "Justonic Tuning"),
+    ([0x01, 0x16], "TorComp Research"),
+    ([0x01, 0x17], "Newtek"),
+    ([0x01, 0x18], "Sound Sculpture"),
+    ([0x01, 0x19], "Walker Technical"),
+    ([0x01, 0x1A], "Digital Harmony"),
+    ([0x01, 0x1B], "InVision Interactive"),
+    ([0x01, 0x1C], "T-Square Design"),
+    ([0x01, 0x1D], "Nemesys Music Technology"),
+    ([0x01, 0x1E], "DBX Professional"),
+    ([0x01, 0x1F], "Syndyne"),
+    ([0x01, 0x20], "Bitheadz"),
+    ([0x01, 0x21], "Cakewalk"),
+    ([0x01, 0x22], "Analog Devices"),
+    ([0x01, 0x23], "National Semiconductor"),
+    ([0x01, 0x24], "Boom Theory"),
+    ([0x01, 0x25], "Virtual DSP"),
+    ([0x01, 0x26], "Antares Systems"),
+    ([0x01, 0x27], "Angel Software"),
+    ([0x01, 0x28], "St Louis Music"),
+    ([0x01, 0x29], "Passport Music Software"),
+    ([0x01, 0x2A], "Ashly Audio"),
+    ([0x01, 0x2B], "Vari-Lite"),
+    ([0x01, 0x2C], "Summit Audio"),
+    ([0x01, 0x2D], "Aureal Semiconductor"),
+    ([0x01, 0x2E], "SeaSound"),
+    ([0x01, 0x2F], "U.S. Robotics"),
+    ([0x01, 0x30], "Aurisis Research"),
+    ([0x01, 0x31], "Nearfield Research"),
+    ([0x01, 0x32], "FM7"),
+    ([0x01, 0x33], "Swivel Systems"),
+    ([0x01, 0x34], "Hyperactive Audio Systems"),
+    ([0x01, 0x35], "MidiLite"),
+    ([0x01, 0x36], "Radikal Technologies"),
+    ([0x01, 0x37], "Roger Linn Design"),
+    ([0x01, 0x38], "TC-Helicon Vocal Technologies"),
+    ([0x01, 0x39], "Event Electronics"),
+    ([0x01, 0x3A], "Sonic Network"),
+    ([0x01, 0x3B], "Realtime Music Solutions"),
+    ([0x01, 0x3C], "Apogee Digital"),
+    ([0x01, 0x3D], "Classical Organs"),
+    ([0x01, 0x3E], "Microtools"),
+    ([0x01, 0x3F], "Numark Industries"),
+    ([0x01, 0x40], "Frontier Design Group"),
+    ([0x01, 0x41], "Recordare"),
+    ([0x01, 0x42], "Starr Labs"),
+    ([0x01, 0x43], "Voyager Sound"),
+    ([0x01, 0x44], "Manifold Labs"),
+    ([0x01, 0x45], "Aviom"),
+    ([0x01, 0x46], "Mixmeister Technology"),
+    ([0x01, 0x47], "Notation Software"),
+    ([0x01, 0x48], "Mercurial Communications"),
+    ([0x01, 0x49], "Wave Arts"),
+    ([0x01, 0x4A], "Logic Sequencing Devices"),
+    ([0x01, 0x4B], "Axess Electronics"),
+    ([0x01, 0x4C], "Muse Research"),
+    ([0x01, 0x4D], "Open Labs"),
+    ([0x01, 0x4E], "Guillemot"),
+    ([0x01, 0x4F], "Samson Technologies"),
+    ([0x01, 0x50], "Electronic Theatre Controls"),
+    ([0x01, 0x51], "BlackBerry"),
+    ([0x01, 0x52], "Mobileer"),
+    ([0x01, 0x53], "Synthogy"),
+    ([0x01, 0x54], "Lynx Studio Technology"),
+    ([0x01, 0x55], "Damage Control Engineering"),
+    ([0x01, 0x56], "Yost Engineering"),
+    ([0x01, 0x57], "Brooks & Forsman Designs"),
+    ([0x01, 0x58], "Infinite Response"),
+    ([0x01, 0x59], "Garritan"),
+    ([0x01, 0x5A], "Plogue Art et Technologie"),
+    ([0x01, 0x5B], "RJM Music Technology"),
+    ([0x01, 0x5C], "Custom Solutions Software"),
+    ([0x01, 0x5D], "Sonarcana / Highly Liquid"),
+    ([0x01, 0x5E], "Centrance"),
+    ([0x01, 0x5F], "Kesumo"),
+    ([0x01, 0x60], "Stanton"),
+    ([0x01, 0x61], "Livid Instruments"),
+    ([0x01, 0x62], "First Act / 745 Media"),
+    ([0x01, 0x63], "Pygraphics"),
+    ([0x01, 0x64], "Panadigm Innovations"),
+    ([0x01, 0x65], "Avedis Zildjian"),
+    ([0x01, 0x66], "Auvital Music"),
+    ([0x01, 0x67], "You Rock Guitar"),
+    ([0x01, 0x68], "Chris Grigg Designs"),
+    ([0x01, 0x69], "Slate Digital"),
+    ([0x01, 0x6A], "Mixware"),
+    ([0x01, 0x6B], "Social Entropy"),
+    ([0x01, 0x6C], "Source Audio"),
+    ([0x01, 0x6D], "Ernie Ball / Music Man"),
+    ([0x01, 0x6E], "Fishman"),
+    ([0x01, 0x6F], "Custom Audio Electronics"),
+    ([0x01, 0x70], "American Audio/DJ"),
+    ([0x01, 0x71], "Mega Control Systems"),
+    ([0x01, 0x72], "Kilpatrick Audio"),
+    ([0x01, 0x73], "iConnectivity"),
+    ([0x01, 0x74], "Fractal Audio"),
+    ([0x01, 0x75], "NetLogic Microsystems"),
+    ([0x01, 0x76], "Music Computing"),
+    ([0x01, 0x77], "Nektar Technology"),
+    ([0x01, 0x78], "Zenph Sound Innovations"),
+    ([0x01, 0x79], "DJTechTools.com"),
+    ([0x01, 0x7A], "Rezonance Labs"),
+    ([0x01, 0x7B], "Decibel Eleven"),
+    ([0x01, 0x7C], "CNMAT"),
+    ([0x01, 0x7D], "Media Overkill"),
+    ([0x01, 0x7E], "Confusion Studios"),
+    ([0x01, 0x7F], "moForte"),
+    ([0x02, 0x00], "Miselu"),
+    ([0x02, 0x01], "Amelia's Compass"),
+    ([0x02, 0x02], "Zivix"),
+    ([0x02, 0x03], "Artiphon"),
+    ([0x02, 0x04], "Synclavier Digital"),
+    ([0x02, 0x05], "Light & Sound Control Devices"),
+    ([0x02, 0x06], "Retronyms"),
+    ([0x02, 0x07], "JS Technologies"),
+    ([0x02, 0x08], "Quicco Sound"),
+    ([0x02, 0x09], "A-Designs Audio"),
+    ([0x02, 0x0A], "McCarthy Music"),
+    ([0x02, 0x0B], "Denon DJ"),
+    ([0x02, 0x0C], "Keith Robert Murray"),
+    ([0x02, 0x0D], "Google"),
+    ([0x02, 0x0E], "ISP Technologies"),
+    ([0x02, 0x0F], "Abstrakt Instruments"),
+    ([0x02, 0x10], "Meris"),
+    ([0x02, 0x11], "Sensorpoint"),
+    ([0x02, 0x12], "Hi-Z Labs"),
+    ([0x02, 0x13], "Imitone"),
+    ([0x02, 0x14], "Intellijel Designs"),
+    ([0x02, 0x15], "Dasz Instruments"),
+    ([0x02, 0x16], "Remidi"),
+    ([0x02, 0x17], "Disaster Area Designs"),
+    ([0x02, 0x18], "Universal Audio"),
+    ([0x02, 0x19], "Carter Duncan"),
+    ([0x02, 0x1A], "Essential Technology"),
+    ([0x02, 0x1B], "Cantux Research"),
+    ([0x02, 0x1C], "Hummel Technologies"),
+    ([0x02, 0x1D], "Sensel"),
+    ([0x02, 0x1E], "DBML Group"),
+    ([0x02, 0x1F], "Madrona Labs"),
+    ([0x02, 0x20], "Mesa Boogie"),
+    ([0x02, 0x21], "Effigy Labs"),
+    ([0x02, 0x22], "Amenote"),
+    ([0x02, 0x23], "Red Panda"),
+    ([0x02, 0x24], "OnSong"),
+    ([0x02, 0x25], "Jamboxx"),
+    ([0x02, 0x26], "Electro-Harmonix"),
+    ([0x02, 0x27], "RnD64"),
+    ([0x02, 0x28], "Neunaber Technology"),
+    ([0x02, 0x29], "Kaom"),
+    ([0x02, 0x2A], "Hallowell EMC"),
+    ([0x02, 0x2B], "Sound Devices"),
+    ([0x02, 0x2C], "Spectrasonics"),
+    ([0x20, 0x00], "Dream SAS"),
+    ([0x20, 0x01], "Strand Lighting"),
+    ([0x20, 0x02], "Amek"),
+    ([0x20, 0x03], "Casa Di Risparmio Di Loreto"),
+    ([0x20, 0x04], "Böhm electronic"),
+    ([0x20, 0x05], "Syntec Digital Audio"),
+    ([0x20, 0x06], "Trident Audio Developments"),
+    ([0x20, 0x07], "Real World Studio"),
+    ([0x20, 0x08], "Evolution Synthesis"),
+    ([0x20, 0x09], "Yes Technology"),
+    ([0x20, 0x0A], "Audiomatica"),
+    ([0x20, 0x0B], "Bontempi / Farfisa"),
+    ([0x20, 0x0C], "F.B.T. Elettronica"),
+    ([0x20, 0x0D], "MidiTemp"),
+    ([0x20, 0x0E], "LA Audio"),
+    ([0x20, 0x0F], "Zero 88 Lighting"),
+    ([0x20, 0x10], "Micon Audio Electronics"),
+    ([0x20, 0x11], "Forefront Technology"),
+    ([0x20, 0x12], "Studio Audio and Video"),
+    ([0x20, 0x13], "Kenton Electronics"),
+    ([0x20, 0x14], "Celco / Electrosonic"),
+    ([0x20, 0x15], "ADB"),
+    ([0x20, 0x16], "Marshall Products"),
+    ([0x20, 0x17], "DDA"),
+    ([0x20, 0x18], "BSS Audio"),
+    ([0x20, 0x19], "MA Lighting Technology"),
+    ([0x20, 0x1A], "Fatar"),
+    ([0x20, 0x1B], "QSC Audio Products"),
+    ([0x20, 0x1C], "Artisan Classic Organ"),
+    ([0x20, 0x1D], "Orla"),
+    ([0x20, 0x1E], "Pinnacle Audio"),
+    ([0x20, 0x1F], "TC Electronic"),
+    ([0x20, 0x20], "Doepfer Musikelektronik"),
+    ([0x20, 0x21], "Creative ATC / E-mu"),
+    ([0x20, 0x22], "Seyddo / Minami"),
+    ([0x20, 0x23], "LG Electronics"),
+    ([0x20, 0x24], "Midisoft sas"),
+    ([0x20, 0x25], "Samick Musical Instruments"),
+    ([0x20, 0x26], "Penny and Giles"),
+    ([0x20, 0x27], "Acorn Computer"),
+    ([0x20, 0x28], "LSC Electronics"),
+    ([0x20, 0x29], "Focusrite/Novation"),
+    ([0x20, 0x2A], "Samkyung Mechatronics"),
+    ([0x20, 0x2B], "Medeli Electronics"),
+    ([0x20, 0x2C], "Charlie Lab"),
+    ([0x20, 0x2D], "Blue Chip Music Technology"),
+    ([0x20, 0x2E], "BEE OH"),
+    ([0x20, 0x2F], "LG Semicon America"),
+    ([0x20, 0x30], "TESI"),
+    ([0x20, 0x31], "Emagic"),
+    ([0x20, 0x32], "Behringer"),
+    ([0x20, 0x33], "Access Music"),
+    ([0x20, 0x34], "Synoptic"),
+    ([0x20, 0x35], "Hanmesoft"),
+    ([0x20, 0x36], "Terratec Electronic"),
+    ([0x20, 0x37], "Proel"),
+    ([0x20, 0x38], "IBK MIDI"),
+    ([0x20, 0x39], "IRCAM"),
+    ([0x20, 0x3A], "Propellerhead Software"),
+    ([0x20, 0x3B], "Red Sound Systems"),
+    ([0x20, 0x3C], "Elektron"),
+    ([0x20, 0x3D], "Sintefex Audio"),
+    ([0x20, 0x3E], "MAM (Music and More)"),
+    ([0x20, 0x3F], "Amsaro"),
+    ([0x20, 0x40], "CDS Advanced Technology (Lanbox)"),
+    ([0x20, 0x41], "Mode Machines"),
+    ([0x20, 0x42], "DSP Arts"),
+    ([0x20, 0x43], "Phil Rees Music Tech"),
+    ([0x20, 0x44], "Stamer Musikanlagen"),
+    ([0x20, 0x45], "Soundart"),
+    ([0x20, 0x46], "C-Mexx Software"),
+    ([0x20, 0x47], "Klavis Technologies"),
+    ([0x20, 0x48], "Noteheads"),
+    ([0x20, 0x49], "Algorithmix"),
+    ([0x20, 0x4A], "Skrydstrup R&D"),
+    ([0x20, 0x4B], "Professional Audio Company"),
+    ([0x20, 0x4C], "NewWave Labs"),
+    ([0x20, 0x4D], "Vermona"),
+    ([0x20, 0x4E], "Nokia"),
+    ([0x20, 0x4F], "Wave Idea"),
+    ([0x20, 0x50], "Hartmann"),
+    ([0x20, 0x51], "Lion's Tracs"),
+    ([0x20, 0x52], "Analogue Systems"),
+    ([0x20, 0x53], "Focal-JMlab"),
+    ([0x20, 0x54], "Ringway Electronics"),
+    ([0x20, 0x55], "Faith Technologies (Digiplug)"),
+    ([0x20, 0x56], "Showworks"),
+    ([0x20, 0x57], "Manikin Electronic"),
+    ([0x20, 0x58], "1 Come Tech"),
+    ([0x20, 0x59], "Phonic"),
+    ([0x20, 0x5A], "Dolby Australia (Lake)"),
+    ([0x20, 0x5B], "Silansys Technologies"),
+    ([0x20, 0x5C], "Winbond Electronics"),
+    ([0x20, 0x5D], "Cinetix Medien und Interface"),
+    ([0x20, 0x5E], "A&G Soluzioni Digitali"),
+    ([0x20, 0x5F], "Sequentix"),
+    ([0x20, 0x60], "Oram Pro Audio"),
+    ([0x20, 0x61], "Be4"),
+    ([0x20, 0x62], "Infection Music"),
+    ([0x20, 0x63], "Central Music (CME)"),
+    ([0x20, 0x64], "genoQs Machines"),
+    ([0x20, 0x65], "Medialon"),
+    ([0x20, 0x66], "Waves Audio"),
+    ([0x20, 0x67], "Jerash Labs"),
+    ([0x20, 0x68], "Da Fact"),
+    ([0x20, 0x69], "Elby Designs"),
+    ([0x20, 0x6A], "Spectral Audio"),
+    ([0x20, 0x6B], "Arturia"),
+    ([0x20, 0x6C], "Vixid"),
+    ([0x20, 0x6D], "C-Thru Music"),
+    ([0x20, 0x6E], "Ya Horng Electronic"),
+    ([0x20, 0x6F], "SM Pro Audio"),
+    ([0x20, 0x70], "OTO Machines"),
+    ([0x20, 0x71], "ELZAB (G LAB)"),
+    ([0x20, 0x72], "Blackstar Amplification"),
+    ([0x20, 0x73], "M3i Technologies"),
+    ([0x20, 0x74], "Gemalto"),
+    ([0x20, 0x75], "Prostage"),
+    ([0x20, 0x76], "Teenage Engineering"),
+    ([0x20, 0x77], "Tobias Erichsen Consulting"),
+    ([0x20, 0x78], "Nixer"),
+    ([0x20, 0x79], "Hanpin Electron"),
+    ([0x20, 0x7A], "MIDI-hardware R. Sowa"),
+    ([0x20, 0x7B], "Beyond Music Industrial"),
+    ([0x20, 0x7C], "Kiss Box"),
+    ([0x20, 0x7D], "Misa Digital Technologies"),
+    ([0x20, 0x7E], "AI Musics Technology"),
+    ([0x20, 0x7F], "Serato"),
+    ([0x21, 0x00], "Limex"),
+    ([0x21, 0x01], "Kyodday (Tokai)"),
+    ([0x21, 0x02], "Mutable Instruments"),
+    ([0x21, 0x03], "PreSonus Software"),
+    ([0x21, 0x04], "Ingenico"),
+    ([0x21, 0x05], "Fairlight Instruments"),
+    ([0x21, 0x06], "Musicom Lab"),
+    ([0x21, 0x07], "Modal Electronics"),
+    ([0x21, 0x08], "RWA (Hong Kong)"),
+    ([0x21, 0x09], "Native Instruments"),
+    ([0x21, 0x0A], "Naonext"),
+    ([0x21, 0x0B], "MFB"),
+    ([0x21, 0x0C], "Teknel Research"),
+    ([0x21, 0x0D], "Ploytec"),
+    ([0x21, 0x0E], "Surfin Kangaroo Studio"),
+    ([0x21, 0x0F], "Philips Electronics HK"),
+    ([0x21, 0x10], "ROLI"),
+    ([0x21, 0x11], "Panda-Audio"),
+    ([0x21, 0x12], "BauM Software"),
+    ([0x21, 0x13], "Machinewerks"),
+    ([0x21, 0x14], "Xiamen Elane Electronics"),
+    ([0x21, 0x15], "Marshall Amplification"),
+    ([0x21, 0x16], "Kiwitechnics"),
+    ([0x21, 0x17], "Rob Papen"),
+    ([0x21, 0x18], "Spicetone"),
+    ([0x21, 0x19], "V3Sound"),
+    ([0x21, 0x1A], "IK Multimedia"),
+    ([0x21, 0x1B], "Novalia"),
+    ([0x21, 0x1C], "Modor Music"),
+    ([0x21, 0x1D], "Ableton"),
+    ([0x21, 0x1E], "Dtronics"),
+    ([0x21, 0x1F], "ZAQ Audio"),
+    ([0x21, 0x20], "Muabaobao Education Technology"),
+    ([0x21, 0x21], "Flux Effects"),
+    ([0x21, 0x22], "Audiothingies"),
+    ([0x21, 0x23], "Retrokits"),
+    ([0x21, 0x24], "Morningstar FX"),
+    ([0x21, 0x25], "Hotone Audio"),
+    ([0x21, 0x26], "Expressive E"),
+    ([0x21, 0x27], "Expert Sleepers"),
+    ([0x21, 0x28], "Timecode-Vision Technology"),
+    ([0x21, 0x29], "Hornberg Research"),
+    ([0x21, 0x2A], "Sonic Potions"),
+    ([0x21, 0x2B], "Audiofront"),
+    ([0x21, 0x2C], "Fred's Lab"),
+    ([0x21, 0x2D], "Audio Modeling"),
+    ([0x21, 0x2E], "C. Bechstein Digital"),
+    ([0x21, 0x2F], "Motas Electronics"),
+    ([0x21, 0x30], "Elk Audio"),
+    ([0x21, 0x31], "Sonic Academy"),
+    ([0x21, 0x32], "Bome Software"),
+    ([0x21, 0x33], "AODYO"),
+    ([0x21, 0x34], "Pianoforce"),
+    ([0x21, 0x35], "Dreadbox"),
+    ([0x21, 0x36], "TouchKeys Instruments"),
+    ([0x21, 0x37], "The Gigrig"),
+    ([0x21, 0x38], "ALM Co"),
+    ([0x21, 0x39], "CH Sound Design"),
+    ([0x21, 0x3A], "Beat Bars"),
+    ([0x21, 0x3B], "Blokas"),
+    ([0x21, 0x3C], "GEWA Music"),
+    ([0x21, 0x3D], "dadamachines"),
+    ([0x21, 0x3E], "Augmented Instruments (Bela)"),
+    ([0x21, 0x3F], "Supercritical"),
+    ([0x21, 0x40], "Genki Instruments"),
+    ([0x21, 0x41], "Marienberg Devices Germany"),
+    ([0x21, 0x42], "Supperware"),
+    ([0x21, 0x43], "Imoxplus"),
+    ([0x21, 0x44], "Swapp Technologies"),
+    ([0x21, 0x45], "Electra One"),
+    ([0x21, 0x46], "Digital Clef"),
+    ([0x21, 0x47], "Paul Whittington Group"),
+    ([0x21, 0x48], "Music Hackspace"),
+    ([0x21, 0x49], "Bitwig"),
+    ([0x21, 0x4A], "Enhancia"),
+    ([0x21, 0x4B], "KV 331"),
+    ([0x21, 0x4C], "Tehnicadelarte"),
+    ([0x21, 0x4D], "Endlesss Studio"),
+    ([0x40, 0x00], "Crimson Technology"),
+    ([0x40, 0x01], "Softbank Mobile"),
+    ([0x40, 0x03], "D&M Holdings"),
+    ([0x40, 0x04], "Xing"),
+    ([0x40, 0x05], "AlphaTheta"),
+    ([0x40, 0x06], "Pioneer"),
+    ([0x40, 0x07], "Slik"),
+];
+
+impl ManufacturerId {
+    /// Reads the ID at the start of SysEx data, without `F0`,
+    /// returning it and the rest of the data.
+    pub fn parse(data: &[u8]) -> Result<(ManufacturerId, &[u8])> {
+        match data {
+            [0x00, a, b, rest @ ..] => Ok((ManufacturerId::extended([*a, *b])?, rest)),
+            [0x00, ..] => bail!("truncated manufacturer ID"),
+            [id, rest @ ..] => Ok((ManufacturerId::short(*id)?, rest)),
+            [] => bail!("empty SysEx message"),
+        }
+    }
+
+    pub fn short(id: u8) -> Result<ManufacturerId> {
+        if !(0x01..=0x7C).contains(&id) {
+            bail!("invalid manufacturer ID {:02X}", id);
+        }
+        Ok(ManufacturerId::Short(id))
+    }
+
+    pub fn extended(id: [u8; 2]) -> Result<ManufacturerId> {
+        if id.iter().any(|byte| *byte > 0x7F) {
+            bail!("invalid manufacturer ID 00 {:02X} {:02X}", id[0], id[1]);
+        }
+        Ok(ManufacturerId::Extended(id))
+    }
+
+    /// The three-byte form used by MIDI-CI, with one-byte IDs followed by two zeros.
+    pub fn from_padded(id: [u8; 3]) -> Result<ManufacturerId> {
+        match id {
+            [0x00, a, b] => ManufacturerId::extended([a, b]),
+            [id, 0x00, 0x00] => ManufacturerId::short(id),
+            _ => bail!("invalid manufacturer ID {:02X?}", id),
+        }
+    }
+
+    pub fn to_padded(self) -> [u8; 3] {
+        match self {
+            ManufacturerId::Short(id) => [id, 0x00, 0x00],
+            ManufacturerId::Extended([a, b]) => [0x00, a, b],
+        }
+    }
+
+    /// Appends the ID as sent in SysEx.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ManufacturerId::Short(id) => buf.push(*id),
+            ManufacturerId::Extended([a, b]) => buf.extend([0x00, *a, *b]),
+        }
+    }
+
+    /// The registered name, if the ID is assigned.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            ManufacturerId::Short(id) => SHORT_NAMES.iter().find(|(i, _)| i == id).map(|(_, name)| *name),
+            ManufacturerId::Extended(id) => EXTENDED_NAMES.iter().find(|(i, _)| i == id).map(|(_, name)| *name),
+        }
+    }
+
+    /// Finds an ID by its name, ignoring case.
+    ///
+    /// A few manufacturers registered more than one ID; the first is returned.
+    pub fn from_name(name: &str) -> Option<ManufacturerId> {
+        known().find(|(_, n)| n.eq_ignore_ascii_case(name)).map(|(id, _)| id)
+    }
+}
+
+/// Every assigned ID, with its registered name.
+pub fn known() -> impl Iterator<Item = (ManufacturerId, &'static str)> {
+    SHORT_NAMES.iter().map(|(id, name)| (ManufacturerId::Short(*id), *name))
+        .chain(EXTENDED_NAMES.iter().map(|(id, name)| (ManufacturerId::Extended(*id), *name)))
+}
+
+/// Formats as hex bytes, such as `41` or `00 20 29`.
+impl fmt::Display for ManufacturerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManufacturerId::Short(id) => write!(f, "{:02X}", id),
+            ManufacturerId::Extended([a, b]) => write!(f, "00 {:02X} {:02X}", a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(data: &[u8]) -> Option<&'static str> {
+        ManufacturerId::parse(data).unwrap().0.name()
+    }
+
+    #[test]
+    fn names_from_each_group() {
+        assert_eq!(name(&[0x00, 0x00, 0x0E]), Some("Alesis"));
+        assert_eq!(name(&[0x00, 0x00, 0x41]), Some("Microsoft"));
+        assert_eq!(name(&[0x00, 0x01, 0x05]), Some("M-Audio"));
+        assert_eq!(name(&[0x00, 0x02, 0x0D]), Some("Google"));
+        assert_eq!(name(&[0x00, 0x20, 0x29]), Some("Focusrite/Novation"));
+        assert_eq!(name(&[0x00, 0x20, 0x6B]), Some("Arturia"));
+        assert_eq!(name(&[0x00, 0x21, 0x09]), Some("Native Instruments"));
+        assert_eq!(name(&[0x00, 0x21, 0x1D]), Some("Ableton"));
+        assert_eq!(name(&[0x41]), Some("Roland"));
+        assert_eq!(name(&[0x43]), Some("Yamaha"));
+        assert_eq!(name(&[0x52]), Some("Zoom"));
+        assert_eq!(name(&[0x5F]), Some("SD Card Association"));
+        assert_eq!(name(&[0x45]), None);
+        assert_eq!(name(&[0x00, 0x00, 0x2C]), None);
+    }
+
+    #[test]
+    fn tables_sorted_and_names_found() {
+        assert!(SHORT_NAMES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(EXTENDED_NAMES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(ManufacturerId::from_name("teenage engineering"), Some(ManufacturerId::Extended([0x20, 0x76])));
+        assert_eq!(ManufacturerId::from_name("Mesa Boogie"), Some(ManufacturerId::Extended([0x00, 0x4A])));
+        assert_eq!(known().count(), SHORT_NAMES.len() + EXTENDED_NAMES.len());
+    }
+}
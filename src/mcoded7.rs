@@ -0,0 +1,40 @@
+//! Mcoded7, the 8-bit to 7-bit encoding of MIDI-CI Property Exchange.
+//!
+//! Each group of up to seven bytes is sent as a byte holding their high bits,
+//! the first byte's in bit 6, followed by their low seven bits.
+//!
+//! Reference: M2-103-UM Common Rules for Property Exchange, section 8.5
+
+use anyhow::{Result, bail};
+
+/// The encoded length of `len` bytes.
+pub fn encoded_len(len: usize) -> usize {
+    len + len.div_ceil(7)
+}
+
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(encoded_len(data.len()));
+    for group in data.chunks(7) {
+        let high_bits = group.iter().enumerate()
+            .fold(0, |bits, (index, byte)| bits | (byte >> 7) << (6 - index));
+        buf.push(high_bits);
+        buf.extend(group.iter().map(|byte| byte & 0x7F));
+    }
+    buf
+}
+
+pub fn decode(data: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(data.len());
+    for group in data.chunks(8) {
+        if group.len() < 2 {
+            bail!("truncated Mcoded7 group");
+        }
+        if group.iter().any(|byte| *byte > 0x7F) {
+            bail!("Mcoded7 byte out of 7-bit range");
+        }
+        let high_bits = group[0];
+        buf.extend(group[1..].iter().enumerate()
+            .map(|(index, byte)| byte | (high_bits >> (6 - index) & 1) << 7));
+    }
+    Ok(buf)
+}
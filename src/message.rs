@@ -2,24 +2,32 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 use anyhow::{Result, anyhow};
 
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum Message {
     Channel(ChannelMessage),
     System(SystemMessage),
 }
 
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct ChannelMessage {
     pub channel: MidiChannelId,
     pub message: ChannelMessageType,
 }
 
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum ChannelMessageType {
     ChannelVoice(ChannelVoiceMessage),
-    ChannelMode(ChannelModeMessage),
+    ChannelMode {
+        mode: ChannelModeMessage,
+        /// The second data byte, such as 127 for Local Control On or the channel count of Mono On.
+        value: u7::Unsigned7,
+    },
 }
 
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum ChannelVoiceMessage {
     NoteOff(cvm::NoteOff),
     NoteOn(cvm::NoteOn),
@@ -32,7 +40,7 @@ pub enum ChannelVoiceMessage {
 
 pub mod u7 {
     #[derive(Debug)]
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct Unsigned7(u8);
 
     impl TryFrom<u8> for Unsigned7 {
@@ -56,7 +64,7 @@ pub mod u7 {
 
 pub mod u14 {
     #[derive(Debug)]
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct Unsigned14(u16);
 
     impl TryFrom<[u8; 2]> for Unsigned14 {
@@ -72,6 +80,18 @@ pub mod u14 {
         }
     }
 
+    impl TryFrom<u16> for Unsigned14 {
+        type Error = anyhow::Error;
+
+        fn try_from(value: u16) -> anyhow::Result<Unsigned14> {
+            if value <= 0x3FFF {
+                Ok(Unsigned14(value))
+            } else {
+                Err(anyhow::anyhow!("out of range"))
+            }
+        }
+    }
+
     impl From<Unsigned14> for u16 {
         fn from(other: Unsigned14) -> u16 {
             other.0
@@ -85,61 +105,68 @@ pub mod cvm {
     pub use super::u14::Unsigned14;
 
     #[derive(Debug)]
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct NoteNumber(pub Unsigned7);
     #[derive(Debug)]
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct KeyVelocity(pub Unsigned7);
     #[derive(Debug)]
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct ControlNumber(pub Unsigned7); // todo restrict range to < 120
     #[derive(Debug)]
-    #[derive(Copy, Clone)]
+    #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub struct ProgramNumber(pub Unsigned7);
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct NoteOff {
         pub note_number: NoteNumber,
         pub velocity: KeyVelocity,
     }
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct NoteOn {
         pub note_number: NoteNumber,
         pub velocity: KeyVelocity,
     }
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct PolyphonicKeyPressureAftertouch {
         pub note_number: NoteNumber,
         pub value: Unsigned7,
     }
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct ControlChange {
         pub control_number: ControlNumber,
         pub value: Unsigned7,
     }
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct ProgramChange {
         pub program_number: ProgramNumber,
     }
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct ChannelPressureAftertouch {
         pub value: Unsigned7,
     }
 
     #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
     pub struct PitchBendChange {
         pub value: Unsigned14,
     }
 }
 
-// FIXME some of these carry data
 /// Referenc: MIDI spec table IV
 #[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[derive(IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum ChannelModeMessage {
@@ -154,6 +181,7 @@ pub enum ChannelModeMessage {
 }
 
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum SystemMessage {
     SystemCommon(SystemCommonMessage),
     SystemRealTime(SystemRealTimeMessage),
@@ -161,6 +189,7 @@ pub enum SystemMessage {
 }
 
 #[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct MidiChannelId(u8);
 
 impl TryFrom<u8> for MidiChannelId {
@@ -175,10 +204,18 @@ impl TryFrom<u8> for MidiChannelId {
     }
 }
 
+impl From<MidiChannelId> for u8 {
+    fn from(other: MidiChannelId) -> u8 {
+        other.0
+    }
+}
+
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct SystemCommonMessage;
 
 #[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[derive(IntoPrimitive, TryFromPrimitive)]
 #[repr(u8)]
 pub enum SystemRealTimeMessage {
@@ -193,6 +230,7 @@ pub enum SystemRealTimeMessage {
 }
 
 #[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct SystemExclusiveMessage;
 
 impl ChannelVoiceMessage {
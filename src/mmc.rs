@@ -0,0 +1,610 @@
+//! MIDI Machine Control: transport commands and the responses of controlled devices.
+//!
+//! Commands are Universal Real Time SysEx with sub-ID#1 `06`, responses with `07`;
+//! one message may hold several of either.
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//!
+//! Reference: MIDI Machine Control 1.0
+
+use anyhow::{Result, bail};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::collections::HashMap;
+use crate::sysex::{ALL_CALL, UNIVERSAL_REALTIME};
+use crate::sysex::realtime::{MIDI_MACHINE_CONTROL_COMMAND, MIDI_MACHINE_CONTROL_RESPONSE};
+
+pub mod commands {
+    pub const STOP: u8 = 0x01;
+    pub const PLAY: u8 = 0x02;
+    pub const DEFERRED_PLAY: u8 = 0x03;
+    pub const FAST_FORWARD: u8 = 0x04;
+    pub const REWIND: u8 = 0x05;
+    pub const RECORD_STROBE: u8 = 0x06;
+    pub const RECORD_EXIT: u8 = 0x07;
+    pub const RECORD_PAUSE: u8 = 0x08;
+    pub const PAUSE: u8 = 0x09;
+    pub const EJECT: u8 = 0x0A;
+    pub const CHASE: u8 = 0x0B;
+    pub const COMMAND_ERROR_RESET: u8 = 0x0C;
+    pub const MMC_RESET: u8 = 0x0D;
+    pub const WRITE: u8 = 0x40;
+    pub const READ: u8 = 0x42;
+    pub const LOCATE: u8 = 0x44;
+    pub const VARIABLE_PLAY: u8 = 0x45;
+    pub const SEARCH: u8 = 0x46;
+    pub const SHUTTLE: u8 = 0x47;
+    pub const STEP: u8 = 0x48;
+    pub const WAIT: u8 = 0x7C;
+    pub const RESUME: u8 = 0x7F;
+}
+
+/// Information fields, the subjects of responses and of Locate.
+pub mod fields {
+    pub const SELECTED_TIME_CODE: u8 = 0x01;
+    pub const SELECTED_MASTER_CODE: u8 = 0x02;
+    pub const REQUESTED_OFFSET: u8 = 0x03;
+    pub const ACTUAL_OFFSET: u8 = 0x04;
+    pub const LOCK_DEVIATION: u8 = 0x05;
+    pub const GENERATOR_TIME_CODE: u8 = 0x06;
+    pub const MIDI_TIME_CODE_INPUT: u8 = 0x07;
+    /// GP0, also the Locate Point.
+    pub const GP0: u8 = 0x08;
+    pub const GP7: u8 = 0x0F;
+    pub const SIGNATURE: u8 = 0x40;
+    pub const UPDATE_RATE: u8 = 0x41;
+    pub const RESPONSE_ERROR: u8 = 0x42;
+    pub const COMMAND_ERROR: u8 = 0x43;
+    pub const COMMAND_ERROR_LEVEL: u8 = 0x44;
+    pub const TIME_STANDARD: u8 = 0x45;
+    pub const SELECTED_TIME_CODE_SOURCE: u8 = 0x46;
+    pub const SELECTED_TIME_CODE_USERBITS: u8 = 0x47;
+    pub const MOTION_CONTROL_TALLY: u8 = 0x48;
+    pub const VELOCITY_TALLY: u8 = 0x49;
+    pub const STOP_MODE: u8 = 0x4A;
+    pub const FAST_MODE: u8 = 0x4B;
+    pub const RECORD_MODE: u8 = 0x4C;
+    pub const RECORD_STATUS: u8 = 0x4D;
+    pub const TRACK_RECORD_STATUS: u8 = 0x4E;
+    pub const TRACK_RECORD_READY: u8 = 0x4F;
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum TimeCodeType {
+    Fps24 = 0,
+    Fps25 = 1,
+    Fps30DropFrame = 2,
+    Fps30 = 3,
+}
+
+/// The last byte of a standard time code.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FinalByte {
+    /// Hundredths of a frame.
+    Subframes(u8),
+    /// Status flags, as sent by some responses.
+    Status(u8),
+}
+
+/// A time in the five-byte standard time code of MMC.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct StandardTime {
+    pub time_code_type: TimeCodeType,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub final_byte: FinalByte,
+    /// Set on offsets before zero.
+    pub negative: bool,
+    pub color_frame: bool,
+}
+
+impl StandardTime {
+    pub fn new(time_code_type: TimeCodeType, hours: u8, minutes: u8, seconds: u8, frames: u8) -> Result<StandardTime> {
+        if hours > 23 || minutes > 59 || seconds > 59 || frames > 29 {
+            bail!("time code out of range: {:02}:{:02}:{:02}:{:02}", hours, minutes, seconds, frames);
+        }
+        Ok(StandardTime {
+            time_code_type,
+            hours,
+            minutes,
+            seconds,
+            frames,
+            final_byte: FinalByte::Subframes(0),
+            negative: false,
+            color_frame: false,
+        })
+    }
+
+    pub fn read(bytes: [u8; 5]) -> Result<StandardTime> {
+        let [hr, mn, sc, fr, ff] = bytes;
+        if bytes.iter().any(|byte| *byte > 0x7F) {
+            bail!("time code byte out of 7-bit range");
+        }
+        Ok(StandardTime {
+            time_code_type: TimeCodeType::try_from(hr >> 5).expect("two bits"),
+            hours: hr & 0x1F,
+            minutes: mn & 0x3F,
+            seconds: sc & 0x3F,
+            frames: fr & 0x1F,
+            final_byte: if fr & 0x20 != 0 { FinalByte::Status(ff) } else { FinalByte::Subframes(ff) },
+            negative: fr & 0x40 != 0,
+            color_frame: mn & 0x40 != 0,
+        })
+    }
+
+    pub fn write(&self, buf: &mut Vec<u8>) {
+        let (final_byte_id, ff) = match self.final_byte {
+            FinalByte::Subframes(subframes) => (0, subframes),
+            FinalByte::Status(status) => (0x20, status),
+        };
+        buf.extend([
+            u8::from(self.time_code_type) << 5 | self.hours & 0x1F,
+            (self.color_frame as u8) << 6 | self.minutes & 0x3F,
+            self.seconds & 0x3F,
+            (self.negative as u8) << 6 | final_byte_id | self.frames & 0x1F,
+            ff,
+        ]);
+    }
+
+    /// Whole frames since midnight, counting dropped frame numbers as if they existed.
+    pub fn total_frames(&self) -> u32 {
+        let fps = match self.time_code_type {
+            TimeCodeType::Fps24 => 24,
+            TimeCodeType::Fps25 => 25,
+            TimeCodeType::Fps30DropFrame | TimeCodeType::Fps30 => 30,
+        };
+        ((self.hours as u32 * 60 + self.minutes as u32) * 60 + self.seconds as u32) * fps + self.frames as u32
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LocateTarget {
+    /// The time held in an information field, such as [`fields::GP0`].
+    Field(u8),
+    Time(StandardTime),
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    /// Play once a Locate in progress completes.
+    DeferredPlay,
+    FastForward,
+    Rewind,
+    /// Punch in, or start recording and playing from stop.
+    RecordStrobe,
+    RecordExit,
+    RecordPause,
+    Pause,
+    Eject,
+    Chase,
+    CommandErrorReset,
+    MmcReset,
+    Locate(LocateTarget),
+    Wait,
+    Resume,
+    Other {
+        command: u8,
+        /// Without the count byte of commands 0x40 to 0x77.
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum MmcResponse {
+    /// Fields 0x01 to 0x1F.
+    Time {
+        field: u8,
+        time: StandardTime,
+    },
+    /// Fields 0x21 to 0x3F: the frames and subframes bytes of a field in 0x01 to 0x1F.
+    ShortTime {
+        field: u8,
+        data: [u8; 2],
+    },
+    /// Fields 0x40 to 0x77, such as [`fields::MOTION_CONTROL_TALLY`].
+    Data {
+        field: u8,
+        /// Without the count byte.
+        data: Vec<u8>,
+    },
+    /// Fields 0x78 to 0x7F, such as Wait and Resume.
+    Flag(u8),
+}
+
+impl MmcResponse {
+    /// A Motion Control Tally: the last motion command received,
+    /// the motion now in effect, and status flags.
+    pub fn motion_control_tally(received: u8, in_effect: u8, status: u8) -> MmcResponse {
+        MmcResponse::Data {
+            field: fields::MOTION_CONTROL_TALLY,
+            data: vec![received, in_effect, status],
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum MmcBody {
+    Commands(Vec<MmcCommand>),
+    Responses(Vec<MmcResponse>),
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct MmcMessage {
+    /// The controlled device, or [`ALL_CALL`].
+    pub device_id: u8,
+    pub body: MmcBody,
+}
+
+fn take<'buf>(reader: &mut &'buf [u8], len: usize) -> Result<&'buf [u8]> {
+    if reader.len() < len {
+        bail!("truncated MMC message");
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(bytes)
+}
+
+/// Reads the count byte and data of fields and commands 0x40 to 0x77.
+fn take_counted<'buf>(reader: &mut &'buf [u8]) -> Result<&'buf [u8]> {
+    let count = take(reader, 1)?[0] as usize;
+    take(reader, count)
+}
+
+fn write_counted(data: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    if data.len() > 0x7F {
+        bail!("MMC field too long: {} bytes", data.len());
+    }
+    buf.push(data.len() as u8);
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+impl MmcCommand {
+    fn read(reader: &mut &[u8]) -> Result<MmcCommand> {
+        use commands::*;
+
+        let command = take(reader, 1)?[0];
+        Ok(match command {
+            0x00 => bail!("extended MMC commands are not supported"),
+            STOP => MmcCommand::Stop,
+            PLAY => MmcCommand::Play,
+            DEFERRED_PLAY => MmcCommand::DeferredPlay,
+            FAST_FORWARD => MmcCommand::FastForward,
+            REWIND => MmcCommand::Rewind,
+            RECORD_STROBE => MmcCommand::RecordStrobe,
+            RECORD_EXIT => MmcCommand::RecordExit,
+            RECORD_PAUSE => MmcCommand::RecordPause,
+            PAUSE => MmcCommand::Pause,
+            EJECT => MmcCommand::Eject,
+            CHASE => MmcCommand::Chase,
+            COMMAND_ERROR_RESET => MmcCommand::CommandErrorReset,
+            MMC_RESET => MmcCommand::MmcReset,
+            WAIT => MmcCommand::Wait,
+            RESUME => MmcCommand::Resume,
+            LOCATE => match take_counted(reader)? {
+                [0x00, field] => MmcCommand::Locate(LocateTarget::Field(*field)),
+                [0x01, time @ ..] if time.len() == 5 => {
+                    MmcCommand::Locate(LocateTarget::Time(StandardTime::read(time.try_into().expect("length"))?))
+                }
+                data => bail!("invalid MMC Locate {:02X?}", data),
+            },
+            0x40..=0x77 => MmcCommand::Other {
+                command,
+                data: take_counted(reader)?.to_vec(),
+            },
+            _ => MmcCommand::Other {
+                command,
+                data: Vec::new(),
+            },
+        })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        use commands::*;
+
+        let command = match self {
+            MmcCommand::Stop => STOP,
+            MmcCommand::Play => PLAY,
+            MmcCommand::DeferredPlay => DEFERRED_PLAY,
+            MmcCommand::FastForward => FAST_FORWARD,
+            MmcCommand::Rewind => REWIND,
+            MmcCommand::RecordStrobe => RECORD_STROBE,
+            MmcCommand::RecordExit => RECORD_EXIT,
+            MmcCommand::RecordPause => RECORD_PAUSE,
+            MmcCommand::Pause => PAUSE,
+            MmcCommand::Eject => EJECT,
+            MmcCommand::Chase => CHASE,
+            MmcCommand::CommandErrorReset => COMMAND_ERROR_RESET,
+            MmcCommand::MmcReset => MMC_RESET,
+            MmcCommand::Wait => WAIT,
+            MmcCommand::Resume => RESUME,
+            MmcCommand::Locate(target) => {
+                buf.push(LOCATE);
+                match target {
+                    LocateTarget::Field(field) => buf.extend([0x02, 0x00, *field]),
+                    LocateTarget::Time(time) => {
+                        buf.extend([0x06, 0x01]);
+                        time.write(buf);
+                    }
+                }
+                return Ok(());
+            }
+            MmcCommand::Other { command, data } => {
+                if *command == 0x00 {
+                    bail!("extended MMC commands are not supported");
+                }
+                buf.push(*command);
+                if (0x40..=0x77).contains(command) {
+                    write_counted(data, buf)?;
+                } else if !data.is_empty() {
+                    bail!("MMC command {:02X} takes no data", command);
+                }
+                return Ok(());
+            }
+        };
+        buf.push(command);
+        Ok(())
+    }
+}
+
+impl MmcResponse {
+    fn read(reader: &mut &[u8]) -> Result<MmcResponse> {
+        let field = take(reader, 1)?[0];
+        Ok(match field {
+            0x00 => bail!("extended MMC responses are not supported"),
+            0x01..=0x1F => MmcResponse::Time {
+                field,
+                time: StandardTime::read(take(reader, 5)?.try_into().expect("length"))?,
+            },
+            0x20..=0x3F => MmcResponse::ShortTime {
+                field,
+                data: take(reader, 2)?.try_into().expect("length"),
+            },
+            0x40..=0x77 => MmcResponse::Data {
+                field,
+                data: take_counted(reader)?.to_vec(),
+            },
+            _ => MmcResponse::Flag(field),
+        })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            MmcResponse::Time { field: field @ 0x01..=0x1F, time } => {
+                buf.push(*field);
+                time.write(buf);
+            }
+            MmcResponse::ShortTime { field: field @ 0x20..=0x3F, data } => {
+                buf.push(*field);
+                buf.extend_from_slice(data);
+            }
+            MmcResponse::Data { field: field @ 0x40..=0x77, data } => {
+                buf.push(*field);
+                write_counted(data, buf)?;
+            }
+            MmcResponse::Flag(field @ 0x78..=0x7F) => buf.push(*field),
+            _ => bail!("MMC response field out of range for its format: {:?}", self),
+        }
+        Ok(())
+    }
+}
+
+impl MmcMessage {
+    pub fn commands(device_id: u8, commands: Vec<MmcCommand>) -> MmcMessage {
+        MmcMessage {
+            device_id,
+            body: MmcBody::Commands(commands),
+        }
+    }
+
+    pub fn is_mmc(data: &[u8]) -> bool {
+        matches!(data, [UNIVERSAL_REALTIME, _, MIDI_MACHINE_CONTROL_COMMAND | MIDI_MACHINE_CONTROL_RESPONSE, ..])
+    }
+
+    /// Parses SysEx data, without `F0` and `F7`.
+    pub fn parse(data: &[u8]) -> Result<MmcMessage> {
+        if !MmcMessage::is_mmc(data) {
+            bail!("not an MMC message");
+        }
+        let device_id = data[1];
+        let reader = &mut &data[3..];
+        let body = if data[2] == MIDI_MACHINE_CONTROL_COMMAND {
+            let mut commands = Vec::new();
+            while !reader.is_empty() {
+                commands.push(MmcCommand::read(reader)?);
+            }
+            MmcBody::Commands(commands)
+        } else {
+            let mut responses = Vec::new();
+            while !reader.is_empty() {
+                responses.push(MmcResponse::read(reader)?);
+            }
+            MmcBody::Responses(responses)
+        };
+        Ok(MmcMessage { device_id, body })
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        match &self.body {
+            MmcBody::Commands(commands) => {
+                buf.extend([UNIVERSAL_REALTIME, self.device_id, MIDI_MACHINE_CONTROL_COMMAND]);
+                for command in commands {
+                    command.write(buf)?;
+                }
+            }
+            MmcBody::Responses(responses) => {
+                buf.extend([UNIVERSAL_REALTIME, self.device_id, MIDI_MACHINE_CONTROL_RESPONSE]);
+                for response in responses {
+                    response.write(buf)?;
+                }
+            }
+        }
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("MMC field out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TransportState {
+    Stopped,
+    Playing,
+    FastForward,
+    Rewind,
+    Paused,
+}
+
+/// The transport of a controlled device, following the MMC commands addressed to it.
+///
+/// Locating is taken to complete at once, so Deferred Play plays immediately.
+#[derive(Debug)]
+pub struct Transport {
+    device_id: u8,
+    state: TransportState,
+    recording: bool,
+    position: Option<StandardTime>,
+    fields: HashMap<u8, StandardTime>,
+    last_command: Option<u8>,
+}
+
+impl Transport {
+    pub fn new(device_id: u8) -> Transport {
+        Transport {
+            device_id,
+            state: TransportState::Stopped,
+            recording: false,
+            position: None,
+            fields: HashMap::new(),
+            last_command: None,
+        }
+    }
+
+    pub fn state(&self) -> TransportState {
+        self.state
+    }
+
+    /// Whether recording, or ready to record on leaving Record Pause.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// The last time located to.
+    pub fn position(&self) -> Option<StandardTime> {
+        self.position
+    }
+
+    /// Stores a time that Locate may refer to, such as [`fields::GP0`].
+    pub fn set_field(&mut self, field: u8, time: StandardTime) {
+        self.fields.insert(field, time);
+    }
+
+    /// Follows the commands of a message addressed to this device or to all,
+    /// returning whether the state changed.
+    pub fn handle(&mut self, message: &MmcMessage) -> bool {
+        let MmcBody::Commands(commands) = &message.body else {
+            return false;
+        };
+        if message.device_id != self.device_id && message.device_id != ALL_CALL {
+            return false;
+        }
+        let mut changed = false;
+        for command in commands {
+            changed |= self.handle_command(command);
+        }
+        changed
+    }
+
+    /// Follows one command, returning whether the state changed.
+    pub fn handle_command(&mut self, command: &MmcCommand) -> bool {
+        let before = (self.state, self.recording, self.position);
+        match command {
+            MmcCommand::Stop | MmcCommand::MmcReset => {
+                self.state = TransportState::Stopped;
+                self.recording = false;
+            }
+            MmcCommand::Play | MmcCommand::DeferredPlay => self.state = TransportState::Playing,
+            MmcCommand::FastForward | MmcCommand::Rewind => {
+                self.state = if *command == MmcCommand::FastForward { TransportState::FastForward } else { TransportState::Rewind };
+                self.recording = false;
+            }
+            MmcCommand::RecordStrobe => {
+                self.state = TransportState::Playing;
+                self.recording = true;
+            }
+            MmcCommand::RecordExit => self.recording = false,
+            MmcCommand::RecordPause => {
+                self.state = TransportState::Paused;
+                self.recording = true;
+            }
+            MmcCommand::Pause => self.state = TransportState::Paused,
+            MmcCommand::Locate(target) => {
+                let time = match target {
+                    LocateTarget::Field(field) => self.fields.get(field).copied(),
+                    LocateTarget::Time(time) => Some(*time),
+                };
+                if let Some(time) = time {
+                    self.position = Some(time);
+                    self.state = TransportState::Stopped;
+                    self.recording = false;
+                }
+            }
+            _ => {}
+        }
+        self.last_command = match command {
+            MmcCommand::Stop => Some(commands::STOP),
+            MmcCommand::Play => Some(commands::PLAY),
+            MmcCommand::DeferredPlay => Some(commands::DEFERRED_PLAY),
+            MmcCommand::FastForward => Some(commands::FAST_FORWARD),
+            MmcCommand::Rewind => Some(commands::REWIND),
+            MmcCommand::Pause => Some(commands::PAUSE),
+            MmcCommand::Locate(_) => Some(commands::LOCATE),
+            _ => self.last_command,
+        };
+        before != (self.state, self.recording, self.position)
+    }
+
+    /// Responses reporting the state: the selected time code, if located, and a Motion Control Tally.
+    pub fn responses(&self) -> Vec<MmcResponse> {
+        let mut responses = Vec::new();
+        if let Some(time) = self.position {
+            responses.push(MmcResponse::Time {
+                field: fields::SELECTED_TIME_CODE,
+                time,
+            });
+        }
+        let motion = match self.state {
+            TransportState::Stopped => commands::STOP,
+            TransportState::Playing => commands::PLAY,
+            TransportState::FastForward => commands::FAST_FORWARD,
+            TransportState::Rewind => commands::REWIND,
+            TransportState::Paused => commands::PAUSE,
+        };
+        responses.push(MmcResponse::motion_control_tally(self.last_command.unwrap_or(motion), motion, 0x00));
+        responses
+    }
+}
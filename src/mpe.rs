@@ -0,0 +1,569 @@
+//! MIDI Polyphonic Expression: zones, a decoder of per-note expression,
+//! and an encoder that allocates member channels to notes.
+//!
+//! Each note of an MPE zone plays on a member channel of its own,
+//! so the channel's Pitch Bend, Channel Pressure and CC74 (timbre)
+//! shape that note alone. The zone's master channel carries zone-wide messages.
+//!
+//! Reference: M1-100-UM MIDI Polyphonic Expression 1.1
+
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::assert_from::AssertFrom;
+use crate::control_number::ControlNumber;
+use crate::message::*;
+use crate::pitch_bend::BendRange;
+use crate::rpn::{self, Parameter, ParameterTracker, ParameterValue};
+
+/// Pitch Bend Sensitivity of member channels until set.
+pub const DEFAULT_MEMBER_BEND_RANGE: BendRange = BendRange { semitones: 48, cents: 0 };
+/// Pitch Bend Sensitivity of master channels until set.
+pub const DEFAULT_MASTER_BEND_RANGE: BendRange = BendRange::DEFAULT;
+
+/// The controller MPE uses for timbre.
+const TIMBRE: ControlNumber = ControlNumber::SoundController5;
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ZoneKind {
+    /// Master channel 1, members counting up from channel 2.
+    Lower,
+    /// Master channel 16, members counting down from channel 15.
+    Upper,
+}
+
+impl ZoneKind {
+    pub fn master_channel(self) -> MidiChannelId {
+        match self {
+            ZoneKind::Lower => MidiChannelId::assert_from(0),
+            ZoneKind::Upper => MidiChannelId::assert_from(15),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ChannelRole {
+    Master,
+    Member,
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct Zone {
+    pub kind: ZoneKind,
+    /// 1 to 15.
+    pub member_count: u8,
+    pub master_bend_range: BendRange,
+    pub member_bend_range: BendRange,
+}
+
+impl Zone {
+    pub fn new(kind: ZoneKind, member_count: u8) -> Result<Zone> {
+        if !(1..=15).contains(&member_count) {
+            bail!("MPE zone member count out of range: {}", member_count);
+        }
+        Ok(Zone {
+            kind,
+            member_count,
+            master_bend_range: DEFAULT_MASTER_BEND_RANGE,
+            member_bend_range: DEFAULT_MEMBER_BEND_RANGE,
+        })
+    }
+
+    pub fn master_channel(&self) -> MidiChannelId {
+        self.kind.master_channel()
+    }
+
+    /// Member channels, nearest the master channel first.
+    pub fn member_channels(&self) -> impl Iterator<Item = MidiChannelId> {
+        let kind = self.kind;
+        (1..=self.member_count).map(move |offset| match kind {
+            ZoneKind::Lower => MidiChannelId::assert_from(offset),
+            ZoneKind::Upper => MidiChannelId::assert_from(15 - offset),
+        })
+    }
+
+    pub fn role(&self, channel: MidiChannelId) -> Option<ChannelRole> {
+        let channel = u8::from(channel);
+        let master = u8::from(self.master_channel());
+        let distance = channel.abs_diff(master);
+        if distance == 0 {
+            Some(ChannelRole::Master)
+        } else if distance <= self.member_count {
+            Some(ChannelRole::Member)
+        } else {
+            None
+        }
+    }
+}
+
+/// The zones of an MPE port, as set by MPE Configuration Messages.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Default)]
+pub struct ZoneLayout {
+    pub lower: Option<Zone>,
+    pub upper: Option<Zone>,
+}
+
+impl ZoneLayout {
+    /// Applies an MPE Configuration Message.
+    ///
+    /// Zero members disables the zone. The other zone shrinks to make room,
+    /// and is disabled if no member channels remain for it.
+    /// Bend ranges of the configured zone return to their defaults.
+    pub fn configure(&mut self, kind: ZoneKind, member_count: u8) {
+        let member_count = member_count.min(15);
+        let (zone, other) = match kind {
+            ZoneKind::Lower => (&mut self.lower, &mut self.upper),
+            ZoneKind::Upper => (&mut self.upper, &mut self.lower),
+        };
+        *zone = Zone::new(kind, member_count).ok();
+        if let Some(other_zone) = other {
+            let room = 14u8.saturating_sub(member_count);
+            if room == 0 {
+                *other = None;
+            } else {
+                other_zone.member_count = other_zone.member_count.min(room);
+            }
+        }
+    }
+
+    pub fn zone(&self, kind: ZoneKind) -> Option<&Zone> {
+        match kind {
+            ZoneKind::Lower => self.lower.as_ref(),
+            ZoneKind::Upper => self.upper.as_ref(),
+        }
+    }
+
+    fn zone_mut(&mut self, kind: ZoneKind) -> Option<&mut Zone> {
+        match kind {
+            ZoneKind::Lower => self.lower.as_mut(),
+            ZoneKind::Upper => self.upper.as_mut(),
+        }
+    }
+
+    /// The zone a channel belongs to, and its role there.
+    pub fn role(&self, channel: MidiChannelId) -> Option<(ZoneKind, ChannelRole)> {
+        [self.lower, self.upper].into_iter().flatten()
+            .find_map(|zone| zone.role(channel).map(|role| (zone.kind, role)))
+    }
+}
+
+/// The expression of a note.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct Expression {
+    /// In semitones, including the zone's master channel bend.
+    pub pitch_bend: f32,
+    /// CC74, from 0.0 to 1.0.
+    pub timbre: f32,
+    /// Channel Pressure, from 0.0 to 1.0.
+    pub pressure: f32,
+}
+
+impl Default for Expression {
+    fn default() -> Expression {
+        Expression {
+            pitch_bend: 0.0,
+            timbre: 64.0 / 127.0,
+            pressure: 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct ExpressionPoint {
+    pub time: Duration,
+    pub expression: Expression,
+}
+
+/// Identifies a note over its lifetime.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NoteId(pub u64);
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct MpeNote {
+    pub id: NoteId,
+    pub zone: ZoneKind,
+    pub channel: MidiChannelId,
+    pub note_number: cvm::NoteNumber,
+    pub velocity: cvm::KeyVelocity,
+    pub release_velocity: Option<cvm::KeyVelocity>,
+    pub start: Duration,
+    pub end: Option<Duration>,
+    /// Expression over the note's lifetime, starting at `start`.
+    pub curve: Vec<ExpressionPoint>,
+}
+
+impl MpeNote {
+    /// The latest expression.
+    pub fn expression(&self) -> Expression {
+        self.curve.last().map(|point| point.expression).unwrap_or_default()
+    }
+
+    /// The sounding pitch in semitones, as a fractional note number.
+    pub fn pitch(&self) -> f32 {
+        u8::from(self.note_number.0) as f32 + self.expression().pitch_bend
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub enum MpeEvent {
+    NoteOn(MpeNote),
+    /// A note's expression changed; the point is also added to its curve.
+    Expression {
+        id: NoteId,
+        point: ExpressionPoint,
+    },
+    /// A note ended, with its complete expression curve.
+    NoteOff(MpeNote),
+    ZonesChanged(ZoneLayout),
+    /// A message that isn't per-note expression, such as a master channel Control Change,
+    /// or any message outside a zone.
+    Other(ChannelMessage),
+}
+
+#[derive(Clone)]
+struct ChannelExpression {
+    pitch_bend: cvm::PitchBendChange,
+    timbre: u8,
+    pressure: u8,
+}
+
+impl Default for ChannelExpression {
+    fn default() -> ChannelExpression {
+        ChannelExpression {
+            pitch_bend: cvm::PitchBendChange {
+                value: cvm::Unsigned14::assert_from(cvm::PitchBendChange::CENTER),
+            },
+            timbre: 64,
+            pressure: 0,
+        }
+    }
+}
+
+/// Turns the messages of an MPE port into notes with expression.
+#[derive(Default)]
+pub struct MpeDecoder {
+    layout: ZoneLayout,
+    parameters: ParameterTracker,
+    channels: [ChannelExpression; 16],
+    notes: Vec<MpeNote>,
+    next_id: u64,
+}
+
+impl MpeDecoder {
+    /// Creates a decoder with no zones, waiting for MPE Configuration Messages.
+    pub fn new() -> MpeDecoder {
+        MpeDecoder::default()
+    }
+
+    /// Creates a decoder for a known layout.
+    pub fn with_layout(layout: ZoneLayout) -> MpeDecoder {
+        MpeDecoder {
+            layout,
+            ..MpeDecoder::default()
+        }
+    }
+
+    pub fn layout(&self) -> &ZoneLayout {
+        &self.layout
+    }
+
+    /// Notes sounding now.
+    pub fn active_notes(&self) -> &[MpeNote] {
+        &self.notes
+    }
+
+    /// Handles a message received at `time`.
+    pub fn handle(&mut self, time: Duration, message: &ChannelMessage) -> Vec<MpeEvent> {
+        let channel = message.channel;
+        if let Some(change) = self.parameters.handle(message) {
+            return self.parameter_changed(change);
+        }
+        let Some((zone, role)) = self.layout.role(channel) else {
+            return vec![MpeEvent::Other(message.clone())];
+        };
+        let index = u8::from(channel) as usize;
+
+        let voice = match &message.message {
+            ChannelMessageType::ChannelVoice(voice) => voice,
+            ChannelMessageType::ChannelMode { mode: ChannelModeMessage::AllSoundOff, .. } => {
+                let mut events = self.end_notes(time, |note| note.channel == channel, None);
+                events.push(MpeEvent::Other(message.clone()));
+                return events;
+            }
+            ChannelMessageType::ChannelMode { mode: ChannelModeMessage::ResetAllControllers, .. } => {
+                self.channels[index] = ChannelExpression::default();
+                let mut events = self.update_notes(time, zone, role, channel);
+                events.push(MpeEvent::Other(message.clone()));
+                return events;
+            }
+            ChannelMessageType::ChannelMode { .. } => return vec![MpeEvent::Other(message.clone())],
+        };
+
+        if let Some((note_number, velocity)) = voice.should_note_on() {
+            let id = NoteId(self.next_id);
+            self.next_id += 1;
+            let note = MpeNote {
+                id,
+                zone,
+                channel,
+                note_number,
+                velocity,
+                release_velocity: None,
+                start: time,
+                end: None,
+                curve: vec![ExpressionPoint {
+                    time,
+                    expression: self.expression(zone, role, channel),
+                }],
+            };
+            self.notes.push(note.clone());
+            return vec![MpeEvent::NoteOn(note)];
+        }
+        if let Some((note_number, velocity)) = voice.should_note_off() {
+            return self.end_notes(time, |note| note.channel == channel && note.note_number == note_number, Some(velocity));
+        }
+
+        let state = &mut self.channels[index];
+        match voice {
+            ChannelVoiceMessage::PitchBendChange(bend) => state.pitch_bend = bend.clone(),
+            ChannelVoiceMessage::ChannelPressureAftertouch(pressure) if role == ChannelRole::Member => {
+                state.pressure = u8::from(pressure.value);
+            }
+            ChannelVoiceMessage::ControlChange(cc)
+                if role == ChannelRole::Member && u8::from(cc.control_number.0) == u8::from(TIMBRE) =>
+            {
+                state.timbre = u8::from(cc.value);
+            }
+            _ => return vec![MpeEvent::Other(message.clone())],
+        }
+        self.update_notes(time, zone, role, channel)
+    }
+
+    fn parameter_changed(&mut self, change: rpn::ParameterChange) -> Vec<MpeEvent> {
+        match change.parameter {
+            Parameter::Registered(rpn::registered::MPE_CONFIGURATION) => {
+                let kind = match u8::from(change.channel) {
+                    0 => ZoneKind::Lower,
+                    15 => ZoneKind::Upper,
+                    _ => {
+                        log::debug!("ignoring MPE Configuration Message on channel {:?}", change.channel);
+                        return vec![];
+                    }
+                };
+                let previous = self.layout;
+                self.layout.configure(kind, change.value.msb);
+                if self.layout == previous {
+                    // Such as the Data Entry LSB following the MSB.
+                    vec![]
+                } else {
+                    vec![MpeEvent::ZonesChanged(self.layout)]
+                }
+            }
+            Parameter::Registered(rpn::registered::PITCH_BEND_SENSITIVITY) => {
+                let Some((zone, role)) = self.layout.role(change.channel) else {
+                    return vec![];
+                };
+                let range = BendRange::from_parameter_value(change.value);
+                let zone = self.layout.zone_mut(zone).expect("zone of channel");
+                match role {
+                    ChannelRole::Master => zone.master_bend_range = range,
+                    ChannelRole::Member => zone.member_bend_range = range,
+                }
+                vec![]
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The expression of a note on `channel`.
+    fn expression(&self, zone: ZoneKind, role: ChannelRole, channel: MidiChannelId) -> Expression {
+        let Some(zone) = self.layout.zone(zone) else {
+            return Expression::default();
+        };
+        let master = &self.channels[u8::from(zone.master_channel()) as usize];
+        let master_bend = master.pitch_bend.semitones(zone.master_bend_range);
+        match role {
+            ChannelRole::Master => Expression {
+                pitch_bend: master_bend,
+                timbre: master.timbre as f32 / 127.0,
+                pressure: master.pressure as f32 / 127.0,
+            },
+            ChannelRole::Member => {
+                let member = &self.channels[u8::from(channel) as usize];
+                Expression {
+                    pitch_bend: master_bend + member.pitch_bend.semitones(zone.member_bend_range),
+                    timbre: member.timbre as f32 / 127.0,
+                    pressure: member.pressure as f32 / 127.0,
+                }
+            }
+        }
+    }
+
+    /// Adds a point to the curves of the notes a channel's expression affects.
+    fn update_notes(&mut self, time: Duration, zone: ZoneKind, role: ChannelRole, channel: MidiChannelId) -> Vec<MpeEvent> {
+        let mut events = vec![];
+        for index in 0..self.notes.len() {
+            let note = &self.notes[index];
+            let affected = match role {
+                ChannelRole::Master => note.zone == zone,
+                ChannelRole::Member => note.channel == channel,
+            };
+            if !affected {
+                continue;
+            }
+            let note_role = self.layout.role(note.channel).map_or(ChannelRole::Member, |(_, role)| role);
+            let point = ExpressionPoint {
+                time,
+                expression: self.expression(zone, note_role, note.channel),
+            };
+            self.notes[index].curve.push(point);
+            events.push(MpeEvent::Expression {
+                id: self.notes[index].id,
+                point,
+            });
+        }
+        events
+    }
+
+    fn end_notes(
+        &mut self,
+        time: Duration,
+        matches: impl Fn(&MpeNote) -> bool,
+        velocity: Option<cvm::KeyVelocity>,
+    ) -> Vec<MpeEvent> {
+        let (ended, active) = std::mem::take(&mut self.notes).into_iter().partition(|note| matches(note));
+        self.notes = active;
+        ended.into_iter().map(|mut note: MpeNote| {
+            note.release_velocity = velocity;
+            note.end = Some(time);
+            MpeEvent::NoteOff(note)
+        }).collect()
+    }
+}
+
+struct EncodedNote {
+    channel: MidiChannelId,
+    note_number: cvm::NoteNumber,
+}
+
+/// Sends notes of one zone, each on a member channel of its own where possible.
+pub struct MpeEncoder {
+    zone: Zone,
+    notes: HashMap<NoteId, EncodedNote>,
+    /// When each member channel last started or ended a note, by index among members.
+    last_used: Vec<u64>,
+    clock: u64,
+    next_id: u64,
+}
+
+impl MpeEncoder {
+    pub fn new(zone: Zone) -> MpeEncoder {
+        MpeEncoder {
+            zone,
+            notes: HashMap::new(),
+            last_used: vec![0; zone.member_count as usize],
+            clock: 0,
+            next_id: 0,
+        }
+    }
+
+    pub fn zone(&self) -> &Zone {
+        &self.zone
+    }
+
+    /// The MPE Configuration Message and Pitch Bend Sensitivity of the zone.
+    ///
+    /// Member bend range is sent to every member channel,
+    /// for receivers that don't apply it zone-wide.
+    pub fn configuration_messages(&self) -> Vec<ChannelMessage> {
+        let mut messages = rpn::parameter_messages(
+            self.zone.master_channel(),
+            Parameter::Registered(rpn::registered::MPE_CONFIGURATION),
+            ParameterValue { msb: self.zone.member_count, lsb: 0 },
+        );
+        messages.extend(self.zone.master_bend_range.messages(self.zone.master_channel()));
+        for channel in self.zone.member_channels() {
+            messages.extend(self.zone.member_bend_range.messages(channel));
+        }
+        messages
+    }
+
+    /// Starts a note on the member channel with the fewest notes,
+    /// preferring the one unused for longest so releases can ring out.
+    ///
+    /// Expression is sent before the Note On. Its pitch bend is relative to
+    /// the master channel, which this encoder leaves centered.
+    pub fn note_on(&mut self, note_number: cvm::NoteNumber, velocity: cvm::KeyVelocity, expression: Expression) -> (NoteId, Vec<ChannelMessage>) {
+        let channels: Vec<MidiChannelId> = self.zone.member_channels().collect();
+        let index = (0..channels.len())
+            .min_by_key(|index| {
+                let notes = self.notes.values().filter(|note| note.channel == channels[*index]).count();
+                (notes, self.last_used[*index])
+            })
+            .expect("zone has members");
+        let channel = channels[index];
+        self.touch(index);
+
+        let id = NoteId(self.next_id);
+        self.next_id += 1;
+        self.notes.insert(id, EncodedNote { channel, note_number });
+
+        let mut messages = self.expression_messages(channel, expression);
+        messages.push(voice(channel, ChannelVoiceMessage::NoteOn(cvm::NoteOn { note_number, velocity })));
+        (id, messages)
+    }
+
+    /// Changes the expression of a sounding note.
+    pub fn update(&mut self, id: NoteId, expression: Expression) -> Result<Vec<ChannelMessage>> {
+        let note = self.notes.get(&id).ok_or_else(|| anyhow!("unknown note {:?}", id))?;
+        Ok(self.expression_messages(note.channel, expression))
+    }
+
+    pub fn note_off(&mut self, id: NoteId, velocity: cvm::KeyVelocity) -> Result<Vec<ChannelMessage>> {
+        let note = self.notes.remove(&id).ok_or_else(|| anyhow!("unknown note {:?}", id))?;
+        if let Some(index) = self.zone.member_channels().position(|channel| channel == note.channel) {
+            self.touch(index);
+        }
+        Ok(vec![voice(note.channel, ChannelVoiceMessage::NoteOff(cvm::NoteOff {
+            note_number: note.note_number,
+            velocity,
+        }))])
+    }
+
+    fn touch(&mut self, index: usize) {
+        self.clock += 1;
+        self.last_used[index] = self.clock;
+    }
+
+    fn expression_messages(&self, channel: MidiChannelId, expression: Expression) -> Vec<ChannelMessage> {
+        let unit = |value: f32| cvm::Unsigned7::assert_from((value.clamp(0.0, 1.0) * 127.0).round() as u8);
+        vec![
+            voice(channel, ChannelVoiceMessage::PitchBendChange(
+                cvm::PitchBendChange::from_semitones(expression.pitch_bend, self.zone.member_bend_range),
+            )),
+            voice(channel, ChannelVoiceMessage::ControlChange(cvm::ControlChange {
+                control_number: cvm::ControlNumber(cvm::Unsigned7::assert_from(u8::from(TIMBRE))),
+                value: unit(expression.timbre),
+            })),
+            voice(channel, ChannelVoiceMessage::ChannelPressureAftertouch(cvm::ChannelPressureAftertouch {
+                value: unit(expression.pressure),
+            })),
+        ]
+    }
+}
+
+fn voice(channel: MidiChannelId, message: ChannelVoiceMessage) -> ChannelMessage {
+    ChannelMessage {
+        channel,
+        message: ChannelMessageType::ChannelVoice(message),
+    }
+}
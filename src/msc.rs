@@ -0,0 +1,477 @@
+//! MIDI Show Control: cues for lighting, sound, machinery and other show equipment.
+//!
+//! MSC messages are Universal Real Time SysEx with sub-ID#1 `02`.
+//! Cue numbers, lists and paths are sent as ASCII digits and decimal points, such as `235.6`.
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//!
+//! Reference: MIDI Show Control 1.1
+
+use anyhow::{Result, anyhow, bail};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use crate::mmc::StandardTime;
+use crate::sysex::UNIVERSAL_REALTIME;
+
+pub const SUB_ID_1_MIDI_SHOW_CONTROL: u8 = 0x02;
+
+/// The device ID addressing every device; 0x70 to 0x7E address groups.
+pub const ALL_CALL: u8 = 0x7F;
+
+/// The kind of equipment a command is for.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum CommandFormat {
+    Lighting = 0x01,
+    MovingLights = 0x02,
+    ColorChangers = 0x03,
+    Strobes = 0x04,
+    Lasers = 0x05,
+    Chasers = 0x06,
+    Sound = 0x10,
+    Music = 0x11,
+    CdPlayers = 0x12,
+    EpromPlayback = 0x13,
+    AudioTapeMachines = 0x14,
+    Intercoms = 0x15,
+    Amplifiers = 0x16,
+    AudioEffectsDevices = 0x17,
+    Equalizers = 0x18,
+    Machinery = 0x20,
+    Rigging = 0x21,
+    Flys = 0x22,
+    Lifts = 0x23,
+    Turntables = 0x24,
+    Trusses = 0x25,
+    Robots = 0x26,
+    Animation = 0x27,
+    Floats = 0x28,
+    Breakaways = 0x29,
+    Barges = 0x2A,
+    Video = 0x30,
+    VideoTapeMachines = 0x31,
+    VideoCassetteMachines = 0x32,
+    VideoDiscPlayers = 0x33,
+    VideoSwitchers = 0x34,
+    VideoEffects = 0x35,
+    VideoCharacterGenerators = 0x36,
+    VideoStillStores = 0x37,
+    VideoMonitors = 0x38,
+    Projection = 0x40,
+    FilmProjectors = 0x41,
+    SlideProjectors = 0x42,
+    VideoProjectors = 0x43,
+    Dissolvers = 0x44,
+    ShutterControls = 0x45,
+    ProcessControl = 0x50,
+    HydraulicOil = 0x51,
+    H2o = 0x52,
+    Co2 = 0x53,
+    CompressedAir = 0x54,
+    NaturalGas = 0x55,
+    Fog = 0x56,
+    Smoke = 0x57,
+    CrackedHaze = 0x58,
+    Pyro = 0x60,
+    Fireworks = 0x61,
+    Explosions = 0x62,
+    Flame = 0x63,
+    SmokePots = 0x64,
+    AllTypes = 0x7F,
+}
+
+pub mod commands {
+    pub const GO: u8 = 0x01;
+    pub const STOP: u8 = 0x02;
+    pub const RESUME: u8 = 0x03;
+    pub const TIMED_GO: u8 = 0x04;
+    pub const LOAD: u8 = 0x05;
+    pub const SET: u8 = 0x06;
+    pub const FIRE: u8 = 0x07;
+    pub const ALL_OFF: u8 = 0x08;
+    pub const RESTORE: u8 = 0x09;
+    pub const RESET: u8 = 0x0A;
+    pub const GO_OFF: u8 = 0x0B;
+    pub const GO_JAM_CLOCK: u8 = 0x10;
+    pub const STANDBY_PLUS: u8 = 0x11;
+    pub const STANDBY_MINUS: u8 = 0x12;
+    pub const SEQUENCE_PLUS: u8 = 0x13;
+    pub const SEQUENCE_MINUS: u8 = 0x14;
+    pub const START_CLOCK: u8 = 0x15;
+    pub const STOP_CLOCK: u8 = 0x16;
+    pub const ZERO_CLOCK: u8 = 0x17;
+    pub const SET_CLOCK: u8 = 0x18;
+    pub const MTC_CHASE_ON: u8 = 0x19;
+    pub const MTC_CHASE_OFF: u8 = 0x1A;
+    pub const OPEN_CUE_LIST: u8 = 0x1B;
+    pub const CLOSE_CUE_LIST: u8 = 0x1C;
+    pub const OPEN_CUE_PATH: u8 = 0x1D;
+    pub const CLOSE_CUE_PATH: u8 = 0x1E;
+}
+
+/// A cue number, optionally in a cue list, optionally in a cue path.
+///
+/// Each field is ASCII digits and decimal points.
+/// An empty cue, with no number, means the next cue in sequence.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+pub struct Cue {
+    pub number: Option<String>,
+    pub list: Option<String>,
+    pub path: Option<String>,
+}
+
+impl Cue {
+    pub fn new(number: &str) -> Result<Cue> {
+        check_field(number)?;
+        Ok(Cue {
+            number: Some(number.to_string()),
+            list: None,
+            path: None,
+        })
+    }
+
+    pub fn in_list(mut self, list: &str) -> Result<Cue> {
+        check_field(list)?;
+        self.list = Some(list.to_string());
+        Ok(self)
+    }
+
+    pub fn in_path(mut self, path: &str) -> Result<Cue> {
+        check_field(path)?;
+        self.path = Some(path.to_string());
+        Ok(self)
+    }
+
+    fn read(data: &[u8]) -> Result<Cue> {
+        let mut fields = read_fields(data)?.into_iter();
+        let cue = Cue {
+            number: fields.next(),
+            list: fields.next(),
+            path: fields.next(),
+        };
+        if fields.next().is_some() {
+            bail!("MSC cue with more than three fields");
+        }
+        Ok(cue)
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let fields = match (&self.number, &self.list, &self.path) {
+            (None, None, None) => vec![],
+            (Some(number), None, None) => vec![number],
+            (Some(number), Some(list), None) => vec![number, list],
+            (Some(number), Some(list), Some(path)) => vec![number, list, path],
+            _ => bail!("MSC cue list needs a cue number, and cue path a cue list"),
+        };
+        for (index, field) in fields.into_iter().enumerate() {
+            if index > 0 {
+                buf.push(0x00);
+            }
+            write_field(field, buf)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_field(field: &str) -> Result<()> {
+    if field.is_empty() || !field.bytes().all(|byte| byte.is_ascii_digit() || byte == b'.') {
+        bail!("MSC cue field must be digits and decimal points: {:?}", field);
+    }
+    Ok(())
+}
+
+/// Splits data into cue fields separated by `00`, allowing a trailing `00` as some devices send.
+fn read_fields(data: &[u8]) -> Result<Vec<String>> {
+    let data = data.strip_suffix(&[0x00]).unwrap_or(data);
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+    data.split(|byte| *byte == 0x00)
+        .map(|field| {
+            let field = String::from_utf8_lossy(field).into_owned();
+            check_field(&field)?;
+            Ok(field)
+        })
+        .collect()
+}
+
+fn write_field(field: &str, buf: &mut Vec<u8>) -> Result<()> {
+    check_field(field)?;
+    buf.extend(field.bytes());
+    Ok(())
+}
+
+/// Reads a single optional field, such as the cue list of Standby.
+fn read_optional_field(data: &[u8]) -> Result<Option<String>> {
+    let mut fields = read_fields(data)?;
+    if fields.len() > 1 {
+        bail!("MSC command takes at most one cue field");
+    }
+    Ok(fields.pop())
+}
+
+fn read_time(data: &[u8]) -> Result<(StandardTime, &[u8])> {
+    if data.len() < 5 {
+        bail!("truncated MSC time");
+    }
+    let (time, rest) = data.split_at(5);
+    Ok((StandardTime::read(time.try_into().expect("length"))?, rest))
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum MscCommand {
+    Go(Cue),
+    Stop(Cue),
+    Resume(Cue),
+    TimedGo {
+        time: StandardTime,
+        cue: Cue,
+    },
+    Load(Cue),
+    Set {
+        /// 14 bits, such as a fader or channel.
+        control: u16,
+        /// 14 bits.
+        value: u16,
+        time: Option<StandardTime>,
+    },
+    /// Fires a macro.
+    Fire(u8),
+    AllOff,
+    Restore,
+    Reset,
+    GoOff(Cue),
+    GoJamClock(Cue),
+    StandbyPlus(Option<String>),
+    StandbyMinus(Option<String>),
+    SequencePlus(Option<String>),
+    SequenceMinus(Option<String>),
+    StartClock(Option<String>),
+    StopClock(Option<String>),
+    ZeroClock(Option<String>),
+    SetClock {
+        time: StandardTime,
+        list: Option<String>,
+    },
+    MtcChaseOn(Option<String>),
+    MtcChaseOff(Option<String>),
+    OpenCueList(String),
+    CloseCueList(String),
+    OpenCuePath(String),
+    CloseCuePath(String),
+}
+
+impl MscCommand {
+    pub fn command(&self) -> u8 {
+        use commands::*;
+
+        match self {
+            MscCommand::Go(_) => GO,
+            MscCommand::Stop(_) => STOP,
+            MscCommand::Resume(_) => RESUME,
+            MscCommand::TimedGo { .. } => TIMED_GO,
+            MscCommand::Load(_) => LOAD,
+            MscCommand::Set { .. } => SET,
+            MscCommand::Fire(_) => FIRE,
+            MscCommand::AllOff => ALL_OFF,
+            MscCommand::Restore => RESTORE,
+            MscCommand::Reset => RESET,
+            MscCommand::GoOff(_) => GO_OFF,
+            MscCommand::GoJamClock(_) => GO_JAM_CLOCK,
+            MscCommand::StandbyPlus(_) => STANDBY_PLUS,
+            MscCommand::StandbyMinus(_) => STANDBY_MINUS,
+            MscCommand::SequencePlus(_) => SEQUENCE_PLUS,
+            MscCommand::SequenceMinus(_) => SEQUENCE_MINUS,
+            MscCommand::StartClock(_) => START_CLOCK,
+            MscCommand::StopClock(_) => STOP_CLOCK,
+            MscCommand::ZeroClock(_) => ZERO_CLOCK,
+            MscCommand::SetClock { .. } => SET_CLOCK,
+            MscCommand::MtcChaseOn(_) => MTC_CHASE_ON,
+            MscCommand::MtcChaseOff(_) => MTC_CHASE_OFF,
+            MscCommand::OpenCueList(_) => OPEN_CUE_LIST,
+            MscCommand::CloseCueList(_) => CLOSE_CUE_LIST,
+            MscCommand::OpenCuePath(_) => OPEN_CUE_PATH,
+            MscCommand::CloseCuePath(_) => CLOSE_CUE_PATH,
+        }
+    }
+
+    fn read(command: u8, data: &[u8]) -> Result<MscCommand> {
+        use commands::*;
+
+        let no_data = |command| {
+            if !data.is_empty() {
+                bail!("MSC command {:02X} takes no data", command);
+            }
+            Ok(())
+        };
+        let required_field = || read_optional_field(data)?
+            .ok_or_else(|| anyhow!("MSC command {:02X} needs a cue field", command));
+
+        Ok(match command {
+            GO => MscCommand::Go(Cue::read(data)?),
+            STOP => MscCommand::Stop(Cue::read(data)?),
+            RESUME => MscCommand::Resume(Cue::read(data)?),
+            TIMED_GO => {
+                let (time, rest) = read_time(data)?;
+                MscCommand::TimedGo { time, cue: Cue::read(rest)? }
+            }
+            LOAD => MscCommand::Load(Cue::read(data)?),
+            SET => {
+                let (time, control, value) = match data {
+                    [a, b, c, d] => (None, [*a, *b], [*c, *d]),
+                    [a, b, c, d, time @ ..] if time.len() == 5 => {
+                        (Some(StandardTime::read(time.try_into().expect("length"))?), [*a, *b], [*c, *d])
+                    }
+                    _ => bail!("invalid MSC Set {:02X?}", data),
+                };
+                MscCommand::Set {
+                    control: control[0] as u16 | (control[1] as u16) << 7,
+                    value: value[0] as u16 | (value[1] as u16) << 7,
+                    time,
+                }
+            }
+            FIRE => match data {
+                [macro_number] => MscCommand::Fire(*macro_number),
+                _ => bail!("invalid MSC Fire {:02X?}", data),
+            },
+            ALL_OFF => {
+                no_data(command)?;
+                MscCommand::AllOff
+            }
+            RESTORE => {
+                no_data(command)?;
+                MscCommand::Restore
+            }
+            RESET => {
+                no_data(command)?;
+                MscCommand::Reset
+            }
+            GO_OFF => MscCommand::GoOff(Cue::read(data)?),
+            GO_JAM_CLOCK => MscCommand::GoJamClock(Cue::read(data)?),
+            STANDBY_PLUS => MscCommand::StandbyPlus(read_optional_field(data)?),
+            STANDBY_MINUS => MscCommand::StandbyMinus(read_optional_field(data)?),
+            SEQUENCE_PLUS => MscCommand::SequencePlus(read_optional_field(data)?),
+            SEQUENCE_MINUS => MscCommand::SequenceMinus(read_optional_field(data)?),
+            START_CLOCK => MscCommand::StartClock(read_optional_field(data)?),
+            STOP_CLOCK => MscCommand::StopClock(read_optional_field(data)?),
+            ZERO_CLOCK => MscCommand::ZeroClock(read_optional_field(data)?),
+            SET_CLOCK => {
+                let (time, rest) = read_time(data)?;
+                MscCommand::SetClock { time, list: read_optional_field(rest)? }
+            }
+            MTC_CHASE_ON => MscCommand::MtcChaseOn(read_optional_field(data)?),
+            MTC_CHASE_OFF => MscCommand::MtcChaseOff(read_optional_field(data)?),
+            OPEN_CUE_LIST => MscCommand::OpenCueList(required_field()?),
+            CLOSE_CUE_LIST => MscCommand::CloseCueList(required_field()?),
+            OPEN_CUE_PATH => MscCommand::OpenCuePath(required_field()?),
+            CLOSE_CUE_PATH => MscCommand::CloseCuePath(required_field()?),
+            _ => bail!("unknown MSC command {:02X}", command),
+        })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.push(self.command());
+        match self {
+            MscCommand::Go(cue)
+            | MscCommand::Stop(cue)
+            | MscCommand::Resume(cue)
+            | MscCommand::Load(cue)
+            | MscCommand::GoOff(cue)
+            | MscCommand::GoJamClock(cue) => cue.write(buf)?,
+            MscCommand::TimedGo { time, cue } => {
+                time.write(buf);
+                cue.write(buf)?;
+            }
+            MscCommand::Set { control, value, time } => {
+                if *control > 0x3FFF || *value > 0x3FFF {
+                    bail!("MSC Set control and value are 14 bits: {} {}", control, value);
+                }
+                buf.extend([(control & 0x7F) as u8, (control >> 7) as u8, (value & 0x7F) as u8, (value >> 7) as u8]);
+                if let Some(time) = time {
+                    time.write(buf);
+                }
+            }
+            MscCommand::Fire(macro_number) => buf.push(*macro_number),
+            MscCommand::AllOff | MscCommand::Restore | MscCommand::Reset => {}
+            MscCommand::StandbyPlus(list)
+            | MscCommand::StandbyMinus(list)
+            | MscCommand::SequencePlus(list)
+            | MscCommand::SequenceMinus(list)
+            | MscCommand::StartClock(list)
+            | MscCommand::StopClock(list)
+            | MscCommand::ZeroClock(list)
+            | MscCommand::MtcChaseOn(list)
+            | MscCommand::MtcChaseOff(list) => {
+                if let Some(list) = list {
+                    write_field(list, buf)?;
+                }
+            }
+            MscCommand::SetClock { time, list } => {
+                time.write(buf);
+                if let Some(list) = list {
+                    write_field(list, buf)?;
+                }
+            }
+            MscCommand::OpenCueList(field)
+            | MscCommand::CloseCueList(field)
+            | MscCommand::OpenCuePath(field)
+            | MscCommand::CloseCuePath(field) => write_field(field, buf)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct MscMessage {
+    /// 0x00 to 0x6F for a device, 0x70 to 0x7E for a group, or [`ALL_CALL`].
+    pub device_id: u8,
+    pub command_format: CommandFormat,
+    pub command: MscCommand,
+}
+
+impl MscMessage {
+    pub fn is_msc(data: &[u8]) -> bool {
+        matches!(data, [UNIVERSAL_REALTIME, _, SUB_ID_1_MIDI_SHOW_CONTROL, ..])
+    }
+
+    /// Parses SysEx data, without `F0` and `F7`.
+    pub fn parse(data: &[u8]) -> Result<MscMessage> {
+        if !MscMessage::is_msc(data) {
+            bail!("not an MSC message");
+        }
+        let [_, device_id, _, command_format, command, ref rest @ ..] = *data else {
+            bail!("truncated MSC message");
+        };
+        if data.iter().any(|byte| *byte > 0x7F) {
+            bail!("MSC byte out of 7-bit range");
+        }
+        Ok(MscMessage {
+            device_id,
+            command_format: CommandFormat::try_from(command_format)?,
+            command: MscCommand::read(command, rest)?,
+        })
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        buf.extend([UNIVERSAL_REALTIME, self.device_id, SUB_ID_1_MIDI_SHOW_CONTROL, self.command_format.into()]);
+        self.command.write(buf)?;
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("MSC field out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+}
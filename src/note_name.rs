@@ -0,0 +1,93 @@
+//! Scientific pitch names of note numbers, such as `C#4` and `Eb-1`.
+//!
+//! Conventions differ on the octave number of middle C (note 60):
+//! 4 is most common, while Yamaha and others use 3.
+
+use anyhow::{Context, Result, anyhow, bail};
+use crate::message::cvm;
+
+/// How black keys are spelled when naming notes.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Accidental {
+    Sharp,
+    Flat,
+}
+
+/// A convention for naming notes.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NoteNaming {
+    /// The octave number of note 60.
+    pub middle_c_octave: i8,
+    pub accidental: Accidental,
+}
+
+impl NoteNaming {
+    /// Middle C is C4, and black keys are sharps.
+    pub const SCIENTIFIC: NoteNaming = NoteNaming {
+        middle_c_octave: 4,
+        accidental: Accidental::Sharp,
+    };
+    /// Middle C is C3, as in Yamaha documentation.
+    pub const YAMAHA: NoteNaming = NoteNaming {
+        middle_c_octave: 3,
+        accidental: Accidental::Sharp,
+    };
+
+    pub fn name(&self, note_number: cvm::NoteNumber) -> String {
+        const SHARPS: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+        const FLATS: [&str; 12] = ["C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B"];
+        let note = u8::from(note_number.0) as i32;
+        let names = match self.accidental {
+            Accidental::Sharp => &SHARPS,
+            Accidental::Flat => &FLATS,
+        };
+        let octave = note / 12 - 5 + self.middle_c_octave as i32;
+        format!("{}{}", names[(note % 12) as usize], octave)
+    }
+
+    /// Parses a name such as `C4`, `f#-1` or `Bb3`.
+    ///
+    /// Any number of `#` or `b` may follow the letter, as may `♯`, `♭` and `x` for a double sharp.
+    pub fn parse(&self, name: &str) -> Result<cvm::NoteNumber> {
+        let name = name.trim();
+        let mut chars = name.chars();
+        let letter = chars.next().ok_or_else(|| anyhow!("empty note name"))?;
+        let mut semitone: i32 = match letter.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => bail!("invalid note name {:?}", name),
+        };
+        let rest = chars.as_str();
+        let octave_start = rest.find(|c: char| c == '-' || c.is_ascii_digit())
+            .ok_or_else(|| anyhow!("note name {:?} has no octave", name))?;
+        for accidental in rest[..octave_start].chars() {
+            semitone += match accidental {
+                '#' | '♯' => 1,
+                'b' | '♭' => -1,
+                'x' => 2,
+                _ => bail!("invalid accidental in note name {:?}", name),
+            };
+        }
+        let octave: i64 = rest[octave_start..].parse()
+            .with_context(|| format!("invalid octave in note name {:?}", name))?;
+        let note = (octave - self.middle_c_octave as i64 + 5).checked_mul(12)
+            .and_then(|note| note.checked_add(semitone as i64));
+        note.and_then(|note| u8::try_from(note).ok())
+            .and_then(|note| cvm::Unsigned7::try_from(note).ok())
+            .map(cvm::NoteNumber)
+            .ok_or_else(|| anyhow!("note {:?} out of range", name))
+    }
+}
+
+impl Default for NoteNaming {
+    fn default() -> NoteNaming {
+        NoteNaming::SCIENTIFIC
+    }
+}
@@ -0,0 +1,298 @@
+//! Packing 8-bit data into the 7-bit bytes of SysEx, in the schemes vendors use for dumps.
+//!
+//! Each scheme works in groups: [`Encoder`] and [`Decoder`] accept data in pieces of any size,
+//! such as the SysEx slices returned by [`crate::parser::Parser::parse`],
+//! holding back only an incomplete group until more arrives or the stream finishes.
+
+use anyhow::{Result, bail};
+use crate::mcoded7;
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Packing {
+    /// A byte of high bits, the first byte's in bit 6, then seven low-bit bytes, as in [`crate::mcoded7`].
+    Mcoded7,
+    /// A byte of high bits, the first byte's in bit 0, then seven low-bit bytes, as Korg dumps use.
+    Korg,
+    /// Each byte as its high nibble, then its low nibble.
+    NibblesHighFirst,
+    /// Each byte as its low nibble, then its high nibble.
+    NibblesLowFirst,
+    /// The bits of the data, most significant first, seven to a byte.
+    /// A final partial byte is padded with zeros.
+    BitStream,
+}
+
+impl Packing {
+    /// Unpacked bytes per complete group.
+    fn unpacked_group(self) -> usize {
+        match self {
+            Packing::Mcoded7 | Packing::Korg | Packing::BitStream => 7,
+            Packing::NibblesHighFirst | Packing::NibblesLowFirst => 1,
+        }
+    }
+
+    /// Packed bytes per complete group.
+    fn packed_group(self) -> usize {
+        match self {
+            Packing::Mcoded7 | Packing::Korg | Packing::BitStream => 8,
+            Packing::NibblesHighFirst | Packing::NibblesLowFirst => 2,
+        }
+    }
+
+    /// The packed length of `len` bytes.
+    pub fn encoded_len(self, len: usize) -> usize {
+        match self {
+            Packing::Mcoded7 | Packing::Korg => mcoded7::encoded_len(len),
+            Packing::NibblesHighFirst | Packing::NibblesLowFirst => len * 2,
+            Packing::BitStream => (len * 8).div_ceil(7),
+        }
+    }
+
+    pub fn encode(self, data: &[u8]) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.encoded_len(data.len()));
+        let mut encoder = self.encoder();
+        encoder.push(data, &mut buf);
+        encoder.finish(&mut buf);
+        buf
+    }
+
+    pub fn decode(self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(data.len());
+        let mut decoder = self.decoder();
+        decoder.push(data, &mut buf)?;
+        decoder.finish(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn encoder(self) -> Encoder {
+        Encoder {
+            packing: self,
+            pending: Vec::with_capacity(self.unpacked_group()),
+        }
+    }
+
+    pub fn decoder(self) -> Decoder {
+        Decoder {
+            packing: self,
+            pending: Vec::with_capacity(self.packed_group()),
+        }
+    }
+
+    /// Packs one group, complete or final.
+    fn encode_group(self, group: &[u8], buf: &mut Vec<u8>) {
+        match self {
+            Packing::Mcoded7 => buf.extend(mcoded7::encode(group)),
+            Packing::Korg => {
+                let high_bits = group.iter().enumerate()
+                    .fold(0, |bits, (index, byte)| bits | (byte >> 7) << index);
+                buf.push(high_bits);
+                buf.extend(group.iter().map(|byte| byte & 0x7F));
+            }
+            Packing::NibblesHighFirst => buf.extend(group.iter().flat_map(|byte| [byte >> 4, byte & 0x0F])),
+            Packing::NibblesLowFirst => buf.extend(group.iter().flat_map(|byte| [byte & 0x0F, byte >> 4])),
+            Packing::BitStream => {
+                let bits = group.iter().fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+                let bit_len = group.len() * 8;
+                let packed_len = bit_len.div_ceil(7);
+                let bits = bits << (packed_len * 7 - bit_len);
+                buf.extend((0..packed_len).rev().map(|index| (bits >> (index * 7)) as u8 & 0x7F));
+            }
+        }
+    }
+
+    /// Unpacks one group, complete or final.
+    fn decode_group(self, group: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+        if group.iter().any(|byte| *byte > 0x7F) {
+            bail!("packed byte out of 7-bit range");
+        }
+        match self {
+            Packing::Mcoded7 => buf.extend(mcoded7::decode(group)?),
+            Packing::Korg => {
+                if group.len() < 2 {
+                    bail!("truncated Korg group");
+                }
+                let high_bits = group[0];
+                buf.extend(group[1..].iter().enumerate()
+                    .map(|(index, byte)| byte | (high_bits >> index & 1) << 7));
+            }
+            Packing::NibblesHighFirst | Packing::NibblesLowFirst => {
+                let [first, second] = *group else {
+                    bail!("odd number of nibbles");
+                };
+                if first > 0x0F || second > 0x0F {
+                    bail!("nibble out of range: {:02X} {:02X}", first, second);
+                }
+                let (high, low) = if self == Packing::NibblesHighFirst { (first, second) } else { (second, first) };
+                buf.push(high << 4 | low);
+            }
+            Packing::BitStream => {
+                let bits = group.iter().fold(0u64, |bits, byte| bits << 7 | *byte as u64);
+                let bit_len = group.len() * 7;
+                let len = bit_len / 8;
+                let bits = bits >> (bit_len - len * 8);
+                buf.extend((0..len).rev().map(|index| (bits >> (index * 8)) as u8));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Packs data given in pieces.
+#[derive(Debug)]
+pub struct Encoder {
+    packing: Packing,
+    pending: Vec<u8>,
+}
+
+impl Encoder {
+    /// Packs every complete group, holding back the rest.
+    pub fn push(&mut self, mut data: &[u8], buf: &mut Vec<u8>) {
+        let group_len = self.packing.unpacked_group();
+        while !data.is_empty() {
+            let take = (group_len - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() == group_len {
+                self.packing.encode_group(&self.pending, buf);
+                self.pending.clear();
+            }
+        }
+    }
+
+    /// Packs the final, incomplete group.
+    pub fn finish(self, buf: &mut Vec<u8>) {
+        if !self.pending.is_empty() {
+            self.packing.encode_group(&self.pending, buf);
+        }
+    }
+}
+
+/// Unpacks data given in pieces.
+#[derive(Debug)]
+pub struct Decoder {
+    packing: Packing,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    /// Unpacks every complete group, holding back the rest.
+    pub fn push(&mut self, mut data: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+        let group_len = self.packing.packed_group();
+        while !data.is_empty() {
+            let take = (group_len - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() == group_len {
+                self.packing.decode_group(&self.pending, buf)?;
+                self.pending.clear();
+            }
+        }
+        Ok(())
+    }
+
+    /// Unpacks the final, incomplete group.
+    pub fn finish(self, buf: &mut Vec<u8>) -> Result<()> {
+        if !self.pending.is_empty() {
+            self.packing.decode_group(&self.pending, buf)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Packing; 5] = [
+        Packing::Mcoded7,
+        Packing::Korg,
+        Packing::NibblesHighFirst,
+        Packing::NibblesLowFirst,
+        Packing::BitStream,
+    ];
+
+    /// Cases per packing for the generated-input tests.
+    const CASES: usize = 200;
+
+    /// A xorshift generator, seeded so that failures reproduce.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        /// Random bytes, of a length that often isn't a multiple of any group size.
+        fn data(&mut self) -> Vec<u8> {
+            let len = self.below(300);
+            (0..len).map(|_| self.next() as u8).collect()
+        }
+
+        /// Splits `data` into random pieces, including empty ones.
+        fn pieces<'data>(&mut self, mut data: &'data [u8]) -> Vec<&'data [u8]> {
+            let mut pieces = vec![];
+            while !data.is_empty() {
+                let (head, tail) = data.split_at(self.below(20).min(data.len()));
+                pieces.push(head);
+                data = tail;
+            }
+            pieces
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+        for packing in ALL {
+            for _ in 0..CASES {
+                let data = rng.data();
+                let encoded = packing.encode(&data);
+                assert_eq!(encoded.len(), packing.encoded_len(data.len()), "{:?} {:02X?}", packing, data);
+                assert!(encoded.iter().all(|byte| *byte <= 0x7F), "{:?} {:02X?}", packing, data);
+                assert_eq!(packing.decode(&encoded).unwrap(), data, "{:?}", packing);
+            }
+        }
+    }
+
+    #[test]
+    fn streaming_matches_one_shot() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for packing in ALL {
+            for _ in 0..CASES {
+                let data = rng.data();
+                let encoded = packing.encode(&data);
+
+                let mut encoder = packing.encoder();
+                let mut streamed = Vec::new();
+                for piece in rng.pieces(&data) {
+                    encoder.push(piece, &mut streamed);
+                }
+                encoder.finish(&mut streamed);
+                assert_eq!(streamed, encoded, "{:?} {:02X?}", packing, data);
+
+                let mut decoder = packing.decoder();
+                let mut decoded = Vec::new();
+                for piece in rng.pieces(&encoded) {
+                    decoder.push(piece, &mut decoded).unwrap();
+                }
+                decoder.finish(&mut decoded).unwrap();
+                assert_eq!(decoded, data, "{:?}", packing);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_8_bit_input() {
+        for packing in ALL {
+            assert!(packing.decode(&[0x80, 0x00]).is_err(), "{:?}", packing);
+        }
+    }
+}
@@ -364,9 +364,10 @@ impl StatusByte {
                         status: MessageParseOutcomeStatus::Message (
                             Message::Channel(ChannelMessage {
                                 channel,
-                                message: ChannelMessageType::ChannelMode(
-                                    ChannelModeMessage::try_from(bytes[0]).unwrap(),
-                                )
+                                message: ChannelMessageType::ChannelMode {
+                                    mode: ChannelModeMessage::try_from(bytes[0]).unwrap(),
+                                    value: cvm::Unsigned7::assert_from(bytes[1]),
+                                }
                             })
                         )
                     })
@@ -606,7 +607,7 @@ enum DataBytes<'buf> {
 }
 
 /// Reference: MIDI spec table I
-mod status_nibbles {
+pub(crate) mod status_nibbles {
     pub const CHANNEL_VOICE_MESSAGE_NOTE_OFF: u8 = 0b1000;
     pub const CHANNEL_VOICE_MESSAGE_NOTE_ON: u8 = 0b1001;
     pub const CHANNEL_VOICE_MESSAGE_POLYPHONIC_KEY_PRESSURE_AFTERTOUCH: u8 = 0b1010;
@@ -618,7 +619,7 @@ mod status_nibbles {
 }
 
 /// Reference: MIDI spec tables V, VI, VII
-mod system_status_bytes {
+pub(crate) mod system_status_bytes {
     pub const SYSTEM_EXCLUSIVE: u8 = 0xF0;
     pub const SYSTEM_COMMON_MIDI_TIME_QUARTER_FRAME: u8 = 0xF1;
     pub const SYSTEM_COMMON_SONG_POSITION_POINTER: u8 = 0xF2;
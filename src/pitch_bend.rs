@@ -0,0 +1,134 @@
+//! Pitch Bend Sensitivity, and tracking it per channel through RPN 0.
+//!
+//! Conversions of Pitch Bend values themselves are methods of [`cvm::PitchBendChange`].
+
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use crate::message::*;
+use crate::rpn::{self, Parameter, ParameterTracker, ParameterValue};
+
+/// The bend of a Pitch Bend value at either extreme.
+///
+/// Reference: MIDI spec table IIIa, RPN 0
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BendRange {
+    pub semitones: u8,
+    /// 0 to 99.
+    pub cents: u8,
+}
+
+impl BendRange {
+    /// ±2 semitones, which receivers assume until told otherwise.
+    pub const DEFAULT: BendRange = BendRange { semitones: 2, cents: 0 };
+
+    pub fn new(semitones: u8, cents: u8) -> Result<BendRange> {
+        if semitones > 127 || cents > 99 {
+            bail!("bend range out of range: {} semitones {} cents", semitones, cents);
+        }
+        Ok(BendRange { semitones, cents })
+    }
+
+    /// Rounds a range in semitones to the nearest cent.
+    pub fn from_semitones(semitones: f32) -> Result<BendRange> {
+        if !(0.0..127.995).contains(&semitones) {
+            bail!("bend range out of range: {} semitones", semitones);
+        }
+        let cents = (semitones * 100.0).round() as u32;
+        BendRange::new((cents / 100) as u8, (cents % 100) as u8)
+    }
+
+    pub fn as_semitones(self) -> f32 {
+        self.semitones as f32 + self.cents as f32 / 100.0
+    }
+
+    /// The Data Entry value of RPN 0: semitones in the MSB, cents in the LSB.
+    pub fn to_parameter_value(self) -> ParameterValue {
+        ParameterValue {
+            msb: self.semitones,
+            lsb: self.cents,
+        }
+    }
+
+    /// Cents above 99 are clamped, as receivers treat them.
+    pub fn from_parameter_value(value: ParameterValue) -> BendRange {
+        BendRange {
+            semitones: value.msb,
+            cents: value.lsb.min(99),
+        }
+    }
+
+    /// The Control Changes that set this range on a channel.
+    pub fn messages(self, channel: MidiChannelId) -> Vec<ChannelMessage> {
+        rpn::parameter_messages(
+            channel,
+            Parameter::Registered(rpn::registered::PITCH_BEND_SENSITIVITY),
+            self.to_parameter_value(),
+        )
+    }
+}
+
+impl Default for BendRange {
+    fn default() -> BendRange {
+        BendRange::DEFAULT
+    }
+}
+
+/// Follows Pitch Bend Sensitivity and Pitch Bend of each channel.
+pub struct BendRangeTracker {
+    parameters: ParameterTracker,
+    ranges: HashMap<MidiChannelId, BendRange>,
+    bends: HashMap<MidiChannelId, cvm::PitchBendChange>,
+    /// The range of channels that haven't received RPN 0.
+    pub default_range: BendRange,
+}
+
+impl Default for BendRangeTracker {
+    fn default() -> BendRangeTracker {
+        BendRangeTracker::new(BendRange::DEFAULT)
+    }
+}
+
+impl BendRangeTracker {
+    pub fn new(default_range: BendRange) -> BendRangeTracker {
+        BendRangeTracker {
+            parameters: ParameterTracker::new(),
+            ranges: HashMap::new(),
+            bends: HashMap::new(),
+            default_range,
+        }
+    }
+
+    pub fn range(&self, channel: MidiChannelId) -> BendRange {
+        self.ranges.get(&channel).copied().unwrap_or(self.default_range)
+    }
+
+    /// The current bend of a channel, in semitones.
+    pub fn semitones(&self, channel: MidiChannelId) -> f32 {
+        self.bends.get(&channel).map_or(0.0, |bend| bend.semitones(self.range(channel)))
+    }
+
+    /// Follows a message, returning the channel's new bend in semitones
+    /// if it changed the bend or its range.
+    pub fn handle(&mut self, message: &ChannelMessage) -> Option<f32> {
+        let channel = message.channel;
+        if let Some(change) = self.parameters.handle(message) {
+            if change.parameter == Parameter::Registered(rpn::registered::PITCH_BEND_SENSITIVITY) {
+                self.ranges.insert(channel, BendRange::from_parameter_value(change.value));
+                return Some(self.semitones(channel));
+            }
+            return None;
+        }
+        match &message.message {
+            ChannelMessageType::ChannelVoice(ChannelVoiceMessage::PitchBendChange(bend)) => {
+                self.bends.insert(channel, bend.clone());
+                Some(self.semitones(channel))
+            }
+            // Reset All Controllers centers Pitch Bend but keeps its range.
+            ChannelMessageType::ChannelMode { mode: ChannelModeMessage::ResetAllControllers, .. } => {
+                self.bends.remove(&channel).map(|_| 0.0)
+            }
+            _ => None,
+        }
+    }
+}
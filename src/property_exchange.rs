@@ -0,0 +1,631 @@
+//! MIDI-CI Property Exchange: Get, Set and Subscribe transactions.
+//!
+//! Property Exchange messages carry a JSON header and property data,
+//! split into chunks that fit the receiver's maximum SysEx size.
+//! The [`PropertyExchangeClient`] sends requests to one device,
+//! reassembles the chunks of its replies and expires requests that go unanswered.
+//! Like the [`crate::sequencer::Sequencer`], it reads no clock of its own.
+//!
+//! Reference: M2-103-UM Common Rules for Property Exchange 1.1
+
+use anyhow::{Result, anyhow, bail};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::ci::{self, CiBody, CiMessage, Muid, Reader, write_u14};
+use crate::mcoded7;
+
+/// Bytes of a Property Exchange message besides its header and property data,
+/// including `F0` and `F7`.
+const MESSAGE_OVERHEAD: usize = 24;
+
+/// Reference: M2-103-UM table 2
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum PropertyExchangeKind {
+    GetPropertyData = ci::sub_id_2::GET_PROPERTY_DATA,
+    ReplyToGetPropertyData = ci::sub_id_2::REPLY_TO_GET_PROPERTY_DATA,
+    SetPropertyData = ci::sub_id_2::SET_PROPERTY_DATA,
+    ReplyToSetPropertyData = ci::sub_id_2::REPLY_TO_SET_PROPERTY_DATA,
+    Subscription = ci::sub_id_2::SUBSCRIPTION,
+    ReplyToSubscription = ci::sub_id_2::REPLY_TO_SUBSCRIPTION,
+    Notify = ci::sub_id_2::NOTIFY,
+}
+
+impl PropertyExchangeKind {
+    pub fn is_reply(self) -> bool {
+        matches!(self,
+            PropertyExchangeKind::ReplyToGetPropertyData
+            | PropertyExchangeKind::ReplyToSetPropertyData
+            | PropertyExchangeKind::ReplyToSubscription)
+    }
+}
+
+/// One message of a Property Exchange transaction.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct PropertyExchangeChunk {
+    pub kind: PropertyExchangeKind,
+    pub request_id: u8,
+    /// JSON, present in the first chunk only.
+    pub header: Vec<u8>,
+    /// The number of chunks in the transaction, or 0 if unknown.
+    pub chunk_count: u16,
+    /// One-based.
+    pub chunk_number: u16,
+    pub data: Vec<u8>,
+}
+
+impl PropertyExchangeChunk {
+    /// Decodes the body of a MIDI-CI message,
+    /// returning `None` for messages other than Property Exchange.
+    pub fn from_body(body: &CiBody) -> Result<Option<PropertyExchangeChunk>> {
+        let CiBody::Other { sub_id_2, data } = body else {
+            return Ok(None);
+        };
+        let Ok(kind) = PropertyExchangeKind::try_from(*sub_id_2) else {
+            return Ok(None);
+        };
+        let mut reader = Reader::new(data);
+        let request_id = reader.u8()?;
+        let header_len = reader.u14()? as usize;
+        let header = reader.bytes(header_len)?.to_vec();
+        let chunk_count = reader.u14()?;
+        let chunk_number = reader.u14()?;
+        let data_len = reader.u14()? as usize;
+        let data = reader.bytes(data_len)?.to_vec();
+        Ok(Some(PropertyExchangeChunk {
+            kind,
+            request_id,
+            header,
+            chunk_count,
+            chunk_number,
+            data,
+        }))
+    }
+
+    /// Fails if the header or data is longer than 0x3FFF bytes,
+    /// or the chunk count or number is out of 14-bit range.
+    pub fn to_body(&self) -> Result<CiBody> {
+        let u14 = |value: usize, field: &str| u16::try_from(value).ok().filter(|value| *value <= 0x3FFF)
+            .ok_or_else(|| anyhow!("Property Exchange {} out of range: {}", field, value));
+        let mut data = vec![self.request_id];
+        write_u14(u14(self.header.len(), "header length")?, &mut data);
+        data.extend_from_slice(&self.header);
+        write_u14(u14(self.chunk_count.into(), "chunk count")?, &mut data);
+        write_u14(u14(self.chunk_number.into(), "chunk number")?, &mut data);
+        write_u14(u14(self.data.len(), "data length")?, &mut data);
+        data.extend_from_slice(&self.data);
+        Ok(CiBody::Other {
+            sub_id_2: self.kind.into(),
+            data,
+        })
+    }
+}
+
+/// Splits a header and encoded property data into chunks
+/// whose messages are no longer than `max_sysex_size`.
+pub fn split_into_chunks(
+    kind: PropertyExchangeKind,
+    request_id: u8,
+    header: &[u8],
+    data: &[u8],
+    max_sysex_size: u32,
+) -> Result<Vec<PropertyExchangeChunk>> {
+    if request_id > 0x7F {
+        bail!("request ID out of range: {}", request_id);
+    }
+    if header.len() > 0x3FFF {
+        bail!("Property Exchange header too long");
+    }
+    let capacity = (max_sysex_size as usize)
+        .checked_sub(MESSAGE_OVERHEAD + header.len())
+        .filter(|capacity| *capacity > 0)
+        .ok_or_else(|| anyhow!("Property Exchange header doesn't fit in {} bytes", max_sysex_size))?
+        .min(0x3FFF);
+    let chunk_count = data.len().div_ceil(capacity).max(1);
+    if chunk_count > 0x3FFF {
+        bail!("Property Exchange data too long");
+    }
+    Ok((0..chunk_count).map(|index| PropertyExchangeChunk {
+        kind,
+        request_id,
+        header: if index == 0 { header.to_vec() } else { vec![] },
+        chunk_count: chunk_count as u16,
+        chunk_number: index as u16 + 1,
+        data: data[(index * capacity).min(data.len())..((index + 1) * capacity).min(data.len())].to_vec(),
+    }).collect())
+}
+
+/// Serializes a header, escaping non-ASCII characters as JSON requires of SysEx.
+pub fn encode_header(header: &Value) -> Vec<u8> {
+    let mut text = String::new();
+    for c in header.to_string().chars() {
+        if c.is_ascii() {
+            text.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                text.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    text.into_bytes()
+}
+
+/// The `mutualEncoding` of property data.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Encoding {
+    Ascii,
+    Mcoded7,
+    /// zlib compressed, then Mcoded7 encoded.
+    ZlibMcoded7,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Ascii => "ASCII",
+            Encoding::Mcoded7 => "Mcoded7",
+            Encoding::ZlibMcoded7 => "zlib+Mcoded7",
+        }
+    }
+
+    /// The encoding named by a header, ASCII if it names none.
+    pub fn of_header(header: &Value) -> Result<Encoding> {
+        match header.get("mutualEncoding").and_then(Value::as_str) {
+            None | Some("ASCII") => Ok(Encoding::Ascii),
+            Some("Mcoded7") => Ok(Encoding::Mcoded7),
+            Some("zlib+Mcoded7") => Ok(Encoding::ZlibMcoded7),
+            Some(other) => Err(anyhow!("unknown Property Exchange encoding {:?}", other)),
+        }
+    }
+
+    pub fn encode(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Ascii => {
+                if data.iter().any(|byte| *byte > 0x7F) {
+                    bail!("property data isn't 7-bit; use Mcoded7");
+                }
+                Ok(data.to_vec())
+            }
+            Encoding::Mcoded7 | Encoding::ZlibMcoded7 => Ok(mcoded7::encode(data)),
+        }
+    }
+
+    /// Decodes property data. zlib compressed data is left compressed.
+    pub fn decode(self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Encoding::Ascii => Ok(data.to_vec()),
+            Encoding::Mcoded7 | Encoding::ZlibMcoded7 => mcoded7::decode(data),
+        }
+    }
+}
+
+/// A complete Property Exchange message, reassembled from its chunks.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct PropertyExchangeMessage {
+    pub kind: PropertyExchangeKind,
+    pub request_id: u8,
+    pub header: Value,
+    /// Property data, decoded from its `mutualEncoding`.
+    pub data: Vec<u8>,
+}
+
+impl PropertyExchangeMessage {
+    /// The HTTP-like status of a reply.
+    pub fn status(&self) -> Option<u64> {
+        self.header.get("status").and_then(Value::as_u64)
+    }
+
+    /// Parses the property data as JSON, as most resources are.
+    pub fn json(&self) -> Result<Value> {
+        Ok(serde_json::from_slice(&self.data)?)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub enum PropertyExchangeEvent {
+    /// A reply to a request, with the resource requested.
+    Reply {
+        resource: String,
+        reply: PropertyExchangeMessage,
+    },
+    /// A request got no complete reply in time.
+    TimedOut {
+        request_id: u8,
+        resource: String,
+    },
+    /// A subscription update or notification from the device.
+    ///
+    /// Subscription updates are acknowledged by [`PropertyExchangeClient::handle`].
+    Subscription(PropertyExchangeMessage),
+}
+
+struct Reassembly {
+    kind: PropertyExchangeKind,
+    header: Vec<u8>,
+    data: Vec<u8>,
+    next_chunk: u16,
+}
+
+impl Reassembly {
+    fn new(kind: PropertyExchangeKind) -> Reassembly {
+        Reassembly {
+            kind,
+            header: vec![],
+            data: vec![],
+            next_chunk: 1,
+        }
+    }
+
+    /// Adds a chunk, returning whether it was the last.
+    fn add(&mut self, chunk: PropertyExchangeChunk) -> Result<bool> {
+        if chunk.kind != self.kind || chunk.chunk_number != self.next_chunk {
+            bail!("unexpected Property Exchange chunk {} of request {}", chunk.chunk_number, chunk.request_id);
+        }
+        if chunk.chunk_number == 1 {
+            self.header = chunk.header;
+        }
+        self.data.extend(chunk.data);
+        self.next_chunk += 1;
+        Ok(chunk.chunk_count != 0 && chunk.chunk_number >= chunk.chunk_count)
+    }
+
+    fn finish(self, request_id: u8) -> Result<PropertyExchangeMessage> {
+        let header: Value = serde_json::from_slice(&self.header)?;
+        let data = Encoding::of_header(&header)?.decode(&self.data)?;
+        Ok(PropertyExchangeMessage {
+            kind: self.kind,
+            request_id,
+            header,
+            data,
+        })
+    }
+}
+
+struct PendingRequest {
+    resource: String,
+    deadline: Duration,
+    reply: Option<Reassembly>,
+}
+
+/// The initiator side of Property Exchange with one device.
+pub struct PropertyExchangeClient {
+    muid: Muid,
+    destination: Muid,
+    /// The receiver's maximum SysEx size, from its Reply to Discovery.
+    pub max_sysex_size: u32,
+    /// From the receiver's Reply to Property Exchange Capabilities.
+    pub simultaneous_requests: u8,
+    /// How long to wait for each chunk of a reply.
+    pub timeout: Duration,
+    pending: HashMap<u8, PendingRequest>,
+    inbound: HashMap<u8, Reassembly>,
+}
+
+impl PropertyExchangeClient {
+    pub fn new(muid: Muid, destination: Muid) -> PropertyExchangeClient {
+        PropertyExchangeClient {
+            muid,
+            destination,
+            max_sysex_size: 512,
+            simultaneous_requests: 1,
+            timeout: Duration::from_secs(3),
+            pending: HashMap::new(),
+            inbound: HashMap::new(),
+        }
+    }
+
+    /// Requests a resource such as `ResourceList` or `DeviceInfo`.
+    ///
+    /// Returns the request ID and the messages to send.
+    pub fn get(&mut self, resource: &str, now: Duration) -> Result<(u8, Vec<CiMessage>)> {
+        self.request(PropertyExchangeKind::GetPropertyData, json!({ "resource": resource }), &[], now)
+    }
+
+    /// Replaces the data of a resource.
+    pub fn set(&mut self, resource: &str, data: &[u8], encoding: Encoding, now: Duration) -> Result<(u8, Vec<CiMessage>)> {
+        let mut header = json!({ "resource": resource });
+        if encoding != Encoding::Ascii {
+            header["mutualEncoding"] = json!(encoding.name());
+        }
+        let data = encoding.encode(data)?;
+        self.request(PropertyExchangeKind::SetPropertyData, header, &data, now)
+    }
+
+    /// Subscribes to changes of a resource.
+    ///
+    /// The reply's header holds the `subscribeId` for [`PropertyExchangeClient::unsubscribe`].
+    pub fn subscribe(&mut self, resource: &str, now: Duration) -> Result<(u8, Vec<CiMessage>)> {
+        let header = json!({ "resource": resource, "command": "start" });
+        self.request(PropertyExchangeKind::Subscription, header, &[], now)
+    }
+
+    pub fn unsubscribe(&mut self, resource: &str, subscribe_id: &str, now: Duration) -> Result<(u8, Vec<CiMessage>)> {
+        let header = json!({ "resource": resource, "command": "end", "subscribeId": subscribe_id });
+        self.request(PropertyExchangeKind::Subscription, header, &[], now)
+    }
+
+    /// Sends a request with any header, such as one with `resId` or `offset`.
+    ///
+    /// `data` must already be in the header's `mutualEncoding`.
+    pub fn request(
+        &mut self,
+        kind: PropertyExchangeKind,
+        header: Value,
+        data: &[u8],
+        now: Duration,
+    ) -> Result<(u8, Vec<CiMessage>)> {
+        if self.pending.len() >= self.simultaneous_requests.max(1) as usize {
+            bail!("too many Property Exchange requests in progress");
+        }
+        let resource = header.get("resource").and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Property Exchange header has no resource"))?
+            .to_string();
+        let request_id = (0..=0x7F).find(|id| !self.pending.contains_key(id))
+            .expect("fewer than 128 requests pending");
+        let chunks = split_into_chunks(kind, request_id, &encode_header(&header), data, self.max_sysex_size)?;
+        let messages = chunks.iter().map(|chunk| Ok(self.message(chunk.to_body()?))).collect::<Result<_>>()?;
+        self.pending.insert(request_id, PendingRequest {
+            resource,
+            deadline: now + self.timeout,
+            reply: None,
+        });
+        Ok((request_id, messages))
+    }
+
+    /// Handles a message from the device, returning a completed reply or update.
+    ///
+    /// Acknowledgements of subscription updates are appended to `out`.
+    pub fn handle(&mut self, message: &CiMessage, now: Duration, out: &mut Vec<CiMessage>) -> Result<Option<PropertyExchangeEvent>> {
+        if message.source != self.destination || message.destination != self.muid {
+            return Ok(None);
+        }
+        let Some(chunk) = PropertyExchangeChunk::from_body(&message.body)? else {
+            return Ok(None);
+        };
+        let request_id = chunk.request_id;
+
+        if chunk.kind.is_reply() {
+            let Some(request) = self.pending.get_mut(&request_id) else {
+                log::warn!("Property Exchange reply to unknown request {}", request_id);
+                return Ok(None);
+            };
+            request.deadline = now + self.timeout;
+            let reply = request.reply.get_or_insert_with(|| Reassembly::new(chunk.kind));
+            match reply.add(chunk) {
+                Ok(false) => return Ok(None),
+                Ok(true) => { }
+                Err(error) => {
+                    self.pending.remove(&request_id);
+                    return Err(error);
+                }
+            }
+            let request = self.pending.remove(&request_id).expect("pending request");
+            let reply = request.reply.expect("reassembly").finish(request_id)?;
+            return Ok(Some(PropertyExchangeEvent::Reply {
+                resource: request.resource,
+                reply,
+            }));
+        }
+
+        let kind = chunk.kind;
+        let inbound = self.inbound.entry(request_id).or_insert_with(|| Reassembly::new(kind));
+        match inbound.add(chunk) {
+            Ok(false) => return Ok(None),
+            Ok(true) => { }
+            Err(error) => {
+                self.inbound.remove(&request_id);
+                return Err(error);
+            }
+        }
+        let inbound = self.inbound.remove(&request_id).expect("inbound message");
+        let update = inbound.finish(request_id)?;
+        if kind == PropertyExchangeKind::Subscription {
+            let header = encode_header(&json!({ "status": 200 }));
+            for chunk in split_into_chunks(PropertyExchangeKind::ReplyToSubscription, request_id, &header, &[], self.max_sysex_size)? {
+                out.push(self.message(chunk.to_body()?));
+            }
+        }
+        Ok(Some(PropertyExchangeEvent::Subscription(update)))
+    }
+
+    /// Abandons requests whose replies are overdue.
+    pub fn expire(&mut self, now: Duration) -> Vec<PropertyExchangeEvent> {
+        let mut expired: Vec<u8> = self.pending.iter()
+            .filter(|(_, request)| request.deadline <= now)
+            .map(|(request_id, _)| *request_id)
+            .collect();
+        expired.sort();
+        expired.into_iter().map(|request_id| {
+            let request = self.pending.remove(&request_id).expect("pending request");
+            PropertyExchangeEvent::TimedOut {
+                request_id,
+                resource: request.resource,
+            }
+        }).collect()
+    }
+
+    pub fn pending_requests(&self) -> usize {
+        self.pending.len()
+    }
+
+    fn message(&self, body: CiBody) -> CiMessage {
+        CiMessage {
+            device_id: ci::DEVICE_ID_FUNCTION_BLOCK,
+            version: ci::MESSAGE_VERSION,
+            source: self.muid,
+            destination: self.destination,
+            body,
+        }
+    }
+}
+
+/// The resource names of a `ResourceList` reply.
+pub fn resource_names(resource_list: &Value) -> Result<Vec<String>> {
+    let entries = resource_list.as_array().ok_or_else(|| anyhow!("ResourceList isn't an array"))?;
+    entries.iter().map(|entry| {
+        entry.get("resource").and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("ResourceList entry has no resource"))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{MessageParseOutcomeStatus, Parser};
+
+    fn muid(value: u32) -> Muid {
+        Muid::try_from(value).unwrap()
+    }
+
+    fn client() -> PropertyExchangeClient {
+        PropertyExchangeClient::new(muid(0x0100_0001), muid(0x0200_0002))
+    }
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    /// Sends a message as SysEx bytes.
+    fn transmit(message: &CiMessage) -> CiMessage {
+        let bytes = message.to_sysex_bytes().unwrap();
+        assert!(bytes.len() <= 512);
+        let outcome = Parser::new().parse(&bytes).unwrap();
+        let MessageParseOutcomeStatus::SystemExclusiveMessage(data) = outcome.status else {
+            panic!("not SysEx: {:?}", outcome.status);
+        };
+        CiMessage::parse(data).unwrap()
+    }
+
+    /// The messages a simulated device sends for a Property Exchange message to the client.
+    fn from_device(
+        client: &PropertyExchangeClient,
+        kind: PropertyExchangeKind,
+        request_id: u8,
+        header: Value,
+        data: &[u8],
+    ) -> Vec<CiMessage> {
+        split_into_chunks(kind, request_id, &encode_header(&header), data, client.max_sysex_size).unwrap()
+            .iter()
+            .map(|chunk| transmit(&CiMessage {
+                device_id: ci::DEVICE_ID_FUNCTION_BLOCK,
+                version: ci::MESSAGE_VERSION,
+                source: client.destination,
+                destination: client.muid,
+                body: chunk.to_body().unwrap(),
+            }))
+            .collect()
+    }
+
+    /// Feeds messages to the client, returning the events it completes.
+    fn feed(client: &mut PropertyExchangeClient, messages: &[CiMessage], now: Duration, out: &mut Vec<CiMessage>) -> Vec<PropertyExchangeEvent> {
+        messages.iter().filter_map(|message| client.handle(message, now, out).unwrap()).collect()
+    }
+
+    #[test]
+    fn multi_chunk_reply_reassembled() {
+        let mut client = client();
+        let (request_id, request) = client.get("DeviceInfo", secs(0)).unwrap();
+        let request = PropertyExchangeChunk::from_body(&transmit(&request[0]).body).unwrap().unwrap();
+        assert_eq!(request.kind, PropertyExchangeKind::GetPropertyData);
+        assert_eq!(serde_json::from_slice::<Value>(&request.header).unwrap(), json!({ "resource": "DeviceInfo" }));
+
+        let info = json!({ "manufacturer": "Example", "serial": "x".repeat(1200) }).to_string();
+        let reply = from_device(&client, PropertyExchangeKind::ReplyToGetPropertyData, request_id, json!({ "status": 200 }), info.as_bytes());
+        assert_eq!(reply.len(), 3);
+
+        let mut out = vec![];
+        let (last, rest) = reply.split_last().unwrap();
+        assert_eq!(feed(&mut client, rest, secs(1), &mut out), []);
+        let events = feed(&mut client, std::slice::from_ref(last), secs(1), &mut out);
+        let [PropertyExchangeEvent::Reply { resource, reply }] = events.as_slice() else {
+            panic!("expected a reply: {:?}", events);
+        };
+        assert_eq!(resource, "DeviceInfo");
+        assert_eq!(reply.status(), Some(200));
+        assert_eq!(reply.json().unwrap()["manufacturer"], "Example");
+        assert_eq!(reply.data, info.as_bytes());
+        assert_eq!(client.pending_requests(), 0);
+        assert_eq!(out, []);
+    }
+
+    #[test]
+    fn replies_matched_by_request_id() {
+        let mut client = client();
+        client.simultaneous_requests = 2;
+        let (first, _) = client.get("ResourceList", secs(0)).unwrap();
+        let (second, _) = client.get("DeviceInfo", secs(0)).unwrap();
+        assert_ne!(first, second);
+        assert!(client.get("ChannelList", secs(0)).is_err());
+
+        let mut out = vec![];
+        let unknown = from_device(&client, PropertyExchangeKind::ReplyToGetPropertyData, 0x55, json!({ "status": 200 }), b"{}");
+        assert_eq!(feed(&mut client, &unknown, secs(1), &mut out), []);
+
+        let reply = from_device(&client, PropertyExchangeKind::ReplyToGetPropertyData, second, json!({ "status": 200 }), b"{}");
+        let events = feed(&mut client, &reply, secs(1), &mut out);
+        assert!(matches!(&events[..], [PropertyExchangeEvent::Reply { resource, .. }] if resource == "DeviceInfo"));
+        let reply = from_device(&client, PropertyExchangeKind::ReplyToGetPropertyData, first, json!({ "status": 200 }), b"[]");
+        let events = feed(&mut client, &reply, secs(1), &mut out);
+        assert!(matches!(&events[..], [PropertyExchangeEvent::Reply { resource, .. }] if resource == "ResourceList"));
+    }
+
+    #[test]
+    fn expire_overdue_requests() {
+        let mut client = client();
+        let (request_id, _) = client.get("DeviceInfo", secs(0)).unwrap();
+        assert_eq!(client.expire(secs(2)), []);
+
+        let data = "x".repeat(1200);
+        let reply = from_device(&client, PropertyExchangeKind::ReplyToGetPropertyData, request_id, json!({ "status": 200 }), data.as_bytes());
+        assert_eq!(feed(&mut client, &reply[..1], secs(2), &mut vec![]), []);
+        assert_eq!(client.expire(secs(4)), []);
+        assert_eq!(client.expire(secs(5)), [PropertyExchangeEvent::TimedOut {
+            request_id,
+            resource: "DeviceInfo".to_string(),
+        }]);
+        assert_eq!(client.pending_requests(), 0);
+        assert_eq!(feed(&mut client, &reply[1..], secs(6), &mut vec![]), []);
+    }
+
+    #[test]
+    fn subscription_update_acknowledged() {
+        let mut client = client();
+        let mut out = vec![];
+        let update = from_device(&client, PropertyExchangeKind::Subscription, 3, json!({ "command": "full", "subscribeId": "a1" }), b"{\"volume\":64}");
+        let events = feed(&mut client, &update, secs(0), &mut out);
+        let [PropertyExchangeEvent::Subscription(update)] = events.as_slice() else {
+            panic!("expected an update: {:?}", events);
+        };
+        assert_eq!(update.json().unwrap(), json!({ "volume": 64 }));
+
+        let [ack] = out.as_slice() else {
+            panic!("expected an acknowledgement: {:?}", out);
+        };
+        let ack = PropertyExchangeChunk::from_body(&transmit(ack).body).unwrap().unwrap();
+        assert_eq!((ack.kind, ack.request_id), (PropertyExchangeKind::ReplyToSubscription, 3));
+    }
+
+    #[test]
+    fn to_body_rejects_long_fields() {
+        let chunk = PropertyExchangeChunk {
+            kind: PropertyExchangeKind::SetPropertyData,
+            request_id: 0,
+            header: b"{}".to_vec(),
+            chunk_count: 1,
+            chunk_number: 1,
+            data: vec![0; 0x4000],
+        };
+        assert!(chunk.to_body().is_err());
+        assert!(PropertyExchangeChunk { data: vec![0; 0x3FFF], ..chunk.clone() }.to_body().is_ok());
+        assert!(PropertyExchangeChunk { header: vec![b' '; 0x4000], data: vec![], ..chunk }.to_body().is_err());
+    }
+}
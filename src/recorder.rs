@@ -0,0 +1,198 @@
+//! Capturing live messages into an SMF.
+//!
+//! The [`Recorder`] reads no clock of its own:
+//! the caller timestamps each message from a monotonic clock,
+//! such as the elapsed time of a [`std::time::Instant`] taken when recording started.
+
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use crate::message::*;
+use crate::parser::MessageParseOutcomeStatus;
+use crate::parser::system_status_bytes;
+use crate::smf::{Smf, Format, Track, TrackEvent, TrackEventKind, MetaEvent, TextKind};
+use crate::tempo_map::Division;
+
+/// How recorded events are sorted into tracks.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TrackSeparation {
+    /// One track per MIDI channel.
+    ///
+    /// SysEx goes to the first track along with the tempo.
+    Channel,
+    /// One track per input port, each starting with a Port meta event.
+    Port,
+}
+
+struct RecordedEvent {
+    time: Duration,
+    port: u8,
+    kind: TrackEventKind,
+}
+
+pub struct Recorder {
+    ticks_per_quarter: u16,
+    microseconds_per_quarter: u32,
+    separation: TrackSeparation,
+    quantize: Option<u64>,
+    events: Vec<RecordedEvent>,
+}
+
+impl Recorder {
+    /// Creates a recorder that writes at a fixed resolution and tempo.
+    pub fn new(
+        ticks_per_quarter: u16,
+        microseconds_per_quarter: u32,
+        separation: TrackSeparation,
+    ) -> Result<Recorder> {
+        Division::Metrical(ticks_per_quarter).validate()?;
+        if microseconds_per_quarter == 0 || microseconds_per_quarter > 0xFF_FFFF {
+            bail!("tempo out of range: {}", microseconds_per_quarter);
+        }
+        Ok(Recorder {
+            ticks_per_quarter,
+            microseconds_per_quarter,
+            separation,
+            quantize: None,
+            events: vec![],
+        })
+    }
+
+    /// Rounds timestamps to the nearest multiple of `grid` ticks, or stops quantizing.
+    ///
+    /// Applies to all events, including those already recorded.
+    pub fn set_quantize(&mut self, grid: Option<u64>) -> Result<()> {
+        if grid == Some(0) {
+            bail!("zero quantization grid");
+        }
+        self.quantize = grid;
+        Ok(())
+    }
+
+    /// Records a message received at `time` since recording started.
+    ///
+    /// Only channel messages are recorded:
+    /// system real time messages such as Timing Clock have no place in an SMF.
+    pub fn record(&mut self, time: Duration, port: u8, message: Message) {
+        match message {
+            Message::Channel(_) => {
+                self.events.push(RecordedEvent {
+                    time,
+                    port,
+                    kind: TrackEventKind::Midi(message),
+                });
+            }
+            Message::System(_) => {
+                log::trace!("not recording system message {:?}", message);
+            }
+        }
+    }
+
+    /// Records a SysEx message, as returned by [`crate::parser::Parser::parse`],
+    /// without its `F0` and `F7` status bytes.
+    pub fn record_sysex(&mut self, time: Duration, port: u8, data: &[u8]) {
+        let mut data = data.to_vec();
+        data.push(system_status_bytes::SYSTEM_END_OF_SYSTEM_EXCLUSIVE_FLAG);
+        self.events.push(RecordedEvent {
+            time,
+            port,
+            kind: TrackEventKind::SystemExclusive(data),
+        });
+    }
+
+    /// Records the message of a parse outcome, if it has one.
+    pub fn record_outcome(&mut self, time: Duration, port: u8, status: MessageParseOutcomeStatus) {
+        match status {
+            MessageParseOutcomeStatus::Message(message) => self.record(time, port, message),
+            MessageParseOutcomeStatus::SystemExclusiveMessage(data) => self.record_sysex(time, port, data),
+            _ => { }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Converts a timestamp to ticks at the recording tempo, quantized.
+    pub fn time_to_ticks(&self, time: Duration) -> u64 {
+        let quarters = time.as_secs_f64() * 1_000_000.0 / self.microseconds_per_quarter as f64;
+        let ticks = (quarters * self.ticks_per_quarter as f64).round() as u64;
+        match self.quantize {
+            None => ticks,
+            Some(grid) => (ticks + grid / 2) / grid * grid,
+        }
+    }
+
+    /// Builds a format 1 file.
+    ///
+    /// The first track holds the tempo, followed by a track per channel or port
+    /// in ascending order. Events are expected to be recorded in time order.
+    pub fn to_smf(&self) -> Smf {
+        let mut conductor = Track::default();
+        conductor.events.push(TrackEvent {
+            tick: 0,
+            kind: TrackEventKind::Meta(MetaEvent::SetTempo {
+                microseconds_per_quarter: self.microseconds_per_quarter,
+            }),
+        });
+
+        let mut tracks: BTreeMap<u8, Track> = BTreeMap::new();
+        for event in &self.events {
+            let tick = self.time_to_ticks(event.time);
+            let track_key = match (self.separation, &event.kind) {
+                (TrackSeparation::Port, _) => Some(event.port),
+                (TrackSeparation::Channel, TrackEventKind::Midi(Message::Channel(message))) => {
+                    Some(u8::from(message.channel))
+                }
+                (TrackSeparation::Channel, _) => None,
+            };
+            let track = match track_key {
+                None => &mut conductor,
+                Some(key) => tracks.entry(key).or_insert_with(|| self.new_track(key)),
+            };
+            // Quantization can't reorder events, but the caller's clock might.
+            let tick = tick.max(track.end_tick());
+            track.events.push(TrackEvent {
+                tick,
+                kind: event.kind.clone(),
+            });
+        }
+
+        let end_tick = std::iter::once(&conductor).chain(tracks.values()).map(Track::end_tick).max().unwrap_or(0);
+        let mut tracks: Vec<Track> = std::iter::once(conductor).chain(tracks.into_values()).collect();
+        for track in &mut tracks {
+            track.events.push(TrackEvent {
+                tick: end_tick,
+                kind: TrackEventKind::Meta(MetaEvent::EndOfTrack),
+            });
+        }
+
+        Smf {
+            format: Format::MultiTrack,
+            division: Division::Metrical(self.ticks_per_quarter),
+            tracks,
+        }
+    }
+
+    fn new_track(&self, key: u8) -> Track {
+        let (name, port) = match self.separation {
+            TrackSeparation::Channel => (format!("Channel {}", key as u32 + 1), None),
+            TrackSeparation::Port => (format!("Port {}", key), Some(key)),
+        };
+        let mut events = vec![TrackEvent {
+            tick: 0,
+            kind: TrackEventKind::Meta(MetaEvent::Text {
+                kind: TextKind::TrackName,
+                text: name.into_bytes(),
+            }),
+        }];
+        if let Some(port) = port {
+            events.push(TrackEvent {
+                tick: 0,
+                kind: TrackEventKind::Meta(MetaEvent::Port(port)),
+            });
+        }
+        Track { events }
+    }
+}
@@ -0,0 +1,162 @@
+//! RIFF MIDI (`.rmi`) files, which wrap an SMF in a RIFF container.
+//!
+//! The SMF is stored in a `data` chunk,
+//! optionally accompanied by a `LIST` chunk of `INFO` text such as the song name.
+//!
+//! Reference: Microsoft Multimedia Programming Interface and Data Specifications 1.0, "RIFF MIDI Format"
+
+use anyhow::{Result, anyhow, bail};
+use crate::smf::Smf;
+
+const RIFF_CHUNK_TYPE: [u8; 4] = *b"RIFF";
+const RMID_FORM_TYPE: [u8; 4] = *b"RMID";
+const DATA_CHUNK_TYPE: [u8; 4] = *b"data";
+const LIST_CHUNK_TYPE: [u8; 4] = *b"LIST";
+const INFO_LIST_TYPE: [u8; 4] = *b"INFO";
+
+/// Common `INFO` chunk ids.
+pub mod info_ids {
+    pub const NAME: [u8; 4] = *b"INAM";
+    pub const COPYRIGHT: [u8; 4] = *b"ICOP";
+    pub const ARTIST: [u8; 4] = *b"IART";
+    pub const COMMENTS: [u8; 4] = *b"ICMT";
+    pub const CREATION_DATE: [u8; 4] = *b"ICRD";
+    pub const GENRE: [u8; 4] = *b"IGNR";
+    pub const SOFTWARE: [u8; 4] = *b"ISFT";
+    pub const SUBJECT: [u8; 4] = *b"ISBJ";
+    pub const ENGINEER: [u8; 4] = *b"IENG";
+    pub const KEYWORDS: [u8; 4] = *b"IKEY";
+}
+
+/// A text entry of a RIFF `INFO` list.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct InfoEntry {
+    pub id: [u8; 4],
+    /// The text, without its terminating NUL.
+    pub text: Vec<u8>,
+}
+
+/// A MIDI file that may have been wrapped in RIFF.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct MidiFile {
+    pub smf: Smf,
+    /// The `INFO` entries of an RMID file, in file order.
+    ///
+    /// Empty for a plain SMF.
+    pub info: Vec<InfoEntry>,
+}
+
+impl MidiFile {
+    /// Reads either a plain SMF or an RMID file.
+    pub fn read(bytes: &[u8]) -> Result<MidiFile> {
+        if is_rmid(bytes) {
+            let (smf_bytes, info) = unwrap_rmid(bytes)?;
+            Ok(MidiFile {
+                smf: Smf::read(smf_bytes)?,
+                info,
+            })
+        } else {
+            Ok(MidiFile {
+                smf: Smf::read(bytes)?,
+                info: vec![],
+            })
+        }
+    }
+
+    /// The first `INFO` entry with the given id.
+    pub fn info_text(&self, id: [u8; 4]) -> Option<&[u8]> {
+        self.info.iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| &entry.text[..])
+    }
+}
+
+pub fn is_rmid(bytes: &[u8]) -> bool {
+    bytes.len() >= 12 && bytes[0..4] == RIFF_CHUNK_TYPE && bytes[8..12] == RMID_FORM_TYPE
+}
+
+/// Returns the SMF bytes of an RMID file and its `INFO` entries.
+///
+/// Unknown chunks, such as `DISP`, are ignored.
+pub fn unwrap_rmid(bytes: &[u8]) -> Result<(&[u8], Vec<InfoEntry>)> {
+    if !is_rmid(bytes) {
+        bail!("not a RIFF RMID file");
+    }
+    let riff_len = u32::from_le_bytes(bytes[4..8].try_into().expect("4 bytes")) as usize;
+    // Some writers get the RIFF length wrong, so don't trust it past the end of the file.
+    let end = bytes.len().min(8 + riff_len);
+    let chunks = read_riff_chunks(&bytes[12..end])?;
+
+    let mut smf_bytes = None;
+    let mut info = vec![];
+    for (chunk_type, data) in chunks {
+        match chunk_type {
+            DATA_CHUNK_TYPE if smf_bytes.is_none() => {
+                smf_bytes = Some(data);
+            }
+            LIST_CHUNK_TYPE if data.get(0..4) == Some(&INFO_LIST_TYPE[..]) => {
+                for (id, text) in read_riff_chunks(&data[4..])? {
+                    let text = match text.iter().position(|&byte| byte == 0) {
+                        Some(nul) => &text[..nul],
+                        None => text,
+                    };
+                    info.push(InfoEntry {
+                        id,
+                        text: text.to_vec(),
+                    });
+                }
+            }
+            _ => { }
+        }
+    }
+
+    let smf_bytes = smf_bytes.ok_or_else(|| anyhow!("RMID file has no data chunk"))?;
+    Ok((smf_bytes, info))
+}
+
+/// Writes an SMF wrapped in RIFF, with an `INFO` list if `info` isn't empty.
+pub fn write_rmid(smf: &Smf, info: &[InfoEntry], buf: &mut Vec<u8>) -> Result<()> {
+    let mut form = vec![];
+    form.extend_from_slice(&RMID_FORM_TYPE);
+    write_riff_chunk(DATA_CHUNK_TYPE, &smf.to_bytes()?, &mut form)?;
+    if !info.is_empty() {
+        let mut list = vec![];
+        list.extend_from_slice(&INFO_LIST_TYPE);
+        for entry in info {
+            let mut text = entry.text.clone();
+            text.push(0);
+            write_riff_chunk(entry.id, &text, &mut list)?;
+        }
+        write_riff_chunk(LIST_CHUNK_TYPE, &list, &mut form)?;
+    }
+    write_riff_chunk(RIFF_CHUNK_TYPE, &form, buf)
+}
+
+/// Splits RIFF data into chunks, which are little-endian and padded to even lengths.
+fn read_riff_chunks(mut bytes: &[u8]) -> Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = vec![];
+    while bytes.len() >= 8 {
+        let chunk_type = <[u8; 4]>::try_from(&bytes[0..4]).expect("4 bytes");
+        let len = u32::from_le_bytes(bytes[4..8].try_into().expect("4 bytes")) as usize;
+        let data = bytes.get(8..8 + len).ok_or_else(|| {
+            anyhow!("truncated RIFF {} chunk", String::from_utf8_lossy(&chunk_type))
+        })?;
+        chunks.push((chunk_type, data));
+        let padded_len = len + len % 2;
+        bytes = bytes.get(8 + padded_len..).unwrap_or(&[]);
+    }
+    Ok(chunks)
+}
+
+fn write_riff_chunk(chunk_type: [u8; 4], data: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| anyhow!("RIFF chunk too long"))?;
+    buf.extend_from_slice(&chunk_type);
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(data);
+    if !data.len().is_multiple_of(2) {
+        buf.push(0);
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+//! Tracking of registered and non-registered parameters set through Data Entry.
+//!
+//! Reference: MIDI spec table III, and RP-018 Response to Data Increment/Decrement
+
+use std::collections::HashMap;
+use crate::control_number::ControlNumber;
+use crate::message::*;
+
+/// Registered parameter numbers.
+///
+/// Reference: MIDI spec table IIIa
+pub mod registered {
+    pub const PITCH_BEND_SENSITIVITY: u16 = 0x0000;
+    pub const FINE_TUNING: u16 = 0x0001;
+    pub const COARSE_TUNING: u16 = 0x0002;
+    pub const TUNING_PROGRAM_CHANGE: u16 = 0x0003;
+    pub const TUNING_BANK_SELECT: u16 = 0x0004;
+    pub const MODULATION_DEPTH_RANGE: u16 = 0x0005;
+    /// Reference: M1-100-UM MIDI Polyphonic Expression 1.1
+    pub const MPE_CONFIGURATION: u16 = 0x0006;
+    /// Deselects any parameter, so Data Entry has no effect.
+    pub const NULL: u16 = 0x3FFF;
+}
+
+/// A parameter number, MSB in the high seven bits.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Parameter {
+    Registered(u16),
+    NonRegistered(u16),
+}
+
+/// A parameter value set by Data Entry.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct ParameterValue {
+    pub msb: u8,
+    /// Zero until a Data Entry LSB is received.
+    pub lsb: u8,
+}
+
+impl ParameterValue {
+    /// The value as 14 bits, MSB in the high seven bits.
+    pub fn to_u14(self) -> u16 {
+        (self.msb as u16) << 7 | self.lsb as u16
+    }
+
+    fn from_u14(value: u16) -> ParameterValue {
+        ParameterValue {
+            msb: (value >> 7) as u8,
+            lsb: (value & 0x7F) as u8,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct ParameterChange {
+    pub channel: MidiChannelId,
+    pub parameter: Parameter,
+    pub value: ParameterValue,
+}
+
+#[derive(Default)]
+struct ChannelState {
+    rpn: [Option<u8>; 2],
+    nrpn: [Option<u8>; 2],
+    selected: Option<Parameter>,
+}
+
+/// Follows the parameter number controllers and Data Entry of each channel.
+#[derive(Default)]
+pub struct ParameterTracker {
+    channels: HashMap<MidiChannelId, ChannelState>,
+    values: HashMap<(MidiChannelId, Parameter), ParameterValue>,
+}
+
+impl ParameterTracker {
+    pub fn new() -> ParameterTracker {
+        ParameterTracker::default()
+    }
+
+    /// The last value set for a parameter on a channel.
+    pub fn value(&self, channel: MidiChannelId, parameter: Parameter) -> Option<ParameterValue> {
+        self.values.get(&(channel, parameter)).copied()
+    }
+
+    /// The parameter Data Entry currently addresses on a channel.
+    pub fn selected(&self, channel: MidiChannelId) -> Option<Parameter> {
+        self.channels.get(&channel).and_then(|state| state.selected)
+    }
+
+    /// Follows a message, returning the parameter it changed, if any.
+    ///
+    /// Data Entry MSB leaves the LSB as it was.
+    /// Data Increment and Decrement step the 14-bit value by one,
+    /// which for Pitch Bend Sensitivity is a cent.
+    pub fn handle(&mut self, message: &ChannelMessage) -> Option<ParameterChange> {
+        let cc = match &message.message {
+            ChannelMessageType::ChannelVoice(ChannelVoiceMessage::ControlChange(cc)) => cc,
+            ChannelMessageType::ChannelMode { mode: ChannelModeMessage::ResetAllControllers, .. } => {
+                self.reset_selection(message.channel);
+                return None;
+            }
+            _ => return None,
+        };
+        let channel = message.channel;
+        let value = u8::from(cc.value);
+        let state = self.channels.entry(channel).or_default();
+
+        let parameter_number = |number: [Option<u8>; 2]| {
+            (number[0].unwrap_or(0) as u16) << 7 | number[1].unwrap_or(0) as u16
+        };
+        let update: fn(ParameterValue, u8) -> ParameterValue = match ControlNumber::try_from(u8::from(cc.control_number.0)) {
+            Ok(ControlNumber::RegisteredParameterNumberMSB) => {
+                state.rpn[0] = Some(value);
+                state.selected = Some(Parameter::Registered(parameter_number(state.rpn)));
+                return None;
+            }
+            Ok(ControlNumber::RegisteredParameterNumberLSB) => {
+                state.rpn[1] = Some(value);
+                state.selected = Some(Parameter::Registered(parameter_number(state.rpn)));
+                return None;
+            }
+            Ok(ControlNumber::NonRegisteredParameterNumberMSB) => {
+                state.nrpn[0] = Some(value);
+                state.selected = Some(Parameter::NonRegistered(parameter_number(state.nrpn)));
+                return None;
+            }
+            Ok(ControlNumber::NonRegisteredParameterNumberLSB) => {
+                state.nrpn[1] = Some(value);
+                state.selected = Some(Parameter::NonRegistered(parameter_number(state.nrpn)));
+                return None;
+            }
+            Ok(ControlNumber::DataEntryMSB) => |old, value| ParameterValue { msb: value, ..old },
+            Ok(ControlNumber::DataEntryLSB) => |old, value| ParameterValue { lsb: value, ..old },
+            Ok(ControlNumber::DataIncrement) => |old, _| ParameterValue::from_u14((old.to_u14() + 1).min(0x3FFF)),
+            Ok(ControlNumber::DataDecrement) => |old, _| ParameterValue::from_u14(old.to_u14().saturating_sub(1)),
+            _ => return None,
+        };
+
+        let parameter = match state.selected {
+            None | Some(Parameter::Registered(registered::NULL)) | Some(Parameter::NonRegistered(registered::NULL)) => {
+                return None;
+            }
+            Some(parameter) => parameter,
+        };
+        let old = self.values.get(&(channel, parameter)).copied()
+            .unwrap_or(ParameterValue { msb: 0, lsb: 0 });
+        let new = update(old, value);
+        self.values.insert((channel, parameter), new);
+        Some(ParameterChange {
+            channel,
+            parameter,
+            value: new,
+        })
+    }
+
+    /// Deselects the parameter of a channel, as Reset All Controllers does.
+    ///
+    /// Parameter values are kept.
+    pub fn reset_selection(&mut self, channel: MidiChannelId) {
+        self.channels.remove(&channel);
+    }
+}
+
+/// The Control Changes that set a parameter, ending with a null parameter selection.
+pub fn parameter_messages(channel: MidiChannelId, parameter: Parameter, value: ParameterValue) -> Vec<ChannelMessage> {
+    use crate::assert_from::AssertFrom;
+
+    let (number, msb_control, lsb_control) = match parameter {
+        Parameter::Registered(number) => {
+            (number, ControlNumber::RegisteredParameterNumberMSB, ControlNumber::RegisteredParameterNumberLSB)
+        }
+        Parameter::NonRegistered(number) => {
+            (number, ControlNumber::NonRegisteredParameterNumberMSB, ControlNumber::NonRegisteredParameterNumberLSB)
+        }
+    };
+    let control_change = |control: ControlNumber, value: u8| ChannelMessage {
+        channel,
+        message: ChannelMessageType::ChannelVoice(ChannelVoiceMessage::ControlChange(cvm::ControlChange {
+            control_number: cvm::ControlNumber(cvm::Unsigned7::assert_from(u8::from(control))),
+            value: cvm::Unsigned7::assert_from(value & 0x7F),
+        })),
+    };
+    vec![
+        control_change(msb_control, (number >> 7) as u8),
+        control_change(lsb_control, number as u8),
+        control_change(ControlNumber::DataEntryMSB, value.msb),
+        control_change(ControlNumber::DataEntryLSB, value.lsb),
+        control_change(ControlNumber::RegisteredParameterNumberMSB, 0x7F),
+        control_change(ControlNumber::RegisteredParameterNumberLSB, 0x7F),
+    ]
+}
@@ -0,0 +1,309 @@
+//! Scala scale (`.scl`) and keyboard mapping (`.kbm`) files.
+//!
+//! A scale lists pitches above an implicit 1/1, the last being the period it repeats at.
+//! A keyboard mapping assigns scale degrees to keys and fixes one key's frequency.
+//! Together they give the frequency of each of the 128 notes,
+//! which can be sent to a synthesizer as MTS messages.
+//!
+//! Reference: <https://www.huygens-fokker.org/scala/scl_format.html>,
+//! and the keyboard mapping section of the Scala help
+
+use anyhow::{Context, Result, anyhow, bail};
+use crate::message::*;
+use crate::tuning::{self, MtsBody, MtsMessage, NoteTuning, TuningTable};
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum ScalePitch {
+    /// Written with a period.
+    Cents(f64),
+    /// Written as `n/d` or a whole number.
+    Ratio(u64, u64),
+}
+
+impl ScalePitch {
+    pub fn parse(text: &str) -> Result<ScalePitch> {
+        let token = text.split_whitespace().next().ok_or_else(|| anyhow!("missing pitch"))?;
+        if token.contains('.') {
+            let cents: f64 = token.parse().with_context(|| format!("invalid cents {:?}", token))?;
+            if !cents.is_finite() {
+                bail!("invalid cents {:?}", token);
+            }
+            return Ok(ScalePitch::Cents(cents));
+        }
+        let (numerator, denominator) = token.split_once('/').unwrap_or((token, "1"));
+        let numerator: u64 = numerator.parse().with_context(|| format!("invalid ratio {:?}", token))?;
+        let denominator: u64 = denominator.parse().with_context(|| format!("invalid ratio {:?}", token))?;
+        if numerator == 0 || denominator == 0 {
+            bail!("invalid ratio {:?}", token);
+        }
+        Ok(ScalePitch::Ratio(numerator, denominator))
+    }
+
+    pub fn to_ratio(self) -> f64 {
+        match self {
+            ScalePitch::Cents(cents) => 2f64.powf(cents / 1200.0),
+            ScalePitch::Ratio(numerator, denominator) => numerator as f64 / denominator as f64,
+        }
+    }
+
+    pub fn to_cents(self) -> f64 {
+        match self {
+            ScalePitch::Cents(cents) => cents,
+            ScalePitch::Ratio(..) => 1200.0 * self.to_ratio().log2(),
+        }
+    }
+}
+
+/// The lines of a Scala file that aren't comments.
+fn content_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines().enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+/// A scale, from a `.scl` file.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    /// Pitches above the implicit 1/1, the last being the period.
+    pub pitches: Vec<ScalePitch>,
+}
+
+impl Scale {
+    pub fn parse(text: &str) -> Result<Scale> {
+        let mut lines = content_lines(text);
+        let (_, description) = lines.next().ok_or_else(|| anyhow!("missing scale description"))?;
+        let (line_number, count) = lines.next().ok_or_else(|| anyhow!("missing scale note count"))?;
+        let count: usize = count.trim().parse()
+            .with_context(|| format!("line {}: invalid note count {:?}", line_number, count.trim()))?;
+        let pitches = (0..count).map(|_| {
+            let (line_number, line) = lines.next().ok_or_else(|| anyhow!("expected {} pitches", count))?;
+            ScalePitch::parse(line).with_context(|| format!("line {}", line_number))
+        }).collect::<Result<_>>()?;
+        Ok(Scale {
+            description: description.trim().to_string(),
+            pitches,
+        })
+    }
+
+    /// Twelve-tone equal temperament.
+    pub fn equal_temperament() -> Scale {
+        Scale {
+            description: "12-tone equal temperament".to_string(),
+            pitches: (1..=12).map(|step| ScalePitch::Cents(step as f64 * 100.0)).collect(),
+        }
+    }
+
+    /// The number of degrees before the scale repeats.
+    pub fn len(&self) -> usize {
+        self.pitches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pitches.is_empty()
+    }
+
+    /// The ratio of a degree above degree 0, which may be outside the first period.
+    pub fn degree_ratio(&self, degree: i64) -> Result<f64> {
+        let period = self.pitches.last().ok_or_else(|| anyhow!("scale has no pitches"))?.to_ratio();
+        let len = self.len() as i64;
+        let periods = degree.div_euclid(len);
+        let index = degree.rem_euclid(len) as usize;
+        let ratio = if index == 0 { 1.0 } else { self.pitches[index - 1].to_ratio() };
+        Ok(ratio * period.powi(periods as i32))
+    }
+}
+
+/// A keyboard mapping, from a `.kbm` file.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct KeyboardMapping {
+    pub first_note: u8,
+    pub last_note: u8,
+    /// The note scale degree 0 is mapped to.
+    pub middle_note: u8,
+    pub reference_note: u8,
+    pub reference_frequency: f64,
+    /// The degree the mapping repeats at; 0 for the scale's period.
+    pub octave_degree: usize,
+    /// Scale degrees of keys from the middle note, repeating. `None` keys are unmapped.
+    ///
+    /// Empty for a linear mapping, where each key is the next degree.
+    pub map: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    /// A linear mapping with degree 0 on middle C at its equal-tempered frequency.
+    fn default() -> KeyboardMapping {
+        KeyboardMapping {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 60,
+            reference_frequency: tuning::semitones_to_frequency(60.0, tuning::CONCERT_A),
+            octave_degree: 0,
+            map: vec![],
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn parse(text: &str) -> Result<KeyboardMapping> {
+        let mut lines = content_lines(text).filter(|(_, line)| !line.trim().is_empty());
+        let lines = &mut lines;
+        let size = kbm_number(lines, "map size", 0x400)?;
+        let first_note = kbm_number(lines, "first note", 127)? as u8;
+        let last_note = kbm_number(lines, "last note", 127)? as u8;
+        let middle_note = kbm_number(lines, "middle note", 127)? as u8;
+        let reference_note = kbm_number(lines, "reference note", 127)? as u8;
+        let (line_number, token) = kbm_field(lines, "reference frequency")?;
+        let reference_frequency: f64 = token.parse().ok().filter(|frequency: &f64| *frequency > 0.0 && frequency.is_finite())
+            .ok_or_else(|| anyhow!("line {}: invalid reference frequency {:?}", line_number, token))?;
+        let octave_degree = kbm_number(lines, "octave degree", usize::MAX)?;
+        let map = (0..size).map(|_| {
+            // Trailing keys may be left out, and are unmapped.
+            let Ok((line_number, token)) = kbm_field(lines, "key") else {
+                return Ok(None);
+            };
+            if token.eq_ignore_ascii_case("x") {
+                Ok(None)
+            } else {
+                token.parse().map(Some)
+                    .map_err(|_| anyhow!("line {}: invalid scale degree {:?}", line_number, token))
+            }
+        }).collect::<Result<_>>()?;
+        Ok(KeyboardMapping {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            map,
+        })
+    }
+
+    /// The scale degree of a note, or `None` if unmapped.
+    pub fn degree(&self, note: u8, scale: &Scale) -> Option<i64> {
+        let offset = note as i64 - self.middle_note as i64;
+        if self.map.is_empty() {
+            return Some(offset);
+        }
+        let size = self.map.len() as i64;
+        let octave_degree = if self.octave_degree == 0 { scale.len() } else { self.octave_degree } as i64;
+        let degree = self.map[offset.rem_euclid(size) as usize]? as i64;
+        Some(degree + offset.div_euclid(size) * octave_degree)
+    }
+
+    /// The frequency of each note, `None` for notes outside the mapped range or unmapped.
+    pub fn frequencies(&self, scale: &Scale) -> Result<[Option<f64>; 128]> {
+        if scale.is_empty() {
+            bail!("scale has no pitches");
+        }
+        let reference_degree = self.degree(self.reference_note, scale)
+            .ok_or_else(|| anyhow!("reference note {} is unmapped", self.reference_note))?;
+        let reference_ratio = scale.degree_ratio(reference_degree)?;
+        let mut frequencies = [None; 128];
+        for note in self.first_note..=self.last_note.min(127) {
+            if let Some(degree) = self.degree(note, scale) {
+                frequencies[note as usize] = Some(self.reference_frequency * scale.degree_ratio(degree)? / reference_ratio);
+            }
+        }
+        Ok(frequencies)
+    }
+}
+
+/// The first whitespace-separated token of the next line.
+fn kbm_field<'a>(lines: &mut impl Iterator<Item = (usize, &'a str)>, name: &str) -> Result<(usize, &'a str)> {
+    let (line_number, line) = lines.next().ok_or_else(|| anyhow!("missing {}", name))?;
+    Ok((line_number, line.split_whitespace().next().unwrap_or("")))
+}
+
+fn kbm_number<'a>(lines: &mut impl Iterator<Item = (usize, &'a str)>, name: &str, max: usize) -> Result<usize> {
+    let (line_number, token) = kbm_field(lines, name)?;
+    token.parse().ok().filter(|value| *value <= max)
+        .ok_or_else(|| anyhow!("line {}: invalid {} {:?}", line_number, name, token))
+}
+
+/// The frequencies a scale and keyboard mapping give the 128 notes.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct ScalaTuning {
+    /// `None` for notes the mapping leaves untuned.
+    pub frequencies: [Option<f64>; 128],
+}
+
+impl ScalaTuning {
+    pub fn new(scale: &Scale, mapping: &KeyboardMapping) -> Result<ScalaTuning> {
+        Ok(ScalaTuning {
+            frequencies: mapping.frequencies(scale)?,
+        })
+    }
+
+    /// Reads a `.scl` file and optional `.kbm` file.
+    pub fn parse(scl: &str, kbm: Option<&str>) -> Result<ScalaTuning> {
+        let scale = Scale::parse(scl).context("in scale file")?;
+        let mapping = match kbm {
+            None => KeyboardMapping::default(),
+            Some(kbm) => KeyboardMapping::parse(kbm).context("in keyboard mapping file")?,
+        };
+        ScalaTuning::new(&scale, &mapping)
+    }
+
+    /// The tuning as a table, untuned notes left in equal temperament.
+    pub fn to_tuning_table(&self) -> TuningTable {
+        let mut table = TuningTable::equal_temperament();
+        for (note, frequency) in self.frequencies.iter().enumerate() {
+            if let Some(frequency) = frequency {
+                table.set_frequency(note_number(note), *frequency);
+            }
+        }
+        table
+    }
+
+    fn note_tunings(&self) -> impl Iterator<Item = Option<NoteTuning>> + '_ {
+        self.frequencies.iter().map(|frequency| frequency.map(NoteTuning::from_frequency))
+    }
+
+    /// A bulk dump, or key-based tuning dump with a bank.
+    ///
+    /// Untuned notes are sent as "no change".
+    pub fn to_bulk_dump(&self, device_id: u8, bank: Option<u8>, program: u8, name: &str) -> MtsMessage {
+        let mut tunings = Box::new([None; 128]);
+        for (tuning, note_tuning) in tunings.iter_mut().zip(self.note_tunings()) {
+            *tuning = note_tuning;
+        }
+        MtsMessage {
+            device_id,
+            body: MtsBody::BulkDump {
+                bank,
+                program,
+                name: name.to_string(),
+                tunings,
+            },
+        }
+    }
+
+    /// Real time single note tuning changes of the tuned notes, at most 127 per message.
+    pub fn to_single_note_changes(&self, device_id: u8, bank: Option<u8>, program: u8) -> Vec<MtsMessage> {
+        let changes: Vec<(cvm::NoteNumber, Option<NoteTuning>)> = self.note_tunings().enumerate()
+            .filter(|(_, tuning)| tuning.is_some())
+            .map(|(note, tuning)| (note_number(note), tuning))
+            .collect();
+        changes.chunks(0x7F).map(|changes| MtsMessage {
+            device_id,
+            body: MtsBody::SingleNoteTuningChange {
+                realtime: true,
+                bank,
+                program,
+                changes: changes.to_vec(),
+            },
+        }).collect()
+    }
+}
+
+fn note_number(note: usize) -> cvm::NoteNumber {
+    cvm::NoteNumber(cvm::Unsigned7::try_from(note as u8).expect("note number"))
+}
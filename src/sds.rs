@@ -0,0 +1,968 @@
+//! The MIDI Sample Dump Standard: transferring samples with a handshake.
+//!
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//! Every SDS message is at most 127 bytes, so the [`SampleSender`] and [`SampleReceiver`]
+//! handle a dump one packet at a time and never hold the whole sample.
+//! Like the [`crate::sequencer::Sequencer`], they read no clock of their own.
+//! An [`SdsPort`] runs either side over the raw bytes of a MIDI port.
+//!
+//! Reference: MIDI spec, Sample Dump Standard; CA-019 Loop Points
+
+use anyhow::{Context, Result, anyhow, bail};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use std::io::Write;
+use std::time::Duration;
+use crate::parser::{MessageParseOutcomeStatus, Parser};
+use crate::sysex::{HandshakeKind, UNIVERSAL_NON_REALTIME};
+use crate::sysex::non_realtime;
+
+pub const SUB_ID_1_LOOP_POINTS: u8 = 0x05;
+pub const SUB_ID_2_LOOP_POINT_TRANSMISSION: u8 = 0x01;
+pub const SUB_ID_2_LOOP_POINT_REQUEST: u8 = 0x02;
+
+/// Bytes of sample data in each Data Packet.
+pub const PACKET_DATA_LEN: usize = 120;
+
+/// The loop number addressing every loop of a sample.
+pub const ALL_LOOPS: u16 = 0x3FFF;
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum LoopType {
+    Forward = 0x00,
+    BackwardForward = 0x01,
+    Off = 0x7F,
+}
+
+/// Describes the sample that follows in Data Packets.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DumpHeader {
+    /// 14 bits.
+    pub sample_number: u16,
+    /// Significant bits per sample word, 8 to 28.
+    pub bits: u8,
+    /// The sample period in nanoseconds, 21 bits.
+    pub period_ns: u32,
+    /// The length in words, 21 bits.
+    pub length: u32,
+    /// The word number of the sustain loop start.
+    pub sustain_loop_start: u32,
+    /// The word number of the sustain loop end.
+    pub sustain_loop_end: u32,
+    pub loop_type: LoopType,
+}
+
+impl DumpHeader {
+    /// A header with no loop, rounding the sample rate to the nearest nanosecond period.
+    pub fn new(sample_number: u16, bits: u8, sample_rate: f64, length: u32) -> Result<DumpHeader> {
+        if sample_rate.is_nan() || sample_rate <= 0.0 {
+            bail!("SDS sample rate must be positive, not {}", sample_rate);
+        }
+        let header = DumpHeader {
+            sample_number,
+            bits,
+            period_ns: (1e9 / sample_rate).round() as u32,
+            length,
+            sustain_loop_start: 0,
+            sustain_loop_end: 0,
+            loop_type: LoopType::Off,
+        };
+        header.validate()?;
+        Ok(header)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if !(8..=28).contains(&self.bits) {
+            bail!("SDS sample format must be 8 to 28 bits, not {}", self.bits);
+        }
+        if self.period_ns == 0 {
+            bail!("SDS sample period must be positive");
+        }
+        if self.period_ns > 0x1F_FFFF {
+            bail!("SDS sample period too long: {} ns", self.period_ns);
+        }
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        1e9 / self.period_ns as f64
+    }
+
+    /// Bytes per word in Data Packets; each word is left-justified in 7-bit bytes.
+    pub fn bytes_per_word(&self) -> usize {
+        (self.bits as usize).div_ceil(7)
+    }
+
+    pub fn words_per_packet(&self) -> usize {
+        PACKET_DATA_LEN / self.bytes_per_word()
+    }
+
+    /// Decodes the words of a Data Packet into signed samples.
+    pub fn decode_words(&self, data: &[u8; PACKET_DATA_LEN]) -> Vec<i32> {
+        let bytes_per_word = self.bytes_per_word();
+        let shift = bytes_per_word as u32 * 7 - self.bits as u32;
+        let offset = 1i64 << (self.bits - 1);
+        data.chunks_exact(bytes_per_word)
+            .map(|word| {
+                let value = word.iter().fold(0u64, |value, byte| value << 7 | *byte as u64) >> shift;
+                (value as i64 - offset) as i32
+            })
+            .collect()
+    }
+
+    /// Encodes signed samples as the words of a Data Packet, padding with silence.
+    /// Samples out of range for the format are clamped.
+    pub fn encode_words(&self, samples: &[i32]) -> Result<[u8; PACKET_DATA_LEN]> {
+        if samples.len() > self.words_per_packet() {
+            bail!("too many samples for one data packet: {}", samples.len());
+        }
+        let bytes_per_word = self.bytes_per_word();
+        let shift = bytes_per_word as u32 * 7 - self.bits as u32;
+        let offset = 1i64 << (self.bits - 1);
+        let mut data = [0; PACKET_DATA_LEN];
+        for (index, word) in data.chunks_exact_mut(bytes_per_word).enumerate() {
+            let sample = samples.get(index).copied().unwrap_or(0) as i64;
+            let value = ((sample.clamp(-offset, offset - 1) + offset) as u64) << shift;
+            for (position, byte) in word.iter_mut().enumerate() {
+                *byte = (value >> ((bytes_per_word - 1 - position) * 7)) as u8 & 0x7F;
+            }
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum SdsBody {
+    DumpHeader(DumpHeader),
+    DataPacket {
+        /// Counts from 0, wrapping after 0x7F.
+        packet_number: u8,
+        data: Box<[u8; PACKET_DATA_LEN]>,
+    },
+    DumpRequest {
+        sample_number: u16,
+    },
+    LoopPoints {
+        sample_number: u16,
+        loop_number: u16,
+        loop_type: LoopType,
+        start: u32,
+        end: u32,
+    },
+    LoopPointsRequest {
+        sample_number: u16,
+        /// [`ALL_LOOPS`] requests every loop.
+        loop_number: u16,
+    },
+    Handshake {
+        kind: HandshakeKind,
+        packet_number: u8,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct SdsMessage {
+    /// The channel of the sampler.
+    pub device_id: u8,
+    pub body: SdsBody,
+}
+
+fn take<'buf>(reader: &mut &'buf [u8], len: usize) -> Result<&'buf [u8]> {
+    if reader.len() < len {
+        bail!("truncated SDS message");
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(bytes)
+}
+
+/// Reads a little-endian number of 7-bit bytes.
+fn take_number(reader: &mut &[u8], len: usize) -> Result<u32> {
+    Ok(take(reader, len)?.iter().rev().fold(0, |value, byte| value << 7 | *byte as u32))
+}
+
+fn write_number(value: u32, len: usize, buf: &mut Vec<u8>) -> Result<()> {
+    if value >> (len * 7) != 0 {
+        bail!("SDS field out of range: {}", value);
+    }
+    buf.extend((0..len).map(|index| (value >> (index * 7)) as u8 & 0x7F));
+    Ok(())
+}
+
+/// The XOR of a Data Packet from the Universal SysEx ID, masked to seven bits.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum ^ byte) & 0x7F
+}
+
+impl SdsMessage {
+    pub fn is_sds(data: &[u8]) -> bool {
+        matches!(data, [UNIVERSAL_NON_REALTIME, _, sub_id_1, ..] if matches!(*sub_id_1,
+            non_realtime::SAMPLE_DUMP_HEADER
+            | non_realtime::SAMPLE_DATA_PACKET
+            | non_realtime::SAMPLE_DUMP_REQUEST
+            | SUB_ID_1_LOOP_POINTS
+            | non_realtime::WAIT..=non_realtime::ACK))
+    }
+
+    /// Parses SysEx data, without `F0` and `F7`, checking the checksums of Data Packets.
+    pub fn parse(data: &[u8]) -> Result<SdsMessage> {
+        if !SdsMessage::is_sds(data) {
+            bail!("not an SDS message");
+        }
+        if data.iter().any(|byte| *byte > 0x7F) {
+            bail!("SDS byte out of 7-bit range");
+        }
+        let device_id = data[1];
+        let reader = &mut &data[3..];
+        let body = match data[2] {
+            non_realtime::SAMPLE_DUMP_HEADER => {
+                let header = DumpHeader {
+                    sample_number: take_number(reader, 2)? as u16,
+                    bits: take(reader, 1)?[0],
+                    period_ns: take_number(reader, 3)?,
+                    length: take_number(reader, 3)?,
+                    sustain_loop_start: take_number(reader, 3)?,
+                    sustain_loop_end: take_number(reader, 3)?,
+                    loop_type: LoopType::try_from(take(reader, 1)?[0])?,
+                };
+                header.validate()?;
+                SdsBody::DumpHeader(header)
+            }
+            non_realtime::SAMPLE_DATA_PACKET => {
+                let packet_number = take(reader, 1)?[0];
+                let packet_data: [u8; PACKET_DATA_LEN] = take(reader, PACKET_DATA_LEN)?.try_into().expect("length");
+                let expected = checksum(&data[..data.len() - reader.len()]);
+                let actual = take(reader, 1)?[0];
+                if actual != expected {
+                    bail!("SDS checksum mismatch in packet {}: expected {:02X}, found {:02X}", packet_number, expected, actual);
+                }
+                SdsBody::DataPacket {
+                    packet_number,
+                    data: Box::new(packet_data),
+                }
+            }
+            non_realtime::SAMPLE_DUMP_REQUEST => SdsBody::DumpRequest {
+                sample_number: take_number(reader, 2)? as u16,
+            },
+            SUB_ID_1_LOOP_POINTS => match take(reader, 1)?[0] {
+                SUB_ID_2_LOOP_POINT_TRANSMISSION => SdsBody::LoopPoints {
+                    sample_number: take_number(reader, 2)? as u16,
+                    loop_number: take_number(reader, 2)? as u16,
+                    loop_type: LoopType::try_from(take(reader, 1)?[0])?,
+                    start: take_number(reader, 3)?,
+                    end: take_number(reader, 3)?,
+                },
+                SUB_ID_2_LOOP_POINT_REQUEST => SdsBody::LoopPointsRequest {
+                    sample_number: take_number(reader, 2)? as u16,
+                    loop_number: take_number(reader, 2)? as u16,
+                },
+                sub_id_2 => bail!("unknown SDS loop point message {:02X}", sub_id_2),
+            },
+            sub_id_1 => SdsBody::Handshake {
+                kind: match sub_id_1 {
+                    non_realtime::WAIT => HandshakeKind::Wait,
+                    non_realtime::CANCEL => HandshakeKind::Cancel,
+                    non_realtime::NAK => HandshakeKind::Nak,
+                    _ => HandshakeKind::Ack,
+                },
+                packet_number: take(reader, 1).context("handshake message without a packet number")?[0],
+            },
+        };
+        Ok(SdsMessage { device_id, body })
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        buf.extend([UNIVERSAL_NON_REALTIME, self.device_id]);
+        match &self.body {
+            SdsBody::DumpHeader(header) => {
+                header.validate()?;
+                buf.push(non_realtime::SAMPLE_DUMP_HEADER);
+                write_number(header.sample_number as u32, 2, buf)?;
+                buf.push(header.bits);
+                for value in [header.period_ns, header.length, header.sustain_loop_start, header.sustain_loop_end] {
+                    write_number(value, 3, buf)?;
+                }
+                buf.push(header.loop_type.into());
+            }
+            SdsBody::DataPacket { packet_number, data } => {
+                buf.extend([non_realtime::SAMPLE_DATA_PACKET, *packet_number]);
+                buf.extend_from_slice(&data[..]);
+                buf.push(checksum(&buf[start..]));
+            }
+            SdsBody::DumpRequest { sample_number } => {
+                buf.push(non_realtime::SAMPLE_DUMP_REQUEST);
+                write_number(*sample_number as u32, 2, buf)?;
+            }
+            SdsBody::LoopPoints { sample_number, loop_number, loop_type, start, end } => {
+                buf.extend([SUB_ID_1_LOOP_POINTS, SUB_ID_2_LOOP_POINT_TRANSMISSION]);
+                write_number(*sample_number as u32, 2, buf)?;
+                write_number(*loop_number as u32, 2, buf)?;
+                buf.push((*loop_type).into());
+                write_number(*start, 3, buf)?;
+                write_number(*end, 3, buf)?;
+            }
+            SdsBody::LoopPointsRequest { sample_number, loop_number } => {
+                buf.extend([SUB_ID_1_LOOP_POINTS, SUB_ID_2_LOOP_POINT_REQUEST]);
+                write_number(*sample_number as u32, 2, buf)?;
+                write_number(*loop_number as u32, 2, buf)?;
+            }
+            SdsBody::Handshake { kind, packet_number } => {
+                let sub_id_1 = match kind {
+                    HandshakeKind::EndOfFile => bail!("End of File is not part of the sample dump handshake"),
+                    HandshakeKind::Wait => non_realtime::WAIT,
+                    HandshakeKind::Cancel => non_realtime::CANCEL,
+                    HandshakeKind::Nak => non_realtime::NAK,
+                    HandshakeKind::Ack => non_realtime::ACK,
+                };
+                buf.extend([sub_id_1, *packet_number]);
+            }
+        }
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("SDS field out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+
+    fn handshake(device_id: u8, kind: HandshakeKind, packet_number: u8) -> SdsMessage {
+        SdsMessage {
+            device_id,
+            body: SdsBody::Handshake { kind, packet_number },
+        }
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SenderState {
+    /// Waiting for the receiver to acknowledge the header or the last packet.
+    AwaitingReply,
+    /// The receiver asked to wait, without a timeout.
+    Paused,
+    Finished,
+    Cancelled,
+}
+
+/// Sends a sample, reading its samples from an iterator as packets are needed.
+///
+/// Without replies, the sender continues after a timeout, as the standard allows
+/// for receivers without a connection back to the sender.
+pub struct SampleSender<S> {
+    device_id: u8,
+    header: DumpHeader,
+    samples: S,
+    words_sent: u32,
+    /// The last packet sent, kept to resend after a NAK.
+    packet: Option<SdsMessage>,
+    state: SenderState,
+    deadline: Option<Duration>,
+    /// How long to wait for the header to be acknowledged.
+    pub header_timeout: Duration,
+    /// How long to wait for each packet to be acknowledged.
+    pub packet_timeout: Duration,
+}
+
+impl<S: Iterator<Item = i32>> SampleSender<S> {
+    pub fn new(device_id: u8, header: DumpHeader, samples: S) -> Result<SampleSender<S>> {
+        header.validate()?;
+        Ok(SampleSender {
+            device_id,
+            header,
+            samples,
+            words_sent: 0,
+            packet: None,
+            state: SenderState::AwaitingReply,
+            deadline: None,
+            header_timeout: Duration::from_secs(2),
+            packet_timeout: Duration::from_millis(20),
+        })
+    }
+
+    /// Starts the dump, returning the Dump Header to send.
+    pub fn start(&mut self, now: Duration) -> SdsMessage {
+        self.words_sent = 0;
+        self.packet = None;
+        self.state = SenderState::AwaitingReply;
+        self.deadline = Some(now + self.header_timeout);
+        SdsMessage {
+            device_id: self.device_id,
+            body: SdsBody::DumpHeader(self.header),
+        }
+    }
+
+    pub fn state(&self) -> SenderState {
+        self.state
+    }
+
+    /// Samples sent so far.
+    pub fn words_sent(&self) -> u32 {
+        self.words_sent
+    }
+
+    /// Handles a message from the receiver, returning the next message to send.
+    pub fn handle(&mut self, message: &SdsMessage, now: Duration) -> Result<Option<SdsMessage>> {
+        let SdsBody::Handshake { kind, packet_number } = message.body else {
+            return Ok(None);
+        };
+        if message.device_id != self.device_id || matches!(self.state, SenderState::Finished | SenderState::Cancelled) {
+            return Ok(None);
+        }
+        let current_packet = match &self.packet {
+            Some(SdsMessage { body: SdsBody::DataPacket { packet_number, .. }, .. }) => Some(*packet_number),
+            _ => None,
+        };
+        // Replies to the header carry packet number 0 by convention, but any is accepted.
+        if current_packet.is_some_and(|current| current != packet_number) && kind != HandshakeKind::Cancel {
+            return Ok(None);
+        }
+        match kind {
+            HandshakeKind::Ack => self.next_packet(now),
+            HandshakeKind::Nak => {
+                self.state = SenderState::AwaitingReply;
+                self.deadline = Some(now + self.packet_timeout);
+                Ok(Some(match &self.packet {
+                    Some(packet) => packet.clone(),
+                    None => SdsMessage {
+                        device_id: self.device_id,
+                        body: SdsBody::DumpHeader(self.header),
+                    },
+                }))
+            }
+            HandshakeKind::Wait => {
+                self.state = SenderState::Paused;
+                self.deadline = None;
+                Ok(None)
+            }
+            HandshakeKind::Cancel => {
+                self.state = SenderState::Cancelled;
+                self.deadline = None;
+                Ok(None)
+            }
+            HandshakeKind::EndOfFile => Ok(None),
+        }
+    }
+
+    /// Continues without a reply once the timeout has passed, returning the next message to send.
+    pub fn poll(&mut self, now: Duration) -> Result<Option<SdsMessage>> {
+        match self.deadline {
+            Some(deadline) if self.state == SenderState::AwaitingReply && now >= deadline => self.next_packet(now),
+            _ => Ok(None),
+        }
+    }
+
+    /// Cancels the dump, returning the Cancel message to send.
+    pub fn cancel(&mut self) -> SdsMessage {
+        self.state = SenderState::Cancelled;
+        self.deadline = None;
+        let packet_number = self.words_sent.div_ceil(self.header.words_per_packet() as u32).saturating_sub(1) as u8 & 0x7F;
+        SdsMessage::handshake(self.device_id, HandshakeKind::Cancel, packet_number)
+    }
+
+    fn next_packet(&mut self, now: Duration) -> Result<Option<SdsMessage>> {
+        if self.words_sent >= self.header.length {
+            self.state = SenderState::Finished;
+            self.deadline = None;
+            self.packet = None;
+            return Ok(None);
+        }
+        let count = (self.header.length - self.words_sent).min(self.header.words_per_packet() as u32);
+        let samples: Vec<i32> = self.samples.by_ref().take(count as usize).collect();
+        let packet_number = (self.words_sent / self.header.words_per_packet() as u32) as u8 & 0x7F;
+        let packet = SdsMessage {
+            device_id: self.device_id,
+            body: SdsBody::DataPacket {
+                packet_number,
+                data: Box::new(self.header.encode_words(&samples)?),
+            },
+        };
+        self.words_sent += count;
+        self.packet = Some(packet.clone());
+        self.state = SenderState::AwaitingReply;
+        self.deadline = Some(now + self.packet_timeout);
+        Ok(Some(packet))
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum ReceiverEvent {
+    /// A dump started.
+    Header(DumpHeader),
+    /// The samples of the next packet, without padding past the end of the sample.
+    Samples {
+        samples: Vec<i32>,
+        /// Whether these are the last samples of the dump.
+        complete: bool,
+    },
+    LoopPoints {
+        sample_number: u16,
+        loop_number: u16,
+        loop_type: LoopType,
+        start: u32,
+        end: u32,
+    },
+    Cancelled,
+}
+
+/// Receives a sample, handing on its samples packet by packet.
+pub struct SampleReceiver {
+    device_id: u8,
+    header: Option<DumpHeader>,
+    expected_packet: u8,
+    words_received: u32,
+}
+
+impl SampleReceiver {
+    pub fn new(device_id: u8) -> SampleReceiver {
+        SampleReceiver {
+            device_id,
+            header: None,
+            expected_packet: 0,
+            words_received: 0,
+        }
+    }
+
+    /// The Dump Request to send for a sample.
+    pub fn request(&self, sample_number: u16) -> SdsMessage {
+        SdsMessage {
+            device_id: self.device_id,
+            body: SdsBody::DumpRequest { sample_number },
+        }
+    }
+
+    /// The header of the dump in progress.
+    pub fn header(&self) -> Option<&DumpHeader> {
+        self.header.as_ref()
+    }
+
+    pub fn words_received(&self) -> u32 {
+        self.words_received
+    }
+
+    /// Handles SysEx data, as returned by [`crate::parser::Parser::parse`],
+    /// pushing any reply to `out`.
+    ///
+    /// Data Packets failing their checksum are answered with a NAK rather than an error.
+    pub fn handle_sysex(&mut self, data: &[u8], out: &mut Vec<SdsMessage>) -> Result<Option<ReceiverEvent>> {
+        if !SdsMessage::is_sds(data) || data[1] != self.device_id {
+            return Ok(None);
+        }
+        let message = match SdsMessage::parse(data) {
+            Ok(message) => message,
+            Err(error) => match data.get(3) {
+                Some(packet_number) if data[2] == non_realtime::SAMPLE_DATA_PACKET && self.header.is_some() => {
+                    log::warn!("{}", error);
+                    out.push(SdsMessage::handshake(self.device_id, HandshakeKind::Nak, *packet_number));
+                    return Ok(None);
+                }
+                _ => return Err(error),
+            },
+        };
+        self.handle(&message, out)
+    }
+
+    /// Handles a message from the sender, pushing any reply to `out`.
+    pub fn handle(&mut self, message: &SdsMessage, out: &mut Vec<SdsMessage>) -> Result<Option<ReceiverEvent>> {
+        if message.device_id != self.device_id {
+            return Ok(None);
+        }
+        match &message.body {
+            SdsBody::DumpHeader(header) => {
+                self.header = Some(*header);
+                self.expected_packet = 0;
+                self.words_received = 0;
+                out.push(SdsMessage::handshake(self.device_id, HandshakeKind::Ack, 0));
+                Ok(Some(ReceiverEvent::Header(*header)))
+            }
+            SdsBody::DataPacket { packet_number, data } => {
+                let Some(header) = self.header else {
+                    return Ok(None);
+                };
+                if *packet_number != self.expected_packet {
+                    // A resent packet whose ACK was lost is acknowledged again.
+                    let kind = if *packet_number == self.expected_packet.wrapping_sub(1) & 0x7F {
+                        HandshakeKind::Ack
+                    } else {
+                        HandshakeKind::Nak
+                    };
+                    let reply_number = if kind == HandshakeKind::Ack { *packet_number } else { self.expected_packet };
+                    out.push(SdsMessage::handshake(self.device_id, kind, reply_number));
+                    return Ok(None);
+                }
+                let mut samples = header.decode_words(data);
+                samples.truncate((header.length - self.words_received) as usize);
+                self.words_received += samples.len() as u32;
+                self.expected_packet = (self.expected_packet + 1) & 0x7F;
+                out.push(SdsMessage::handshake(self.device_id, HandshakeKind::Ack, *packet_number));
+                let complete = self.words_received >= header.length;
+                if complete {
+                    self.header = None;
+                }
+                Ok(Some(ReceiverEvent::Samples { samples, complete }))
+            }
+            SdsBody::LoopPoints { sample_number, loop_number, loop_type, start, end } => Ok(Some(ReceiverEvent::LoopPoints {
+                sample_number: *sample_number,
+                loop_number: *loop_number,
+                loop_type: *loop_type,
+                start: *start,
+                end: *end,
+            })),
+            SdsBody::Handshake { kind: HandshakeKind::Cancel, .. } if self.header.is_some() => {
+                self.header = None;
+                Ok(Some(ReceiverEvent::Cancelled))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Runs the handshake over a byte stream, such as a MIDI port.
+///
+/// Outgoing messages are written to the sink as complete SysEx.
+/// Incoming bytes may arrive in pieces of any size; SysEx messages are framed with a [`Parser`],
+/// and bytes outside them, such as MIDI Time Code, are skipped.
+pub struct SdsPort<W> {
+    sink: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> SdsPort<W> {
+    pub fn new(sink: W) -> SdsPort<W> {
+        SdsPort {
+            sink,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn sink(&self) -> &W {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut W {
+        &mut self.sink
+    }
+
+    pub fn send(&mut self, message: &SdsMessage) -> Result<()> {
+        self.sink.write_all(&message.to_sysex_bytes()?)?;
+        self.sink.flush()?;
+        Ok(())
+    }
+
+    /// Reads bytes from the port, returning the data of each SysEx message they complete,
+    /// without `F0` and `F7`.
+    pub fn read_sysex(&mut self, bytes: &[u8]) -> Result<Vec<Vec<u8>>> {
+        self.pending.extend_from_slice(bytes);
+        let mut messages = vec![];
+        loop {
+            let start = self.pending.iter().position(|byte| *byte == 0xF0).unwrap_or(self.pending.len());
+            self.pending.drain(..start);
+            if self.pending.is_empty() {
+                break;
+            }
+            let outcome = Parser::new().parse(&self.pending)?;
+            let consumed = outcome.bytes_consumed;
+            match outcome.status {
+                MessageParseOutcomeStatus::NeedMoreBytes(_) => break,
+                MessageParseOutcomeStatus::SystemExclusiveMessage(data) => messages.push(data.to_vec()),
+                MessageParseOutcomeStatus::InterruptingSystemRealTimeMessage { byte_index, .. } => {
+                    self.pending.remove(byte_index);
+                    continue;
+                }
+                status => log::warn!("skipping {:?} in SDS stream", status),
+            }
+            self.pending.drain(..consumed.max(1));
+        }
+        Ok(messages)
+    }
+
+    /// Passes bytes from the port to a sender, writing the messages it sends next.
+    pub fn feed_sender<S: Iterator<Item = i32>>(&mut self, sender: &mut SampleSender<S>, bytes: &[u8], now: Duration) -> Result<()> {
+        for data in self.read_sysex(bytes)? {
+            if !SdsMessage::is_sds(&data) {
+                continue;
+            }
+            if let Some(message) = sender.handle(&SdsMessage::parse(&data)?, now)? {
+                self.send(&message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes bytes from the port to a receiver, writing its replies and returning its events.
+    pub fn feed_receiver(&mut self, receiver: &mut SampleReceiver, bytes: &[u8]) -> Result<Vec<ReceiverEvent>> {
+        let mut events = vec![];
+        let mut replies = vec![];
+        for data in self.read_sysex(bytes)? {
+            events.extend(receiver.handle_sysex(&data, &mut replies)?);
+            for reply in replies.drain(..) {
+                self.send(&reply)?;
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// Writes samples as a mono PCM WAV file as they arrive.
+///
+/// The header is written first, sized from the dump's length,
+/// so the sink need not be seekable.
+pub struct WavWriter<W> {
+    sink: W,
+    bits: u8,
+    container_bytes: usize,
+    data_len: u32,
+    written: u32,
+}
+
+impl<W: Write> WavWriter<W> {
+    pub fn new(mut sink: W, header: &DumpHeader) -> Result<WavWriter<W>> {
+        header.validate()?;
+        let container_bytes = (header.bits as usize).div_ceil(8);
+        let data_len = header.length.checked_mul(container_bytes as u32)
+            .filter(|len| *len <= u32::MAX - 37)
+            .ok_or_else(|| anyhow!("sample too long for WAV"))?;
+        let sample_rate = header.sample_rate().round() as u32;
+        let block_align = container_bytes as u16;
+        let mut buf = Vec::with_capacity(44);
+        buf.extend(b"RIFF");
+        buf.extend((36 + data_len + data_len % 2).to_le_bytes());
+        buf.extend(b"WAVEfmt ");
+        buf.extend(16u32.to_le_bytes());
+        buf.extend(1u16.to_le_bytes());
+        buf.extend(1u16.to_le_bytes());
+        buf.extend(sample_rate.to_le_bytes());
+        buf.extend((sample_rate * block_align as u32).to_le_bytes());
+        buf.extend(block_align.to_le_bytes());
+        buf.extend((block_align * 8).to_le_bytes());
+        buf.extend(b"data");
+        buf.extend(data_len.to_le_bytes());
+        sink.write_all(&buf)?;
+        Ok(WavWriter {
+            sink,
+            bits: header.bits,
+            container_bytes,
+            data_len,
+            written: 0,
+        })
+    }
+
+    /// Writes samples of the dump's format, left-justified in whole bytes.
+    pub fn write_samples(&mut self, samples: &[i32]) -> Result<()> {
+        let shift = self.container_bytes as u32 * 8 - self.bits as u32;
+        let mut buf = Vec::with_capacity(samples.len() * self.container_bytes);
+        for sample in samples {
+            let value = sample << shift;
+            if self.container_bytes == 1 {
+                // 8-bit WAV is unsigned.
+                buf.push((value + 128) as u8);
+            } else {
+                buf.extend(&value.to_le_bytes()[..self.container_bytes]);
+            }
+        }
+        self.written = self.written.checked_add(buf.len() as u32)
+            .filter(|written| *written <= self.data_len)
+            .ok_or_else(|| anyhow!("more samples than the WAV header declares"))?;
+        self.sink.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Pads the data chunk and returns the sink, failing if samples are missing.
+    pub fn finish(mut self) -> Result<W> {
+        if self.written != self.data_len {
+            bail!("WAV incomplete: {} of {} bytes written", self.written, self.data_len);
+        }
+        if self.data_len % 2 == 1 {
+            self.sink.write_all(&[0])?;
+        }
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE_ID: u8 = 0x05;
+
+    /// The samples and replies of a dump from a sender to a receiver through SysEx bytes.
+    struct Transfer {
+        samples: Vec<i32>,
+        replies: Vec<(HandshakeKind, u8)>,
+        packets_sent: Vec<SdsMessage>,
+        state: SenderState,
+    }
+
+    /// Runs a dump, letting `corrupt` alter each message's SysEx data in transit.
+    fn transfer(header: DumpHeader, samples: &[i32], mut corrupt: impl FnMut(usize, &mut Vec<u8>)) -> Transfer {
+        let mut sender = SampleSender::new(DEVICE_ID, header, samples.iter().copied()).unwrap();
+        let mut receiver = SampleReceiver::new(DEVICE_ID);
+        let mut result = Transfer {
+            samples: vec![],
+            replies: vec![],
+            packets_sent: vec![],
+            state: SenderState::AwaitingReply,
+        };
+        let mut now = Duration::ZERO;
+        let mut next = Some(sender.start(now));
+        let mut sent = 0;
+        while let Some(message) = next.take() {
+            if matches!(message.body, SdsBody::DataPacket { .. }) {
+                result.packets_sent.push(message.clone());
+            }
+            let bytes = message.to_sysex_bytes().unwrap();
+            let mut data = bytes[1..bytes.len() - 1].to_vec();
+            corrupt(sent, &mut data);
+            sent += 1;
+
+            let mut replies = vec![];
+            if let Some(ReceiverEvent::Samples { samples, .. }) = receiver.handle_sysex(&data, &mut replies).unwrap() {
+                result.samples.extend(samples);
+            }
+            now += Duration::from_millis(1);
+            for reply in replies {
+                let bytes = reply.to_sysex_bytes().unwrap();
+                let reply = SdsMessage::parse(&bytes[1..bytes.len() - 1]).unwrap();
+                if let SdsBody::Handshake { kind, packet_number } = reply.body {
+                    result.replies.push((kind, packet_number));
+                }
+                next = sender.handle(&reply, now).unwrap();
+            }
+        }
+        result.state = sender.state();
+        result
+    }
+
+    fn ramp(len: usize, bits: u8) -> Vec<i32> {
+        let max = 1i64 << (bits - 1);
+        (0..len).map(|index| ((index as i64 * 7919) % (2 * max) - max) as i32).collect()
+    }
+
+    #[test]
+    fn acknowledged_transfer() {
+        let header = DumpHeader::new(1, 16, 44100.0, 100).unwrap();
+        let samples = ramp(100, 16);
+        let result = transfer(header, &samples, |_, _| {});
+        assert_eq!(result.samples, samples);
+        assert_eq!(result.state, SenderState::Finished);
+        assert_eq!(result.packets_sent.len(), 3);
+        assert_eq!(result.replies, [
+            (HandshakeKind::Ack, 0),
+            (HandshakeKind::Ack, 0),
+            (HandshakeKind::Ack, 1),
+            (HandshakeKind::Ack, 2),
+        ]);
+    }
+
+    #[test]
+    fn checksum_failure_is_naked_and_resent() {
+        let header = DumpHeader::new(1, 12, 22050.0, 150).unwrap();
+        let samples = ramp(150, 12);
+        // The third message is the second Data Packet; flip a bit of its sample data.
+        let result = transfer(header, &samples, |index, data| {
+            if index == 2 {
+                data[10] ^= 0x01;
+            }
+        });
+        assert_eq!(result.samples, samples);
+        assert_eq!(result.state, SenderState::Finished);
+        assert_eq!(result.replies, [
+            (HandshakeKind::Ack, 0),
+            (HandshakeKind::Ack, 0),
+            (HandshakeKind::Nak, 1),
+            (HandshakeKind::Ack, 1),
+            (HandshakeKind::Ack, 2),
+        ]);
+        assert_eq!(result.packets_sent[1], result.packets_sent[2]);
+    }
+
+    #[test]
+    fn corrupt_packet_fails_checksum() {
+        let header = DumpHeader::new(1, 8, 8000.0, 10).unwrap();
+        let packet = SdsMessage {
+            device_id: DEVICE_ID,
+            body: SdsBody::DataPacket {
+                packet_number: 3,
+                data: Box::new(header.encode_words(&ramp(10, 8)).unwrap()),
+            },
+        };
+        let bytes = packet.to_sysex_bytes().unwrap();
+        let mut data = bytes[1..bytes.len() - 1].to_vec();
+        assert_eq!(SdsMessage::parse(&data).unwrap(), packet);
+        data[20] ^= 0x40;
+        assert!(SdsMessage::parse(&data).is_err());
+
+        let mut receiver = SampleReceiver::new(DEVICE_ID);
+        let mut replies = vec![];
+        receiver.handle(&SdsMessage { device_id: DEVICE_ID, body: SdsBody::DumpHeader(header) }, &mut replies).unwrap();
+        replies.clear();
+        assert_eq!(receiver.handle_sysex(&data, &mut replies).unwrap(), None);
+        assert_eq!(replies, [SdsMessage::handshake(DEVICE_ID, HandshakeKind::Nak, 3)]);
+        assert_eq!(receiver.words_received(), 0);
+    }
+
+    /// Bytes as a busy port would deliver them: with MIDI Time Code and clock mixed in, in small pieces.
+    fn deliver(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut stream = vec![0xF1, 0x20, 0x90, 0x3C, 0x7F];
+        for (index, byte) in bytes.iter().enumerate() {
+            if index % 50 == 7 {
+                stream.push(0xF8);
+            }
+            stream.push(*byte);
+        }
+        stream.chunks(5).map(<[u8]>::to_vec).collect()
+    }
+
+    #[test]
+    fn handshake_over_byte_stream() {
+        let header = DumpHeader::new(2, 16, 32000.0, 130).unwrap();
+        let samples = ramp(130, 16);
+        let mut sender = SampleSender::new(DEVICE_ID, header, samples.iter().copied()).unwrap();
+        let mut receiver = SampleReceiver::new(DEVICE_ID);
+        let mut sender_port = SdsPort::new(Vec::new());
+        let mut receiver_port = SdsPort::new(Vec::new());
+
+        let mut received = vec![];
+        let mut now = Duration::ZERO;
+        sender_port.send(&sender.start(now)).unwrap();
+        while !sender_port.sink().is_empty() {
+            for piece in deliver(&std::mem::take(sender_port.sink_mut())) {
+                for event in receiver_port.feed_receiver(&mut receiver, &piece).unwrap() {
+                    if let ReceiverEvent::Samples { samples, .. } = event {
+                        received.extend(samples);
+                    }
+                }
+            }
+            now += Duration::from_millis(1);
+            for piece in deliver(&std::mem::take(receiver_port.sink_mut())) {
+                sender_port.feed_sender(&mut sender, &piece, now).unwrap();
+            }
+        }
+        assert_eq!(received, samples);
+        assert_eq!(sender.state(), SenderState::Finished);
+    }
+
+    #[test]
+    fn sample_rate_must_fit_period() {
+        assert!(DumpHeader::new(0, 16, 0.0, 10).is_err());
+        assert!(DumpHeader::new(0, 16, -44100.0, 10).is_err());
+        assert!(DumpHeader::new(0, 16, f64::NAN, 10).is_err());
+        assert!(DumpHeader::new(0, 16, 100.0, 10).is_err());
+        assert_eq!(DumpHeader::new(0, 16, 500.0, 10).unwrap().period_ns, 2_000_000);
+    }
+}
@@ -0,0 +1,356 @@
+//! Playback of timestamped messages, driven by a caller-supplied clock.
+//!
+//! The [`Sequencer`] does no I/O and reads no clock of its own:
+//! the caller passes the current time to each method
+//! and sends the returned messages wherever they need to go.
+
+use anyhow::{Result, bail};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+use crate::assert_from::AssertFrom;
+use crate::control_number::ControlNumber;
+use crate::message::*;
+use crate::smf::{Smf, Format, TrackEventKind};
+use crate::tempo_map::TempoMap;
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct TimedMessage {
+    pub tick: u64,
+    pub message: Message,
+}
+
+/// An in-memory song.
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct Song {
+    /// Tracks of messages, each in time order.
+    pub tracks: Vec<Vec<TimedMessage>>,
+    pub tempo_map: TempoMap,
+}
+
+impl Song {
+    /// Takes the channel messages of a format 0 or 1 file.
+    ///
+    /// Meta and SysEx events are not played.
+    pub fn from_smf(smf: &Smf) -> Result<Song> {
+        if smf.format == Format::MultiSequence {
+            bail!("can't play the independent sequences of a format 2 file as one song");
+        }
+        let tracks = smf.tracks.iter().map(|track| {
+            track.events.iter().filter_map(|event| match &event.kind {
+                TrackEventKind::Midi(message @ Message::Channel(_)) => Some(TimedMessage {
+                    tick: event.tick,
+                    message: message.clone(),
+                }),
+                _ => None,
+            }).collect()
+        }).collect();
+        Ok(Song {
+            tracks,
+            tempo_map: smf.tempo_map()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+enum Transport {
+    Stopped,
+    /// Playing since `clock`, when the song was at `seconds`.
+    Playing {
+        clock: Duration,
+        seconds: f64,
+    },
+}
+
+pub struct Sequencer {
+    /// All tracks merged in time order.
+    events: Vec<TimedMessage>,
+    tempo_map: TempoMap,
+    transport: Transport,
+    /// Song position in seconds, while stopped.
+    seconds: f64,
+    /// Index of the next event to play.
+    next_event: usize,
+    loop_region: Option<(u64, u64)>,
+    tempo_scale: f64,
+    /// Messages queued by transport changes, returned by the next [`Sequencer::poll`].
+    pending: Vec<Message>,
+    sounding_notes: BTreeSet<(MidiChannelId, cvm::NoteNumber)>,
+}
+
+impl Sequencer {
+    /// Creates a stopped sequencer positioned at the start of the song.
+    pub fn new(song: Song) -> Sequencer {
+        let mut events: Vec<(usize, usize, TimedMessage)> = vec![];
+        for (track_index, track) in song.tracks.into_iter().enumerate() {
+            for (event_index, event) in track.into_iter().enumerate() {
+                events.push((track_index, event_index, event));
+            }
+        }
+        // Stable across tracks for simultaneous events.
+        events.sort_by_key(|(track_index, event_index, event)| (event.tick, *track_index, *event_index));
+
+        Sequencer {
+            events: events.into_iter().map(|(_, _, event)| event).collect(),
+            tempo_map: song.tempo_map,
+            transport: Transport::Stopped,
+            seconds: 0.0,
+            next_event: 0,
+            loop_region: None,
+            tempo_scale: 1.0,
+            pending: vec![],
+            sounding_notes: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.transport, Transport::Playing { .. })
+    }
+
+    /// Starts playing from the current position.
+    pub fn play(&mut self, now: Duration) {
+        if !self.is_playing() {
+            self.transport = Transport::Playing {
+                clock: now,
+                seconds: self.seconds,
+            };
+        }
+    }
+
+    /// Stops playing, queueing Note Offs for any sounding notes.
+    ///
+    /// Events due before `now` that haven't been polled are not played.
+    pub fn stop(&mut self, now: Duration) {
+        if self.is_playing() {
+            self.seconds = self.song_seconds_at(now);
+            self.transport = Transport::Stopped;
+            self.release_notes();
+        }
+    }
+
+    /// The song position at `now`, in ticks.
+    pub fn position(&self, now: Duration) -> f64 {
+        self.tempo_map.seconds_to_ticks(self.song_seconds_at(now))
+    }
+
+    /// Moves to a tick, chasing controller and program state.
+    ///
+    /// Sounding notes are released, and the last Bank Select, Program Change,
+    /// Control Change, Pitch Bend and Channel Pressure of each channel before `tick`
+    /// are queued so the receiver is in the state it would be had the song played to here.
+    /// Data Entry and parameter number controllers are not chased,
+    /// as replaying their last values out of order would corrupt RPNs.
+    pub fn locate(&mut self, tick: u64, now: Duration) {
+        self.release_notes();
+        self.chase(tick);
+        self.next_event = self.events.partition_point(|event| event.tick < tick);
+        let seconds = self.tempo_map.ticks_to_seconds(tick);
+        self.seconds = seconds;
+        if self.is_playing() {
+            self.transport = Transport::Playing {
+                clock: now,
+                seconds,
+            };
+        }
+    }
+
+    /// Loops between two ticks, or stops looping.
+    ///
+    /// Playback reaching `end` jumps to `start`, chasing as [`Sequencer::locate`] does.
+    /// Events at `end` are not played.
+    pub fn set_loop(&mut self, region: Option<(u64, u64)>) -> Result<()> {
+        if let Some((start, end)) = region {
+            if end <= start {
+                bail!("empty loop region {}..{}", start, end);
+            }
+        }
+        self.loop_region = region;
+        Ok(())
+    }
+
+    /// Sets the playback speed relative to the song's tempo, e.g. 2.0 for double speed.
+    pub fn set_tempo_scale(&mut self, scale: f64, now: Duration) -> Result<()> {
+        if !(scale > 0.0 && scale.is_finite()) {
+            bail!("invalid tempo scale {}", scale);
+        }
+        if self.is_playing() {
+            self.transport = Transport::Playing {
+                clock: now,
+                seconds: self.song_seconds_at(now),
+            };
+        }
+        self.tempo_scale = scale;
+        Ok(())
+    }
+
+    /// Appends the messages due for output by `now`.
+    ///
+    /// Messages queued by transport changes come first.
+    pub fn poll(&mut self, now: Duration, out: &mut Vec<Message>) {
+        out.append(&mut self.pending);
+
+        let (clock, mut seconds) = match self.transport {
+            Transport::Stopped => return,
+            Transport::Playing { clock, seconds } => (clock, seconds),
+        };
+        let mut elapsed = now.saturating_sub(clock).as_secs_f64() * self.tempo_scale;
+
+        loop {
+            let start_seconds = seconds;
+            let target_seconds = seconds + elapsed;
+            let loop_end = self.loop_region.and_then(|(start, end)| {
+                let end_seconds = self.tempo_map.ticks_to_seconds(end);
+                if start_seconds < end_seconds && target_seconds >= end_seconds {
+                    Some((start, end, end_seconds))
+                } else {
+                    None
+                }
+            });
+
+            match loop_end {
+                None => {
+                    let target_tick = self.tempo_map.seconds_to_ticks(target_seconds);
+                    // Allow for rounding in the seconds to ticks conversion.
+                    self.play_events_through(|tick| tick as f64 <= target_tick + 1e-6, out);
+                    self.seconds = target_seconds;
+                    break;
+                }
+                Some((loop_start, loop_end, loop_end_seconds)) => {
+                    self.play_events_through(|tick| tick < loop_end, out);
+                    elapsed = target_seconds - loop_end_seconds;
+                    self.release_notes();
+                    self.chase(loop_start);
+                    out.append(&mut self.pending);
+                    self.next_event = self.events.partition_point(|event| event.tick < loop_start);
+                    seconds = self.tempo_map.ticks_to_seconds(loop_start);
+                }
+            }
+        }
+
+        self.transport = Transport::Playing {
+            clock: now,
+            seconds: self.seconds,
+        };
+    }
+
+    fn song_seconds_at(&self, now: Duration) -> f64 {
+        match self.transport {
+            Transport::Stopped => self.seconds,
+            Transport::Playing { clock, seconds } => {
+                seconds + now.saturating_sub(clock).as_secs_f64() * self.tempo_scale
+            }
+        }
+    }
+
+    fn play_events_through(&mut self, is_due: impl Fn(u64) -> bool, out: &mut Vec<Message>) {
+        while let Some(event) = self.events.get(self.next_event) {
+            if !is_due(event.tick) {
+                break;
+            }
+            if let Message::Channel(ChannelMessage {
+                channel,
+                message: ChannelMessageType::ChannelVoice(message),
+            }) = &event.message {
+                if let Some((note_number, _)) = message.should_note_on() {
+                    self.sounding_notes.insert((*channel, note_number));
+                } else if let Some((note_number, _)) = message.should_note_off() {
+                    self.sounding_notes.remove(&(*channel, note_number));
+                }
+            }
+            out.push(event.message.clone());
+            self.next_event += 1;
+        }
+    }
+
+    /// Queues Note Offs for every sounding note.
+    fn release_notes(&mut self) {
+        for (channel, note_number) in std::mem::take(&mut self.sounding_notes) {
+            self.pending.push(Message::Channel(ChannelMessage {
+                channel,
+                message: ChannelMessageType::ChannelVoice(ChannelVoiceMessage::NoteOff(cvm::NoteOff {
+                    note_number,
+                    velocity: cvm::KeyVelocity(cvm::Unsigned7::assert_from(0)),
+                })),
+            }));
+        }
+    }
+
+    /// Queues the channel state in effect just before `tick`.
+    fn chase(&mut self, tick: u64) {
+        #[derive(Default)]
+        struct ChannelState {
+            bank: BTreeMap<u8, cvm::Unsigned7>,
+            program: Option<cvm::ProgramChange>,
+            controllers: BTreeMap<u8, cvm::Unsigned7>,
+            pitch_bend: Option<cvm::PitchBendChange>,
+            pressure: Option<cvm::ChannelPressureAftertouch>,
+        }
+
+        let mut channels: BTreeMap<MidiChannelId, ChannelState> = BTreeMap::new();
+        for event in self.events.iter().take_while(|event| event.tick < tick) {
+            let (channel, message) = match &event.message {
+                Message::Channel(ChannelMessage {
+                    channel,
+                    message: ChannelMessageType::ChannelVoice(message),
+                }) => (*channel, message),
+                _ => continue,
+            };
+            let state = channels.entry(channel).or_default();
+            match message {
+                ChannelVoiceMessage::ControlChange(cc) => {
+                    let number = u8::from(cc.control_number.0);
+                    match ControlNumber::try_from(number) {
+                        Ok(ControlNumber::BankSelect | ControlNumber::BankSelectLSB) => {
+                            state.bank.insert(number, cc.value);
+                        }
+                        Ok(
+                            ControlNumber::DataEntryMSB
+                            | ControlNumber::DataEntryLSB
+                            | ControlNumber::DataIncrement
+                            | ControlNumber::DataDecrement
+                            | ControlNumber::NonRegisteredParameterNumberLSB
+                            | ControlNumber::NonRegisteredParameterNumberMSB
+                            | ControlNumber::RegisteredParameterNumberLSB
+                            | ControlNumber::RegisteredParameterNumberMSB
+                        ) => { }
+                        _ => {
+                            state.controllers.insert(number, cc.value);
+                        }
+                    }
+                }
+                ChannelVoiceMessage::ProgramChange(program) => state.program = Some(program.clone()),
+                ChannelVoiceMessage::PitchBendChange(bend) => state.pitch_bend = Some(bend.clone()),
+                ChannelVoiceMessage::ChannelPressureAftertouch(pressure) => state.pressure = Some(pressure.clone()),
+                _ => { }
+            }
+        }
+
+        for (channel, state) in channels {
+            let voice = |message| Message::Channel(ChannelMessage {
+                channel,
+                message: ChannelMessageType::ChannelVoice(message),
+            });
+            let control_change = |number: u8, value| voice(ChannelVoiceMessage::ControlChange(cvm::ControlChange {
+                control_number: cvm::ControlNumber(cvm::Unsigned7::assert_from(number)),
+                value,
+            }));
+            for (number, value) in state.bank {
+                self.pending.push(control_change(number, value));
+            }
+            if let Some(program) = state.program {
+                self.pending.push(voice(ChannelVoiceMessage::ProgramChange(program)));
+            }
+            for (number, value) in state.controllers {
+                self.pending.push(control_change(number, value));
+            }
+            if let Some(bend) = state.pitch_bend {
+                self.pending.push(voice(ChannelVoiceMessage::PitchBendChange(bend)));
+            }
+            if let Some(pressure) = state.pressure {
+                self.pending.push(voice(ChannelVoiceMessage::ChannelPressureAftertouch(pressure)));
+            }
+        }
+    }
+}
@@ -0,0 +1,641 @@
+//! Standard MIDI File reading and writing.
+//!
+//! Track events are stored with absolute tick positions
+//! rather than the delta times used in the file,
+//! which makes merging and splitting tracks straightforward.
+//!
+//! Reference: Standard MIDI Files 1.0
+
+use anyhow::{Result, anyhow, bail};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use crate::message::*;
+use crate::parser::{Parser, MessageParseOutcomeStatus};
+use crate::tempo_map::{Division, TempoMap, TempoChange, TimeSignatureChange};
+
+pub const HEADER_CHUNK_TYPE: [u8; 4] = *b"MThd";
+pub const TRACK_CHUNK_TYPE: [u8; 4] = *b"MTrk";
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u16)]
+pub enum Format {
+    /// A single multi-channel track.
+    SingleTrack = 0,
+    /// Simultaneous tracks of one sequence.
+    MultiTrack = 1,
+    /// Sequentially independent single-track patterns.
+    MultiSequence = 2,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct Smf {
+    pub format: Format,
+    pub division: Division,
+    pub tracks: Vec<Track>,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Track {
+    /// Events in time order.
+    pub events: Vec<TrackEvent>,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct TrackEvent {
+    /// Absolute position in ticks from the start of the track.
+    pub tick: u64,
+    pub kind: TrackEventKind,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum TrackEventKind {
+    Midi(Message),
+    /// An `F0` event.
+    ///
+    /// The data follows the `F0` status and includes the terminating `F7`
+    /// unless the message continues in following escape events.
+    SystemExclusive(Vec<u8>),
+    /// An `F7` event, for SysEx continuation packets
+    /// or arbitrary bytes such as real time and system common messages.
+    Escape(Vec<u8>),
+    Meta(MetaEvent),
+}
+
+/// Reference: Standard MIDI Files 1.0, "Meta-Events"
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum MetaEvent {
+    SequenceNumber(u16),
+    /// Text of unspecified encoding.
+    Text {
+        kind: TextKind,
+        text: Vec<u8>,
+    },
+    ChannelPrefix(MidiChannelId),
+    Port(u8),
+    EndOfTrack,
+    SetTempo {
+        microseconds_per_quarter: u32,
+    },
+    SmpteOffset {
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        frames: u8,
+        fractional_frames: u8,
+    },
+    TimeSignature {
+        numerator: u8,
+        /// The denominator as a power of two, e.g. 3 for eighth notes.
+        denominator_power: u8,
+        clocks_per_metronome_click: u8,
+        thirty_seconds_per_quarter: u8,
+    },
+    KeySignature {
+        /// Negative for flats, positive for sharps.
+        sharps: i8,
+        minor: bool,
+    },
+    SequencerSpecific(Vec<u8>),
+    Unknown {
+        meta_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum TextKind {
+    Text = 0x01,
+    Copyright = 0x02,
+    TrackName = 0x03,
+    InstrumentName = 0x04,
+    Lyric = 0x05,
+    Marker = 0x06,
+    CuePoint = 0x07,
+    ProgramName = 0x08,
+    DeviceName = 0x09,
+}
+
+mod meta_types {
+    pub const SEQUENCE_NUMBER: u8 = 0x00;
+    pub const CHANNEL_PREFIX: u8 = 0x20;
+    pub const PORT: u8 = 0x21;
+    pub const END_OF_TRACK: u8 = 0x2F;
+    pub const SET_TEMPO: u8 = 0x51;
+    pub const SMPTE_OFFSET: u8 = 0x54;
+    pub const TIME_SIGNATURE: u8 = 0x58;
+    pub const KEY_SIGNATURE: u8 = 0x59;
+    pub const SEQUENCER_SPECIFIC: u8 = 0x7F;
+}
+
+const SYSEX_EVENT: u8 = 0xF0;
+const ESCAPE_EVENT: u8 = 0xF7;
+const META_EVENT: u8 = 0xFF;
+
+/// A chunk of a RIFF-style chunked file.
+pub struct Chunk<'buf> {
+    pub chunk_type: [u8; 4],
+    pub data: &'buf [u8],
+}
+
+/// Splits an SMF byte stream into its chunks.
+///
+/// Fails if the last chunk is truncated.
+pub fn read_chunks(mut bytes: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    let mut chunks = vec![];
+    while !bytes.is_empty() {
+        if bytes.len() < 8 {
+            bail!("truncated chunk header");
+        }
+        let chunk_type = <[u8; 4]>::try_from(&bytes[0..4]).expect("4 bytes");
+        let len = u32::from_be_bytes(bytes[4..8].try_into().expect("4 bytes")) as usize;
+        let data = bytes.get(8..8 + len).ok_or_else(|| {
+            anyhow!("truncated {} chunk", String::from_utf8_lossy(&chunk_type))
+        })?;
+        chunks.push(Chunk { chunk_type, data });
+        bytes = &bytes[8 + len..];
+    }
+    Ok(chunks)
+}
+
+pub fn write_chunk(chunk_type: [u8; 4], data: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| anyhow!("chunk too long"))?;
+    buf.extend_from_slice(&chunk_type);
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+impl Smf {
+    /// Reads an SMF.
+    ///
+    /// Chunks of unknown type are ignored.
+    pub fn read(bytes: &[u8]) -> Result<Smf> {
+        let chunks = read_chunks(bytes)?;
+        let header = chunks.first().ok_or_else(|| anyhow!("empty file"))?;
+        if header.chunk_type != HEADER_CHUNK_TYPE {
+            bail!("missing header chunk");
+        }
+        if header.data.len() < 6 {
+            bail!("header chunk too short");
+        }
+        let format = u16::from_be_bytes([header.data[0], header.data[1]]);
+        let format = Format::try_from(format).map_err(|_| anyhow!("unknown SMF format {}", format))?;
+        let num_tracks = u16::from_be_bytes([header.data[2], header.data[3]]) as usize;
+        let division = Division::from_header_word(u16::from_be_bytes([header.data[4], header.data[5]]))?;
+
+        let tracks = chunks[1..].iter()
+            .filter(|chunk| chunk.chunk_type == TRACK_CHUNK_TYPE)
+            .map(|chunk| Track::read(chunk.data))
+            .collect::<Result<Vec<_>>>()?;
+        if tracks.len() != num_tracks {
+            log::warn!("header declares {} tracks but file contains {}", num_tracks, tracks.len());
+        }
+
+        Ok(Smf {
+            format,
+            division,
+            tracks,
+        })
+    }
+
+    pub fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        if self.format == Format::SingleTrack && self.tracks.len() != 1 {
+            bail!("format 0 file with {} tracks", self.tracks.len());
+        }
+        let num_tracks = u16::try_from(self.tracks.len()).map_err(|_| anyhow!("too many tracks"))?;
+        let mut header = vec![];
+        header.extend_from_slice(&u16::from(self.format).to_be_bytes());
+        header.extend_from_slice(&num_tracks.to_be_bytes());
+        header.extend_from_slice(&self.division.to_header_word().to_be_bytes());
+        write_chunk(HEADER_CHUNK_TYPE, &header, buf)?;
+
+        for track in &self.tracks {
+            let mut data = vec![];
+            track.write(&mut data)?;
+            write_chunk(TRACK_CHUNK_TYPE, &data, buf)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.write(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Builds a tempo map from the tempo and time signature events of every track.
+    ///
+    /// For format 2 files, where each track has its own tempo map,
+    /// use [`Track::tempo_map`] instead.
+    pub fn tempo_map(&self) -> Result<TempoMap> {
+        let mut tempo_changes = vec![];
+        let mut time_signature_changes = vec![];
+        for track in &self.tracks {
+            track.collect_tempo_events(&mut tempo_changes, &mut time_signature_changes);
+        }
+        TempoMap::new(self.division, &tempo_changes, &time_signature_changes)
+    }
+}
+
+impl Track {
+    /// Reads the events of an `MTrk` chunk.
+    ///
+    /// Events after End of Track are ignored.
+    pub fn read(mut bytes: &[u8]) -> Result<Track> {
+        let mut events = vec![];
+        let mut tick = 0_u64;
+        let mut parser = Parser::new();
+
+        while !bytes.is_empty() {
+            let delta_time = read_variable_length_quantity(&mut bytes)?;
+            tick += delta_time as u64;
+            let status = *bytes.first().ok_or_else(|| anyhow!("truncated event"))?;
+            let kind = match status {
+                SYSEX_EVENT | ESCAPE_EVENT => {
+                    bytes = &bytes[1..];
+                    // Sysex and meta events cancel running status.
+                    parser = Parser::new();
+                    let data = read_length_prefixed(&mut bytes)?.to_vec();
+                    if status == SYSEX_EVENT {
+                        TrackEventKind::SystemExclusive(data)
+                    } else {
+                        TrackEventKind::Escape(data)
+                    }
+                }
+                META_EVENT => {
+                    let meta_type = *bytes.get(1).ok_or_else(|| anyhow!("truncated meta event"))?;
+                    bytes = &bytes[2..];
+                    parser = Parser::new();
+                    let data = read_length_prefixed(&mut bytes)?;
+                    TrackEventKind::Meta(MetaEvent::parse(meta_type, data)?)
+                }
+                0xF1..=0xFE => {
+                    bail!("unexpected status byte {:02X} in track", status);
+                }
+                _ => {
+                    let outcome = parser.parse(bytes)?;
+                    match outcome.status {
+                        MessageParseOutcomeStatus::Message(message) => {
+                            bytes = &bytes[outcome.bytes_consumed..];
+                            TrackEventKind::Midi(message)
+                        }
+                        MessageParseOutcomeStatus::NeedMoreBytes(_) => {
+                            bail!("truncated channel message");
+                        }
+                        MessageParseOutcomeStatus::UnexpectedDataByte => {
+                            bail!("data byte without running status");
+                        }
+                        _ => {
+                            bail!("malformed channel message");
+                        }
+                    }
+                }
+            };
+            let is_end = matches!(kind, TrackEventKind::Meta(MetaEvent::EndOfTrack));
+            events.push(TrackEvent { tick, kind });
+            if is_end {
+                break;
+            }
+        }
+
+        Ok(Track { events })
+    }
+
+    /// Writes the track's events, using running status for channel messages.
+    ///
+    /// Events must be in time order.
+    /// An End of Track event is appended if the track doesn't end with one,
+    /// and any End of Track event before the last event is dropped.
+    pub fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let mut tick = 0;
+        let mut running_status = None;
+        let mut message_bytes = vec![];
+
+        for event in &self.events {
+            if event.kind == TrackEventKind::Meta(MetaEvent::EndOfTrack) {
+                continue;
+            }
+            let delta_time = event.tick.checked_sub(tick)
+                .ok_or_else(|| anyhow!("track events out of order at tick {}", event.tick))?;
+            let delta_time = u32::try_from(delta_time).map_err(|_| anyhow!("delta time too large"))?;
+            tick = event.tick;
+            write_variable_length_quantity(delta_time, buf)?;
+            match &event.kind {
+                TrackEventKind::Midi(message) => {
+                    message_bytes.clear();
+                    message.encode(&mut message_bytes)?;
+                    let status = message_bytes[0];
+                    if status >= 0xF0 {
+                        bail!("system message in track must be written as an escape event");
+                    }
+                    if running_status == Some(status) {
+                        buf.extend_from_slice(&message_bytes[1..]);
+                    } else {
+                        buf.extend_from_slice(&message_bytes);
+                        running_status = Some(status);
+                    }
+                }
+                TrackEventKind::SystemExclusive(data) => {
+                    buf.push(SYSEX_EVENT);
+                    write_length_prefixed(data, buf)?;
+                    running_status = None;
+                }
+                TrackEventKind::Escape(data) => {
+                    buf.push(ESCAPE_EVENT);
+                    write_length_prefixed(data, buf)?;
+                    running_status = None;
+                }
+                TrackEventKind::Meta(meta) => {
+                    meta.write(buf)?;
+                    running_status = None;
+                }
+            }
+        }
+
+        let delta_time = self.end_tick().checked_sub(tick)
+            .ok_or_else(|| anyhow!("End of Track before last event"))?;
+        write_variable_length_quantity(u32::try_from(delta_time).map_err(|_| anyhow!("delta time too large"))?, buf)?;
+        MetaEvent::EndOfTrack.write(buf)?;
+
+        Ok(())
+    }
+
+    /// The tick of the End of Track event,
+    /// or of the last event if there is none.
+    pub fn end_tick(&self) -> u64 {
+        self.events.last().map(|event| event.tick).unwrap_or(0)
+    }
+
+    /// Builds a tempo map from this track's tempo and time signature events.
+    pub fn tempo_map(&self, division: Division) -> Result<TempoMap> {
+        let mut tempo_changes = vec![];
+        let mut time_signature_changes = vec![];
+        self.collect_tempo_events(&mut tempo_changes, &mut time_signature_changes);
+        TempoMap::new(division, &tempo_changes, &time_signature_changes)
+    }
+
+    fn collect_tempo_events(
+        &self,
+        tempo_changes: &mut Vec<TempoChange>,
+        time_signature_changes: &mut Vec<TimeSignatureChange>,
+    ) {
+        for event in &self.events {
+            match event.kind {
+                TrackEventKind::Meta(MetaEvent::SetTempo { microseconds_per_quarter }) => {
+                    tempo_changes.push(TempoChange {
+                        tick: event.tick,
+                        microseconds_per_quarter,
+                    });
+                }
+                TrackEventKind::Meta(MetaEvent::TimeSignature { numerator, denominator_power, .. }) => {
+                    time_signature_changes.push(TimeSignatureChange {
+                        tick: event.tick,
+                        numerator,
+                        denominator_power,
+                    });
+                }
+                _ => { }
+            }
+        }
+    }
+}
+
+impl MetaEvent {
+    pub fn parse(meta_type: u8, data: &[u8]) -> Result<MetaEvent> {
+        let expect_len = |len: usize| -> Result<()> {
+            if data.len() == len {
+                Ok(())
+            } else {
+                Err(anyhow!("meta event {:02X} has length {}, expected {}", meta_type, data.len(), len))
+            }
+        };
+        Ok(match meta_type {
+            meta_types::SEQUENCE_NUMBER => {
+                expect_len(2)?;
+                MetaEvent::SequenceNumber(u16::from_be_bytes([data[0], data[1]]))
+            }
+            meta_types::CHANNEL_PREFIX => {
+                expect_len(1)?;
+                MetaEvent::ChannelPrefix(MidiChannelId::try_from(data[0])?)
+            }
+            meta_types::PORT => {
+                expect_len(1)?;
+                MetaEvent::Port(data[0])
+            }
+            meta_types::END_OF_TRACK => {
+                expect_len(0)?;
+                MetaEvent::EndOfTrack
+            }
+            meta_types::SET_TEMPO => {
+                expect_len(3)?;
+                MetaEvent::SetTempo {
+                    microseconds_per_quarter: u32::from_be_bytes([0, data[0], data[1], data[2]]),
+                }
+            }
+            meta_types::SMPTE_OFFSET => {
+                expect_len(5)?;
+                MetaEvent::SmpteOffset {
+                    hours: data[0],
+                    minutes: data[1],
+                    seconds: data[2],
+                    frames: data[3],
+                    fractional_frames: data[4],
+                }
+            }
+            meta_types::TIME_SIGNATURE => {
+                expect_len(4)?;
+                MetaEvent::TimeSignature {
+                    numerator: data[0],
+                    denominator_power: data[1],
+                    clocks_per_metronome_click: data[2],
+                    thirty_seconds_per_quarter: data[3],
+                }
+            }
+            meta_types::KEY_SIGNATURE => {
+                expect_len(2)?;
+                MetaEvent::KeySignature {
+                    sharps: data[0] as i8,
+                    minor: data[1] != 0,
+                }
+            }
+            meta_types::SEQUENCER_SPECIFIC => {
+                MetaEvent::SequencerSpecific(data.to_vec())
+            }
+            _ => {
+                if let Ok(kind) = TextKind::try_from(meta_type) {
+                    MetaEvent::Text {
+                        kind,
+                        text: data.to_vec(),
+                    }
+                } else {
+                    MetaEvent::Unknown {
+                        meta_type,
+                        data: data.to_vec(),
+                    }
+                }
+            }
+        })
+    }
+
+    pub fn meta_type(&self) -> u8 {
+        match self {
+            MetaEvent::SequenceNumber(_) => meta_types::SEQUENCE_NUMBER,
+            MetaEvent::Text { kind, .. } => u8::from(*kind),
+            MetaEvent::ChannelPrefix(_) => meta_types::CHANNEL_PREFIX,
+            MetaEvent::Port(_) => meta_types::PORT,
+            MetaEvent::EndOfTrack => meta_types::END_OF_TRACK,
+            MetaEvent::SetTempo { .. } => meta_types::SET_TEMPO,
+            MetaEvent::SmpteOffset { .. } => meta_types::SMPTE_OFFSET,
+            MetaEvent::TimeSignature { .. } => meta_types::TIME_SIGNATURE,
+            MetaEvent::KeySignature { .. } => meta_types::KEY_SIGNATURE,
+            MetaEvent::SequencerSpecific(_) => meta_types::SEQUENCER_SPECIFIC,
+            MetaEvent::Unknown { meta_type, .. } => *meta_type,
+        }
+    }
+
+    /// Writes the event, starting with the `FF` status byte.
+    pub fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let data = match self {
+            MetaEvent::SequenceNumber(number) => number.to_be_bytes().to_vec(),
+            MetaEvent::Text { text, .. } => text.clone(),
+            MetaEvent::ChannelPrefix(channel) => vec![u8::from(*channel)],
+            MetaEvent::Port(port) => vec![*port],
+            MetaEvent::EndOfTrack => vec![],
+            MetaEvent::SetTempo { microseconds_per_quarter } => {
+                if *microseconds_per_quarter > 0xFF_FFFF {
+                    bail!("tempo out of range: {}", microseconds_per_quarter);
+                }
+                microseconds_per_quarter.to_be_bytes()[1..].to_vec()
+            }
+            MetaEvent::SmpteOffset { hours, minutes, seconds, frames, fractional_frames } => {
+                vec![*hours, *minutes, *seconds, *frames, *fractional_frames]
+            }
+            MetaEvent::TimeSignature {
+                numerator, denominator_power, clocks_per_metronome_click, thirty_seconds_per_quarter,
+            } => {
+                vec![*numerator, *denominator_power, *clocks_per_metronome_click, *thirty_seconds_per_quarter]
+            }
+            MetaEvent::KeySignature { sharps, minor } => vec![*sharps as u8, *minor as u8],
+            MetaEvent::SequencerSpecific(data) => data.clone(),
+            MetaEvent::Unknown { data, .. } => data.clone(),
+        };
+        buf.push(META_EVENT);
+        buf.push(self.meta_type());
+        write_length_prefixed(&data, buf)
+    }
+}
+
+/// Reference: Standard MIDI Files 1.0, "Variable Length Quantity"
+pub fn read_variable_length_quantity(bytes: &mut &[u8]) -> Result<u32> {
+    let mut value = 0_u32;
+    for index in 0..4 {
+        let byte = *bytes.get(index).ok_or_else(|| anyhow!("truncated variable length quantity"))?;
+        value = value << 7 | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[index + 1..];
+            return Ok(value);
+        }
+    }
+    Err(anyhow!("variable length quantity longer than 4 bytes"))
+}
+
+pub fn write_variable_length_quantity(value: u32, buf: &mut Vec<u8>) -> Result<()> {
+    if value > 0x0FFF_FFFF {
+        bail!("variable length quantity out of range: {}", value);
+    }
+    let mut shift = 21;
+    while shift > 0 && value >> shift == 0 {
+        shift -= 7;
+    }
+    while shift > 0 {
+        buf.push((value >> shift) as u8 & 0x7F | 0x80);
+        shift -= 7;
+    }
+    buf.push(value as u8 & 0x7F);
+    Ok(())
+}
+
+fn read_length_prefixed<'buf>(bytes: &mut &'buf [u8]) -> Result<&'buf [u8]> {
+    let len = read_variable_length_quantity(bytes)? as usize;
+    let data = bytes.get(..len).ok_or_else(|| anyhow!("truncated event data"))?;
+    *bytes = &bytes[len..];
+    Ok(data)
+}
+
+fn write_length_prefixed(data: &[u8], buf: &mut Vec<u8>) -> Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| anyhow!("event data too long"))?;
+    write_variable_length_quantity(len, buf)?;
+    buf.extend_from_slice(data);
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::assert_from::AssertFrom;
+
+    /// A format 1 file with a tempo track and a track using running status and channel mode messages.
+    pub(crate) const MULTI_TRACK: &[u8] = &[
+        b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 2, 0, 96,
+        b'M', b'T', b'r', b'k', 0, 0, 0, 11,
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+        0x00, 0xFF, 0x2F, 0x00,
+        b'M', b'T', b'r', b'k', 0, 0, 0, 19,
+        0x00, 0x90, 0x3C, 0x64,
+        0x60, 0x3C, 0x00,
+        0x00, 0xB0, 0x7A, 0x7F,
+        0x00, 0xB1, 0x7E, 0x02,
+        0x00, 0xFF, 0x2F, 0x00,
+    ];
+
+    fn channel_mode(channel: u8, mode: ChannelModeMessage, value: u8) -> TrackEventKind {
+        TrackEventKind::Midi(Message::Channel(ChannelMessage {
+            channel: MidiChannelId::try_from(channel).unwrap(),
+            message: ChannelMessageType::ChannelMode {
+                mode,
+                value: cvm::Unsigned7::assert_from(value),
+            },
+        }))
+    }
+
+    #[test]
+    fn read_multi_track() {
+        let smf = Smf::read(MULTI_TRACK).unwrap();
+        assert_eq!(smf.format, Format::MultiTrack);
+        assert_eq!(smf.division, Division::Metrical(96));
+        assert_eq!(smf.tracks.len(), 2);
+        assert_eq!(smf.tracks[0].events[0].kind, TrackEventKind::Meta(MetaEvent::SetTempo {
+            microseconds_per_quarter: 500_000,
+        }));
+
+        let events = &smf.tracks[1].events;
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[1].tick, 96);
+        assert_eq!(events[2].kind, channel_mode(0, ChannelModeMessage::LocalControl, 0x7F));
+        assert_eq!(events[3].kind, channel_mode(1, ChannelModeMessage::MonoOn, 2));
+        assert_eq!(events[4].tick, 96);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let smf = Smf::read(MULTI_TRACK).unwrap();
+        assert_eq!(smf.to_bytes().unwrap(), MULTI_TRACK);
+    }
+
+    #[test]
+    fn end_of_track_before_last_event() {
+        let mut track = Smf::read(MULTI_TRACK).unwrap().tracks.remove(1);
+        track.events.last_mut().unwrap().tick = 0;
+        let error = track.write(&mut vec![]).unwrap_err();
+        assert_eq!(error.to_string(), "End of Track before last event");
+    }
+}
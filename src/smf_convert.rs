@@ -0,0 +1,248 @@
+//! Conversions between SMF formats, and track rearrangement.
+
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use crate::message::*;
+use crate::smf::{Smf, Format, Track, TrackEvent, TrackEventKind, MetaEvent};
+
+/// Merges tracks into one, in time order.
+///
+/// Simultaneous events keep their relative order within a track,
+/// and events from earlier tracks come before events from later tracks.
+/// End of Track events are dropped, except for one at the end of the longest track.
+pub fn merge_tracks(tracks: &[Track]) -> Track {
+    let mut events: Vec<(u64, usize, usize, &TrackEvent)> = vec![];
+    for (track_index, track) in tracks.iter().enumerate() {
+        for (event_index, event) in track.events.iter().enumerate() {
+            if !is_end_of_track(event) {
+                events.push((event.tick, track_index, event_index, event));
+            }
+        }
+    }
+    events.sort_by_key(|&(tick, track_index, event_index, _)| (tick, track_index, event_index));
+
+    let end_tick = tracks.iter().map(Track::end_tick).max().unwrap_or(0);
+    let mut events: Vec<TrackEvent> = events.into_iter().map(|(_, _, _, event)| event.clone()).collect();
+    events.push(TrackEvent {
+        tick: end_tick,
+        kind: TrackEventKind::Meta(MetaEvent::EndOfTrack),
+    });
+
+    Track { events }
+}
+
+/// Converts a format 1 file to format 0 by merging its tracks.
+///
+/// Format 0 files are returned unchanged.
+/// Format 2 files can't be converted because their tracks aren't simultaneous.
+pub fn to_format_0(smf: &Smf) -> Result<Smf> {
+    match smf.format {
+        Format::SingleTrack => Ok(smf.clone()),
+        Format::MultiTrack => {
+            Ok(Smf {
+                format: Format::SingleTrack,
+                division: smf.division,
+                tracks: vec![merge_tracks(&smf.tracks)],
+            })
+        }
+        Format::MultiSequence => {
+            bail!("can't merge the independent sequences of a format 2 file");
+        }
+    }
+}
+
+/// Converts a file to format 1 with one track per MIDI channel.
+///
+/// Events that don't belong to a channel, such as meta and SysEx events,
+/// go to the first track, followed by a track for each used channel
+/// in ascending channel order.
+pub fn split_by_channel(smf: &Smf) -> Result<Smf> {
+    split_tracks(smf, |kind| match kind {
+        TrackEventKind::Midi(Message::Channel(message)) => Some(message.channel),
+        _ => None,
+    })
+}
+
+/// Broad categories of track events, for [`split_by_kind`].
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EventClass {
+    Notes,
+    ControlChanges,
+    ProgramChanges,
+    PitchBends,
+    Aftertouch,
+    ChannelMode,
+    SystemExclusive,
+}
+
+impl EventClass {
+    /// Returns `None` for meta events.
+    pub fn of(kind: &TrackEventKind) -> Option<EventClass> {
+        match kind {
+            TrackEventKind::Midi(Message::Channel(message)) => match &message.message {
+                ChannelMessageType::ChannelVoice(message) => Some(match message {
+                    ChannelVoiceMessage::NoteOff(_) => EventClass::Notes,
+                    ChannelVoiceMessage::NoteOn(_) => EventClass::Notes,
+                    ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(_) => EventClass::Aftertouch,
+                    ChannelVoiceMessage::ControlChange(_) => EventClass::ControlChanges,
+                    ChannelVoiceMessage::ProgramChange(_) => EventClass::ProgramChanges,
+                    ChannelVoiceMessage::ChannelPressureAftertouch(_) => EventClass::Aftertouch,
+                    ChannelVoiceMessage::PitchBendChange(_) => EventClass::PitchBends,
+                }),
+                ChannelMessageType::ChannelMode { .. } => Some(EventClass::ChannelMode),
+            },
+            TrackEventKind::Midi(Message::System(_)) => Some(EventClass::SystemExclusive),
+            TrackEventKind::SystemExclusive(_) => Some(EventClass::SystemExclusive),
+            TrackEventKind::Escape(_) => Some(EventClass::SystemExclusive),
+            TrackEventKind::Meta(_) => None,
+        }
+    }
+}
+
+/// Converts a file to format 1 with one track per [`EventClass`].
+///
+/// Meta events go to the first track.
+pub fn split_by_kind(smf: &Smf) -> Result<Smf> {
+    split_tracks(smf, EventClass::of)
+}
+
+/// Converts a file to format 1 by sorting its events into tracks by key.
+///
+/// The tracks are first merged in time order.
+/// Events with no key go to the first track,
+/// followed by a track per key in ascending key order.
+/// Each track ends at the end of the longest source track.
+pub fn split_tracks<K: Ord>(smf: &Smf, key: impl Fn(&TrackEventKind) -> Option<K>) -> Result<Smf> {
+    let merged = to_format_0(smf)?;
+    let merged = &merged.tracks[0];
+    let end_tick = merged.end_tick();
+
+    let mut first = Track::default();
+    let mut keyed: BTreeMap<K, Track> = BTreeMap::new();
+    for event in &merged.events {
+        if is_end_of_track(event) {
+            continue;
+        }
+        match key(&event.kind) {
+            None => first.events.push(event.clone()),
+            Some(key) => keyed.entry(key).or_default().events.push(event.clone()),
+        }
+    }
+
+    let mut tracks: Vec<Track> = std::iter::once(first).chain(keyed.into_values()).collect();
+    for track in &mut tracks {
+        track.events.push(TrackEvent {
+            tick: end_tick,
+            kind: TrackEventKind::Meta(MetaEvent::EndOfTrack),
+        });
+    }
+
+    Ok(Smf {
+        format: Format::MultiTrack,
+        division: smf.division,
+        tracks,
+    })
+}
+
+/// Removes tracks for which `keep` returns false.
+///
+/// `keep` is called with each track's original index.
+/// A format 0 file must keep exactly one track.
+pub fn retain_tracks(smf: &mut Smf, mut keep: impl FnMut(usize, &Track) -> bool) -> Result<()> {
+    let mut index = 0;
+    let tracks = std::mem::take(&mut smf.tracks);
+    smf.tracks = tracks.into_iter().filter(|track| {
+        let keep = keep(index, track);
+        index += 1;
+        keep
+    }).collect();
+    if smf.format == Format::SingleTrack && smf.tracks.len() != 1 {
+        bail!("format 0 file left with {} tracks", smf.tracks.len());
+    }
+    Ok(())
+}
+
+/// Reorders tracks so that new track `n` is old track `order[n]`.
+///
+/// `order` must be a permutation of the track indexes.
+/// For format 2 files, Sequence Number events at the start of each track
+/// are renumbered to match the new track index.
+pub fn renumber_tracks(smf: &mut Smf, order: &[usize]) -> Result<()> {
+    let mut seen = vec![false; smf.tracks.len()];
+    if order.len() != smf.tracks.len() {
+        bail!("track order has {} entries for {} tracks", order.len(), smf.tracks.len());
+    }
+    for &index in order {
+        match seen.get_mut(index) {
+            Some(seen @ false) => *seen = true,
+            Some(true) => bail!("track {} appears twice in track order", index),
+            None => bail!("no track {}", index),
+        }
+    }
+
+    let mut tracks: Vec<Option<Track>> = std::mem::take(&mut smf.tracks).into_iter().map(Some).collect();
+    smf.tracks = order.iter().map(|&index| tracks[index].take().expect("permutation")).collect();
+
+    if smf.format == Format::MultiSequence {
+        for (index, track) in smf.tracks.iter_mut().enumerate() {
+            let number = u16::try_from(index)?;
+            for event in track.events.iter_mut().take_while(|event| event.tick == 0) {
+                if let TrackEventKind::Meta(MetaEvent::SequenceNumber(n)) = &mut event.kind {
+                    *n = number;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_end_of_track(event: &TrackEvent) -> bool {
+    event.kind == TrackEventKind::Meta(MetaEvent::EndOfTrack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smf::tests::MULTI_TRACK;
+
+    fn midi_events(track: &Track) -> Vec<(u64, Message)> {
+        track.events.iter().filter_map(|event| match &event.kind {
+            TrackEventKind::Midi(message) => Some((event.tick, message.clone())),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn merge_round_trip() {
+        let smf = Smf::read(MULTI_TRACK).unwrap();
+        let merged = to_format_0(&smf).unwrap();
+        assert_eq!(merged.tracks.len(), 1);
+        let events = &merged.tracks[0].events;
+        assert_eq!(events.len(), 6);
+        assert!(matches!(events[0].kind, TrackEventKind::Meta(MetaEvent::SetTempo { .. })));
+        assert_eq!(midi_events(&merged.tracks[0]), midi_events(&smf.tracks[1]));
+
+        let reread = Smf::read(&merged.to_bytes().unwrap()).unwrap();
+        assert_eq!(reread, merged);
+    }
+
+    #[test]
+    fn split_by_channel_round_trip() {
+        let smf = Smf::read(MULTI_TRACK).unwrap();
+        let split = split_by_channel(&smf).unwrap();
+        assert_eq!(split.tracks.len(), 3);
+        assert_eq!(midi_events(&split.tracks[0]), vec![]);
+        assert_eq!(midi_events(&split.tracks[1]).len(), 3);
+        assert_eq!(midi_events(&split.tracks[2]).len(), 1);
+        for track in &split.tracks {
+            assert_eq!(track.end_tick(), 96);
+        }
+
+        let reread = Smf::read(&split.to_bytes().unwrap()).unwrap();
+        assert_eq!(reread, split);
+        let merged = to_format_0(&reread).unwrap();
+        assert_eq!(midi_events(&merged.tracks[0]), midi_events(&smf.tracks[1]));
+    }
+}
@@ -0,0 +1,404 @@
+//! Classification and decoding of System Exclusive messages.
+//!
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//! Universal messages with modules of their own, such as [`crate::tuning`] and [`crate::ci`],
+//! are left as `Other` here.
+//!
+//! Reference: MIDI spec table VIIa, VIIb
+
+use anyhow::{Result, anyhow, bail};
+use std::fmt;
+use crate::manufacturer::ManufacturerId;
+use crate::message::cvm::Unsigned14;
+
+pub const NON_COMMERCIAL: u8 = 0x7D;
+pub const UNIVERSAL_NON_REALTIME: u8 = 0x7E;
+pub const UNIVERSAL_REALTIME: u8 = 0x7F;
+
+/// The device ID addressing all devices.
+pub const ALL_CALL: u8 = 0x7F;
+
+/// Sub-ID#1 and #2 of Universal Non-Real Time messages.
+pub mod non_realtime {
+    pub const SAMPLE_DUMP_HEADER: u8 = 0x01;
+    pub const SAMPLE_DATA_PACKET: u8 = 0x02;
+    pub const SAMPLE_DUMP_REQUEST: u8 = 0x03;
+    pub const GENERAL_INFORMATION: u8 = 0x06;
+    pub const IDENTITY_REQUEST: u8 = 0x01;
+    pub const IDENTITY_REPLY: u8 = 0x02;
+    pub const MIDI_TUNING_STANDARD: u8 = 0x08;
+    pub const GENERAL_MIDI: u8 = 0x09;
+    pub const GENERAL_MIDI_1_SYSTEM_ON: u8 = 0x01;
+    pub const GENERAL_MIDI_SYSTEM_OFF: u8 = 0x02;
+    pub const GENERAL_MIDI_2_SYSTEM_ON: u8 = 0x03;
+    pub const MIDI_CAPABILITY_INQUIRY: u8 = 0x0D;
+    pub const END_OF_FILE: u8 = 0x7B;
+    pub const WAIT: u8 = 0x7C;
+    pub const CANCEL: u8 = 0x7D;
+    pub const NAK: u8 = 0x7E;
+    pub const ACK: u8 = 0x7F;
+}
+
+/// Sub-ID#1 and #2 of Universal Real Time messages.
+pub mod realtime {
+    pub const MIDI_TIME_CODE: u8 = 0x01;
+    pub const FULL_MESSAGE: u8 = 0x01;
+    pub const MIDI_MACHINE_CONTROL_COMMAND: u8 = 0x06;
+    pub const MIDI_MACHINE_CONTROL_RESPONSE: u8 = 0x07;
+    pub const MIDI_TUNING_STANDARD: u8 = 0x08;
+    pub const DEVICE_CONTROL: u8 = 0x04;
+    pub const MASTER_VOLUME: u8 = 0x01;
+    pub const MASTER_BALANCE: u8 = 0x02;
+    pub const MASTER_FINE_TUNING: u8 = 0x03;
+    pub const MASTER_COARSE_TUNING: u8 = 0x04;
+}
+
+/// The reply to an Identity Request.
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub manufacturer_id: ManufacturerId,
+    /// 14 bits, sent LSB first.
+    pub family: u16,
+    /// 14 bits, sent LSB first.
+    pub model: u16,
+    /// Software revision, in the order sent.
+    pub version: [u8; 4],
+}
+
+/// Messages of the sample dump handshake.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HandshakeKind {
+    EndOfFile,
+    Wait,
+    Cancel,
+    Nak,
+    Ack,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum NonRealtimeMessage {
+    IdentityRequest,
+    IdentityReply(Identity),
+    GeneralMidi1SystemOn,
+    GeneralMidiSystemOff,
+    GeneralMidi2SystemOn,
+    Handshake {
+        kind: HandshakeKind,
+        packet_number: u8,
+    },
+    Other {
+        sub_id_1: u8,
+        /// From sub-ID#2 on.
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum RealtimeMessage {
+    /// MIDI Time Code full message, locating to a frame.
+    TimeCodeFullMessage {
+        /// Hours in the low five bits, frame rate in bits 5 and 6.
+        hours_and_rate: u8,
+        minutes: u8,
+        seconds: u8,
+        frames: u8,
+    },
+    /// 0 is silence, 0x3FFF the maximum.
+    MasterVolume(Unsigned14),
+    /// 0 is left, 0x2000 center and 0x3FFF right.
+    MasterBalance(Unsigned14),
+    /// 0x2000 is A440, each step 100/8192 cents.
+    MasterFineTuning(Unsigned14),
+    /// 0x2000 is A440; only the MSB is used, in semitones.
+    MasterCoarseTuning(Unsigned14),
+    Other {
+        sub_id_1: u8,
+        /// From sub-ID#2 on.
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum SysEx {
+    UniversalNonRealtime {
+        device_id: u8,
+        message: NonRealtimeMessage,
+    },
+    UniversalRealtime {
+        device_id: u8,
+        message: RealtimeMessage,
+    },
+    /// For research and education, not to be used in products.
+    NonCommercial(Vec<u8>),
+    Manufacturer {
+        id: ManufacturerId,
+        data: Vec<u8>,
+    },
+}
+
+fn u14(lsb: u8, msb: u8) -> Result<Unsigned14> {
+    Unsigned14::try_from([lsb, msb])
+}
+
+fn write_u14(value: Unsigned14, buf: &mut Vec<u8>) {
+    let value = u16::from(value);
+    buf.extend([(value & 0x7F) as u8, (value >> 7) as u8]);
+}
+
+impl SysEx {
+    /// Parses SysEx data, without `F0` and `F7`.
+    pub fn parse(data: &[u8]) -> Result<SysEx> {
+        let Some((&id, rest)) = data.split_first() else {
+            bail!("empty SysEx message");
+        };
+        if data.iter().any(|byte| *byte > 0x7F) {
+            bail!("SysEx data byte out of 7-bit range");
+        }
+        match id {
+            UNIVERSAL_NON_REALTIME | UNIVERSAL_REALTIME => {
+                let [device_id, sub_id_1, ..] = *rest else {
+                    bail!("truncated Universal SysEx message");
+                };
+                let body = &rest[2..];
+                if id == UNIVERSAL_NON_REALTIME {
+                    Ok(SysEx::UniversalNonRealtime {
+                        device_id,
+                        message: NonRealtimeMessage::parse(sub_id_1, body)?,
+                    })
+                } else {
+                    Ok(SysEx::UniversalRealtime {
+                        device_id,
+                        message: RealtimeMessage::parse(sub_id_1, body)?,
+                    })
+                }
+            }
+            NON_COMMERCIAL => Ok(SysEx::NonCommercial(rest.to_vec())),
+            _ => {
+                let (id, rest) = ManufacturerId::parse(data)?;
+                Ok(SysEx::Manufacturer {
+                    id,
+                    data: rest.to_vec(),
+                })
+            }
+        }
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        match self {
+            SysEx::UniversalNonRealtime { device_id, message } => {
+                buf.extend([UNIVERSAL_NON_REALTIME, *device_id]);
+                message.encode(buf);
+            }
+            SysEx::UniversalRealtime { device_id, message } => {
+                buf.extend([UNIVERSAL_REALTIME, *device_id]);
+                message.encode(buf);
+            }
+            SysEx::NonCommercial(data) => {
+                buf.push(NON_COMMERCIAL);
+                buf.extend_from_slice(data);
+            }
+            SysEx::Manufacturer { id, data } => {
+                id.encode(buf);
+                buf.extend_from_slice(data);
+            }
+        }
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("SysEx data byte out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+
+    /// The device ID of a Universal message.
+    pub fn device_id(&self) -> Option<u8> {
+        match self {
+            SysEx::UniversalNonRealtime { device_id, .. } | SysEx::UniversalRealtime { device_id, .. } => Some(*device_id),
+            _ => None,
+        }
+    }
+}
+
+impl NonRealtimeMessage {
+    fn parse(sub_id_1: u8, body: &[u8]) -> Result<NonRealtimeMessage> {
+        use non_realtime::*;
+
+        let other = || NonRealtimeMessage::Other {
+            sub_id_1,
+            data: body.to_vec(),
+        };
+        let handshake = |kind| match body {
+            [packet_number, ..] => Ok(NonRealtimeMessage::Handshake {
+                kind,
+                packet_number: *packet_number,
+            }),
+            [] => Err(anyhow!("handshake message without a packet number")),
+        };
+
+        match (sub_id_1, body) {
+            (GENERAL_INFORMATION, [IDENTITY_REQUEST, ..]) => Ok(NonRealtimeMessage::IdentityRequest),
+            (GENERAL_INFORMATION, [IDENTITY_REPLY, rest @ ..]) => {
+                let (manufacturer_id, rest) = ManufacturerId::parse(rest)?;
+                if rest.len() < 8 {
+                    bail!("truncated Identity Reply");
+                }
+                Ok(NonRealtimeMessage::IdentityReply(Identity {
+                    manufacturer_id,
+                    family: u16::from(u14(rest[0], rest[1])?),
+                    model: u16::from(u14(rest[2], rest[3])?),
+                    version: rest[4..8].try_into().expect("length"),
+                }))
+            }
+            (GENERAL_MIDI, [GENERAL_MIDI_1_SYSTEM_ON, ..]) => Ok(NonRealtimeMessage::GeneralMidi1SystemOn),
+            (GENERAL_MIDI, [GENERAL_MIDI_SYSTEM_OFF, ..]) => Ok(NonRealtimeMessage::GeneralMidiSystemOff),
+            (GENERAL_MIDI, [GENERAL_MIDI_2_SYSTEM_ON, ..]) => Ok(NonRealtimeMessage::GeneralMidi2SystemOn),
+            (END_OF_FILE, _) => handshake(HandshakeKind::EndOfFile),
+            (WAIT, _) => handshake(HandshakeKind::Wait),
+            (CANCEL, _) => handshake(HandshakeKind::Cancel),
+            (NAK, _) => handshake(HandshakeKind::Nak),
+            (ACK, _) => handshake(HandshakeKind::Ack),
+            _ => Ok(other()),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        use non_realtime::*;
+
+        match self {
+            NonRealtimeMessage::IdentityRequest => buf.extend([GENERAL_INFORMATION, IDENTITY_REQUEST]),
+            NonRealtimeMessage::IdentityReply(identity) => {
+                buf.extend([GENERAL_INFORMATION, IDENTITY_REPLY]);
+                identity.manufacturer_id.encode(buf);
+                for value in [identity.family, identity.model] {
+                    buf.extend([(value & 0x7F) as u8, (value >> 7) as u8]);
+                }
+                buf.extend_from_slice(&identity.version);
+            }
+            NonRealtimeMessage::GeneralMidi1SystemOn => buf.extend([GENERAL_MIDI, GENERAL_MIDI_1_SYSTEM_ON]),
+            NonRealtimeMessage::GeneralMidiSystemOff => buf.extend([GENERAL_MIDI, GENERAL_MIDI_SYSTEM_OFF]),
+            NonRealtimeMessage::GeneralMidi2SystemOn => buf.extend([GENERAL_MIDI, GENERAL_MIDI_2_SYSTEM_ON]),
+            NonRealtimeMessage::Handshake { kind, packet_number } => {
+                let sub_id_1 = match kind {
+                    HandshakeKind::EndOfFile => END_OF_FILE,
+                    HandshakeKind::Wait => WAIT,
+                    HandshakeKind::Cancel => CANCEL,
+                    HandshakeKind::Nak => NAK,
+                    HandshakeKind::Ack => ACK,
+                };
+                buf.extend([sub_id_1, *packet_number]);
+            }
+            NonRealtimeMessage::Other { sub_id_1, data } => {
+                buf.push(*sub_id_1);
+                buf.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+impl RealtimeMessage {
+    fn parse(sub_id_1: u8, body: &[u8]) -> Result<RealtimeMessage> {
+        use realtime::*;
+
+        match (sub_id_1, body) {
+            (MIDI_TIME_CODE, [FULL_MESSAGE, rest @ ..]) => {
+                let [hours_and_rate, minutes, seconds, frames, ..] = *rest else {
+                    bail!("truncated MIDI Time Code full message");
+                };
+                Ok(RealtimeMessage::TimeCodeFullMessage { hours_and_rate, minutes, seconds, frames })
+            }
+            (DEVICE_CONTROL, [sub_id_2 @ MASTER_VOLUME..=MASTER_COARSE_TUNING, rest @ ..]) => {
+                let [lsb, msb, ..] = *rest else {
+                    bail!("truncated device control message");
+                };
+                let value = u14(lsb, msb)?;
+                Ok(match *sub_id_2 {
+                    MASTER_VOLUME => RealtimeMessage::MasterVolume(value),
+                    MASTER_BALANCE => RealtimeMessage::MasterBalance(value),
+                    MASTER_FINE_TUNING => RealtimeMessage::MasterFineTuning(value),
+                    _ => RealtimeMessage::MasterCoarseTuning(value),
+                })
+            }
+            _ => Ok(RealtimeMessage::Other {
+                sub_id_1,
+                data: body.to_vec(),
+            }),
+        }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        use realtime::*;
+
+        match self {
+            RealtimeMessage::TimeCodeFullMessage { hours_and_rate, minutes, seconds, frames } => {
+                buf.extend([MIDI_TIME_CODE, FULL_MESSAGE, *hours_and_rate, *minutes, *seconds, *frames]);
+            }
+            RealtimeMessage::MasterVolume(value)
+            | RealtimeMessage::MasterBalance(value)
+            | RealtimeMessage::MasterFineTuning(value)
+            | RealtimeMessage::MasterCoarseTuning(value) => {
+                let sub_id_2 = match self {
+                    RealtimeMessage::MasterVolume(_) => MASTER_VOLUME,
+                    RealtimeMessage::MasterBalance(_) => MASTER_BALANCE,
+                    RealtimeMessage::MasterFineTuning(_) => MASTER_FINE_TUNING,
+                    _ => MASTER_COARSE_TUNING,
+                };
+                buf.extend([DEVICE_CONTROL, sub_id_2]);
+                write_u14(*value, buf);
+            }
+            RealtimeMessage::Other { sub_id_1, data } => {
+                buf.push(*sub_id_1);
+                buf.extend_from_slice(data);
+            }
+        }
+    }
+
+    /// Master Fine Tuning in cents.
+    pub fn fine_tuning_cents(value: Unsigned14) -> f64 {
+        (u16::from(value) as f64 - 8192.0) / 8192.0 * 100.0
+    }
+
+    /// Master Coarse Tuning in semitones.
+    pub fn coarse_tuning_semitones(value: Unsigned14) -> i8 {
+        (u16::from(value) >> 7) as i8 - 64
+    }
+}
+
+/// Describes SysEx data, without `F0` and `F7`, by its sender and length, such as `SysEx Roland 12 bytes`.
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct Summary<'a>(pub &'a [u8]);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data = self.0;
+        write!(f, "SysEx ")?;
+        match data.first() {
+            None => {}
+            Some(&UNIVERSAL_NON_REALTIME) => write!(f, "UniversalNonRealtime ")?,
+            Some(&UNIVERSAL_REALTIME) => write!(f, "UniversalRealtime ")?,
+            Some(&NON_COMMERCIAL) => write!(f, "NonCommercial ")?,
+            Some(_) => match ManufacturerId::parse(data) {
+                Ok((id, _)) => match id.name() {
+                    Some(name) => write!(f, "{} ", name)?,
+                    None => write!(f, "{} ", id)?,
+                },
+                Err(_) => write!(f, "{:02X?} ", data)?,
+            },
+        }
+        write!(f, "{} bytes", data.len())
+    }
+}
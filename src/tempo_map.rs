@@ -0,0 +1,345 @@
+//! Conversion between MIDI file tick positions, seconds, and bars/beats.
+//!
+//! A [`TempoMap`] is built from the Set Tempo and Time Signature meta events
+//! of a Standard MIDI File along with the file's time division.
+//! Lookups are binary searches over precomputed segments.
+//!
+//! Reference: Standard MIDI Files 1.0, header chunk and meta events FF 51, FF 58
+
+use anyhow::{Result, anyhow, bail};
+
+/// The tempo assumed before the first Set Tempo event: 120 beats per minute.
+pub const DEFAULT_MICROSECONDS_PER_QUARTER: u32 = 500_000;
+
+/// The meaning of a tick, from the `division` word of the SMF header chunk.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Division {
+    /// Ticks per quarter note.
+    Metrical(u16),
+    /// Ticks per SMPTE frame.
+    ///
+    /// `frames_per_second` is one of 24, 25, 29 or 30,
+    /// where 29 indicates 30 drop-frame, i.e. 29.97 frames per second.
+    Timecode {
+        frames_per_second: u8,
+        ticks_per_frame: u8,
+    },
+}
+
+impl Division {
+    /// Decodes the 16-bit `division` field of the header chunk.
+    pub fn from_header_word(word: u16) -> Result<Division> {
+        let division = if word & 0x8000 == 0 {
+            Division::Metrical(word)
+        } else {
+            let frames = (word >> 8) as u8 as i8;
+            Division::Timecode {
+                frames_per_second: frames.unsigned_abs(),
+                ticks_per_frame: word as u8,
+            }
+        };
+        division.validate()?;
+        Ok(division)
+    }
+
+    /// Encodes the 16-bit `division` field of the header chunk.
+    pub fn to_header_word(self) -> u16 {
+        match self {
+            Division::Metrical(ticks) => ticks & 0x7FFF,
+            Division::Timecode { frames_per_second, ticks_per_frame } => {
+                let frames = -(frames_per_second as i8) as u8;
+                (frames as u16) << 8 | ticks_per_frame as u16
+            }
+        }
+    }
+
+    pub fn validate(self) -> Result<()> {
+        match self {
+            Division::Metrical(0) => {
+                Err(anyhow!("zero ticks per quarter note"))
+            }
+            Division::Metrical(ticks) if ticks > 0x7FFF => {
+                Err(anyhow!("ticks per quarter note out of range: {}", ticks))
+            }
+            Division::Metrical(_) => Ok(()),
+            Division::Timecode { ticks_per_frame: 0, .. } => {
+                Err(anyhow!("zero ticks per frame"))
+            }
+            Division::Timecode { frames_per_second: 24 | 25 | 29 | 30, .. } => Ok(()),
+            Division::Timecode { frames_per_second, .. } => {
+                Err(anyhow!("invalid SMPTE frame rate {}", frames_per_second))
+            }
+        }
+    }
+
+    /// Ticks per second, for timecode divisions.
+    fn timecode_ticks_per_second(self) -> Option<f64> {
+        match self {
+            Division::Metrical(_) => None,
+            Division::Timecode { frames_per_second, ticks_per_frame } => {
+                let fps = match frames_per_second {
+                    29 => 30.0 * 1000.0 / 1001.0,
+                    fps => fps as f64,
+                };
+                Some(fps * ticks_per_frame as f64)
+            }
+        }
+    }
+}
+
+/// A Set Tempo meta event at an absolute tick position.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct TempoChange {
+    pub tick: u64,
+    pub microseconds_per_quarter: u32,
+}
+
+/// A Time Signature meta event at an absolute tick position.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct TimeSignatureChange {
+    pub tick: u64,
+    pub numerator: u8,
+    /// The denominator as a power of two, e.g. 3 for eighth notes.
+    pub denominator_power: u8,
+}
+
+/// A musical position.
+///
+/// `bar` and `beat` are zero-based, so the first downbeat is bar 0, beat 0.
+/// A beat is the time signature denominator's note value.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct BarBeat {
+    pub bar: u64,
+    pub beat: u32,
+    /// Position within the beat, in the range `[0, 1)`.
+    pub fraction: f64,
+}
+
+/// A span of ticks with constant tempo.
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+struct TempoSegment {
+    tick: u64,
+    seconds: f64,
+    quarters: f64,
+    seconds_per_tick: f64,
+    quarters_per_tick: f64,
+}
+
+/// A span of quarter notes with constant time signature.
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+struct MeterSegment {
+    quarters: f64,
+    bar: u64,
+    beats_per_bar: u8,
+    quarters_per_beat: f64,
+}
+
+#[derive(Debug)]
+#[derive(Clone)]
+pub struct TempoMap {
+    division: Division,
+    tempo_segments: Vec<TempoSegment>,
+    meter_segments: Vec<MeterSegment>,
+}
+
+impl TempoMap {
+    /// Builds a tempo map.
+    ///
+    /// Changes need not be sorted. If several changes share a tick,
+    /// the last one in the slice takes effect.
+    /// Before the first change the tempo is 120 BPM and the meter is 4/4.
+    pub fn new(
+        division: Division,
+        tempo_changes: &[TempoChange],
+        time_signature_changes: &[TimeSignatureChange],
+    ) -> Result<TempoMap> {
+        division.validate()?;
+
+        let mut tempo_changes = tempo_changes.to_vec();
+        tempo_changes.sort_by_key(|change| change.tick);
+        let mut time_signature_changes = time_signature_changes.to_vec();
+        time_signature_changes.sort_by_key(|change| change.tick);
+
+        let mut tempo_segments: Vec<TempoSegment> = vec![];
+        let first = TempoChange {
+            tick: 0,
+            microseconds_per_quarter: DEFAULT_MICROSECONDS_PER_QUARTER,
+        };
+        for change in std::iter::once(&first).chain(tempo_changes.iter()) {
+            if change.microseconds_per_quarter == 0 {
+                bail!("zero tempo at tick {}", change.tick);
+            }
+            let micros = change.microseconds_per_quarter as f64;
+            let (seconds_per_tick, quarters_per_tick) = match division {
+                Division::Metrical(ticks_per_quarter) => {
+                    let ticks_per_quarter = ticks_per_quarter as f64;
+                    (micros / 1_000_000.0 / ticks_per_quarter, 1.0 / ticks_per_quarter)
+                }
+                Division::Timecode { .. } => {
+                    let ticks_per_second = division.timecode_ticks_per_second().expect("timecode");
+                    let seconds_per_tick = 1.0 / ticks_per_second;
+                    (seconds_per_tick, seconds_per_tick * 1_000_000.0 / micros)
+                }
+            };
+            let (seconds, quarters) = match tempo_segments.last() {
+                None => (0.0, 0.0),
+                Some(prev) => {
+                    let ticks = (change.tick - prev.tick) as f64;
+                    (
+                        prev.seconds + ticks * prev.seconds_per_tick,
+                        prev.quarters + ticks * prev.quarters_per_tick,
+                    )
+                }
+            };
+            let segment = TempoSegment {
+                tick: change.tick,
+                seconds,
+                quarters,
+                seconds_per_tick,
+                quarters_per_tick,
+            };
+            match tempo_segments.last_mut() {
+                Some(prev) if prev.tick == change.tick => *prev = segment,
+                _ => tempo_segments.push(segment),
+            }
+        }
+
+        let mut map = TempoMap {
+            division,
+            tempo_segments,
+            meter_segments: vec![],
+        };
+
+        let first = TimeSignatureChange {
+            tick: 0,
+            numerator: 4,
+            denominator_power: 2,
+        };
+        for change in std::iter::once(&first).chain(time_signature_changes.iter()) {
+            if change.numerator == 0 {
+                bail!("zero time signature numerator at tick {}", change.tick);
+            }
+            if change.denominator_power > 6 {
+                bail!("time signature denominator out of range at tick {}", change.tick);
+            }
+            let quarters = map.ticks_to_quarters(change.tick);
+            let bar = match map.meter_segments.last() {
+                None => 0,
+                Some(prev) => {
+                    // A meter change in the middle of a bar starts a new bar.
+                    let bars = (quarters - prev.quarters) / prev.quarters_per_bar();
+                    prev.bar + (bars - 1e-9).ceil().max(0.0) as u64
+                }
+            };
+            let segment = MeterSegment {
+                quarters,
+                bar,
+                beats_per_bar: change.numerator,
+                quarters_per_beat: 4.0 / (1u32 << change.denominator_power) as f64,
+            };
+            match map.meter_segments.last_mut() {
+                Some(prev) if prev.quarters == quarters => *prev = segment,
+                _ => map.meter_segments.push(segment),
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn division(&self) -> Division {
+        self.division
+    }
+
+    /// The tempo in effect at a tick.
+    pub fn microseconds_per_quarter_at(&self, tick: u64) -> f64 {
+        let segment = self.tempo_segment_at_tick(tick as f64);
+        segment.seconds_per_tick / segment.quarters_per_tick * 1_000_000.0
+    }
+
+    pub fn ticks_to_seconds(&self, tick: u64) -> f64 {
+        self.fractional_ticks_to_seconds(tick as f64)
+    }
+
+    pub fn fractional_ticks_to_seconds(&self, tick: f64) -> f64 {
+        let segment = self.tempo_segment_at_tick(tick);
+        segment.seconds + (tick - segment.tick as f64) * segment.seconds_per_tick
+    }
+
+    /// Returns a possibly fractional tick position.
+    ///
+    /// Negative times are clamped to zero.
+    pub fn seconds_to_ticks(&self, seconds: f64) -> f64 {
+        let seconds = seconds.max(0.0);
+        let index = self.tempo_segments.partition_point(|segment| segment.seconds <= seconds);
+        let segment = &self.tempo_segments[index.saturating_sub(1)];
+        segment.tick as f64 + (seconds - segment.seconds) / segment.seconds_per_tick
+    }
+
+    /// The number of quarter notes elapsed at a tick.
+    pub fn ticks_to_quarters(&self, tick: u64) -> f64 {
+        let tick = tick as f64;
+        let segment = self.tempo_segment_at_tick(tick);
+        segment.quarters + (tick - segment.tick as f64) * segment.quarters_per_tick
+    }
+
+    /// Returns a possibly fractional tick position.
+    pub fn quarters_to_ticks(&self, quarters: f64) -> f64 {
+        let quarters = quarters.max(0.0);
+        let index = self.tempo_segments.partition_point(|segment| segment.quarters <= quarters);
+        let segment = &self.tempo_segments[index.saturating_sub(1)];
+        segment.tick as f64 + (quarters - segment.quarters) / segment.quarters_per_tick
+    }
+
+    pub fn ticks_to_bar_beat(&self, tick: u64) -> BarBeat {
+        let quarters = self.ticks_to_quarters(tick);
+        let index = self.meter_segments.partition_point(|segment| segment.quarters <= quarters);
+        let segment = &self.meter_segments[index.saturating_sub(1)];
+        let beats = (quarters - segment.quarters) / segment.quarters_per_beat;
+        let bars = (beats / segment.beats_per_bar as f64).floor();
+        let beat_in_bar = beats - bars * segment.beats_per_bar as f64;
+        let beat = beat_in_bar.floor();
+        BarBeat {
+            bar: segment.bar + bars as u64,
+            beat: beat as u32,
+            fraction: beat_in_bar - beat,
+        }
+    }
+
+    /// Returns a possibly fractional tick position.
+    ///
+    /// Beats past the end of a bar carry into the following bars
+    /// of the same time signature.
+    pub fn bar_beat_to_ticks(&self, position: BarBeat) -> f64 {
+        let index = self.meter_segments.partition_point(|segment| segment.bar <= position.bar);
+        let segment = &self.meter_segments[index.saturating_sub(1)];
+        let beats = (position.bar - segment.bar) as f64 * segment.beats_per_bar as f64
+            + position.beat as f64
+            + position.fraction;
+        self.quarters_to_ticks(segment.quarters + beats * segment.quarters_per_beat)
+    }
+
+    pub fn seconds_to_bar_beat(&self, seconds: f64) -> BarBeat {
+        self.ticks_to_bar_beat(self.seconds_to_ticks(seconds).round() as u64)
+    }
+
+    pub fn bar_beat_to_seconds(&self, position: BarBeat) -> f64 {
+        self.fractional_ticks_to_seconds(self.bar_beat_to_ticks(position))
+    }
+
+    fn tempo_segment_at_tick(&self, tick: f64) -> &TempoSegment {
+        let index = self.tempo_segments.partition_point(|segment| segment.tick as f64 <= tick);
+        &self.tempo_segments[index.saturating_sub(1)]
+    }
+}
+
+impl MeterSegment {
+    fn quarters_per_bar(&self) -> f64 {
+        self.beats_per_bar as f64 * self.quarters_per_beat
+    }
+}
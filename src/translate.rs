@@ -0,0 +1,651 @@
+//! Translation between MIDI 1.0 messages and MIDI 2.0 channel voice UMPs.
+//!
+//! Values are scaled up with the min-center-max algorithm,
+//! so the minimum, center and maximum of each range map to each other,
+//! and scaled down by discarding low bits.
+//!
+//! Reference: M2-104-UM appendix D, "Translation: MIDI 1.0 Messages and UMP Format"
+
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+use crate::assert_from::AssertFrom;
+use crate::control_number::ControlNumber;
+use crate::message::*;
+use crate::message::cvm::Unsigned7;
+use crate::parser::{MessageParseOutcomeStatus, Parser, system_status_bytes};
+use crate::ump::{self, UmpMessage, Group, Midi2ChannelVoiceMessage, SysEx7Packet, PacketForm};
+
+/// Scales a value up from `source_bits` to `destination_bits` wide.
+///
+/// Reference: M2-104-UM appendix D.3.2
+pub fn scale_up(value: u32, source_bits: u32, destination_bits: u32) -> u32 {
+    assert!(source_bits > 1 && source_bits <= destination_bits && destination_bits <= 32);
+    let scale_bits = destination_bits - source_bits;
+    let shifted = value << scale_bits;
+    let center = 1 << (source_bits - 1);
+    if value <= center {
+        return shifted;
+    }
+    // Fill the low bits by repeating the bits below the most significant bit.
+    let repeat_bits = source_bits - 1;
+    let repeat_mask = (1 << repeat_bits) - 1;
+    let mut repeat_value = value & repeat_mask;
+    if scale_bits > repeat_bits {
+        repeat_value <<= scale_bits - repeat_bits;
+    } else {
+        repeat_value >>= repeat_bits - scale_bits;
+    }
+    let mut result = shifted;
+    while repeat_value != 0 {
+        result |= repeat_value;
+        repeat_value >>= repeat_bits;
+    }
+    result
+}
+
+/// Scales a value down from `source_bits` to `destination_bits` wide.
+pub fn scale_down(value: u32, source_bits: u32, destination_bits: u32) -> u32 {
+    assert!(destination_bits <= source_bits && source_bits <= 32);
+    value >> (source_bits - destination_bits)
+}
+
+/// One step of 14-bit Data Entry, in the 32-bit resolution of MIDI 2.0 controllers.
+const DATA_STEP: i32 = 1 << 18;
+
+/// Which kind of parameter a channel's Data Entry controllers address.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ParameterSelection {
+    Registered,
+    Assignable,
+}
+
+/// MIDI 1.0 state per channel that determines how later messages translate.
+#[derive(Debug)]
+#[derive(Default)]
+struct Midi1ChannelState {
+    bank_msb: Option<u8>,
+    bank_lsb: Option<u8>,
+    rpn: [Option<u8>; 2],
+    nrpn: [Option<u8>; 2],
+    selection: Option<ParameterSelection>,
+    data_entry_msb: Option<u8>,
+}
+
+impl Midi1ChannelState {
+    /// The parameter Data Entry and Data Increment/Decrement address,
+    /// or `None` if none is fully selected or the null parameter is.
+    fn parameter(&self) -> Option<(ParameterSelection, Unsigned7, Unsigned7)> {
+        let selection = self.selection?;
+        let parameter = match selection {
+            ParameterSelection::Registered => self.rpn,
+            ParameterSelection::Assignable => self.nrpn,
+        };
+        match parameter {
+            // 127/127 is the null parameter, which disables Data Entry.
+            [Some(127), Some(127)] => None,
+            [Some(bank), Some(index)] => Some((selection, Unsigned7::assert_from(bank), Unsigned7::assert_from(index))),
+            _ => None,
+        }
+    }
+}
+
+/// Translates MIDI 1.0 messages to MIDI 2.0 protocol UMPs.
+///
+/// Bank Select is held until the next Program Change,
+/// and RPN and NRPN controller sequences are assembled
+/// into Registered and Assignable Controller messages.
+/// A controller message is sent when Data Entry MSB arrives,
+/// and again with full resolution when Data Entry LSB follows.
+/// Data Increment and Decrement become Relative Registered and Assignable Controller messages.
+pub struct Midi1ToMidi2 {
+    channels: HashMap<(Group, MidiChannelId), Midi1ChannelState>,
+    parser: Parser,
+    pending: Vec<u8>,
+}
+
+impl Default for Midi1ToMidi2 {
+    fn default() -> Midi1ToMidi2 {
+        Midi1ToMidi2::new()
+    }
+}
+
+impl Midi1ToMidi2 {
+    pub fn new() -> Midi1ToMidi2 {
+        Midi1ToMidi2 {
+            channels: HashMap::new(),
+            parser: Parser::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Translates a MIDI 1.0 byte stream, such as reads from a port, given in pieces of any size.
+    ///
+    /// Running status carries over between calls.
+    /// Stray data bytes and broken messages are skipped with a warning, as a receiver would.
+    pub fn translate_bytes(&mut self, group: Group, bytes: &[u8], out: &mut Vec<UmpMessage>) -> Result<()> {
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.extend_from_slice(bytes);
+        let result = self.translate_pending(group, &mut pending, out);
+        self.pending = pending;
+        result
+    }
+
+    fn translate_pending(&mut self, group: Group, pending: &mut Vec<u8>, out: &mut Vec<UmpMessage>) -> Result<()> {
+        while let Some(&status) = pending.first() {
+            // System common messages are parsed here, as the parser doesn't support them.
+            if (0xF1..=0xF6).contains(&status) {
+                let len = match status {
+                    0xF2 => 2,
+                    0xF1 | 0xF3 => 1,
+                    _ => 0,
+                };
+                let data_len = pending[1..].iter().take(len).take_while(|byte| **byte < 0x80).count();
+                if data_len < len && 1 + data_len == pending.len() {
+                    break;
+                }
+                let message = match (status, &pending[1..1 + data_len]) {
+                    (0xF1, [value]) => Some(ump::SystemMessage::MidiTimeCodeQuarterFrame(Unsigned7::assert_from(*value))),
+                    (0xF2, [lsb, msb]) => Some(ump::SystemMessage::SongPositionPointer(cvm::Unsigned14::assert_from([*lsb, *msb]))),
+                    (0xF3, [song]) => Some(ump::SystemMessage::SongSelect(Unsigned7::assert_from(*song))),
+                    (0xF6, []) => Some(ump::SystemMessage::TuneRequest),
+                    _ => None,
+                };
+                match message {
+                    Some(message) => out.push(UmpMessage::System { group, message }),
+                    None => log::warn!("skipping system common message {:02X?}", &pending[..1 + data_len]),
+                }
+                pending.drain(..1 + data_len);
+                // System common messages cancel running status.
+                self.parser = Parser::new();
+                continue;
+            }
+            let outcome = self.parser.parse(pending)?;
+            let consumed = outcome.bytes_consumed;
+            match outcome.status {
+                MessageParseOutcomeStatus::NeedMoreBytes(_) => break,
+                MessageParseOutcomeStatus::InterruptingSystemRealTimeMessage { byte_index, .. } => {
+                    self.translate_outcome(group, &outcome.status, out);
+                    pending.remove(byte_index);
+                    continue;
+                }
+                MessageParseOutcomeStatus::Message(_) | MessageParseOutcomeStatus::SystemExclusiveMessage(_) => {
+                    self.translate_outcome(group, &outcome.status, out);
+                }
+                status => log::warn!("skipping {:?} in {:02X?}", status, &pending[..consumed]),
+            }
+            pending.drain(..consumed);
+        }
+        Ok(())
+    }
+
+    /// Translates a parse outcome, if it holds a message or SysEx.
+    pub fn translate_outcome(&mut self, group: Group, status: &MessageParseOutcomeStatus, out: &mut Vec<UmpMessage>) {
+        match status {
+            MessageParseOutcomeStatus::Message(message) => self.translate(group, message, out),
+            MessageParseOutcomeStatus::SystemExclusiveMessage(data) => translate_sysex(group, data, out),
+            MessageParseOutcomeStatus::InterruptingSystemRealTimeMessage { message, .. } => {
+                out.push(UmpMessage::System {
+                    group,
+                    message: ump::SystemMessage::RealTime(*message),
+                });
+            }
+            _ => { }
+        }
+    }
+
+    pub fn translate(&mut self, group: Group, message: &Message, out: &mut Vec<UmpMessage>) {
+        match message {
+            Message::Channel(message) => self.translate_channel_message(group, message, out),
+            Message::System(SystemMessage::SystemRealTime(message)) => {
+                out.push(UmpMessage::System {
+                    group,
+                    message: ump::SystemMessage::RealTime(*message),
+                });
+            }
+            Message::System(_) => {
+                log::debug!("not translating system message {:?}", message);
+            }
+        }
+    }
+
+    fn translate_channel_message(&mut self, group: Group, message: &ChannelMessage, out: &mut Vec<UmpMessage>) {
+        use Midi2ChannelVoiceMessage as M2;
+        let channel = message.channel;
+        let state = self.channels.entry((group, channel)).or_default();
+        let mut send = |message| out.push(UmpMessage::Midi2ChannelVoice {
+            group,
+            channel,
+            message,
+        });
+        let up7 = |value: Unsigned7| scale_up(u8::from(value) as u32, 7, 32);
+
+        match &message.message {
+            ChannelMessageType::ChannelVoice(message) => match message {
+                ChannelVoiceMessage::NoteOff(m) => send(M2::NoteOff {
+                    note_number: m.note_number,
+                    velocity: scale_up(u8::from(m.velocity.0) as u32, 7, 16) as u16,
+                    attribute_type: 0,
+                    attribute: 0,
+                }),
+                ChannelVoiceMessage::NoteOn(m) if u8::from(m.velocity.0) == 0 => send(M2::NoteOff {
+                    note_number: m.note_number,
+                    // MIDI 1.0 treats Note On with zero velocity as Note Off with velocity 64.
+                    velocity: scale_up(64, 7, 16) as u16,
+                    attribute_type: 0,
+                    attribute: 0,
+                }),
+                ChannelVoiceMessage::NoteOn(m) => send(M2::NoteOn {
+                    note_number: m.note_number,
+                    velocity: scale_up(u8::from(m.velocity.0) as u32, 7, 16) as u16,
+                    attribute_type: 0,
+                    attribute: 0,
+                }),
+                ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(m) => send(M2::PolyPressure {
+                    note_number: m.note_number,
+                    data: up7(m.value),
+                }),
+                ChannelVoiceMessage::ControlChange(m) => {
+                    let number = u8::from(m.control_number.0);
+                    let value = u8::from(m.value);
+                    match ControlNumber::try_from(number) {
+                        Ok(ControlNumber::BankSelect) => state.bank_msb = Some(value),
+                        Ok(ControlNumber::BankSelectLSB) => state.bank_lsb = Some(value),
+                        Ok(ControlNumber::RegisteredParameterNumberMSB) => {
+                            state.rpn[0] = Some(value);
+                            state.selection = Some(ParameterSelection::Registered);
+                            state.data_entry_msb = None;
+                        }
+                        Ok(ControlNumber::RegisteredParameterNumberLSB) => {
+                            state.rpn[1] = Some(value);
+                            state.selection = Some(ParameterSelection::Registered);
+                            state.data_entry_msb = None;
+                        }
+                        Ok(ControlNumber::NonRegisteredParameterNumberMSB) => {
+                            state.nrpn[0] = Some(value);
+                            state.selection = Some(ParameterSelection::Assignable);
+                            state.data_entry_msb = None;
+                        }
+                        Ok(ControlNumber::NonRegisteredParameterNumberLSB) => {
+                            state.nrpn[1] = Some(value);
+                            state.selection = Some(ParameterSelection::Assignable);
+                            state.data_entry_msb = None;
+                        }
+                        Ok(ControlNumber::DataEntryMSB | ControlNumber::DataEntryLSB) => {
+                            let is_msb = number == u8::from(ControlNumber::DataEntryMSB);
+                            let value = match (is_msb, state.data_entry_msb) {
+                                (true, _) => {
+                                    state.data_entry_msb = Some(value);
+                                    Some((value as u32) << 7)
+                                }
+                                (false, Some(msb)) => Some((msb as u32) << 7 | value as u32),
+                                (false, None) => None,
+                            };
+                            match (state.parameter(), value) {
+                                (Some((selection, bank, index)), Some(value)) => {
+                                    let data = scale_up(value, 14, 32);
+                                    send(match selection {
+                                        ParameterSelection::Registered => M2::RegisteredController { bank, index, data },
+                                        ParameterSelection::Assignable => M2::AssignableController { bank, index, data },
+                                    });
+                                }
+                                _ => {
+                                    log::debug!("dropping data entry without a selected parameter");
+                                }
+                            }
+                        }
+                        Ok(ControlNumber::DataIncrement | ControlNumber::DataDecrement) => match state.parameter() {
+                            Some((selection, bank, index)) => {
+                                // The data byte is ignored; each message is one step of 14-bit Data Entry.
+                                let data = if number == u8::from(ControlNumber::DataIncrement) { DATA_STEP } else { -DATA_STEP };
+                                send(match selection {
+                                    ParameterSelection::Registered => M2::RelativeRegisteredController { bank, index, data },
+                                    ParameterSelection::Assignable => M2::RelativeAssignableController { bank, index, data },
+                                });
+                            }
+                            None => {
+                                log::debug!("dropping data increment without a selected parameter");
+                            }
+                        },
+                        _ => send(M2::ControlChange {
+                            index: m.control_number.0,
+                            data: up7(m.value),
+                        }),
+                    }
+                }
+                ChannelVoiceMessage::ProgramChange(m) => {
+                    let bank = match (state.bank_msb, state.bank_lsb) {
+                        (None, None) => None,
+                        (msb, lsb) => Some((
+                            Unsigned7::assert_from(msb.unwrap_or(0)),
+                            Unsigned7::assert_from(lsb.unwrap_or(0)),
+                        )),
+                    };
+                    send(M2::ProgramChange {
+                        program: m.program_number.0,
+                        bank,
+                    });
+                }
+                ChannelVoiceMessage::ChannelPressureAftertouch(m) => send(M2::ChannelPressure {
+                    data: up7(m.value),
+                }),
+                ChannelVoiceMessage::PitchBendChange(m) => send(M2::PitchBend {
+                    data: scale_up(u16::from(m.value) as u32, 14, 32),
+                }),
+            },
+            ChannelMessageType::ChannelMode { mode, value } => {
+                send(M2::ControlChange {
+                    index: Unsigned7::assert_from(u8::from(*mode)),
+                    data: up7(*value),
+                });
+            }
+        }
+    }
+}
+
+/// Translates a SysEx message, as returned by [`crate::parser::Parser::parse`],
+/// to SysEx7 UMPs.
+pub fn translate_sysex(group: Group, data: &[u8], out: &mut Vec<UmpMessage>) {
+    for message in packetize_sysex7(data) {
+        out.push(UmpMessage::Data64 {
+            group,
+            message,
+        });
+    }
+}
+
+/// Splits SysEx data, without `F0` and `F7`, into SysEx7 packets of up to 6 bytes.
+pub fn packetize_sysex7(data: &[u8]) -> Vec<SysEx7Packet> {
+    if data.len() <= 6 {
+        return vec![SysEx7Packet {
+            form: PacketForm::Complete,
+            data: data.to_vec(),
+        }];
+    }
+    let chunk_count = data.len().div_ceil(6);
+    data.chunks(6).enumerate().map(|(index, chunk)| {
+        let form = if index == 0 {
+            PacketForm::Start
+        } else if index == chunk_count - 1 {
+            PacketForm::End
+        } else {
+            PacketForm::Continue
+        };
+        SysEx7Packet {
+            form,
+            data: chunk.to_vec(),
+        }
+    }).collect()
+}
+
+/// MIDI 1.0 controller state per channel, to avoid resending parameter numbers.
+#[derive(Debug)]
+#[derive(Default)]
+struct Midi2ChannelState {
+    selected: Option<(ParameterSelection, u8, u8)>,
+}
+
+/// Translates MIDI 2.0 protocol UMPs to a MIDI 1.0 byte stream.
+///
+/// Values are scaled down, Registered and Assignable Controllers become
+/// RPN and NRPN sequences, Program Change with a bank becomes Bank Select
+/// followed by Program Change, and SysEx7 packets are reassembled.
+/// Relative controllers become Data Increment and Decrement of the selected parameter.
+#[derive(Default)]
+pub struct Midi2ToMidi1 {
+    channels: HashMap<(Group, MidiChannelId), Midi2ChannelState>,
+}
+
+impl Midi2ToMidi1 {
+    pub fn new() -> Midi2ToMidi1 {
+        Midi2ToMidi1::default()
+    }
+
+    /// Appends the MIDI 1.0 bytes for a UMP.
+    ///
+    /// Utility and UMP Stream messages, which concern the UMP transport itself, produce no bytes.
+    /// Messages with no MIDI 1.0 equivalent, such as per-note controllers, SysEx8 and Flex Data,
+    /// fail without appending anything, so the caller can skip them and continue.
+    pub fn translate(&mut self, message: &UmpMessage, out: &mut Vec<u8>) -> Result<()> {
+        match message {
+            UmpMessage::Midi1ChannelVoice { message, .. } => {
+                message.encode(out);
+            }
+            UmpMessage::System { message, .. } => {
+                message.encode(out);
+            }
+            UmpMessage::Data64 { message, .. } => {
+                if matches!(message.form, PacketForm::Complete | PacketForm::Start) {
+                    out.push(system_status_bytes::SYSTEM_EXCLUSIVE);
+                }
+                out.extend_from_slice(&message.data);
+                if matches!(message.form, PacketForm::Complete | PacketForm::End) {
+                    out.push(system_status_bytes::SYSTEM_END_OF_SYSTEM_EXCLUSIVE_FLAG);
+                }
+            }
+            UmpMessage::Midi2ChannelVoice { group, channel, message } => {
+                self.translate_channel_voice(*group, *channel, message, out)?;
+            }
+            UmpMessage::Utility(_) | UmpMessage::Stream(_) => { }
+            UmpMessage::Data128 { .. } | UmpMessage::FlexData { .. } | UmpMessage::Reserved(_) => {
+                bail!("no MIDI 1.0 equivalent for {:?}", message);
+            }
+        }
+        Ok(())
+    }
+
+    fn translate_channel_voice(
+        &mut self,
+        group: Group,
+        channel: MidiChannelId,
+        message: &Midi2ChannelVoiceMessage,
+        out: &mut Vec<u8>,
+    ) -> Result<()> {
+        use Midi2ChannelVoiceMessage as M2;
+        let state = self.channels.entry((group, channel)).or_default();
+        let voice = |message, out: &mut Vec<u8>| ChannelMessage {
+            channel,
+            message: ChannelMessageType::ChannelVoice(message),
+        }.encode(out);
+        let control_change = |number: ControlNumber, value: u8, out: &mut Vec<u8>| {
+            voice(ChannelVoiceMessage::ControlChange(cvm::ControlChange {
+                control_number: cvm::ControlNumber(Unsigned7::assert_from(u8::from(number))),
+                value: Unsigned7::assert_from(value),
+            }), out)
+        };
+        let down7 = |data: u32| Unsigned7::assert_from(scale_down(data, 32, 7) as u8);
+
+        match message {
+            M2::NoteOff { note_number, velocity, .. } => {
+                voice(ChannelVoiceMessage::NoteOff(cvm::NoteOff {
+                    note_number: *note_number,
+                    velocity: cvm::KeyVelocity(Unsigned7::assert_from(scale_down(*velocity as u32, 16, 7) as u8)),
+                }), out);
+            }
+            M2::NoteOn { note_number, velocity, .. } => {
+                // Zero velocity would mean Note Off in MIDI 1.0.
+                let velocity = (scale_down(*velocity as u32, 16, 7) as u8).max(1);
+                voice(ChannelVoiceMessage::NoteOn(cvm::NoteOn {
+                    note_number: *note_number,
+                    velocity: cvm::KeyVelocity(Unsigned7::assert_from(velocity)),
+                }), out);
+            }
+            M2::PolyPressure { note_number, data } => {
+                voice(ChannelVoiceMessage::PolyphonicKeyPressureAftertouch(cvm::PolyphonicKeyPressureAftertouch {
+                    note_number: *note_number,
+                    value: down7(*data),
+                }), out);
+            }
+            M2::ControlChange { index, data } => {
+                // Channel mode controllers pass through too, keeping their data,
+                // such as Local Control's on/off and Mono On's channel count.
+                let number = u8::from(*index);
+                if (u8::from(ControlNumber::NonRegisteredParameterNumberLSB)..=u8::from(ControlNumber::RegisteredParameterNumberMSB)).contains(&number) {
+                    // The receiver's parameter selection is no longer known.
+                    state.selected = None;
+                }
+                voice(ChannelVoiceMessage::ControlChange(cvm::ControlChange {
+                    control_number: cvm::ControlNumber(*index),
+                    value: down7(*data),
+                }), out);
+            }
+            M2::RegisteredController { bank, index, data } | M2::AssignableController { bank, index, data } => {
+                let selection = match message {
+                    M2::RegisteredController { .. } => ParameterSelection::Registered,
+                    _ => ParameterSelection::Assignable,
+                };
+                select_parameter(state, (selection, u8::from(*bank), u8::from(*index)), control_change, out);
+                let value = scale_down(*data, 32, 14);
+                control_change(ControlNumber::DataEntryMSB, (value >> 7) as u8, out);
+                control_change(ControlNumber::DataEntryLSB, (value & 0x7F) as u8, out);
+            }
+            M2::RelativeRegisteredController { bank, index, data } | M2::RelativeAssignableController { bank, index, data } => {
+                let selection = match message {
+                    M2::RelativeRegisteredController { .. } => ParameterSelection::Registered,
+                    _ => ParameterSelection::Assignable,
+                };
+                select_parameter(state, (selection, u8::from(*bank), u8::from(*index)), control_change, out);
+                // One message per step of 14-bit Data Entry, and at least one for any change.
+                let number = if *data < 0 { ControlNumber::DataDecrement } else { ControlNumber::DataIncrement };
+                let steps = if *data == 0 { 0 } else { (data.unsigned_abs() >> 18).max(1) };
+                for _ in 0..steps {
+                    control_change(number, 0, out);
+                }
+            }
+            M2::ProgramChange { program, bank } => {
+                if let Some((msb, lsb)) = bank {
+                    control_change(ControlNumber::BankSelect, u8::from(*msb), out);
+                    control_change(ControlNumber::BankSelectLSB, u8::from(*lsb), out);
+                }
+                voice(ChannelVoiceMessage::ProgramChange(cvm::ProgramChange {
+                    program_number: cvm::ProgramNumber(*program),
+                }), out);
+            }
+            M2::ChannelPressure { data } => {
+                voice(ChannelVoiceMessage::ChannelPressureAftertouch(cvm::ChannelPressureAftertouch {
+                    value: down7(*data),
+                }), out);
+            }
+            M2::PitchBend { data } => {
+                let value = scale_down(*data, 32, 14) as u16;
+                let bytes = [(value & 0x7F) as u8, (value >> 7) as u8];
+                voice(ChannelVoiceMessage::PitchBendChange(cvm::PitchBendChange {
+                    value: cvm::Unsigned14::assert_from(bytes),
+                }), out);
+            }
+            M2::RegisteredPerNoteController { .. }
+            | M2::AssignablePerNoteController { .. }
+            | M2::PerNotePitchBend { .. }
+            | M2::PerNoteManagement { .. } => {
+                bail!("no MIDI 1.0 equivalent for {:?}", message);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends RPN or NRPN selection controllers, unless the parameter is already selected.
+fn select_parameter(
+    state: &mut Midi2ChannelState,
+    selected: (ParameterSelection, u8, u8),
+    control_change: impl Fn(ControlNumber, u8, &mut Vec<u8>),
+    out: &mut Vec<u8>,
+) {
+    if state.selected == Some(selected) {
+        return;
+    }
+    let (msb, lsb) = match selected.0 {
+        ParameterSelection::Registered => {
+            (ControlNumber::RegisteredParameterNumberMSB, ControlNumber::RegisteredParameterNumberLSB)
+        }
+        ParameterSelection::Assignable => {
+            (ControlNumber::NonRegisteredParameterNumberMSB, ControlNumber::NonRegisteredParameterNumberLSB)
+        }
+    };
+    control_change(msb, selected.1, out);
+    control_change(lsb, selected.2, out);
+    state.selected = Some(selected);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group() -> Group {
+        Group::try_from(0).unwrap()
+    }
+
+    fn round_trip(bytes: &[u8], piece: usize) -> (Vec<UmpMessage>, Vec<u8>) {
+        let mut to_midi2 = Midi1ToMidi2::new();
+        let mut messages = vec![];
+        for piece in bytes.chunks(piece) {
+            to_midi2.translate_bytes(group(), piece, &mut messages).unwrap();
+        }
+        let mut to_midi1 = Midi2ToMidi1::new();
+        let mut out = vec![];
+        for message in &messages {
+            to_midi1.translate(message, &mut out).unwrap();
+        }
+        (messages, out)
+    }
+
+    #[test]
+    fn byte_stream_in_pieces() {
+        let bytes = [
+            0x90, 0x3C, 0x7F, 0x3E, 0x70,
+            0xF8,
+            0xF1, 0x20,
+            0xF2, 0x01, 0x02,
+            0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7,
+        ];
+        for piece in 1..=bytes.len() {
+            let (messages, out) = round_trip(&bytes, piece);
+            assert_eq!(messages.len(), 6);
+            assert_eq!(out, [
+                0x90, 0x3C, 0x7F, 0x90, 0x3E, 0x70,
+                0xF8,
+                0xF1, 0x20,
+                0xF2, 0x01, 0x02,
+                0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7,
+            ]);
+        }
+    }
+
+    #[test]
+    fn data_increment_keeps_parameter() {
+        let (messages, out) = round_trip(&[0xB0, 0x65, 0x00, 0x64, 0x02, 0x60, 0x00, 0x61, 0x00], 4);
+        let data: Vec<i32> = messages.iter().map(|message| match message {
+            UmpMessage::Midi2ChannelVoice {
+                message: Midi2ChannelVoiceMessage::RelativeRegisteredController { bank, index, data },
+                ..
+            } => {
+                assert_eq!((u8::from(*bank), u8::from(*index)), (0, 2));
+                *data
+            }
+            _ => panic!("unexpected {:?}", message),
+        }).collect();
+        assert_eq!(data, [DATA_STEP, -DATA_STEP]);
+        assert_eq!(out, [0xB0, 0x65, 0x00, 0xB0, 0x64, 0x02, 0xB0, 0x60, 0x00, 0xB0, 0x61, 0x00]);
+
+        // Without a selected parameter, there is nothing to increment.
+        assert_eq!(round_trip(&[0xB0, 0x60, 0x00], 3).0, []);
+    }
+
+    #[test]
+    fn unsupported_messages_fail() {
+        let mut to_midi1 = Midi2ToMidi1::new();
+        let mut out = vec![];
+        let per_note = UmpMessage::Midi2ChannelVoice {
+            group: group(),
+            channel: MidiChannelId::try_from(0).unwrap(),
+            message: Midi2ChannelVoiceMessage::PerNotePitchBend {
+                note_number: cvm::NoteNumber(Unsigned7::assert_from(60)),
+                data: 0x8000_0000,
+            },
+        };
+        assert!(to_midi1.translate(&per_note, &mut out).is_err());
+        assert!(to_midi1.translate(&UmpMessage::Reserved(vec![0xE000_0000, 0, 0]), &mut out).is_err());
+        to_midi1.translate(&UmpMessage::Utility(ump::UtilityMessage::Noop), &mut out).unwrap();
+        assert!(out.is_empty());
+    }
+}
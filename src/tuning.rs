@@ -0,0 +1,667 @@
+//! The MIDI Tuning Standard, and tables mapping notes to frequencies.
+//!
+//! MTS messages are Universal SysEx with sub-ID#1 `08`, non-real time or real time.
+//! Messages are parsed from and encoded to SysEx data without `F0` and `F7`,
+//! as returned by [`crate::parser::Parser::parse`].
+//!
+//! Reference: MIDI Tuning Standard, and its Bank and Scale/Octave extensions (CA-020, CA-021)
+
+use anyhow::{Result, anyhow, bail};
+use std::collections::HashMap;
+use crate::message::*;
+use crate::pitch_bend::BendRange;
+use crate::rpn::{self, Parameter, ParameterTracker, ParameterValue};
+
+pub const UNIVERSAL_NON_REALTIME: u8 = 0x7E;
+pub const UNIVERSAL_REALTIME: u8 = 0x7F;
+pub const SUB_ID_1_MIDI_TUNING_STANDARD: u8 = 0x08;
+
+/// The frequency of note 69, A4, in equal temperament.
+pub const CONCERT_A: f64 = 440.0;
+
+pub mod sub_id_2 {
+    pub const BULK_DUMP_REQUEST: u8 = 0x00;
+    pub const BULK_DUMP: u8 = 0x01;
+    pub const SINGLE_NOTE_TUNING_CHANGE: u8 = 0x02;
+    pub const BULK_DUMP_REQUEST_BANK: u8 = 0x03;
+    pub const KEY_BASED_TUNING_DUMP: u8 = 0x04;
+    pub const SCALE_OCTAVE_TUNING_DUMP_1_BYTE: u8 = 0x05;
+    pub const SCALE_OCTAVE_TUNING_DUMP_2_BYTE: u8 = 0x06;
+    pub const SINGLE_NOTE_TUNING_CHANGE_BANK: u8 = 0x07;
+    pub const SCALE_OCTAVE_TUNING_1_BYTE: u8 = 0x08;
+    pub const SCALE_OCTAVE_TUNING_2_BYTE: u8 = 0x09;
+}
+
+/// The pitch of a note, in semitones above note 0 of equal temperament.
+///
+/// Sent as a semitone and a 14-bit fraction of a semitone.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NoteTuning {
+    semitone: u8,
+    fraction: u16,
+}
+
+impl NoteTuning {
+    /// The highest pitch that can be sent: `7F 7F 7F` means "no change".
+    const MAX: NoteTuning = NoteTuning { semitone: 0x7F, fraction: 0x3FFE };
+
+    pub fn new(semitone: u8, fraction: u16) -> Result<NoteTuning> {
+        let tuning = NoteTuning { semitone, fraction };
+        if semitone > 0x7F || fraction > 0x3FFF || tuning > NoteTuning::MAX {
+            bail!("note tuning out of range: {} {}", semitone, fraction);
+        }
+        Ok(tuning)
+    }
+
+    /// The equal-tempered pitch of a note.
+    pub fn equal_tempered(note_number: cvm::NoteNumber) -> NoteTuning {
+        NoteTuning {
+            semitone: u8::from(note_number.0),
+            fraction: 0,
+        }
+    }
+
+    /// Rounds a pitch to the nearest step, clamping it to the range MTS can send.
+    pub fn from_semitones(semitones: f64) -> NoteTuning {
+        let steps = (semitones * 16384.0).round().clamp(0.0, 0x7F as f64 * 16384.0 + 0x3FFE as f64) as u32;
+        NoteTuning {
+            semitone: (steps / 16384) as u8,
+            fraction: (steps % 16384) as u16,
+        }
+    }
+
+    pub fn from_frequency(frequency: f64) -> NoteTuning {
+        NoteTuning::from_semitones(frequency_to_semitones(frequency, CONCERT_A))
+    }
+
+    pub fn semitone(self) -> u8 {
+        self.semitone
+    }
+
+    pub fn fraction(self) -> u16 {
+        self.fraction
+    }
+
+    pub fn to_semitones(self) -> f64 {
+        self.semitone as f64 + self.fraction as f64 / 16384.0
+    }
+
+    pub fn to_frequency(self) -> f64 {
+        semitones_to_frequency(self.to_semitones(), CONCERT_A)
+    }
+
+    /// Reads three bytes, `None` for `7F 7F 7F`.
+    fn read(bytes: [u8; 3]) -> Result<Option<NoteTuning>> {
+        if bytes == [0x7F; 3] {
+            return Ok(None);
+        }
+        if bytes.iter().any(|byte| *byte > 0x7F) {
+            bail!("note tuning out of 7-bit range");
+        }
+        Ok(Some(NoteTuning {
+            semitone: bytes[0],
+            fraction: (bytes[1] as u16) << 7 | bytes[2] as u16,
+        }))
+    }
+
+    fn write(tuning: Option<NoteTuning>, buf: &mut Vec<u8>) {
+        match tuning {
+            None => buf.extend([0x7F; 3]),
+            Some(tuning) => buf.extend([tuning.semitone, (tuning.fraction >> 7) as u8, (tuning.fraction & 0x7F) as u8]),
+        }
+    }
+}
+
+/// The frequency of a fractional note number, in equal temperament with A4 at `a4` Hz.
+pub fn semitones_to_frequency(semitones: f64, a4: f64) -> f64 {
+    a4 * 2f64.powf((semitones - 69.0) / 12.0)
+}
+
+/// The fractional note number of a frequency, in equal temperament with A4 at `a4` Hz.
+pub fn frequency_to_semitones(frequency: f64, a4: f64) -> f64 {
+    69.0 + 12.0 * (frequency / a4).log2()
+}
+
+/// Channels addressed by a scale/octave tuning message, bit 0 for channel 1.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelMask(pub u16);
+
+impl ChannelMask {
+    pub const ALL: ChannelMask = ChannelMask(0xFFFF);
+
+    pub fn contains(self, channel: MidiChannelId) -> bool {
+        self.0 & 1 << u8::from(channel) != 0
+    }
+
+    fn read(bytes: [u8; 3]) -> ChannelMask {
+        ChannelMask(((bytes[0] & 0x03) as u16) << 14 | ((bytes[1] & 0x7F) as u16) << 7 | (bytes[2] & 0x7F) as u16)
+    }
+
+    fn write(self, buf: &mut Vec<u8>) {
+        buf.extend([(self.0 >> 14 & 0x03) as u8, (self.0 >> 7 & 0x7F) as u8, (self.0 & 0x7F) as u8]);
+    }
+}
+
+/// Offsets of the twelve pitch classes from equal temperament, C first.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ScaleOctaveOffsets {
+    /// 1-byte form: `0x40` is no offset, each step is a cent, from -64 to +63.
+    OneByte([u8; 12]),
+    /// 2-byte form: `0x2000` is no offset, from -100 to almost +100 cents.
+    TwoByte([u16; 12]),
+}
+
+impl ScaleOctaveOffsets {
+    /// Rounds offsets in cents to the 2-byte form, clamping them.
+    pub fn from_cents(cents: [f64; 12]) -> ScaleOctaveOffsets {
+        ScaleOctaveOffsets::TwoByte(cents.map(|cents| {
+            (0x2000 as f64 + cents / 100.0 * 8192.0).round().clamp(0.0, 0x3FFF as f64) as u16
+        }))
+    }
+
+    pub fn to_cents(&self) -> [f64; 12] {
+        match self {
+            ScaleOctaveOffsets::OneByte(offsets) => offsets.map(|offset| offset as f64 - 64.0),
+            ScaleOctaveOffsets::TwoByte(offsets) => offsets.map(|offset| (offset as f64 - 8192.0) / 8192.0 * 100.0),
+        }
+    }
+
+    fn read(one_byte: bool, reader: &mut &[u8]) -> Result<ScaleOctaveOffsets> {
+        let len = if one_byte { 12 } else { 24 };
+        let bytes = take(reader, len)?;
+        if bytes.iter().any(|byte| *byte > 0x7F) {
+            bail!("scale/octave offset out of 7-bit range");
+        }
+        Ok(if one_byte {
+            ScaleOctaveOffsets::OneByte(bytes.try_into().expect("length"))
+        } else {
+            ScaleOctaveOffsets::TwoByte(std::array::from_fn(|index| {
+                (bytes[index * 2] as u16) << 7 | bytes[index * 2 + 1] as u16
+            }))
+        })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        match self {
+            ScaleOctaveOffsets::OneByte(offsets) => buf.extend(offsets.iter().map(|offset| offset & 0x7F)),
+            ScaleOctaveOffsets::TwoByte(offsets) => {
+                for offset in offsets {
+                    buf.extend([(offset >> 7 & 0x7F) as u8, (offset & 0x7F) as u8]);
+                }
+            }
+        }
+    }
+
+    fn is_one_byte(&self) -> bool {
+        matches!(self, ScaleOctaveOffsets::OneByte(_))
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum MtsBody {
+    /// Without a bank, requests a bulk dump; with one, a key-based tuning dump.
+    BulkDumpRequest {
+        bank: Option<u8>,
+        program: u8,
+    },
+    /// Without a bank, a bulk dump; with one, a key-based tuning dump.
+    BulkDump {
+        bank: Option<u8>,
+        program: u8,
+        /// Up to 16 ASCII characters.
+        name: String,
+        /// `None` leaves a note's tuning unchanged.
+        tunings: Box<[Option<NoteTuning>; 128]>,
+    },
+    ScaleOctaveTuningDump {
+        bank: u8,
+        program: u8,
+        name: String,
+        offsets: ScaleOctaveOffsets,
+    },
+    /// Without a bank, the original real time form.
+    SingleNoteTuningChange {
+        realtime: bool,
+        bank: Option<u8>,
+        program: u8,
+        changes: Vec<(cvm::NoteNumber, Option<NoteTuning>)>,
+    },
+    ScaleOctaveTuning {
+        realtime: bool,
+        channels: ChannelMask,
+        offsets: ScaleOctaveOffsets,
+    },
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct MtsMessage {
+    /// 0x7F for all devices.
+    pub device_id: u8,
+    pub body: MtsBody,
+}
+
+fn take<'buf>(reader: &mut &'buf [u8], len: usize) -> Result<&'buf [u8]> {
+    if reader.len() < len {
+        bail!("truncated MTS message");
+    }
+    let (bytes, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(bytes)
+}
+
+fn take_u8(reader: &mut &[u8]) -> Result<u8> {
+    let byte = take(reader, 1)?[0];
+    if byte > 0x7F {
+        bail!("MTS byte out of 7-bit range");
+    }
+    Ok(byte)
+}
+
+fn read_name(reader: &mut &[u8]) -> Result<String> {
+    let name = take(reader, 16)?;
+    if !name.is_ascii() {
+        bail!("tuning name isn't ASCII");
+    }
+    Ok(String::from_utf8_lossy(name).trim_end().to_string())
+}
+
+fn write_name(name: &str, buf: &mut Vec<u8>) -> Result<()> {
+    if !name.is_ascii() || name.len() > 16 || name.bytes().any(|byte| byte.is_ascii_control()) {
+        bail!("tuning name must be up to 16 printable ASCII characters: {:?}", name);
+    }
+    buf.extend(format!("{:16}", name).bytes());
+    Ok(())
+}
+
+/// The XOR of the message from the Universal SysEx ID, masked to seven bits.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum ^ byte) & 0x7F
+}
+
+impl MtsMessage {
+    pub fn is_mts(data: &[u8]) -> bool {
+        data.len() >= 4
+            && (data[0] == UNIVERSAL_NON_REALTIME || data[0] == UNIVERSAL_REALTIME)
+            && data[2] == SUB_ID_1_MIDI_TUNING_STANDARD
+    }
+
+    /// Parses SysEx data, without `F0` and `F7`, checking the checksums of dumps.
+    pub fn parse(data: &[u8]) -> Result<MtsMessage> {
+        if !MtsMessage::is_mts(data) {
+            bail!("not an MTS message");
+        }
+        let realtime = data[0] == UNIVERSAL_REALTIME;
+        let device_id = data[1];
+        let sub_id_2 = data[3];
+        let reader = &mut &data[4..];
+
+        let check_checksum = |reader: &mut &[u8]| -> Result<()> {
+            let expected = checksum(&data[..data.len() - reader.len()]);
+            let actual = take_u8(reader)?;
+            if actual != expected {
+                bail!("MTS checksum mismatch: expected {:02X}, found {:02X}", expected, actual);
+            }
+            Ok(())
+        };
+
+        let body = match (realtime, sub_id_2) {
+            (false, sub_id_2::BULK_DUMP_REQUEST) => MtsBody::BulkDumpRequest {
+                bank: None,
+                program: take_u8(reader)?,
+            },
+            (false, sub_id_2::BULK_DUMP_REQUEST_BANK) => MtsBody::BulkDumpRequest {
+                bank: Some(take_u8(reader)?),
+                program: take_u8(reader)?,
+            },
+            (false, sub_id_2::BULK_DUMP | sub_id_2::KEY_BASED_TUNING_DUMP) => {
+                let bank = if sub_id_2 == sub_id_2::KEY_BASED_TUNING_DUMP { Some(take_u8(reader)?) } else { None };
+                let program = take_u8(reader)?;
+                let name = read_name(reader)?;
+                let mut tunings = Box::new([None; 128]);
+                for tuning in tunings.iter_mut() {
+                    *tuning = NoteTuning::read(take(reader, 3)?.try_into().expect("length"))?;
+                }
+                check_checksum(reader)?;
+                MtsBody::BulkDump { bank, program, name, tunings }
+            }
+            (false, sub_id_2::SCALE_OCTAVE_TUNING_DUMP_1_BYTE | sub_id_2::SCALE_OCTAVE_TUNING_DUMP_2_BYTE) => {
+                let bank = take_u8(reader)?;
+                let program = take_u8(reader)?;
+                let name = read_name(reader)?;
+                let offsets = ScaleOctaveOffsets::read(sub_id_2 == sub_id_2::SCALE_OCTAVE_TUNING_DUMP_1_BYTE, reader)?;
+                check_checksum(reader)?;
+                MtsBody::ScaleOctaveTuningDump { bank, program, name, offsets }
+            }
+            (true, sub_id_2::SINGLE_NOTE_TUNING_CHANGE) | (_, sub_id_2::SINGLE_NOTE_TUNING_CHANGE_BANK) => {
+                let bank = if sub_id_2 == sub_id_2::SINGLE_NOTE_TUNING_CHANGE_BANK { Some(take_u8(reader)?) } else { None };
+                let program = take_u8(reader)?;
+                let count = take_u8(reader)?;
+                let changes = (0..count).map(|_| {
+                    let note_number = cvm::NoteNumber(cvm::Unsigned7::try_from(take_u8(reader)?)?);
+                    let tuning = NoteTuning::read(take(reader, 3)?.try_into().expect("length"))?;
+                    Ok((note_number, tuning))
+                }).collect::<Result<_>>()?;
+                MtsBody::SingleNoteTuningChange { realtime, bank, program, changes }
+            }
+            (_, sub_id_2::SCALE_OCTAVE_TUNING_1_BYTE | sub_id_2::SCALE_OCTAVE_TUNING_2_BYTE) => {
+                let channels = ChannelMask::read(take(reader, 3)?.try_into().expect("length"));
+                let offsets = ScaleOctaveOffsets::read(sub_id_2 == sub_id_2::SCALE_OCTAVE_TUNING_1_BYTE, reader)?;
+                MtsBody::ScaleOctaveTuning { realtime, channels, offsets }
+            }
+            _ => bail!("unknown MTS message {:02X} {:02X}", data[0], sub_id_2),
+        };
+        Ok(MtsMessage { device_id, body })
+    }
+
+    /// Appends the message as SysEx data, without `F0` and `F7`.
+    pub fn encode(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        let (realtime, sub_id_2) = match &self.body {
+            MtsBody::BulkDumpRequest { bank: None, .. } => (false, sub_id_2::BULK_DUMP_REQUEST),
+            MtsBody::BulkDumpRequest { bank: Some(_), .. } => (false, sub_id_2::BULK_DUMP_REQUEST_BANK),
+            MtsBody::BulkDump { bank: None, .. } => (false, sub_id_2::BULK_DUMP),
+            MtsBody::BulkDump { bank: Some(_), .. } => (false, sub_id_2::KEY_BASED_TUNING_DUMP),
+            MtsBody::ScaleOctaveTuningDump { offsets, .. } => (false, if offsets.is_one_byte() {
+                sub_id_2::SCALE_OCTAVE_TUNING_DUMP_1_BYTE
+            } else {
+                sub_id_2::SCALE_OCTAVE_TUNING_DUMP_2_BYTE
+            }),
+            MtsBody::SingleNoteTuningChange { realtime, bank: None, .. } => {
+                if !realtime {
+                    bail!("single note tuning change without a bank is real time only");
+                }
+                (true, sub_id_2::SINGLE_NOTE_TUNING_CHANGE)
+            }
+            MtsBody::SingleNoteTuningChange { realtime, bank: Some(_), .. } => (*realtime, sub_id_2::SINGLE_NOTE_TUNING_CHANGE_BANK),
+            MtsBody::ScaleOctaveTuning { realtime, offsets, .. } => (*realtime, if offsets.is_one_byte() {
+                sub_id_2::SCALE_OCTAVE_TUNING_1_BYTE
+            } else {
+                sub_id_2::SCALE_OCTAVE_TUNING_2_BYTE
+            }),
+        };
+        let universal_id = if realtime { UNIVERSAL_REALTIME } else { UNIVERSAL_NON_REALTIME };
+        buf.extend([universal_id, self.device_id, SUB_ID_1_MIDI_TUNING_STANDARD, sub_id_2]);
+
+        match &self.body {
+            MtsBody::BulkDumpRequest { bank, program } => {
+                buf.extend(*bank);
+                buf.push(*program);
+            }
+            MtsBody::BulkDump { bank, program, name, tunings } => {
+                buf.extend(*bank);
+                buf.push(*program);
+                write_name(name, buf)?;
+                for tuning in tunings.iter() {
+                    NoteTuning::write(*tuning, buf);
+                }
+                buf.push(checksum(&buf[start..]));
+            }
+            MtsBody::ScaleOctaveTuningDump { bank, program, name, offsets } => {
+                buf.extend([*bank, *program]);
+                write_name(name, buf)?;
+                offsets.write(buf);
+                buf.push(checksum(&buf[start..]));
+            }
+            MtsBody::SingleNoteTuningChange { bank, program, changes, .. } => {
+                buf.extend(*bank);
+                buf.push(*program);
+                let count = u8::try_from(changes.len()).ok().filter(|count| *count <= 0x7F)
+                    .ok_or_else(|| anyhow!("too many single note tuning changes: {}", changes.len()))?;
+                buf.push(count);
+                for (note_number, tuning) in changes {
+                    buf.push(u8::from(note_number.0));
+                    NoteTuning::write(*tuning, buf);
+                }
+            }
+            MtsBody::ScaleOctaveTuning { channels, offsets, .. } => {
+                channels.write(buf);
+                offsets.write(buf);
+            }
+        }
+
+        if buf[start..].iter().any(|byte| *byte > 0x7F) {
+            bail!("MTS field out of 7-bit range");
+        }
+        Ok(())
+    }
+
+    /// The complete SysEx message, including `F0` and `F7`.
+    pub fn to_sysex_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0xF0];
+        self.encode(&mut buf)?;
+        buf.push(0xF7);
+        Ok(buf)
+    }
+}
+
+/// The pitch of each of the 128 notes.
+#[derive(Debug)]
+#[derive(Clone, PartialEq)]
+pub struct TuningTable {
+    /// In fractional note numbers of equal temperament.
+    semitones: [f64; 128],
+}
+
+impl Default for TuningTable {
+    fn default() -> TuningTable {
+        TuningTable::equal_temperament()
+    }
+}
+
+impl TuningTable {
+    /// Twelve-tone equal temperament with A4 at 440 Hz.
+    pub fn equal_temperament() -> TuningTable {
+        TuningTable {
+            semitones: std::array::from_fn(|note| note as f64),
+        }
+    }
+
+    pub fn from_frequencies(frequencies: [f64; 128]) -> Result<TuningTable> {
+        if frequencies.iter().any(|frequency| !(*frequency > 0.0 && frequency.is_finite())) {
+            bail!("tuning frequencies must be positive");
+        }
+        Ok(TuningTable {
+            semitones: frequencies.map(|frequency| frequency_to_semitones(frequency, CONCERT_A)),
+        })
+    }
+
+    /// The pitch of a note, in fractional note numbers of equal temperament.
+    pub fn semitones(&self, note_number: cvm::NoteNumber) -> f64 {
+        self.semitones[u8::from(note_number.0) as usize]
+    }
+
+    pub fn frequency(&self, note_number: cvm::NoteNumber) -> f64 {
+        semitones_to_frequency(self.semitones(note_number), CONCERT_A)
+    }
+
+    /// The frequency of a note bent by `bend` semitones.
+    pub fn bent_frequency(&self, note_number: cvm::NoteNumber, bend: f64) -> f64 {
+        semitones_to_frequency(self.semitones(note_number) + bend, CONCERT_A)
+    }
+
+    /// The frequency of a note played with a Pitch Bend.
+    pub fn pitch(&self, note_number: cvm::NoteNumber, bend: &cvm::PitchBendChange, range: BendRange) -> f64 {
+        self.bent_frequency(note_number, bend.semitones(range) as f64)
+    }
+
+    pub fn set_semitones(&mut self, note_number: cvm::NoteNumber, semitones: f64) {
+        self.semitones[u8::from(note_number.0) as usize] = semitones;
+    }
+
+    pub fn set_frequency(&mut self, note_number: cvm::NoteNumber, frequency: f64) {
+        self.set_semitones(note_number, frequency_to_semitones(frequency, CONCERT_A));
+    }
+
+    /// A note's pitch, as close as MTS can send it.
+    pub fn note_tuning(&self, note_number: cvm::NoteNumber) -> NoteTuning {
+        NoteTuning::from_semitones(self.semitones(note_number))
+    }
+
+    /// Sets the notes a dump or change tunes, leaving `None` entries as they are.
+    pub fn apply_note_tunings<'a>(&mut self, tunings: impl IntoIterator<Item = (cvm::NoteNumber, &'a Option<NoteTuning>)>) {
+        for (note_number, tuning) in tunings {
+            if let Some(tuning) = tuning {
+                self.set_semitones(note_number, tuning.to_semitones());
+            }
+        }
+    }
+
+    /// Replaces the table with equal temperament offset per pitch class.
+    pub fn apply_scale_octave(&mut self, offsets: &ScaleOctaveOffsets) {
+        let cents = offsets.to_cents();
+        self.semitones = std::array::from_fn(|note| note as f64 + cents[note % 12] / 100.0);
+    }
+
+    /// A bulk dump, or key-based tuning dump with a bank, of the whole table.
+    pub fn to_bulk_dump(&self, device_id: u8, bank: Option<u8>, program: u8, name: &str) -> MtsMessage {
+        MtsMessage {
+            device_id,
+            body: MtsBody::BulkDump {
+                bank,
+                program,
+                name: name.to_string(),
+                tunings: Box::new(std::array::from_fn(|note| {
+                    Some(NoteTuning::from_semitones(self.semitones[note]))
+                })),
+            },
+        }
+    }
+
+    /// Real time single note tuning changes retuning every note, at most 127 per message.
+    pub fn to_single_note_changes(&self, device_id: u8, bank: Option<u8>, program: u8) -> Vec<MtsMessage> {
+        let changes: Vec<(cvm::NoteNumber, Option<NoteTuning>)> = (0..128u8).map(|note| {
+            let note_number = cvm::NoteNumber(cvm::Unsigned7::try_from(note).expect("note number"));
+            (note_number, Some(self.note_tuning(note_number)))
+        }).collect();
+        changes.chunks(0x7F).map(|changes| MtsMessage {
+            device_id,
+            body: MtsBody::SingleNoteTuningChange {
+                realtime: true,
+                bank,
+                program,
+                changes: changes.to_vec(),
+            },
+        }).collect()
+    }
+}
+
+fn note_numbers() -> impl Iterator<Item = cvm::NoteNumber> {
+    (0..128u8).map(|note| cvm::NoteNumber(cvm::Unsigned7::try_from(note).expect("note number")))
+}
+
+/// The Control Changes selecting a tuning program, and bank if given, through RPN 3 and 4.
+pub fn tuning_select_messages(channel: MidiChannelId, bank: Option<u8>, program: u8) -> Vec<ChannelMessage> {
+    let mut messages = vec![];
+    if let Some(bank) = bank {
+        messages.extend(rpn::parameter_messages(
+            channel,
+            Parameter::Registered(rpn::registered::TUNING_BANK_SELECT),
+            ParameterValue { msb: bank, lsb: 0 },
+        ));
+    }
+    messages.extend(rpn::parameter_messages(
+        channel,
+        Parameter::Registered(rpn::registered::TUNING_PROGRAM_CHANGE),
+        ParameterValue { msb: program, lsb: 0 },
+    ));
+    messages
+}
+
+/// A receiver's stored tuning programs and the tuning of each channel.
+///
+/// Tuning programs are selected per channel through RPN 3 and 4.
+/// Scale/octave tuning messages retune their channels directly.
+#[derive(Default)]
+pub struct Tuner {
+    programs: HashMap<(u8, u8), TuningTable>,
+    parameters: ParameterTracker,
+    /// The selected bank and program of each channel.
+    selected: HashMap<MidiChannelId, (u8, u8)>,
+    /// Banks selected since each channel's last program change.
+    pending_banks: HashMap<MidiChannelId, u8>,
+    /// Scale/octave tunings, overriding the selected program.
+    channel_tables: HashMap<MidiChannelId, TuningTable>,
+    equal_temperament: TuningTable,
+}
+
+impl Tuner {
+    pub fn new() -> Tuner {
+        Tuner::default()
+    }
+
+    /// Stores a tuning program, bank 0 being the bank of messages without one.
+    pub fn set_program(&mut self, bank: u8, program: u8, table: TuningTable) {
+        self.programs.insert((bank, program), table);
+    }
+
+    pub fn program(&self, bank: u8, program: u8) -> Option<&TuningTable> {
+        self.programs.get(&(bank, program))
+    }
+
+    /// The tuning of a channel: its scale/octave tuning, its selected program,
+    /// or equal temperament.
+    pub fn table(&self, channel: MidiChannelId) -> &TuningTable {
+        self.channel_tables.get(&channel)
+            .or_else(|| self.selected.get(&channel).and_then(|selected| self.programs.get(selected)))
+            .unwrap_or(&self.equal_temperament)
+    }
+
+    pub fn frequency(&self, channel: MidiChannelId, note_number: cvm::NoteNumber) -> f64 {
+        self.table(channel).frequency(note_number)
+    }
+
+    /// Follows RPN 3 and 4 tuning program selection.
+    ///
+    /// A tuning bank takes effect at the channel's next tuning program change,
+    /// and later program changes stay in that bank.
+    pub fn handle(&mut self, message: &ChannelMessage) {
+        let Some(change) = self.parameters.handle(message) else {
+            return;
+        };
+        match change.parameter {
+            Parameter::Registered(rpn::registered::TUNING_BANK_SELECT) => {
+                self.pending_banks.insert(change.channel, change.value.msb);
+            }
+            Parameter::Registered(rpn::registered::TUNING_PROGRAM_CHANGE) => {
+                let bank = self.pending_banks.remove(&change.channel)
+                    .or_else(|| self.selected.get(&change.channel).map(|(bank, _)| *bank))
+                    .unwrap_or(0);
+                self.selected.insert(change.channel, (bank, change.value.msb));
+                // Selecting a program replaces any scale/octave tuning.
+                self.channel_tables.remove(&change.channel);
+            }
+            _ => { }
+        }
+    }
+
+    /// Applies a tuning dump or change. Requests are ignored.
+    pub fn handle_mts(&mut self, message: &MtsMessage) {
+        match &message.body {
+            MtsBody::BulkDumpRequest { .. } => { }
+            MtsBody::BulkDump { bank, program, tunings, .. } => {
+                let table = self.programs.entry((bank.unwrap_or(0), *program)).or_default();
+                table.apply_note_tunings(note_numbers().zip(tunings.iter()));
+            }
+            MtsBody::ScaleOctaveTuningDump { bank, program, offsets, .. } => {
+                self.programs.entry((*bank, *program)).or_default().apply_scale_octave(offsets);
+            }
+            MtsBody::SingleNoteTuningChange { bank, program, changes, .. } => {
+                let table = self.programs.entry((bank.unwrap_or(0), *program)).or_default();
+                table.apply_note_tunings(changes.iter().map(|(note_number, tuning)| (*note_number, tuning)));
+            }
+            MtsBody::ScaleOctaveTuning { channels, offsets, .. } => {
+                for channel in 0..16 {
+                    let channel = MidiChannelId::try_from(channel).expect("channel");
+                    if channels.contains(channel) {
+                        self.channel_tables.entry(channel).or_default().apply_scale_octave(offsets);
+                    }
+                }
+            }
+        }
+    }
+}
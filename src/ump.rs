@@ -0,0 +1,1419 @@
+//! MIDI 2.0 Universal MIDI Packets.
+//!
+//! A UMP is one to four 32-bit words.
+//! The message type in the top nibble of the first word determines the packet size,
+//! and most message types carry a group number in the second nibble.
+//!
+//! Reference: M2-104-UM Universal MIDI Packet (UMP) Format and MIDI 2.0 Protocol 1.1
+
+use anyhow::{Result, anyhow, bail};
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use crate::assert_from::AssertFrom;
+use crate::message::*;
+use crate::message::cvm::{Unsigned7, Unsigned14};
+use crate::parser::{Parser, MessageParseOutcomeStatus};
+
+/// One of the 16 groups of a UMP endpoint.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Group(u8);
+
+impl TryFrom<u8> for Group {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Group> {
+        if value < 16 {
+            Ok(Group(value))
+        } else {
+            Err(anyhow!("Invalid UMP group {}", value))
+        }
+    }
+}
+
+impl From<Group> for u8 {
+    fn from(other: Group) -> u8 {
+        other.0
+    }
+}
+
+/// Reference: M2-104-UM table 4
+pub mod message_types {
+    pub const UTILITY: u8 = 0x0;
+    pub const SYSTEM: u8 = 0x1;
+    pub const MIDI1_CHANNEL_VOICE: u8 = 0x2;
+    pub const DATA_64: u8 = 0x3;
+    pub const MIDI2_CHANNEL_VOICE: u8 = 0x4;
+    pub const DATA_128: u8 = 0x5;
+    pub const FLEX_DATA: u8 = 0xD;
+    pub const UMP_STREAM: u8 = 0xF;
+}
+
+/// The number of 32-bit words in a packet, from its first word.
+pub fn packet_len(first_word: u32) -> usize {
+    match first_word >> 28 {
+        0x0 | 0x1 | 0x2 | 0x6 | 0x7 => 1,
+        0x3 | 0x4 | 0x8 | 0x9 | 0xA => 2,
+        0xB | 0xC => 3,
+        _ => 4,
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum UmpMessage {
+    Utility(UtilityMessage),
+    System {
+        group: Group,
+        message: SystemMessage,
+    },
+    Midi1ChannelVoice {
+        group: Group,
+        message: ChannelMessage,
+    },
+    /// SysEx7 data.
+    Data64 {
+        group: Group,
+        message: SysEx7Packet,
+    },
+    Midi2ChannelVoice {
+        group: Group,
+        channel: MidiChannelId,
+        message: Midi2ChannelVoiceMessage,
+    },
+    Data128 {
+        group: Group,
+        message: Data128Message,
+    },
+    FlexData {
+        group: Group,
+        message: FlexDataMessage,
+    },
+    Stream(StreamMessage),
+    /// A packet of a message type reserved for future use.
+    Reserved(Vec<u32>),
+}
+
+/// Reference: M2-104-UM section 7.2
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum UtilityMessage {
+    Noop,
+    JitterReductionClock {
+        sender_clock_time: u16,
+    },
+    JitterReductionTimestamp {
+        sender_clock_timestamp: u16,
+    },
+    DeltaClockstampTicksPerQuarter {
+        ticks_per_quarter: u16,
+    },
+    DeltaClockstamp {
+        /// 20-bit tick count since the last event.
+        ticks: u32,
+    },
+    Other {
+        status: u8,
+        /// The low 20 bits of the packet.
+        data: u32,
+    },
+}
+
+/// System common and real time messages, which UMP carries without SysEx.
+///
+/// Reference: M2-104-UM section 7.6
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum SystemMessage {
+    MidiTimeCodeQuarterFrame(Unsigned7),
+    SongPositionPointer(Unsigned14),
+    SongSelect(Unsigned7),
+    TuneRequest,
+    RealTime(SystemRealTimeMessage),
+}
+
+/// The position of a packet within a multi-packet message.
+///
+/// Used for SysEx data, and as the `form` of Flex Data and UMP Stream messages.
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum PacketForm {
+    Complete = 0,
+    Start = 1,
+    Continue = 2,
+    End = 3,
+}
+
+/// Reference: M2-104-UM section 7.7
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct SysEx7Packet {
+    pub form: PacketForm,
+    /// Up to 6 bytes of 7-bit SysEx data, without `F0` and `F7`.
+    pub data: Vec<u8>,
+}
+
+/// Reference: M2-104-UM section 7.4
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum Midi2ChannelVoiceMessage {
+    RegisteredPerNoteController {
+        note_number: cvm::NoteNumber,
+        index: u8,
+        data: u32,
+    },
+    AssignablePerNoteController {
+        note_number: cvm::NoteNumber,
+        index: u8,
+        data: u32,
+    },
+    /// Formerly RPN.
+    RegisteredController {
+        bank: Unsigned7,
+        index: Unsigned7,
+        data: u32,
+    },
+    /// Formerly NRPN.
+    AssignableController {
+        bank: Unsigned7,
+        index: Unsigned7,
+        data: u32,
+    },
+    RelativeRegisteredController {
+        bank: Unsigned7,
+        index: Unsigned7,
+        data: i32,
+    },
+    RelativeAssignableController {
+        bank: Unsigned7,
+        index: Unsigned7,
+        data: i32,
+    },
+    PerNotePitchBend {
+        note_number: cvm::NoteNumber,
+        data: u32,
+    },
+    NoteOff {
+        note_number: cvm::NoteNumber,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    NoteOn {
+        note_number: cvm::NoteNumber,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    PolyPressure {
+        note_number: cvm::NoteNumber,
+        data: u32,
+    },
+    ControlChange {
+        index: Unsigned7,
+        data: u32,
+    },
+    ProgramChange {
+        program: Unsigned7,
+        /// Bank Select MSB and LSB, if the bank valid flag is set.
+        bank: Option<(Unsigned7, Unsigned7)>,
+    },
+    ChannelPressure {
+        data: u32,
+    },
+    PitchBend {
+        data: u32,
+    },
+    PerNoteManagement {
+        note_number: cvm::NoteNumber,
+        detach: bool,
+        reset: bool,
+    },
+}
+
+/// Reference: M2-104-UM section 7.8
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum Data128Message {
+    SysEx8 {
+        form: PacketForm,
+        stream_id: u8,
+        /// Up to 13 bytes of 8-bit data.
+        data: Vec<u8>,
+    },
+    MixedDataSetHeader {
+        mds_id: u8,
+        bytes_in_chunk: u16,
+        chunk_count: u16,
+        chunk_number: u16,
+        manufacturer_id: u16,
+        device_id: u16,
+        sub_id_1: u16,
+        sub_id_2: u16,
+    },
+    MixedDataSetPayload {
+        mds_id: u8,
+        data: [u8; 14],
+    },
+}
+
+/// Reference: M2-104-UM section 7.5
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct FlexDataMessage {
+    pub form: PacketForm,
+    /// The channel the message applies to, or `None` for the whole group.
+    pub channel: Option<MidiChannelId>,
+    pub payload: FlexDataPayload,
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum FlexDataPayload {
+    SetTempo {
+        ten_nanoseconds_per_quarter: u32,
+    },
+    SetTimeSignature {
+        numerator: u8,
+        denominator_power: u8,
+        thirty_seconds_per_quarter: u8,
+    },
+    SetKeySignature {
+        /// Negative for flats, positive for sharps, from -8 to 7.
+        sharps: i8,
+        /// The tonic, from 1 for A to 7 for G, or 0 if unknown.
+        tonic: u8,
+    },
+    /// Metadata text (status bank 1) or performance text such as lyrics (status bank 2).
+    Text {
+        status_bank: u8,
+        status: u8,
+        /// Up to 12 bytes of UTF-8 text, without trailing NULs.
+        text: Vec<u8>,
+    },
+    Other {
+        status_bank: u8,
+        status: u8,
+        data: [u32; 3],
+    },
+}
+
+/// Reference: M2-104-UM section 7.1
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum StreamMessage {
+    EndpointDiscovery {
+        ump_version: (u8, u8),
+        filter: u8,
+    },
+    EndpointInfoNotification {
+        ump_version: (u8, u8),
+        static_function_blocks: bool,
+        function_block_count: u8,
+        midi2_protocol: bool,
+        midi1_protocol: bool,
+        receive_jitter_reduction: bool,
+        transmit_jitter_reduction: bool,
+    },
+    DeviceIdentityNotification {
+        manufacturer_id: [u8; 3],
+        /// LSB first, as in a SysEx Identity Reply.
+        family: [u8; 2],
+        model: [u8; 2],
+        software_revision: [u8; 4],
+    },
+    EndpointNameNotification {
+        form: PacketForm,
+        /// Up to 14 bytes of UTF-8 text, without trailing NULs.
+        text: Vec<u8>,
+    },
+    ProductInstanceIdNotification {
+        form: PacketForm,
+        text: Vec<u8>,
+    },
+    StreamConfigurationRequest {
+        protocol: u8,
+        receive_jitter_reduction: bool,
+        transmit_jitter_reduction: bool,
+    },
+    StreamConfigurationNotification {
+        protocol: u8,
+        receive_jitter_reduction: bool,
+        transmit_jitter_reduction: bool,
+    },
+    FunctionBlockDiscovery {
+        function_block: u8,
+        filter: u8,
+    },
+    FunctionBlockInfoNotification {
+        active: bool,
+        function_block: u8,
+        ui_hint: u8,
+        midi1: u8,
+        direction: u8,
+        first_group: Group,
+        group_count: u8,
+        midi_ci_version: u8,
+        max_sysex8_streams: u8,
+    },
+    FunctionBlockNameNotification {
+        form: PacketForm,
+        function_block: u8,
+        /// Up to 13 bytes of UTF-8 text, without trailing NULs.
+        text: Vec<u8>,
+    },
+    StartOfClip,
+    EndOfClip,
+    Other {
+        form: PacketForm,
+        status: u16,
+        words: [u32; 4],
+    },
+}
+
+mod utility_statuses {
+    pub const NOOP: u8 = 0x0;
+    pub const JR_CLOCK: u8 = 0x1;
+    pub const JR_TIMESTAMP: u8 = 0x2;
+    pub const DELTA_CLOCKSTAMP_TPQ: u8 = 0x3;
+    pub const DELTA_CLOCKSTAMP: u8 = 0x4;
+}
+
+mod midi2_statuses {
+    pub const REGISTERED_PER_NOTE_CONTROLLER: u8 = 0x0;
+    pub const ASSIGNABLE_PER_NOTE_CONTROLLER: u8 = 0x1;
+    pub const REGISTERED_CONTROLLER: u8 = 0x2;
+    pub const ASSIGNABLE_CONTROLLER: u8 = 0x3;
+    pub const RELATIVE_REGISTERED_CONTROLLER: u8 = 0x4;
+    pub const RELATIVE_ASSIGNABLE_CONTROLLER: u8 = 0x5;
+    pub const PER_NOTE_PITCH_BEND: u8 = 0x6;
+    pub const NOTE_OFF: u8 = 0x8;
+    pub const NOTE_ON: u8 = 0x9;
+    pub const POLY_PRESSURE: u8 = 0xA;
+    pub const CONTROL_CHANGE: u8 = 0xB;
+    pub const PROGRAM_CHANGE: u8 = 0xC;
+    pub const CHANNEL_PRESSURE: u8 = 0xD;
+    pub const PITCH_BEND: u8 = 0xE;
+    pub const PER_NOTE_MANAGEMENT: u8 = 0xF;
+}
+
+mod data128_statuses {
+    pub const SYSEX8_LAST_FORM: u8 = 0x3;
+    pub const MIXED_DATA_SET_HEADER: u8 = 0x8;
+    pub const MIXED_DATA_SET_PAYLOAD: u8 = 0x9;
+}
+
+mod flex_data_statuses {
+    pub const SETUP_AND_PERFORMANCE_BANK: u8 = 0x00;
+    pub const METADATA_TEXT_BANK: u8 = 0x01;
+    pub const PERFORMANCE_TEXT_BANK: u8 = 0x02;
+    pub const SET_TEMPO: u8 = 0x00;
+    pub const SET_TIME_SIGNATURE: u8 = 0x01;
+    pub const SET_KEY_SIGNATURE: u8 = 0x05;
+}
+
+mod stream_statuses {
+    pub const ENDPOINT_DISCOVERY: u16 = 0x00;
+    pub const ENDPOINT_INFO_NOTIFICATION: u16 = 0x01;
+    pub const DEVICE_IDENTITY_NOTIFICATION: u16 = 0x02;
+    pub const ENDPOINT_NAME_NOTIFICATION: u16 = 0x03;
+    pub const PRODUCT_INSTANCE_ID_NOTIFICATION: u16 = 0x04;
+    pub const STREAM_CONFIGURATION_REQUEST: u16 = 0x05;
+    pub const STREAM_CONFIGURATION_NOTIFICATION: u16 = 0x06;
+    pub const FUNCTION_BLOCK_DISCOVERY: u16 = 0x10;
+    pub const FUNCTION_BLOCK_INFO_NOTIFICATION: u16 = 0x11;
+    pub const FUNCTION_BLOCK_NAME_NOTIFICATION: u16 = 0x12;
+    pub const START_OF_CLIP: u16 = 0x20;
+    pub const END_OF_CLIP: u16 = 0x21;
+}
+
+impl UmpMessage {
+    /// Parses the first packet of `words`.
+    ///
+    /// Returns the message and the number of words consumed,
+    /// or `None` if `words` doesn't hold a whole packet.
+    pub fn parse_first(words: &[u32]) -> Result<Option<(UmpMessage, usize)>> {
+        let Some(&first) = words.first() else {
+            return Ok(None);
+        };
+        let len = packet_len(first);
+        match words.get(..len) {
+            None => Ok(None),
+            Some(packet) => Ok(Some((UmpMessage::parse(packet)?, len))),
+        }
+    }
+
+    /// Parses exactly one packet.
+    pub fn parse(packet: &[u32]) -> Result<UmpMessage> {
+        let first = *packet.first().ok_or_else(|| anyhow!("empty packet"))?;
+        if packet.len() != packet_len(first) {
+            bail!("packet of type {:X} has {} words", first >> 28, packet.len());
+        }
+        let message_type = (first >> 28) as u8;
+        let group = Group(nibble(first, 24));
+
+        Ok(match message_type {
+            message_types::UTILITY => UmpMessage::Utility(parse_utility(first)),
+            message_types::SYSTEM => UmpMessage::System {
+                group,
+                message: parse_system(first)?,
+            },
+            message_types::MIDI1_CHANNEL_VOICE => UmpMessage::Midi1ChannelVoice {
+                group,
+                message: parse_midi1_channel_voice(first)?,
+            },
+            message_types::DATA_64 => UmpMessage::Data64 {
+                group,
+                message: parse_sysex7(packet)?,
+            },
+            message_types::MIDI2_CHANNEL_VOICE => UmpMessage::Midi2ChannelVoice {
+                group,
+                channel: MidiChannelId::assert_from(nibble(first, 16)),
+                message: parse_midi2_channel_voice(packet)?,
+            },
+            message_types::DATA_128 => UmpMessage::Data128 {
+                group,
+                message: parse_data128(packet)?,
+            },
+            message_types::FLEX_DATA => UmpMessage::FlexData {
+                group,
+                message: parse_flex_data(packet)?,
+            },
+            message_types::UMP_STREAM => UmpMessage::Stream(parse_stream(packet)?),
+            _ => UmpMessage::Reserved(packet.to_vec()),
+        })
+    }
+
+    /// Parses a sequence of whole packets.
+    pub fn parse_all(mut words: &[u32]) -> Result<Vec<UmpMessage>> {
+        let mut messages = vec![];
+        while !words.is_empty() {
+            let (message, len) = UmpMessage::parse_first(words)?
+                .ok_or_else(|| anyhow!("truncated packet"))?;
+            messages.push(message);
+            words = &words[len..];
+        }
+        Ok(messages)
+    }
+
+    /// Appends the message's packet.
+    pub fn encode(&self, out: &mut Vec<u32>) -> Result<()> {
+        match self {
+            UmpMessage::Utility(message) => {
+                out.push(encode_utility(*message)?);
+            }
+            UmpMessage::System { group, message } => {
+                out.push(header(message_types::SYSTEM, *group) | encode_system(*message));
+            }
+            UmpMessage::Midi1ChannelVoice { group, message } => {
+                let mut bytes = Vec::with_capacity(3);
+                message.encode(&mut bytes);
+                bytes.resize(3, 0);
+                out.push(header(message_types::MIDI1_CHANNEL_VOICE, *group) | bytes_to_word(0, &bytes) >> 8);
+            }
+            UmpMessage::Data64 { group, message } => {
+                encode_sysex7(*group, message, out)?;
+            }
+            UmpMessage::Midi2ChannelVoice { group, channel, message } => {
+                encode_midi2_channel_voice(*group, *channel, message, out);
+            }
+            UmpMessage::Data128 { group, message } => {
+                encode_data128(*group, message, out)?;
+            }
+            UmpMessage::FlexData { group, message } => {
+                encode_flex_data(*group, message, out)?;
+            }
+            UmpMessage::Stream(message) => {
+                encode_stream(message, out)?;
+            }
+            UmpMessage::Reserved(words) => {
+                match words.first() {
+                    Some(&first) if words.len() == packet_len(first) => out.extend_from_slice(words),
+                    _ => bail!("malformed reserved packet"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_words(&self) -> Result<Vec<u32>> {
+        let mut out = vec![];
+        self.encode(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn group(&self) -> Option<Group> {
+        match self {
+            UmpMessage::Utility(_) => None,
+            UmpMessage::System { group, .. } => Some(*group),
+            UmpMessage::Midi1ChannelVoice { group, .. } => Some(*group),
+            UmpMessage::Data64 { group, .. } => Some(*group),
+            UmpMessage::Midi2ChannelVoice { group, .. } => Some(*group),
+            UmpMessage::Data128 { group, .. } => Some(*group),
+            UmpMessage::FlexData { group, .. } => Some(*group),
+            UmpMessage::Stream(_) => None,
+            UmpMessage::Reserved(_) => None,
+        }
+    }
+}
+
+fn nibble(word: u32, shift: u32) -> u8 {
+    (word >> shift) as u8 & 0xF
+}
+
+fn byte(word: u32, shift: u32) -> u8 {
+    (word >> shift) as u8
+}
+
+fn header(message_type: u8, group: Group) -> u32 {
+    (message_type as u32) << 28 | (group.0 as u32) << 24
+}
+
+fn u7(byte: u8) -> Result<Unsigned7> {
+    Unsigned7::try_from(byte)
+}
+
+/// Packs up to 4 bytes into a word, most significant first, starting at byte `start`.
+fn bytes_to_word(start: usize, bytes: &[u8]) -> u32 {
+    let mut word = 0;
+    for (index, byte) in bytes.iter().enumerate().take(4 - start) {
+        word |= (*byte as u32) << (24 - 8 * (start + index));
+    }
+    word
+}
+
+/// The bytes of a packet from byte offset `start`, most significant first.
+fn packet_bytes(packet: &[u32], start: usize, len: usize) -> Vec<u8> {
+    packet.iter()
+        .flat_map(|word| word.to_be_bytes())
+        .skip(start)
+        .take(len)
+        .collect()
+}
+
+/// Writes bytes into a packet from byte offset `start`, most significant first.
+fn write_packet_bytes(packet: &mut [u32], start: usize, bytes: &[u8]) {
+    for (index, byte) in bytes.iter().enumerate() {
+        let offset = start + index;
+        packet[offset / 4] |= (*byte as u32) << (24 - 8 * (offset % 4));
+    }
+}
+
+fn trim_nuls(mut text: Vec<u8>) -> Vec<u8> {
+    while text.last() == Some(&0) {
+        text.pop();
+    }
+    text
+}
+
+fn parse_utility(word: u32) -> UtilityMessage {
+    let status = nibble(word, 20);
+    match status {
+        utility_statuses::NOOP => UtilityMessage::Noop,
+        utility_statuses::JR_CLOCK => UtilityMessage::JitterReductionClock {
+            sender_clock_time: word as u16,
+        },
+        utility_statuses::JR_TIMESTAMP => UtilityMessage::JitterReductionTimestamp {
+            sender_clock_timestamp: word as u16,
+        },
+        utility_statuses::DELTA_CLOCKSTAMP_TPQ => UtilityMessage::DeltaClockstampTicksPerQuarter {
+            ticks_per_quarter: word as u16,
+        },
+        utility_statuses::DELTA_CLOCKSTAMP => UtilityMessage::DeltaClockstamp {
+            ticks: word & 0xF_FFFF,
+        },
+        _ => UtilityMessage::Other {
+            status,
+            data: word & 0xF_FFFF,
+        },
+    }
+}
+
+fn encode_utility(message: UtilityMessage) -> Result<u32> {
+    let (status, data) = match message {
+        UtilityMessage::Noop => (utility_statuses::NOOP, 0),
+        UtilityMessage::JitterReductionClock { sender_clock_time } => {
+            (utility_statuses::JR_CLOCK, sender_clock_time as u32)
+        }
+        UtilityMessage::JitterReductionTimestamp { sender_clock_timestamp } => {
+            (utility_statuses::JR_TIMESTAMP, sender_clock_timestamp as u32)
+        }
+        UtilityMessage::DeltaClockstampTicksPerQuarter { ticks_per_quarter } => {
+            (utility_statuses::DELTA_CLOCKSTAMP_TPQ, ticks_per_quarter as u32)
+        }
+        UtilityMessage::DeltaClockstamp { ticks } => (utility_statuses::DELTA_CLOCKSTAMP, ticks),
+        UtilityMessage::Other { status, data } => (status, data),
+    };
+    if status > 0xF || data > 0xF_FFFF {
+        bail!("utility message field out of range");
+    }
+    Ok((status as u32) << 20 | data)
+}
+
+fn parse_system(word: u32) -> Result<SystemMessage> {
+    let status = byte(word, 16);
+    let data_1 = byte(word, 8);
+    let data_2 = byte(word, 0);
+    Ok(match status {
+        0xF1 => SystemMessage::MidiTimeCodeQuarterFrame(u7(data_1)?),
+        0xF2 => SystemMessage::SongPositionPointer(Unsigned14::try_from([data_1, data_2])?),
+        0xF3 => SystemMessage::SongSelect(u7(data_1)?),
+        0xF6 => SystemMessage::TuneRequest,
+        _ => SystemMessage::RealTime(
+            SystemRealTimeMessage::try_from(status)
+                .map_err(|_| anyhow!("invalid UMP system status {:02X}", status))?
+        ),
+    })
+}
+
+fn encode_system(message: SystemMessage) -> u32 {
+    let (status, data_1, data_2) = match message {
+        SystemMessage::MidiTimeCodeQuarterFrame(value) => (0xF1, u8::from(value), 0),
+        SystemMessage::SongPositionPointer(value) => {
+            let value = u16::from(value);
+            (0xF2, (value & 0x7F) as u8, (value >> 7) as u8)
+        }
+        SystemMessage::SongSelect(value) => (0xF3, u8::from(value), 0),
+        SystemMessage::TuneRequest => (0xF6, 0, 0),
+        SystemMessage::RealTime(message) => (u8::from(message), 0, 0),
+    };
+    (status as u32) << 16 | (data_1 as u32) << 8 | data_2 as u32
+}
+
+impl SystemMessage {
+    /// Appends the message's MIDI 1.0 bytes.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let word = encode_system(*self);
+        let bytes = &word.to_be_bytes()[1..];
+        let len = match bytes[0] {
+            0xF2 => 3,
+            0xF1 | 0xF3 => 2,
+            _ => 1,
+        };
+        buf.extend_from_slice(&bytes[..len]);
+    }
+}
+
+fn parse_midi1_channel_voice(word: u32) -> Result<ChannelMessage> {
+    let bytes = [byte(word, 16), byte(word, 8), byte(word, 0)];
+    if !(0x80..0xF0).contains(&bytes[0]) {
+        bail!("invalid MIDI 1.0 channel voice status {:02X}", bytes[0]);
+    }
+    match Parser::new().parse(&bytes)?.status {
+        MessageParseOutcomeStatus::Message(Message::Channel(message)) => Ok(message),
+        _ => Err(anyhow!("malformed MIDI 1.0 channel voice packet {:08X}", word)),
+    }
+}
+
+fn parse_sysex7(packet: &[u32]) -> Result<SysEx7Packet> {
+    let form = PacketForm::try_from(nibble(packet[0], 20))
+        .map_err(|_| anyhow!("invalid SysEx7 status"))?;
+    let len = nibble(packet[0], 16) as usize;
+    if len > 6 {
+        bail!("SysEx7 packet with {} bytes", len);
+    }
+    Ok(SysEx7Packet {
+        form,
+        data: packet_bytes(packet, 2, len),
+    })
+}
+
+fn encode_sysex7(group: Group, message: &SysEx7Packet, out: &mut Vec<u32>) -> Result<()> {
+    if message.data.len() > 6 {
+        bail!("SysEx7 packet with {} bytes", message.data.len());
+    }
+    let mut packet = [
+        header(message_types::DATA_64, group)
+            | (u8::from(message.form) as u32) << 20
+            | (message.data.len() as u32) << 16,
+        0,
+    ];
+    write_packet_bytes(&mut packet, 2, &message.data);
+    out.extend_from_slice(&packet);
+    Ok(())
+}
+
+fn parse_midi2_channel_voice(packet: &[u32]) -> Result<Midi2ChannelVoiceMessage> {
+    use Midi2ChannelVoiceMessage as M;
+    let (first, data) = (packet[0], packet[1]);
+    let status = nibble(first, 20);
+    let index_msb = byte(first, 8);
+    let index_lsb = byte(first, 0);
+    let note = || Ok::<_, anyhow::Error>(cvm::NoteNumber(u7(index_msb)?));
+    Ok(match status {
+        midi2_statuses::REGISTERED_PER_NOTE_CONTROLLER => M::RegisteredPerNoteController {
+            note_number: note()?,
+            index: index_lsb,
+            data,
+        },
+        midi2_statuses::ASSIGNABLE_PER_NOTE_CONTROLLER => M::AssignablePerNoteController {
+            note_number: note()?,
+            index: index_lsb,
+            data,
+        },
+        midi2_statuses::REGISTERED_CONTROLLER => M::RegisteredController {
+            bank: u7(index_msb)?,
+            index: u7(index_lsb)?,
+            data,
+        },
+        midi2_statuses::ASSIGNABLE_CONTROLLER => M::AssignableController {
+            bank: u7(index_msb)?,
+            index: u7(index_lsb)?,
+            data,
+        },
+        midi2_statuses::RELATIVE_REGISTERED_CONTROLLER => M::RelativeRegisteredController {
+            bank: u7(index_msb)?,
+            index: u7(index_lsb)?,
+            data: data as i32,
+        },
+        midi2_statuses::RELATIVE_ASSIGNABLE_CONTROLLER => M::RelativeAssignableController {
+            bank: u7(index_msb)?,
+            index: u7(index_lsb)?,
+            data: data as i32,
+        },
+        midi2_statuses::PER_NOTE_PITCH_BEND => M::PerNotePitchBend {
+            note_number: note()?,
+            data,
+        },
+        midi2_statuses::NOTE_OFF => M::NoteOff {
+            note_number: note()?,
+            velocity: (data >> 16) as u16,
+            attribute_type: index_lsb,
+            attribute: data as u16,
+        },
+        midi2_statuses::NOTE_ON => M::NoteOn {
+            note_number: note()?,
+            velocity: (data >> 16) as u16,
+            attribute_type: index_lsb,
+            attribute: data as u16,
+        },
+        midi2_statuses::POLY_PRESSURE => M::PolyPressure {
+            note_number: note()?,
+            data,
+        },
+        midi2_statuses::CONTROL_CHANGE => M::ControlChange {
+            index: u7(index_msb)?,
+            data,
+        },
+        midi2_statuses::PROGRAM_CHANGE => {
+            let bank_valid = index_lsb & 1 != 0;
+            M::ProgramChange {
+                program: u7(byte(data, 24))?,
+                bank: if bank_valid {
+                    Some((u7(byte(data, 8))?, u7(byte(data, 0))?))
+                } else {
+                    None
+                },
+            }
+        }
+        midi2_statuses::CHANNEL_PRESSURE => M::ChannelPressure { data },
+        midi2_statuses::PITCH_BEND => M::PitchBend { data },
+        midi2_statuses::PER_NOTE_MANAGEMENT => M::PerNoteManagement {
+            note_number: note()?,
+            detach: index_lsb & 0b10 != 0,
+            reset: index_lsb & 0b01 != 0,
+        },
+        _ => bail!("reserved MIDI 2.0 channel voice status {:X}", status),
+    })
+}
+
+fn encode_midi2_channel_voice(
+    group: Group,
+    channel: MidiChannelId,
+    message: &Midi2ChannelVoiceMessage,
+    out: &mut Vec<u32>,
+) {
+    use Midi2ChannelVoiceMessage as M;
+    let note = |note_number: &cvm::NoteNumber| u8::from(note_number.0);
+    let (status, index_msb, index_lsb, data) = match message {
+        M::RegisteredPerNoteController { note_number, index, data } => {
+            (midi2_statuses::REGISTERED_PER_NOTE_CONTROLLER, note(note_number), *index, *data)
+        }
+        M::AssignablePerNoteController { note_number, index, data } => {
+            (midi2_statuses::ASSIGNABLE_PER_NOTE_CONTROLLER, note(note_number), *index, *data)
+        }
+        M::RegisteredController { bank, index, data } => {
+            (midi2_statuses::REGISTERED_CONTROLLER, u8::from(*bank), u8::from(*index), *data)
+        }
+        M::AssignableController { bank, index, data } => {
+            (midi2_statuses::ASSIGNABLE_CONTROLLER, u8::from(*bank), u8::from(*index), *data)
+        }
+        M::RelativeRegisteredController { bank, index, data } => {
+            (midi2_statuses::RELATIVE_REGISTERED_CONTROLLER, u8::from(*bank), u8::from(*index), *data as u32)
+        }
+        M::RelativeAssignableController { bank, index, data } => {
+            (midi2_statuses::RELATIVE_ASSIGNABLE_CONTROLLER, u8::from(*bank), u8::from(*index), *data as u32)
+        }
+        M::PerNotePitchBend { note_number, data } => {
+            (midi2_statuses::PER_NOTE_PITCH_BEND, note(note_number), 0, *data)
+        }
+        M::NoteOff { note_number, velocity, attribute_type, attribute } => {
+            (midi2_statuses::NOTE_OFF, note(note_number), *attribute_type, (*velocity as u32) << 16 | *attribute as u32)
+        }
+        M::NoteOn { note_number, velocity, attribute_type, attribute } => {
+            (midi2_statuses::NOTE_ON, note(note_number), *attribute_type, (*velocity as u32) << 16 | *attribute as u32)
+        }
+        M::PolyPressure { note_number, data } => {
+            (midi2_statuses::POLY_PRESSURE, note(note_number), 0, *data)
+        }
+        M::ControlChange { index, data } => {
+            (midi2_statuses::CONTROL_CHANGE, u8::from(*index), 0, *data)
+        }
+        M::ProgramChange { program, bank } => {
+            let program = (u8::from(*program) as u32) << 24;
+            match bank {
+                None => (midi2_statuses::PROGRAM_CHANGE, 0, 0, program),
+                Some((msb, lsb)) => {
+                    let bank = (u8::from(*msb) as u32) << 8 | u8::from(*lsb) as u32;
+                    (midi2_statuses::PROGRAM_CHANGE, 0, 1, program | bank)
+                }
+            }
+        }
+        M::ChannelPressure { data } => (midi2_statuses::CHANNEL_PRESSURE, 0, 0, *data),
+        M::PitchBend { data } => (midi2_statuses::PITCH_BEND, 0, 0, *data),
+        M::PerNoteManagement { note_number, detach, reset } => {
+            let flags = (*detach as u8) << 1 | *reset as u8;
+            (midi2_statuses::PER_NOTE_MANAGEMENT, note(note_number), flags, 0)
+        }
+    };
+    out.push(
+        header(message_types::MIDI2_CHANNEL_VOICE, group)
+            | (status as u32) << 20
+            | (u8::from(channel) as u32) << 16
+            | (index_msb as u32) << 8
+            | index_lsb as u32
+    );
+    out.push(data);
+}
+
+fn parse_data128(packet: &[u32]) -> Result<Data128Message> {
+    let status = nibble(packet[0], 20);
+    let low_nibble = nibble(packet[0], 16);
+    Ok(match status {
+        0..=data128_statuses::SYSEX8_LAST_FORM => {
+            let len = low_nibble as usize;
+            if !(1..=14).contains(&len) {
+                bail!("SysEx8 packet with {} bytes", len);
+            }
+            Data128Message::SysEx8 {
+                form: PacketForm::assert_from(status),
+                stream_id: byte(packet[0], 8),
+                data: packet_bytes(packet, 3, len - 1),
+            }
+        }
+        data128_statuses::MIXED_DATA_SET_HEADER => Data128Message::MixedDataSetHeader {
+            mds_id: low_nibble,
+            bytes_in_chunk: packet[0] as u16,
+            chunk_count: (packet[1] >> 16) as u16,
+            chunk_number: packet[1] as u16,
+            manufacturer_id: (packet[2] >> 16) as u16,
+            device_id: packet[2] as u16,
+            sub_id_1: (packet[3] >> 16) as u16,
+            sub_id_2: packet[3] as u16,
+        },
+        data128_statuses::MIXED_DATA_SET_PAYLOAD => Data128Message::MixedDataSetPayload {
+            mds_id: low_nibble,
+            data: packet_bytes(packet, 2, 14).try_into().expect("14 bytes"),
+        },
+        _ => bail!("reserved data 128 status {:X}", status),
+    })
+}
+
+fn encode_data128(group: Group, message: &Data128Message, out: &mut Vec<u32>) -> Result<()> {
+    let mut packet = [header(message_types::DATA_128, group), 0, 0, 0];
+    match message {
+        Data128Message::SysEx8 { form, stream_id, data } => {
+            if data.len() > 13 {
+                bail!("SysEx8 packet with {} bytes", data.len());
+            }
+            packet[0] |= (u8::from(*form) as u32) << 20
+                | (data.len() as u32 + 1) << 16
+                | (*stream_id as u32) << 8;
+            write_packet_bytes(&mut packet, 3, data);
+        }
+        Data128Message::MixedDataSetHeader {
+            mds_id, bytes_in_chunk, chunk_count, chunk_number,
+            manufacturer_id, device_id, sub_id_1, sub_id_2,
+        } => {
+            if *mds_id > 0xF {
+                bail!("mixed data set id out of range: {}", mds_id);
+            }
+            packet[0] |= (data128_statuses::MIXED_DATA_SET_HEADER as u32) << 20
+                | (*mds_id as u32) << 16
+                | *bytes_in_chunk as u32;
+            packet[1] = (*chunk_count as u32) << 16 | *chunk_number as u32;
+            packet[2] = (*manufacturer_id as u32) << 16 | *device_id as u32;
+            packet[3] = (*sub_id_1 as u32) << 16 | *sub_id_2 as u32;
+        }
+        Data128Message::MixedDataSetPayload { mds_id, data } => {
+            if *mds_id > 0xF {
+                bail!("mixed data set id out of range: {}", mds_id);
+            }
+            packet[0] |= (data128_statuses::MIXED_DATA_SET_PAYLOAD as u32) << 20
+                | (*mds_id as u32) << 16;
+            write_packet_bytes(&mut packet, 2, data);
+        }
+    }
+    out.extend_from_slice(&packet);
+    Ok(())
+}
+
+fn parse_flex_data(packet: &[u32]) -> Result<FlexDataMessage> {
+    let first = packet[0];
+    let form = PacketForm::assert_from((first >> 22) as u8 & 0b11);
+    let channel = match (first >> 20) & 0b11 {
+        0 => Some(MidiChannelId::assert_from(nibble(first, 16))),
+        1 => None,
+        address => bail!("reserved flex data address {}", address),
+    };
+    let status_bank = byte(first, 8);
+    let status = byte(first, 0);
+    let data = [packet[1], packet[2], packet[3]];
+    let payload = match (status_bank, status) {
+        (flex_data_statuses::SETUP_AND_PERFORMANCE_BANK, flex_data_statuses::SET_TEMPO) => {
+            FlexDataPayload::SetTempo {
+                ten_nanoseconds_per_quarter: data[0],
+            }
+        }
+        (flex_data_statuses::SETUP_AND_PERFORMANCE_BANK, flex_data_statuses::SET_TIME_SIGNATURE) => {
+            FlexDataPayload::SetTimeSignature {
+                numerator: byte(data[0], 24),
+                denominator_power: byte(data[0], 16),
+                thirty_seconds_per_quarter: byte(data[0], 8),
+            }
+        }
+        (flex_data_statuses::SETUP_AND_PERFORMANCE_BANK, flex_data_statuses::SET_KEY_SIGNATURE) => {
+            // Sign-extend the 4-bit sharps/flats field.
+            let sharps = (byte(data[0], 24) as i8) >> 4;
+            FlexDataPayload::SetKeySignature {
+                sharps,
+                tonic: nibble(data[0], 24),
+            }
+        }
+        (flex_data_statuses::METADATA_TEXT_BANK | flex_data_statuses::PERFORMANCE_TEXT_BANK, _) => {
+            FlexDataPayload::Text {
+                status_bank,
+                status,
+                text: trim_nuls(packet_bytes(packet, 4, 12)),
+            }
+        }
+        _ => FlexDataPayload::Other {
+            status_bank,
+            status,
+            data,
+        },
+    };
+    Ok(FlexDataMessage {
+        form,
+        channel,
+        payload,
+    })
+}
+
+fn encode_flex_data(group: Group, message: &FlexDataMessage, out: &mut Vec<u32>) -> Result<()> {
+    let mut packet = [header(message_types::FLEX_DATA, group), 0, 0, 0];
+    packet[0] |= (u8::from(message.form) as u32) << 22;
+    match message.channel {
+        Some(channel) => packet[0] |= (u8::from(channel) as u32) << 16,
+        None => packet[0] |= 1 << 20,
+    }
+    let (status_bank, status) = match &message.payload {
+        FlexDataPayload::SetTempo { ten_nanoseconds_per_quarter } => {
+            packet[1] = *ten_nanoseconds_per_quarter;
+            (flex_data_statuses::SETUP_AND_PERFORMANCE_BANK, flex_data_statuses::SET_TEMPO)
+        }
+        FlexDataPayload::SetTimeSignature { numerator, denominator_power, thirty_seconds_per_quarter } => {
+            packet[1] = (*numerator as u32) << 24
+                | (*denominator_power as u32) << 16
+                | (*thirty_seconds_per_quarter as u32) << 8;
+            (flex_data_statuses::SETUP_AND_PERFORMANCE_BANK, flex_data_statuses::SET_TIME_SIGNATURE)
+        }
+        FlexDataPayload::SetKeySignature { sharps, tonic } => {
+            if !(-8..=7).contains(sharps) || *tonic > 0xF {
+                bail!("key signature out of range");
+            }
+            packet[1] = ((*sharps as u8 & 0xF) as u32) << 28 | (*tonic as u32) << 24;
+            (flex_data_statuses::SETUP_AND_PERFORMANCE_BANK, flex_data_statuses::SET_KEY_SIGNATURE)
+        }
+        FlexDataPayload::Text { status_bank, status, text } => {
+            if text.len() > 12 {
+                bail!("flex data text of {} bytes", text.len());
+            }
+            write_packet_bytes(&mut packet, 4, text);
+            (*status_bank, *status)
+        }
+        FlexDataPayload::Other { status_bank, status, data } => {
+            packet[1..].copy_from_slice(data);
+            (*status_bank, *status)
+        }
+    };
+    packet[0] |= (status_bank as u32) << 8 | status as u32;
+    out.extend_from_slice(&packet);
+    Ok(())
+}
+
+fn parse_stream(packet: &[u32]) -> Result<StreamMessage> {
+    let first = packet[0];
+    let form = PacketForm::assert_from((first >> 26) as u8 & 0b11);
+    let status = (first >> 16) as u16 & 0x3FF;
+    let flag = |word: u32, bit: u32| word & (1 << bit) != 0;
+    let version = (byte(first, 8), byte(first, 0));
+    Ok(match status {
+        stream_statuses::ENDPOINT_DISCOVERY => StreamMessage::EndpointDiscovery {
+            ump_version: version,
+            filter: byte(packet[1], 0),
+        },
+        stream_statuses::ENDPOINT_INFO_NOTIFICATION => StreamMessage::EndpointInfoNotification {
+            ump_version: version,
+            static_function_blocks: flag(packet[1], 31),
+            function_block_count: byte(packet[1], 24) & 0x7F,
+            midi2_protocol: flag(packet[1], 9),
+            midi1_protocol: flag(packet[1], 8),
+            receive_jitter_reduction: flag(packet[1], 1),
+            transmit_jitter_reduction: flag(packet[1], 0),
+        },
+        stream_statuses::DEVICE_IDENTITY_NOTIFICATION => {
+            let bytes = packet_bytes(packet, 5, 11);
+            StreamMessage::DeviceIdentityNotification {
+                manufacturer_id: [bytes[0], bytes[1], bytes[2]],
+                family: [bytes[3], bytes[4]],
+                model: [bytes[5], bytes[6]],
+                software_revision: [bytes[7], bytes[8], bytes[9], bytes[10]],
+            }
+        }
+        stream_statuses::ENDPOINT_NAME_NOTIFICATION => StreamMessage::EndpointNameNotification {
+            form,
+            text: trim_nuls(packet_bytes(packet, 2, 14)),
+        },
+        stream_statuses::PRODUCT_INSTANCE_ID_NOTIFICATION => StreamMessage::ProductInstanceIdNotification {
+            form,
+            text: trim_nuls(packet_bytes(packet, 2, 14)),
+        },
+        stream_statuses::STREAM_CONFIGURATION_REQUEST => StreamMessage::StreamConfigurationRequest {
+            protocol: byte(first, 8),
+            receive_jitter_reduction: flag(first, 1),
+            transmit_jitter_reduction: flag(first, 0),
+        },
+        stream_statuses::STREAM_CONFIGURATION_NOTIFICATION => StreamMessage::StreamConfigurationNotification {
+            protocol: byte(first, 8),
+            receive_jitter_reduction: flag(first, 1),
+            transmit_jitter_reduction: flag(first, 0),
+        },
+        stream_statuses::FUNCTION_BLOCK_DISCOVERY => StreamMessage::FunctionBlockDiscovery {
+            function_block: byte(first, 8),
+            filter: byte(first, 0),
+        },
+        stream_statuses::FUNCTION_BLOCK_INFO_NOTIFICATION => StreamMessage::FunctionBlockInfoNotification {
+            active: flag(first, 15),
+            function_block: byte(first, 8) & 0x7F,
+            ui_hint: (first >> 4) as u8 & 0b11,
+            midi1: (first >> 2) as u8 & 0b11,
+            direction: first as u8 & 0b11,
+            first_group: Group::try_from(byte(packet[1], 24))?,
+            group_count: byte(packet[1], 16),
+            midi_ci_version: byte(packet[1], 8),
+            max_sysex8_streams: byte(packet[1], 0),
+        },
+        stream_statuses::FUNCTION_BLOCK_NAME_NOTIFICATION => StreamMessage::FunctionBlockNameNotification {
+            form,
+            function_block: byte(first, 8),
+            text: trim_nuls(packet_bytes(packet, 3, 13)),
+        },
+        stream_statuses::START_OF_CLIP => StreamMessage::StartOfClip,
+        stream_statuses::END_OF_CLIP => StreamMessage::EndOfClip,
+        _ => StreamMessage::Other {
+            form,
+            status,
+            words: packet.try_into().expect("4 words"),
+        },
+    })
+}
+
+fn encode_stream(message: &StreamMessage, out: &mut Vec<u32>) -> Result<()> {
+    let mut packet = [(message_types::UMP_STREAM as u32) << 28, 0, 0, 0];
+    let bit = |flag: bool, bit: u32| (flag as u32) << bit;
+    let text_field = |text: &[u8], max: usize| -> Result<()> {
+        if text.len() > max {
+            bail!("UMP stream text of {} bytes", text.len());
+        }
+        Ok(())
+    };
+    let (form, status) = match message {
+        StreamMessage::EndpointDiscovery { ump_version, filter } => {
+            packet[0] |= (ump_version.0 as u32) << 8 | ump_version.1 as u32;
+            packet[1] = *filter as u32;
+            (PacketForm::Complete, stream_statuses::ENDPOINT_DISCOVERY)
+        }
+        StreamMessage::EndpointInfoNotification {
+            ump_version, static_function_blocks, function_block_count,
+            midi2_protocol, midi1_protocol, receive_jitter_reduction, transmit_jitter_reduction,
+        } => {
+            if *function_block_count > 0x7F {
+                bail!("function block count out of range: {}", function_block_count);
+            }
+            packet[0] |= (ump_version.0 as u32) << 8 | ump_version.1 as u32;
+            packet[1] = bit(*static_function_blocks, 31)
+                | (*function_block_count as u32) << 24
+                | bit(*midi2_protocol, 9)
+                | bit(*midi1_protocol, 8)
+                | bit(*receive_jitter_reduction, 1)
+                | bit(*transmit_jitter_reduction, 0);
+            (PacketForm::Complete, stream_statuses::ENDPOINT_INFO_NOTIFICATION)
+        }
+        StreamMessage::DeviceIdentityNotification { manufacturer_id, family, model, software_revision } => {
+            let bytes: Vec<u8> = manufacturer_id.iter()
+                .chain(family)
+                .chain(model)
+                .chain(software_revision)
+                .copied()
+                .collect();
+            write_packet_bytes(&mut packet, 5, &bytes);
+            (PacketForm::Complete, stream_statuses::DEVICE_IDENTITY_NOTIFICATION)
+        }
+        StreamMessage::EndpointNameNotification { form, text } => {
+            text_field(text, 14)?;
+            write_packet_bytes(&mut packet, 2, text);
+            (*form, stream_statuses::ENDPOINT_NAME_NOTIFICATION)
+        }
+        StreamMessage::ProductInstanceIdNotification { form, text } => {
+            text_field(text, 14)?;
+            write_packet_bytes(&mut packet, 2, text);
+            (*form, stream_statuses::PRODUCT_INSTANCE_ID_NOTIFICATION)
+        }
+        StreamMessage::StreamConfigurationRequest { protocol, receive_jitter_reduction, transmit_jitter_reduction } => {
+            packet[0] |= (*protocol as u32) << 8
+                | bit(*receive_jitter_reduction, 1)
+                | bit(*transmit_jitter_reduction, 0);
+            (PacketForm::Complete, stream_statuses::STREAM_CONFIGURATION_REQUEST)
+        }
+        StreamMessage::StreamConfigurationNotification { protocol, receive_jitter_reduction, transmit_jitter_reduction } => {
+            packet[0] |= (*protocol as u32) << 8
+                | bit(*receive_jitter_reduction, 1)
+                | bit(*transmit_jitter_reduction, 0);
+            (PacketForm::Complete, stream_statuses::STREAM_CONFIGURATION_NOTIFICATION)
+        }
+        StreamMessage::FunctionBlockDiscovery { function_block, filter } => {
+            packet[0] |= (*function_block as u32) << 8 | *filter as u32;
+            (PacketForm::Complete, stream_statuses::FUNCTION_BLOCK_DISCOVERY)
+        }
+        StreamMessage::FunctionBlockInfoNotification {
+            active, function_block, ui_hint, midi1, direction,
+            first_group, group_count, midi_ci_version, max_sysex8_streams,
+        } => {
+            if *function_block > 0x7F || *ui_hint > 0b11 || *midi1 > 0b11 || *direction > 0b11 {
+                bail!("function block info field out of range");
+            }
+            packet[0] |= bit(*active, 15)
+                | (*function_block as u32) << 8
+                | (*ui_hint as u32) << 4
+                | (*midi1 as u32) << 2
+                | *direction as u32;
+            packet[1] = (u8::from(*first_group) as u32) << 24
+                | (*group_count as u32) << 16
+                | (*midi_ci_version as u32) << 8
+                | *max_sysex8_streams as u32;
+            (PacketForm::Complete, stream_statuses::FUNCTION_BLOCK_INFO_NOTIFICATION)
+        }
+        StreamMessage::FunctionBlockNameNotification { form, function_block, text } => {
+            text_field(text, 13)?;
+            packet[0] |= (*function_block as u32) << 8;
+            write_packet_bytes(&mut packet, 3, text);
+            (*form, stream_statuses::FUNCTION_BLOCK_NAME_NOTIFICATION)
+        }
+        StreamMessage::StartOfClip => (PacketForm::Complete, stream_statuses::START_OF_CLIP),
+        StreamMessage::EndOfClip => (PacketForm::Complete, stream_statuses::END_OF_CLIP),
+        StreamMessage::Other { form, status, words } => {
+            if *status > 0x3FF {
+                bail!("UMP stream status out of range: {}", status);
+            }
+            packet = *words;
+            packet[0] &= 0xF000_0000 | 0xFFFF;
+            (*form, *status)
+        }
+    };
+    packet[0] |= (u8::from(form) as u32) << 26 | (status as u32) << 16;
+    out.extend_from_slice(&packet);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::packetize_sysex7;
+
+    fn group(value: u8) -> Group {
+        Group::try_from(value).unwrap()
+    }
+
+    fn assert_round_trip(message: UmpMessage) {
+        let words = message.to_words().unwrap();
+        assert_eq!(words.len(), packet_len(words[0]), "{:?}", message);
+        assert_eq!(UmpMessage::parse(&words).unwrap(), message, "{:08X?}", words);
+    }
+
+    fn channel_message(bytes: &[u8]) -> ChannelMessage {
+        match Parser::new().parse(bytes).unwrap().status {
+            MessageParseOutcomeStatus::Message(Message::Channel(message)) => message,
+            status => panic!("{:02X?} parsed as {:?}", bytes, status),
+        }
+    }
+
+    #[test]
+    fn utility_round_trip() {
+        for message in [
+            UtilityMessage::Noop,
+            UtilityMessage::JitterReductionClock { sender_clock_time: 0xBEEF },
+            UtilityMessage::JitterReductionTimestamp { sender_clock_timestamp: 0x1234 },
+            UtilityMessage::DeltaClockstampTicksPerQuarter { ticks_per_quarter: 960 },
+            UtilityMessage::DeltaClockstamp { ticks: 0xF_FFFF },
+            UtilityMessage::Other { status: 0xA, data: 0x5_4321 },
+        ] {
+            assert_round_trip(UmpMessage::Utility(message));
+        }
+        let too_long = UmpMessage::Utility(UtilityMessage::DeltaClockstamp { ticks: 0x10_0000 });
+        assert!(too_long.to_words().is_err());
+    }
+
+    #[test]
+    fn system_round_trip() {
+        for message in [
+            SystemMessage::MidiTimeCodeQuarterFrame(Unsigned7::assert_from(0x35)),
+            SystemMessage::SongPositionPointer(Unsigned14::try_from([0x7F, 0x01]).unwrap()),
+            SystemMessage::SongSelect(Unsigned7::assert_from(9)),
+            SystemMessage::TuneRequest,
+            SystemMessage::RealTime(SystemRealTimeMessage::TimingClock),
+            SystemMessage::RealTime(SystemRealTimeMessage::Stop),
+        ] {
+            assert_round_trip(UmpMessage::System { group: group(3), message });
+        }
+    }
+
+    #[test]
+    fn midi1_channel_voice_round_trip() {
+        for bytes in [
+            &[0x80, 60, 64][..],
+            &[0x91, 60, 100],
+            &[0xA2, 61, 5],
+            &[0xB3, 7, 127],
+            &[0xC4, 42],
+            &[0xD5, 77],
+            &[0xEF, 0x00, 0x40],
+        ] {
+            assert_round_trip(UmpMessage::Midi1ChannelVoice {
+                group: group(15),
+                message: channel_message(bytes),
+            });
+        }
+    }
+
+    #[test]
+    fn data64_round_trip() {
+        for (form, data) in [
+            (PacketForm::Complete, vec![]),
+            (PacketForm::Complete, vec![0x7E, 0x7F, 0x06, 0x01]),
+            (PacketForm::Start, vec![1, 2, 3, 4, 5, 6]),
+            (PacketForm::Continue, vec![7, 8, 9, 10, 11, 12]),
+            (PacketForm::End, vec![13]),
+        ] {
+            assert_round_trip(UmpMessage::Data64 {
+                group: group(1),
+                message: SysEx7Packet { form, data },
+            });
+        }
+        let too_long = UmpMessage::Data64 {
+            group: group(1),
+            message: SysEx7Packet { form: PacketForm::Complete, data: vec![0; 7] },
+        };
+        assert!(too_long.to_words().is_err());
+    }
+
+    #[test]
+    fn midi2_channel_voice_round_trip() {
+        use Midi2ChannelVoiceMessage as M;
+        let note_number = cvm::NoteNumber(Unsigned7::assert_from(60));
+        let bank = Unsigned7::assert_from(0x12);
+        let index = Unsigned7::assert_from(0x34);
+        for message in [
+            M::RegisteredPerNoteController { note_number, index: 3, data: 0x8000_0000 },
+            M::AssignablePerNoteController { note_number, index: 0xFF, data: 1 },
+            M::RegisteredController { bank, index, data: 0xFFFF_FFFF },
+            M::AssignableController { bank, index, data: 0 },
+            M::RelativeRegisteredController { bank, index, data: -1 << 18 },
+            M::RelativeAssignableController { bank, index, data: i32::MAX },
+            M::PerNotePitchBend { note_number, data: 0x8000_0000 },
+            M::NoteOff { note_number, velocity: 0x8000, attribute_type: 0, attribute: 0 },
+            M::NoteOn { note_number, velocity: 0xFFFF, attribute_type: 3, attribute: 0x0200 },
+            M::PolyPressure { note_number, data: 0x1234_5678 },
+            M::ControlChange { index, data: 0xDEAD_BEEF },
+            M::ProgramChange { program: Unsigned7::assert_from(5), bank: None },
+            M::ProgramChange { program: Unsigned7::assert_from(127), bank: Some((bank, index)) },
+            M::ChannelPressure { data: 0x4000_0000 },
+            M::PitchBend { data: 0x8000_0000 },
+            M::PerNoteManagement { note_number, detach: true, reset: false },
+            M::PerNoteManagement { note_number, detach: false, reset: true },
+        ] {
+            assert_round_trip(UmpMessage::Midi2ChannelVoice {
+                group: group(7),
+                channel: MidiChannelId::assert_from(9),
+                message,
+            });
+        }
+    }
+
+    #[test]
+    fn data128_round_trip() {
+        for message in [
+            Data128Message::SysEx8 { form: PacketForm::Complete, stream_id: 0, data: vec![] },
+            Data128Message::SysEx8 { form: PacketForm::Start, stream_id: 0xFF, data: (0xF0..0xFD).collect() },
+            Data128Message::SysEx8 { form: PacketForm::End, stream_id: 2, data: vec![0x80, 0x00] },
+            Data128Message::MixedDataSetHeader {
+                mds_id: 0xF,
+                bytes_in_chunk: 0x1234,
+                chunk_count: 3,
+                chunk_number: 1,
+                manufacturer_id: 0x0041,
+                device_id: 0x7F00,
+                sub_id_1: 0x0102,
+                sub_id_2: 0xFFFF,
+            },
+            Data128Message::MixedDataSetPayload { mds_id: 4, data: std::array::from_fn(|index| 0xF0 + index as u8) },
+        ] {
+            assert_round_trip(UmpMessage::Data128 { group: group(0), message });
+        }
+        let too_long = UmpMessage::Data128 {
+            group: group(0),
+            message: Data128Message::SysEx8 { form: PacketForm::Complete, stream_id: 0, data: vec![0; 14] },
+        };
+        assert!(too_long.to_words().is_err());
+    }
+
+    #[test]
+    fn sysex7_split_round_trip() {
+        for len in [0, 1, 6, 7, 12, 13, 20] {
+            let data: Vec<u8> = (0..len).map(|byte| byte as u8).collect();
+            let packets = packetize_sysex7(&data);
+            let expected_forms: Vec<PacketForm> = match packets.len() {
+                1 => vec![PacketForm::Complete],
+                count => std::iter::once(PacketForm::Start)
+                    .chain(std::iter::repeat(PacketForm::Continue).take(count - 2))
+                    .chain(std::iter::once(PacketForm::End))
+                    .collect(),
+            };
+            assert_eq!(packets.iter().map(|packet| packet.form).collect::<Vec<_>>(), expected_forms, "{} bytes", len);
+
+            let mut words = vec![];
+            for packet in &packets {
+                UmpMessage::Data64 { group: group(2), message: packet.clone() }.encode(&mut words).unwrap();
+            }
+            let mut reassembled = vec![];
+            for message in UmpMessage::parse_all(&words).unwrap() {
+                match message {
+                    UmpMessage::Data64 { message, .. } => reassembled.extend(message.data),
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            assert_eq!(reassembled, data, "{} bytes", len);
+        }
+    }
+}